@@ -23,6 +23,8 @@ pub struct FullDeps<C, P> {
 	pub pool: Arc<P>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// Inclusion statistics of the recent blocks, for the fee estimation.
+	pub inclusion_stats: Arc<noble_transaction_payment_rpc::InclusionStats<Block>>,
 }
 
 /// Instantiate all full RPC extensions.
@@ -45,6 +47,7 @@ pub fn create_full<C, P>(
 		client,
 		pool,
 		deny_unsafe,
+		inclusion_stats,
 	} = deps;
 
 	io.extend_with(
@@ -52,7 +55,7 @@ pub fn create_full<C, P>(
 	);
 
 	io.extend_with(
-		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
+		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone(), inclusion_stats))
 	);
 
 	// Extend this RPC with a custom API by using the following syntax.
@@ -131,6 +131,7 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 			import_queue,
 			on_demand: None,
 			block_announce_validator_builder: None,
+			block_announce_data_builder: None,
 		})?;
 
 	if config.offchain_worker.enabled {
@@ -146,6 +147,15 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	let inclusion_stats = Arc::new(noble_transaction_payment_rpc::InclusionStats::new());
+	task_manager.spawn_handle().spawn(
+		"transaction-payment-stats",
+		noble_transaction_payment_rpc::collect_inclusion_stats::<_, _, node_template_runtime::Balance>(
+			client.clone(),
+			inclusion_stats.clone(),
+		),
+	);
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
@@ -155,6 +165,7 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 				client: client.clone(),
 				pool: pool.clone(),
 				deny_unsafe,
+				inclusion_stats: inclusion_stats.clone(),
 			};
 
 			crate::rpc::create_full(deps)
@@ -303,6 +314,7 @@ pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError>
 			import_queue,
 			on_demand: Some(on_demand.clone()),
 			block_announce_validator_builder: None,
+			block_announce_data_builder: None,
 		})?;
 
 	if config.offchain_worker.enabled {
@@ -96,7 +96,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("node-template"),
 	impl_name: create_runtime_str!("node-template"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 2,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -444,6 +444,14 @@ impl_runtime_apis! {
 		) -> noble_transaction_payment::FeeDetails<Balance> {
 			TransactionPayment::query_fee_details(uxt, len)
 		}
+		fn query_min_tip(uxts: Vec<<Block as BlockT>::Extrinsic>) -> Option<Balance> {
+			uxts.into_iter()
+				.filter_map(|uxt| uxt.signature.map(|(_, _, extra)| extra.6.tip()))
+				.min()
+		}
+		fn query_block_fullness() -> Perbill {
+			TransactionPayment::query_block_fullness()
+		}
 	}
 
 	#[cfg(feature = "runtime-benchmarks")]
@@ -37,10 +37,13 @@ use node_testing::bench::{BenchDb, Profile, BlockType, KeyTypes, DatabaseType};
 use tp_runtime::{
 	generic::BlockId,
 	traits::NumberFor,
+	transaction_validity::TransactionTag,
 	OpaqueExtrinsic,
 };
 use tp_transaction_pool::{
+	BlockHash,
 	ImportNotificationStream,
+	PoolEventStream,
 	PoolFuture,
 	PoolStatus,
 	TransactionFor,
@@ -260,6 +263,25 @@ impl tp_transaction_pool::TransactionPool for Transactions {
 		unimplemented!()
 	}
 
+	fn submit_and_watch_at(
+		&self,
+		_at: &BlockId<Self::Block>,
+		_source: TransactionSource,
+		_xts: Vec<TransactionFor<Self>>,
+	) -> PoolFuture<Vec<Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>>, Self::Error> {
+		unimplemented!()
+	}
+
+	fn replace_at(
+		&self,
+		_at: &BlockId<Self::Block>,
+		_source: TransactionSource,
+		_xt: TransactionFor<Self>,
+		_replaces: TxHash<Self>,
+	) -> PoolFuture<TxHash<Self>, Self::Error> {
+		unimplemented!()
+	}
+
 	fn ready_at(&self, _at: NumberFor<Self::Block>)
 		-> Pin<Box<dyn Future<Output=Box<dyn Iterator<Item=Arc<Self::InPoolTransaction>> + Send>> + Send>>
 	{
@@ -283,6 +305,10 @@ impl tp_transaction_pool::TransactionPool for Transactions {
 		unimplemented!()
 	}
 
+	fn pool_event_stream(&self) -> PoolEventStream<TxHash<Self>, BlockHash<Self>> {
+		unimplemented!()
+	}
+
 	fn on_broadcasted(&self, _propagations: HashMap<TxHash<Self>, Vec<String>>) {
 		unimplemented!()
 	}
@@ -294,4 +320,8 @@ impl tp_transaction_pool::TransactionPool for Transactions {
 	fn ready_transaction(&self, _hash: &TxHash<Self>) -> Option<Arc<Self::InPoolTransaction>> {
 		unimplemented!()
 	}
+
+	fn futures(&self) -> Vec<(Arc<Self::InPoolTransaction>, Vec<TransactionTag>)> {
+		unimplemented!()
+	}
 }
@@ -62,15 +62,24 @@ pub enum Subcommand {
 	/// Validate blocks.
 	CheckBlock(tc_cli::CheckBlockCmd),
 
+	/// Inspect, repair or convert the database.
+	Db(tc_cli::DbCmd),
+
 	/// Export blocks.
 	ExportBlocks(tc_cli::ExportBlocksCmd),
 
 	/// Export the state of a given block into a chain spec.
 	ExportState(tc_cli::ExportStateCmd),
 
+	/// Export a snapshot of the state of a finalized block.
+	ExportSnapshot(tc_cli::ExportSnapshotCmd),
+
 	/// Import blocks.
 	ImportBlocks(tc_cli::ImportBlocksCmd),
 
+	/// Initialize an empty database from a state snapshot.
+	ImportSnapshot(tc_cli::ImportSnapshotCmd),
+
 	/// Remove the whole chain.
 	PurgeChain(tc_cli::PurgeChainCmd),
 
@@ -57,7 +57,10 @@ pub fn new_partial(config: &Configuration) -> Result<tc_service::PartialComponen
 			grandpa::LinkHalf<Block, FullClient, FullSelectChain>,
 			tc_consensus_babe::BabeLink<Block>,
 		),
-		grandpa::SharedVoterState,
+		(
+			grandpa::SharedVoterState,
+			Arc<noble_transaction_payment_rpc::InclusionStats<Block>>,
+		),
 	)
 >, ServiceError> {
 	let (client, backend, keystore_container, task_manager) =
@@ -106,7 +109,8 @@ pub fn new_partial(config: &Configuration) -> Result<tc_service::PartialComponen
 		let justification_stream = grandpa_link.justification_stream();
 		let shared_authority_set = grandpa_link.shared_authority_set().clone();
 		let shared_voter_state = grandpa::SharedVoterState::empty();
-		let rpc_setup = shared_voter_state.clone();
+		let inclusion_stats = Arc::new(noble_transaction_payment_rpc::InclusionStats::new());
+		let rpc_setup = (shared_voter_state.clone(), inclusion_stats.clone());
 
 		let finality_proof_provider = grandpa::FinalityProofProvider::new_for_service(
 			backend.clone(),
@@ -129,6 +133,7 @@ pub fn new_partial(config: &Configuration) -> Result<tc_service::PartialComponen
 				select_chain: select_chain.clone(),
 				chain_spec: chain_spec.cloned_box(),
 				deny_unsafe,
+				inclusion_stats: inclusion_stats.clone(),
 				babe: node_rpc::BabeDeps {
 					babe_config: babe_config.clone(),
 					shared_epoch_changes: shared_epoch_changes.clone(),
@@ -191,7 +196,15 @@ pub fn new_full_base(
 		other: (rpc_extensions_builder, import_setup, rpc_setup),
 	} = new_partial(&config)?;
 
-	let shared_voter_state = rpc_setup;
+	let (shared_voter_state, inclusion_stats) = rpc_setup;
+
+	task_manager.spawn_handle().spawn(
+		"transaction-payment-stats",
+		noble_transaction_payment_rpc::collect_inclusion_stats::<_, _, node_primitives::Balance>(
+			client.clone(),
+			inclusion_stats,
+		),
+	);
 
 	config.network.extra_sets.push(grandpa::grandpa_peers_set_config());
 
@@ -209,6 +222,7 @@ pub fn new_full_base(
 			import_queue,
 			on_demand: None,
 			block_announce_validator_builder: None,
+			block_announce_data_builder: None,
 		})?;
 
 	if config.offchain_worker.enabled {
@@ -414,6 +428,7 @@ pub fn new_light_base(mut config: Configuration) -> Result<(
 			import_queue,
 			on_demand: Some(on_demand.clone()),
 			block_announce_validator_builder: None,
+			block_announce_data_builder: None,
 		})?;
 	network_starter.start_network();
 
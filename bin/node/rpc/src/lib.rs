@@ -99,6 +99,8 @@ pub struct FullDeps<C, P, SC, B> {
 	pub chain_spec: Box<dyn tc_chain_spec::ChainSpec>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// Inclusion statistics of the recent blocks, for the fee estimation.
+	pub inclusion_stats: Arc<noble_transaction_payment_rpc::InclusionStats<Block>>,
 	/// BABE specific dependencies.
 	pub babe: BabeDeps,
 	/// GRANDPA specific dependencies.
@@ -135,6 +137,7 @@ pub fn create_full<C, P, SC, B>(
 		select_chain,
 		chain_spec,
 		deny_unsafe,
+		inclusion_stats,
 		babe,
 		grandpa,
 	} = deps;
@@ -162,7 +165,7 @@ pub fn create_full<C, P, SC, B>(
 		ContractsApi::to_delegate(Contracts::new(client.clone()))
 	);
 	io.extend_with(
-		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
+		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone(), inclusion_stats))
 	);
 	io.extend_with(
 		tc_consensus_babe_rpc::BabeApi::to_delegate(
@@ -112,7 +112,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// and set impl_version to 0. If only runtime
 	// implementation changes and behavior does not, then leave spec_version as
 	// is and increment impl_version.
-	spec_version: 262,
+	spec_version: 263,
 	impl_version: 0,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 2,
@@ -1285,6 +1285,14 @@ impl_runtime_apis! {
 		fn query_fee_details(uxt: <Block as BlockT>::Extrinsic, len: u32) -> FeeDetails<Balance> {
 			TransactionPayment::query_fee_details(uxt, len)
 		}
+		fn query_min_tip(uxts: Vec<<Block as BlockT>::Extrinsic>) -> Option<Balance> {
+			uxts.into_iter()
+				.filter_map(|uxt| uxt.signature.map(|(_, _, extra)| extra.6.tip()))
+				.min()
+		}
+		fn query_block_fullness() -> Perbill {
+			TransactionPayment::query_block_fullness()
+		}
 	}
 
 	impl noble_mmr::primitives::MmrApi<
@@ -414,6 +414,7 @@ impl BenchDb {
 			source: database_type.into_settings(dir.into()),
 			keep_blocks: tc_client_db::KeepBlocks::All,
 			transaction_storage: tc_client_db::TransactionStorageMode::BlockBody,
+			state_storage: tc_client_db::StateStorageMode::Journaled,
 		};
 		let task_executor = TaskExecutor::new();
 
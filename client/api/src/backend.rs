@@ -273,6 +273,7 @@ pub trait AuxStore {
 /// An `Iterator` that iterates keys in a given block under a prefix.
 pub struct KeyIterator<'a, State, Block> {
 	state: State,
+	child_info: Option<ChildInfo>,
 	prefix: Option<&'a StorageKey>,
 	current_key: Vec<u8>,
 	_phantom: PhantomData<Block>,
@@ -283,6 +284,23 @@ impl <'a, State, Block> KeyIterator<'a, State, Block> {
 	pub fn new(state: State, prefix: Option<&'a StorageKey>, current_key: Vec<u8>) -> Self {
 		Self {
 			state,
+			child_info: None,
+			prefix,
+			current_key,
+			_phantom: PhantomData,
+		}
+	}
+
+	/// Create a `KeyIterator` instance for a child storage.
+	pub fn new_child(
+		state: State,
+		child_info: ChildInfo,
+		prefix: Option<&'a StorageKey>,
+		current_key: Vec<u8>,
+	) -> Self {
+		Self {
+			state,
+			child_info: Some(child_info),
 			prefix,
 			current_key,
 			_phantom: PhantomData,
@@ -297,8 +315,10 @@ impl<'a, State, Block> Iterator for KeyIterator<'a, State, Block> where
 	type Item = StorageKey;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		let next_key = self.state
-			.next_storage_key(&self.current_key)
+		let next_key = match &self.child_info {
+			Some(child_info) => self.state.next_child_storage_key(child_info, &self.current_key),
+			None => self.state.next_storage_key(&self.current_key),
+		}
 			.ok()
 			.flatten()?;
 		// this terminates the iterator the first time it fails.
@@ -354,6 +374,16 @@ pub trait StorageProvider<Block: BlockT, B: Backend<Block>> {
 		key_prefix: &StorageKey
 	) -> tp_blockchain::Result<Vec<StorageKey>>;
 
+	/// Given a `BlockId`, a key prefix, and a child info return a `KeyIterator` iterates matching
+	/// child storage keys in that block.
+	fn child_storage_keys_iter<'a>(
+		&self,
+		id: &BlockId<Block>,
+		child_info: ChildInfo,
+		prefix: Option<&'a StorageKey>,
+		start_key: Option<&StorageKey>
+	) -> tp_blockchain::Result<KeyIterator<'a, B::State, Block>>;
+
 	/// Given a `BlockId`, a key and a child storage key, return the hash under the key in that block.
 	fn child_storage_hash(
 		&self,
@@ -362,6 +392,33 @@ pub trait StorageProvider<Block: BlockT, B: Backend<Block>> {
 		key: &StorageKey
 	) -> tp_blockchain::Result<Option<Block::Hash>>;
 
+	/// Given a `BlockId` and a key prefix, call `f` with the matching key-value pairs of the
+	/// storage, or of the child storage if `child_info` is given, in lexicographic key order.
+	/// If `start_key` is given, only the keys following it are visited.
+	///
+	/// Stops as soon as `f` returns `false`.
+	fn storage_pairs_while(
+		&self,
+		id: &BlockId<Block>,
+		child_info: Option<&ChildInfo>,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		f: &mut dyn FnMut(StorageKey, StorageData) -> bool,
+	) -> tp_blockchain::Result<()>;
+
+	/// Given two `BlockId`s and a key prefix, call `f` with every key of the storage that
+	/// starts with the prefix and whose value differs between `first` and `last`, along with
+	/// its value at both blocks, in lexicographic key order.
+	///
+	/// Stops as soon as `f` returns `false`.
+	fn storage_diff(
+		&self,
+		first: &BlockId<Block>,
+		last: &BlockId<Block>,
+		key_prefix: &StorageKey,
+		f: &mut dyn FnMut(StorageKey, Option<StorageData>, Option<StorageData>) -> bool,
+	) -> tp_blockchain::Result<()>;
+
 	/// Get longest range within [first; last] that is possible to use in `key_changes`
 	/// and `key_changes_proof` calls.
 	/// Range could be shortened from the beginning if some changes tries have been pruned.
@@ -148,6 +148,8 @@ impl fmt::Display for MemorySize {
 pub struct StateDbMemoryInfo {
 	/// Memory usage of the non-canonical overlay
 	pub non_canonical: MemorySize,
+	/// Size of the non-canonical values spilled to the database, if the overlay is memory bounded.
+	pub non_canonical_spilled: Option<MemorySize>,
 	/// Memory usage of the pruning window.
 	pub pruning: Option<MemorySize>,
 	/// Memory usage of the pinned blocks.
@@ -210,11 +212,12 @@ impl fmt::Display for UsageInfo {
 		write!(
 			f,
 			"caches: ({} state, {} db overlay), \
-			 state db: ({} non-canonical, {} pruning, {} pinned), \
+			 state db: ({} non-canonical, {} spilled, {} pruning, {} pinned), \
 			 i/o: ({} tx, {} write, {} read, {} avg tx, {}/{} key cache reads/total, {} trie nodes writes)",
 			self.memory.state_cache,
 			self.memory.database_cache,
 			self.memory.state_db.non_canonical,
+			self.memory.state_db.non_canonical_spilled.unwrap_or_default(),
 			self.memory.state_db.pruning.unwrap_or_default(),
 			self.memory.state_db.pinned,
 			self.io.transactions,
@@ -90,6 +90,23 @@ pub struct RemoteReadChildRequest<Header: HeaderT> {
 	pub retry_count: Option<usize>,
 }
 
+/// Remote storage keys read request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteReadKeysRequest<Header: HeaderT> {
+	/// Read at state of given block.
+	pub block: Header::Hash,
+	/// Header of block at which read is performed.
+	pub header: Header,
+	/// Prefix of the keys to read.
+	pub prefix: Vec<u8>,
+	/// Read the keys following this key, or following `prefix` if `None`.
+	pub start_key: Option<Vec<u8>>,
+	/// Maximum number of keys to read.
+	pub count: u32,
+	/// Number of times to retry request. None means that default RETRY_COUNT is used.
+	pub retry_count: Option<usize>,
+}
+
 /// Remote key changes read request.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteChangesRequest<Header: HeaderT> {
@@ -166,6 +183,16 @@ pub trait Fetcher<Block: BlockT>: Send + Sync {
 		Vec<Block::Extrinsic>,
 		ClientError,
 	>> + Unpin + Send + 'static;
+	/// Remote storage proof future.
+	type RemoteProofResult: Future<Output = Result<
+		StorageProof,
+		ClientError,
+	>> + Unpin + Send + 'static;
+	/// Remote storage keys future.
+	type RemoteKeysResult: Future<Output = Result<
+		Vec<Vec<u8>>,
+		ClientError,
+	>> + Unpin + Send + 'static;
 
 	/// Fetch remote header.
 	fn remote_header(&self, request: RemoteHeaderRequest<Block::Header>) -> Self::RemoteHeaderResult;
@@ -179,6 +206,17 @@ pub trait Fetcher<Block: BlockT>: Send + Sync {
 		&self,
 		request: RemoteReadChildRequest<Block::Header>
 	) -> Self::RemoteReadResult;
+	/// Fetch a checked proof of remote storage child values. The proof also covers the
+	/// path from the state root to the child trie root.
+	fn remote_read_child_proof(
+		&self,
+		request: RemoteReadChildRequest<Block::Header>
+	) -> Self::RemoteProofResult;
+	/// Fetch remote storage keys.
+	fn remote_read_keys(
+		&self,
+		request: RemoteReadKeysRequest<Block::Header>
+	) -> Self::RemoteKeysResult;
 	/// Fetch remote call result.
 	fn remote_call(&self, request: RemoteCallRequest<Block::Header>) -> Self::RemoteCallResult;
 	/// Fetch remote changes ((block number, extrinsic index)) where given key has been changed
@@ -212,6 +250,12 @@ pub trait FetchChecker<Block: BlockT>: Send + Sync {
 		request: &RemoteReadChildRequest<Block::Header>,
 		remote_proof: StorageProof,
 	) -> ClientResult<HashMap<Vec<u8>, Option<Vec<u8>>>>;
+	/// Check remote storage keys read proof, returning the proved keys.
+	fn check_read_keys_proof(
+		&self,
+		request: &RemoteReadKeysRequest<Block::Header>,
+		remote_proof: StorageProof,
+	) -> ClientResult<Vec<Vec<u8>>>;
 	/// Check remote method execution proof.
 	fn check_execution_proof(
 		&self,
@@ -337,6 +381,8 @@ pub mod tests {
 		type RemoteCallResult = Ready<Result<Vec<u8>, ClientError>>;
 		type RemoteChangesResult = Ready<Result<Vec<(NumberFor<Block>, u32)>, ClientError>>;
 		type RemoteBodyResult = Ready<Result<Vec<Extrinsic>, ClientError>>;
+		type RemoteProofResult = Ready<Result<StorageProof, ClientError>>;
+		type RemoteKeysResult = Ready<Result<Vec<Vec<u8>>, ClientError>>;
 
 		fn remote_header(&self, _request: RemoteHeaderRequest<Header>) -> Self::RemoteHeaderResult {
 			not_implemented_in_tests()
@@ -350,6 +396,17 @@ pub mod tests {
 			not_implemented_in_tests()
 		}
 
+		fn remote_read_child_proof(
+			&self,
+			_request: RemoteReadChildRequest<Header>,
+		) -> Self::RemoteProofResult {
+			not_implemented_in_tests()
+		}
+
+		fn remote_read_keys(&self, _request: RemoteReadKeysRequest<Header>) -> Self::RemoteKeysResult {
+			not_implemented_in_tests()
+		}
+
 		fn remote_call(&self, _request: RemoteCallRequest<Header>) -> Self::RemoteCallResult {
 			futures::future::ready(Ok((*self.lock()).clone()))
 		}
@@ -42,6 +42,31 @@ pub trait ProofProvider<Block: BlockT> {
 		keys: &mut dyn Iterator<Item=&[u8]>,
 	) -> tp_blockchain::Result<StorageProof>;
 
+	/// Reads the storage entries following `start_key` at a given block, until `size_limit`
+	/// bytes of keys and values are read, returning a range proof of the entries.
+	///
+	/// `child_info` selects a child trie instead of the top trie, and `None` for `start_key`
+	/// starts at the first entry. Also returns whether the last entry of the trie was reached.
+	fn read_range_proof(
+		&self,
+		id: &BlockId<Block>,
+		child_info: Option<&ChildInfo>,
+		start_key: Option<&[u8]>,
+		size_limit: usize,
+	) -> tp_blockchain::Result<(Vec<(Vec<u8>, Vec<u8>)>, bool, StorageProof)>;
+
+	/// Reads at most `count` storage keys starting with `prefix` and following `start_key`
+	/// at a given block, returning the keys and their proof.
+	///
+	/// `None` for `start_key` starts at `prefix`.
+	fn read_keys_proof(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: u32,
+	) -> tp_blockchain::Result<(Vec<Vec<u8>>, StorageProof)>;
+
 	/// Execute a call to a contract on top of state in a block of given hash
 	/// AND returning execution proof.
 	///
@@ -165,6 +165,27 @@ impl Into<tc_service::config::RpcMethods> for RpcMethods {
 	}
 }
 
+arg_enum! {
+	/// Syncing mode.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy, PartialEq)]
+	pub enum SyncMode {
+		// Full sync. Download and verify all blocks.
+		Full,
+		// Download blocks without executing them. Download latest state with proofs.
+		Fast,
+	}
+}
+
+impl Into<tc_network::config::SyncMode> for SyncMode {
+	fn into(self) -> tc_network::config::SyncMode {
+		match self {
+			SyncMode::Full => tc_network::config::SyncMode::Full,
+			SyncMode::Fast => tc_network::config::SyncMode::Fast,
+		}
+	}
+}
+
 arg_enum! {
 	/// Database backend
 	#[allow(missing_docs)]
@@ -0,0 +1,174 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::arg_enums::Database;
+use crate::error;
+use crate::params::{DatabaseParams, SharedParams};
+use crate::CliConfiguration;
+use log::info;
+use tc_service::{chain_ops, DatabaseConfig};
+use tp_runtime::traits::Block as BlockT;
+use std::fs;
+use structopt::StructOpt;
+
+/// The `db` command used to inspect and repair the database of a stopped node.
+#[derive(Debug, StructOpt)]
+pub struct DbCmd {
+	#[allow(missing_docs)]
+	#[structopt(subcommand)]
+	pub subcommand: DbSubcommand,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+/// Database maintenance operations.
+#[derive(Debug, StructOpt)]
+pub enum DbSubcommand {
+	/// Check that the headers, bodies, lookup keys, meta keys and state roots are consistent.
+	Verify,
+
+	/// Rebuild the lookup keys, the leaves and the best and finalized blocks from the headers.
+	Rebuild,
+
+	/// Print the number of entries and the size of each column.
+	Sizes,
+
+	/// Convert the database of the chain to another backend.
+	///
+	/// The database is copied to the directory used by the chain for the new backend, i.e.
+	/// where the node looks for it when started with `--database`, and the current database
+	/// is removed afterwards. If the copy fails, the new database is removed and the current
+	/// one is kept, so the conversion can be retried.
+	///
+	/// Only RocksDb databases can be converted, to TetsyDb: TetsyDb stores hashes of the keys of
+	/// its columns, so the auxiliary and offchain data can't be copied out of it. The journaled
+	/// state of a RocksDb database is stored by node hash with a reference count in TetsyDb.
+	Convert {
+		/// Backend of the new database.
+		#[structopt(
+			long = "to",
+			value_name = "DB",
+			possible_values = &Database::variants(),
+			case_insensitive = true,
+		)]
+		to: Database,
+
+		/// Keep the current database after the conversion.
+		#[structopt(long = "keep-source")]
+		keep_source: bool,
+	},
+}
+
+impl DbCmd {
+	/// Run the db command
+	pub fn run<B: BlockT>(&self, database_config: DatabaseConfig) -> error::Result<()> {
+		match &self.subcommand {
+			DbSubcommand::Verify => {
+				let report = chain_ops::verify_database::<B>(&database_config)?;
+				for issue in &report.issues {
+					println!("{}", issue);
+				}
+				println!(
+					"{} headers, {} without body, {} issues.",
+					report.headers,
+					report.missing_bodies,
+					report.issues.len(),
+				);
+				if report.issues.is_empty() {
+					Ok(())
+				} else {
+					Err(tp_blockchain::Error::Backend(
+						"The database is inconsistent, run `db rebuild` to repair it".into(),
+					).into())
+				}
+			},
+			DbSubcommand::Rebuild => {
+				let report = chain_ops::rebuild_database::<B>(&database_config)?;
+				println!(
+					"Rebuilt {} headers, best: #{} ({}), finalized: #{} ({}), {} leaves.",
+					report.headers,
+					report.best.0,
+					report.best.1,
+					report.finalized.0,
+					report.finalized.1,
+					report.leaves,
+				);
+				Ok(())
+			},
+			DbSubcommand::Sizes => {
+				let stats = chain_ops::database_column_stats::<B>(&database_config)?;
+				println!("{:<16}{:>12}{:>16}{:>16}", "column", "entries", "keys (bytes)", "values (bytes)");
+				for column in stats {
+					println!(
+						"{:<16}{:>12}{:>16}{:>16}",
+						column.name,
+						column.entries,
+						column.key_bytes,
+						column.value_bytes,
+					);
+				}
+				Ok(())
+			},
+			DbSubcommand::Convert { to, keep_source } => {
+				let source_path = database_config.path()
+					.ok_or_else(||
+						error::Error::Input("Cannot convert custom database implementation".into())
+				)?;
+				let parent = source_path.parent()
+					.ok_or_else(|| error::Error::Input("Invalid database path".into()))?;
+				let target = match (to, &database_config) {
+					(Database::TetsyDb, DatabaseConfig::RocksDb { .. }) => DatabaseConfig::TetsyDb {
+						path: parent.join("tetsydb"),
+					},
+					(Database::RocksDb, DatabaseConfig::TetsyDb { .. }) => return Err(error::Error::Input(
+						"TetsyDb databases can't be converted, as the keys of their columns can't be recovered".into(),
+					)),
+					_ => return Err(error::Error::Input(
+						format!("The database already uses {}", database_config),
+					)),
+				};
+
+				info!("Converting {} database at {:?} to {}", database_config, source_path, target);
+				let copied = chain_ops::convert_database::<B>(&database_config, &target)?;
+				println!("Copied {} entries to {:?}.", copied, target.path().unwrap_or(parent));
+
+				if !keep_source {
+					fs::remove_dir_all(source_path)?;
+					println!("{:?} removed.", source_path);
+				}
+				Ok(())
+			},
+		}
+	}
+}
+
+impl CliConfiguration for DbCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
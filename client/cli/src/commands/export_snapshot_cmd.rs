@@ -0,0 +1,105 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2020-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error;
+use crate::params::{BlockNumberOrHash, DatabaseParams, PruningParams, SharedParams};
+use crate::CliConfiguration;
+use log::info;
+use tc_service::chain_ops::export_state_snapshot;
+use tc_client_api::{ProofProvider, StorageProvider};
+use tp_blockchain::HeaderBackend;
+use tp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+/// The `export-snapshot` command used to export the state of a finalized block
+/// together with its header chain.
+#[derive(Debug, StructOpt)]
+pub struct ExportSnapshotCmd {
+	/// Output file name or stdout if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Block hash or number of the snapshot.
+	///
+	/// Default is the last finalized block.
+	#[structopt(long = "at", value_name = "HASH or NUMBER")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// Maximum number of storage entries in a single snapshot chunk.
+	#[structopt(long = "chunk-size", value_name = "COUNT", default_value = "4096")]
+	pub chunk_size: usize,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl ExportSnapshotCmd {
+	/// Run the export-snapshot command
+	pub async fn run<B, BA, C>(
+		&self,
+		client: Arc<C>,
+	) -> error::Result<()>
+	where
+		B: BlockT,
+		C: HeaderBackend<B> + StorageProvider<B, BA> + ProofProvider<B>,
+		BA: tc_client_api::backend::Backend<B>,
+		B::Hash: FromStr,
+		<B::Hash as FromStr>::Err: Debug,
+		<<B::Header as HeaderT>::Number as FromStr>::Err: Debug,
+	{
+		let block_id = self.at.as_ref().map(|b| b.parse()).transpose()?;
+
+		let file: Box<dyn io::Write> = match &self.output {
+			Some(filename) => Box::new(io::BufWriter::new(fs::File::create(filename)?)),
+			None => Box::new(io::stdout()),
+		};
+
+		info!("Exporting state snapshot...");
+		export_state_snapshot(client, block_id, file, self.chunk_size)
+			.map_err(Into::into)
+	}
+}
+
+impl CliConfiguration for ExportSnapshotCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -0,0 +1,103 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2020-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error;
+use crate::params::{DatabaseParams, PruningParams, SharedParams};
+use crate::CliConfiguration;
+use log::warn;
+use tc_client_api::Backend;
+use tc_service::{chain_ops::import_state_snapshot, DatabaseConfig, TFullBackend};
+use tp_blockchain::HeaderBackend;
+use tp_runtime::traits::Block as BlockT;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+/// The `import-snapshot` command used to bootstrap an empty database from a state snapshot.
+#[derive(Debug, StructOpt)]
+pub struct ImportSnapshotCmd {
+	/// Input file or stdin if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub input: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl ImportSnapshotCmd {
+	/// Run the import-snapshot command
+	///
+	/// The backend must be opened without a client, so that the genesis block of the
+	/// empty database is not initialized from the chain spec. The snapshot must start at
+	/// `genesis_hash`, the genesis block of the chain spec.
+	///
+	/// The state is written to the database while the snapshot is read, so the database at
+	/// `database_config` is removed if the import fails.
+	pub fn run<B: BlockT>(
+		&self,
+		backend: Arc<TFullBackend<B>>,
+		genesis_hash: B::Hash,
+		database_config: &DatabaseConfig,
+	) -> error::Result<()> {
+		if backend.blockchain().info().genesis_hash != Default::default() {
+			return Err(error::Error::Input(
+				"State snapshots can only be imported into an empty database".into()
+			));
+		}
+
+		let file: Box<dyn Read> = match &self.input {
+			Some(filename) => Box::new(io::BufReader::new(fs::File::open(filename)?)),
+			None => Box::new(io::stdin()),
+		};
+
+		// The backend is dropped by the import, which closes the database before its removal.
+		if let Err(e) = import_state_snapshot(backend, genesis_hash, file) {
+			if let Some(db_path) = database_config.path() {
+				warn!("Removing partially imported database {:?}", db_path);
+				fs::remove_dir_all(db_path)?;
+			}
+			return Err(e.into());
+		}
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ImportSnapshotCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
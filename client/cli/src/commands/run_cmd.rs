@@ -27,11 +27,15 @@ use crate::params::TransactionPoolParams;
 use crate::CliConfiguration;
 use regex::Regex;
 use tc_service::{
-	config::{BasePath, MultiaddrWithPeerId, PrometheusConfig, TransactionPoolOptions},
+	config::{
+		BasePath, MultiaddrWithPeerId, PrometheusConfig, RateLimit, RpcLimits, RpcMethodFilters,
+		TransactionPoolOptions,
+	},
 	ChainSpec, Role,
 };
 use tc_telemetry::TelemetryEndpoints;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 /// The `run` command used to run a node.
@@ -103,6 +107,15 @@ pub struct RunCmd {
 	)]
 	pub rpc_methods: RpcMethods,
 
+	/// Path to a JSON file listing the RPC methods allowed and denied by the HTTP & WS servers.
+	///
+	/// The file holds a filter per server, each of them with an `allow` and a `deny` list of
+	/// method names or prefixes ending with `*`, applied on top of `--rpc-methods`:
+	///
+	/// `{ "http": { "allow": ["chain_*", "state_getStorage"] }, "ws": { "deny": ["state_getPairs"] } }`
+	#[structopt(long = "rpc-methods-config", value_name = "PATH", parse(from_os_str))]
+	pub rpc_methods_config: Option<PathBuf>,
+
 	/// Listen to all Websocket interfaces.
 	///
 	/// Default is local. Note: not all RPC methods are safe to be exposed publicly. Use an RPC proxy
@@ -139,6 +152,32 @@ pub struct RunCmd {
 	#[structopt(long = "ws-max-connections", value_name = "COUNT")]
 	pub ws_max_connections: Option<usize>,
 
+	/// Maximum number of calls per second of a single RPC connection.
+	///
+	/// Only applies to WS connections, the HTTP server doesn't expose the address of its
+	/// clients. Use a reverse proxy to rate limit HTTP clients.
+	#[structopt(long = "rpc-rate-limit", value_name = "CALLS")]
+	pub rpc_rate_limit: Option<u32>,
+
+	/// Maximum number of calls per second of a single RPC connection to the given method.
+	///
+	/// Given as `METHOD=CALLS`, e.g. `state_getKeysPaged=5`. Can be passed multiple times.
+	/// The limit also applies to the aliases of the method. Only applies to WS connections.
+	#[structopt(
+		long = "rpc-method-rate-limit",
+		value_name = "METHOD=CALLS",
+		parse(try_from_str = parse_method_rate_limit)
+	)]
+	pub rpc_method_rate_limit: Vec<(String, u32)>,
+
+	/// Maximum number of concurrent subscriptions of a single RPC connection.
+	#[structopt(long = "rpc-max-subscriptions", value_name = "COUNT")]
+	pub rpc_max_subscriptions: Option<usize>,
+
+	/// Maximum number of calls in a single RPC batch request.
+	#[structopt(long = "rpc-max-batch-size", value_name = "COUNT")]
+	pub rpc_max_batch_size: Option<usize>,
+
 	/// Specify browser Origins allowed to access the HTTP & WS RPC servers.
 	///
 	/// A comma-separated list of origins (protocol://domain or special `null`
@@ -410,6 +449,17 @@ impl CliConfiguration for RunCmd {
 		Ok(self.ws_max_connections)
 	}
 
+	fn rpc_limits(&self) -> Result<RpcLimits> {
+		Ok(RpcLimits {
+			calls: self.rpc_rate_limit.map(RateLimit::per_second),
+			methods: self.rpc_method_rate_limit.iter()
+				.map(|(method, calls)| (method.clone(), RateLimit::per_second(*calls)))
+				.collect(),
+			max_subscriptions: self.rpc_max_subscriptions,
+			max_batch_size: self.rpc_max_batch_size,
+		})
+	}
+
 	fn rpc_cors(&self, is_dev: bool) -> Result<Option<Vec<String>>> {
 		Ok(self
 			.rpc_cors
@@ -461,6 +511,18 @@ impl CliConfiguration for RunCmd {
 		Ok(self.rpc_methods.into())
 	}
 
+	fn rpc_method_filters(&self) -> Result<RpcMethodFilters> {
+		let path = match &self.rpc_methods_config {
+			Some(path) => path,
+			None => return Ok(Default::default()),
+		};
+
+		let file = std::fs::File::open(path)?;
+		serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| Error::Input(
+			format!("Invalid RPC methods config {}: {}", path.display(), e)
+		))
+	}
+
 	fn transaction_pool(&self) -> Result<TransactionPoolOptions> {
 		Ok(self.pool_config.transaction_pool())
 	}
@@ -561,6 +623,18 @@ fn parse_telemetry_endpoints(s: &str) -> std::result::Result<(String, u8), Telem
 	}
 }
 
+/// Parse a `METHOD=CALLS` rate limit.
+fn parse_method_rate_limit(s: &str) -> std::result::Result<(String, u32), String> {
+	let mut parts = s.splitn(2, '=');
+	match (parts.next(), parts.next()) {
+		(Some(method), Some(calls)) if !method.is_empty() => calls
+			.parse()
+			.map(|calls| (method.to_owned(), calls))
+			.map_err(|e| format!("Invalid rate limit of `{}`: {}", method, e)),
+		_ => Err(format!("Expected `METHOD=CALLS`, got `{}`", s)),
+	}
+}
+
 /// CORS setting
 ///
 /// The type is introduced to overcome `Option<Option<T>>`
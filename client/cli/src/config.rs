@@ -29,10 +29,10 @@ use names::{Generator, Name};
 use tc_client_api::execution_extensions::ExecutionStrategies;
 use tc_service::config::{
 	BasePath, Configuration, DatabaseConfig, ExtTransport, KeystoreConfig, NetworkConfiguration,
-	NodeKeyConfig, OffchainWorkerConfig, PrometheusConfig, PruningMode, Role, RpcMethods,
-	TaskExecutor, TelemetryEndpoints, TransactionPoolOptions, WasmExecutionMethod,
+	NodeKeyConfig, OffchainWorkerConfig, PrometheusConfig, PruningMode, Role, RpcLimits,
+	RpcMethodFilters, RpcMethods, TaskExecutor, TelemetryEndpoints, TransactionPoolOptions, WasmExecutionMethod,
 };
-use tc_service::{ChainSpec, TracingReceiver, KeepBlocks, TransactionStorageMode};
+use tc_service::{ChainSpec, TracingReceiver, KeepBlocks, TransactionStorageMode, StateStorageMode};
 use tc_telemetry::{TelemetryHandle, TelemetrySpan};
 use tc_tracing::logging::LoggerBuilder;
 use std::net::SocketAddr;
@@ -212,6 +212,13 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			.unwrap_or(TransactionStorageMode::BlockBody))
 	}
 
+	/// Get the database state storage scheme.
+	fn database_state_storage(&self) -> Result<StateStorageMode> {
+		Ok(self.database_params()
+			.map(|x| x.state_storage())
+			.unwrap_or(StateStorageMode::Journaled))
+	}
+
 	/// Get the database backend variant.
 	///
 	/// By default this is retrieved from `DatabaseParams` if it is available. Otherwise its `None`.
@@ -350,6 +357,13 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 		Ok(Default::default())
 	}
 
+	/// Get the methods allowed and denied by the HTTP & WS RPC servers.
+	///
+	/// By default all the methods selected by `rpc_methods` are exposed.
+	fn rpc_method_filters(&self) -> Result<RpcMethodFilters> {
+		Ok(Default::default())
+	}
+
 	/// Get the RPC websockets maximum connections (`None` if unlimited).
 	///
 	/// By default this is `None`.
@@ -357,6 +371,13 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 		Ok(None)
 	}
 
+	/// Get the limits applied to the calls of each RPC connection.
+	///
+	/// By default no limits are applied.
+	fn rpc_limits(&self) -> Result<RpcLimits> {
+		Ok(Default::default())
+	}
+
 	/// Get the RPC cors (`None` if disabled)
 	///
 	/// By default this is `Some(Vec::new())`.
@@ -501,11 +522,16 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			.map(|p| p.unsafe_pruning)
 			.unwrap_or(false);
 
+		let mut transaction_pool = self.transaction_pool()?;
+		if let Some(journal) = transaction_pool.journal.as_mut() {
+			*journal = base_path.path().join(&*journal);
+		}
+
 		Ok(Configuration {
 			impl_name: C::impl_name(),
 			impl_version: C::impl_version(),
 			task_executor,
-			transaction_pool: self.transaction_pool()?,
+			transaction_pool,
 			network: self.network_config(
 				&chain_spec,
 				is_dev,
@@ -523,6 +549,7 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			state_pruning: self.state_pruning(unsafe_pruning, &role)?,
 			keep_blocks: self.keep_blocks()?,
 			transaction_storage: self.database_transaction_storage()?,
+			state_storage: self.database_state_storage()?,
 			wasm_method: self.wasm_method()?,
 			wasm_runtime_overrides: self.wasm_runtime_overrides(),
 			execution_strategies: self.execution_strategies(is_dev, is_validator)?,
@@ -530,7 +557,9 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			rpc_ws: self.rpc_ws(DCV::rpc_ws_listen_port())?,
 			rpc_ipc: self.rpc_ipc()?,
 			rpc_methods: self.rpc_methods()?,
+			rpc_method_filters: self.rpc_method_filters()?,
 			rpc_ws_max_connections: self.rpc_ws_max_connections()?,
+			rpc_limits: self.rpc_limits()?,
 			rpc_cors: self.rpc_cors(is_dev)?,
 			prometheus_config: self.prometheus_config(DCV::prometheus_listen_port())?,
 			telemetry_endpoints,
@@ -18,7 +18,7 @@
 
 use crate::arg_enums::Database;
 use structopt::StructOpt;
-use tc_service::TransactionStorageMode;
+use tc_service::{TransactionStorageMode, StateStorageMode};
 
 /// Parameters for block import.
 #[derive(Debug, StructOpt)]
@@ -44,6 +44,14 @@ pub struct DatabaseParams {
 	/// in the block body column.
 	#[structopt(long)]
 	pub storage_chain: bool,
+
+	/// Store state trie nodes with reference counts.
+	///
+	/// Pruning then only updates the reference counts of the changed nodes.
+	/// Can only be enabled when the database is created, a database that was
+	/// created with a different setting will fail to open.
+	#[structopt(long)]
+	pub state_ref_counting: bool,
 }
 
 impl DatabaseParams {
@@ -65,4 +73,13 @@ impl DatabaseParams {
 			TransactionStorageMode::BlockBody
 		}
 	}
+
+	/// State trie node storage scheme.
+	pub fn state_storage(&self) -> StateStorageMode {
+		if self.state_ref_counting {
+			StateStorageMode::RefCounted
+		} else {
+			StateStorageMode::Journaled
+		}
+	}
 }
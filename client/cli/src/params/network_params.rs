@@ -16,13 +16,17 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::arg_enums::SyncMode;
 use crate::params::node_key_params::NodeKeyParams;
 use tc_network::{
-	config::{NetworkConfiguration, NodeKeyConfig, NonReservedPeerMode, SetConfig, TransportConfig},
+	config::{
+		IpFilterConfig, IpNetwork, NetworkConfiguration, NodeKeyConfig, NonReservedPeerMode,
+		SetConfig, TransportConfig,
+	},
 	multiaddr::Protocol,
 };
 use tc_service::{ChainSpec, ChainType, config::{Multiaddr, MultiaddrWithPeerId}};
-use std::path::PathBuf;
+use std::{net::IpAddr, path::PathBuf};
 use structopt::StructOpt;
 
 /// Parameters used to create the network configuration.
@@ -70,6 +74,40 @@ pub struct NetworkParams {
 	#[structopt(long = "in-peers", value_name = "COUNT", default_value = "25")]
 	pub in_peers: u32,
 
+	/// Only allow connections with IP addresses within these ranges.
+	///
+	/// Ranges are given in CIDR notation, e.g. `10.0.0.0/8`, or as single IP addresses.
+	#[structopt(long = "allow-ips", value_name = "CIDR", parse(try_from_str = parse_ip_network))]
+	pub allow_ips: Vec<IpNetwork>,
+
+	/// Deny connections with IP addresses within these ranges.
+	///
+	/// Ranges are given in CIDR notation, e.g. `10.0.0.0/8`, or as single IP addresses.
+	#[structopt(long = "deny-ips", value_name = "CIDR", parse(try_from_str = parse_ip_network))]
+	pub deny_ips: Vec<IpNetwork>,
+
+	/// Maximum number of incoming connections accepted from a single IP address.
+	#[structopt(long = "max-inbound-per-ip", value_name = "COUNT")]
+	pub max_inbound_per_ip: Option<u32>,
+
+	/// Maximum number of incoming connections accepted from a single subnet, i.e. a `/24` for
+	/// IPv4 and a `/64` for IPv6 addresses.
+	#[structopt(long = "max-inbound-per-subnet", value_name = "COUNT")]
+	pub max_inbound_per_subnet: Option<u32>,
+
+	/// Limit the outbound bandwidth of a notifications protocol, all peers combined.
+	///
+	/// Given as `PROTOCOL=BYTES`, where `BYTES` is the number of bytes per second, e.g.
+	/// `/dot/transactions/1=100000`. Can be passed multiple times. Notifications over the limit
+	/// are delayed or dropped, so capping consensus protocols such as GRANDPA can stall them.
+	/// The block announces protocol can't be limited.
+	#[structopt(
+		long = "outbound-bandwidth-cap",
+		value_name = "PROTOCOL=BYTES",
+		parse(try_from_str = parse_bandwidth_cap)
+	)]
+	pub outbound_bandwidth_caps: Vec<(String, u64)>,
+
 	/// Disable mDNS discovery.
 	///
 	/// By default, the network will use mDNS to discover other nodes on the
@@ -88,6 +126,16 @@ pub struct NetworkParams {
 	)]
 	pub max_parallel_downloads: u32,
 
+	/// Maximum number of blocks to ask a peer in a single request during sync.
+	///
+	/// The actual number adapts to the throughput of each peer, starting from 128 blocks.
+	#[structopt(
+		long = "max-blocks-per-request",
+		value_name = "COUNT",
+		default_value = "512"
+	)]
+	pub max_blocks_per_request: u32,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams,
@@ -106,6 +154,21 @@ pub struct NetworkParams {
 	/// security improvements.
 	#[structopt(long)]
 	pub kademlia_disjoint_query_paths: bool,
+
+	/// Blockchain syncing mode.
+	///
+	/// - `Full`: Download and validate full blockchain history.
+	///
+	/// - `Fast`: Download blocks and the latest finalized state, then execute the
+	///   blocks after it.
+	#[structopt(
+		long = "sync",
+		value_name = "SYNC_MODE",
+		possible_values = &SyncMode::variants(),
+		case_insensitive = true,
+		default_value = "Full",
+	)]
+	pub sync: SyncMode,
 }
 
 impl NetworkParams {
@@ -173,9 +236,46 @@ impl NetworkParams {
 				wasm_external_transport: None,
 			},
 			max_parallel_downloads: self.max_parallel_downloads,
+			max_blocks_per_request: self.max_blocks_per_request,
+			sync_mode: self.sync.into(),
 			allow_non_globals_in_dht,
 			kademlia_disjoint_query_paths: self.kademlia_disjoint_query_paths,
+			ip_filter: IpFilterConfig {
+				allow: self.allow_ips.clone(),
+				deny: self.deny_ips.clone(),
+				max_inbound_per_ip: self.max_inbound_per_ip,
+				max_inbound_per_subnet: self.max_inbound_per_subnet,
+			},
+			outbound_bandwidth_caps: self.outbound_bandwidth_caps.iter()
+				.map(|(protocol, bytes_per_sec)| (protocol.clone().into(), *bytes_per_sec))
+				.collect(),
 			remux_window_size: None,
 		}
 	}
 }
+
+/// Parses an IP network in CIDR notation, or a single IP address.
+fn parse_ip_network(s: &str) -> Result<IpNetwork, String> {
+	let mut parts = s.splitn(2, '/');
+	let ip = parts.next().unwrap_or_default().parse::<IpAddr>()
+		.map_err(|e| format!("Invalid IP address in {}: {}", s, e))?;
+	let netmask = match parts.next() {
+		Some(netmask) => netmask.parse::<u8>()
+			.map_err(|e| format!("Invalid netmask in {}: {}", s, e))?,
+		None if ip.is_ipv4() => 32,
+		None => 128,
+	};
+
+	IpNetwork::new(ip, netmask).map_err(|e| format!("Invalid IP network {}: {:?}", s, e))
+}
+
+/// Parses an outbound bandwidth cap of the form `PROTOCOL=BYTES`.
+fn parse_bandwidth_cap(s: &str) -> Result<(String, u64), String> {
+	let mut parts = s.rsplitn(2, '=');
+	let bytes_per_sec = parts.next().unwrap_or_default().parse::<u64>()
+		.map_err(|e| format!("Invalid number of bytes per second in {}: {}", s, e))?;
+	match parts.next() {
+		Some(protocol) if !protocol.is_empty() => Ok((protocol.to_string(), bytes_per_sec)),
+		_ => Err(format!("Missing protocol name in {}", s)),
+	}
+}
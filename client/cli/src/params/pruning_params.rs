@@ -20,6 +20,9 @@ use crate::error;
 use tc_service::{PruningMode, Role, KeepBlocks};
 use structopt::StructOpt;
 
+/// Number of blocks whose state is kept by default on non-archive nodes.
+const DEFAULT_KEEP_BLOCKS: u32 = 256;
+
 /// Parameters to define the pruning mode
 #[derive(Debug, StructOpt)]
 pub struct PruningParams {
@@ -30,6 +33,13 @@ pub struct PruningParams {
 	/// 256 blocks.
 	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
 	pub pruning: Option<String>,
+	/// Specify the maximum memory, in MiB, used by the state of non-finalized blocks.
+	///
+	/// State of the oldest non-finalized blocks beyond this limit is only kept
+	/// on disk. Useful when finality stalls for a long time. Only applies to
+	/// pruned (i.e. non-archive) nodes. Default is to keep all of it in memory.
+	#[structopt(long = "pruning-overlay-mem", value_name = "MiB")]
+	pub pruning_overlay_mem: Option<usize>,
 	/// Specify the number of finalized blocks to keep in the database.
 	///
 	/// Default is to keep all blocks.
@@ -44,10 +54,10 @@ impl PruningParams {
 		// `ArchiveAll`), otherwise we keep state for the last 256 blocks. if the
 		// node is an authority and pruning is enabled explicitly, then we error
 		// unless `unsafe_pruning` is set.
-		Ok(match &self.pruning {
-			Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
-			None if role.is_network_authority() => PruningMode::ArchiveAll,
-			None => PruningMode::default(),
+		let keep_blocks = match &self.pruning {
+			Some(ref s) if s == "archive" => None,
+			None if role.is_network_authority() => None,
+			None => Some(DEFAULT_KEEP_BLOCKS),
 			Some(s) => {
 				if role.is_network_authority() && !unsafe_pruning {
 					return Err(error::Error::Input(
@@ -57,10 +67,24 @@ impl PruningParams {
 					));
 				}
 
-				PruningMode::keep_blocks(s.parse().map_err(|_| {
+				Some(s.parse().map_err(|_| {
 					error::Error::Input("Invalid pruning mode specified".to_string())
 				})?)
 			}
+		};
+
+		Ok(match (keep_blocks, self.pruning_overlay_mem) {
+			(None, None) => PruningMode::ArchiveAll,
+			(None, Some(_)) => return Err(error::Error::Input(
+				"`--pruning-overlay-mem` can't be used with archive nodes".to_string(),
+			)),
+			(Some(n), None) => PruningMode::keep_blocks(n),
+			(Some(n), Some(mem)) => {
+				let mem = mem.checked_mul(1024 * 1024).ok_or_else(|| error::Error::Input(
+					"`--pruning-overlay-mem` is too large".to_string(),
+				))?;
+				PruningMode::keep_blocks_with_overlay_limit(n, mem)
+			},
 		})
 	}
 
@@ -18,6 +18,7 @@
 
 use tc_service::config::TransactionPoolOptions;
 use structopt::StructOpt;
+use std::path::PathBuf;
 
 /// Parameters used to create the pool configuration.
 #[derive(Debug, StructOpt)]
@@ -29,6 +30,34 @@ pub struct TransactionPoolParams {
 	/// Maximum number of kilobytes of all transactions stored in the pool.
 	#[structopt(long = "pool-kbytes", value_name = "COUNT", default_value = "20480")]
 	pub pool_kbytes: usize,
+
+	/// Maximum number of ready transactions of a single sender in the transaction pool.
+	///
+	/// Senders are identified by the tags their transactions provide. A tenth of this limit
+	/// applies to the future transactions of each sender. Unlimited by default.
+	#[structopt(long = "pool-sender-limit", value_name = "COUNT")]
+	pub pool_sender_limit: Option<usize>,
+
+	/// Length, in bytes, of the prefix of the tags provided by a transaction identifying its sender.
+	///
+	/// Used to attribute the transactions to their sender for `--pool-sender-limit` and when the
+	/// pool is full.
+	#[structopt(long = "pool-sender-tag-prefix", value_name = "BYTES", default_value = "32")]
+	pub pool_sender_tag_prefix: usize,
+
+	/// Minimal priority increase, in percent, for a transaction to replace a pending one.
+	///
+	/// By default any strictly higher priority is enough.
+	#[structopt(long = "pool-replace-bump", value_name = "PERCENT", default_value = "0")]
+	pub pool_replace_bump: u32,
+
+	/// Path of a journal persisting the transactions submitted to this node.
+	///
+	/// Journaled transactions that were not finalized yet are submitted again when the node
+	/// restarts. Transactions received from peers are not journaled. A relative path is
+	/// resolved against the base path.
+	#[structopt(long = "pool-journal", value_name = "PATH", parse(from_os_str))]
+	pub pool_journal: Option<PathBuf>,
 }
 
 impl TransactionPoolParams {
@@ -45,6 +74,16 @@ impl TransactionPoolParams {
 		opts.future.count = self.pool_limit / factor;
 		opts.future.total_bytes = self.pool_kbytes * 1024 / factor;
 
+		// per-sender quotas
+		opts.sender_tag_prefix = self.pool_sender_tag_prefix;
+		if let Some(limit) = self.pool_sender_limit {
+			opts.sender.ready = Some(limit);
+			opts.sender.future = Some(std::cmp::max(1, limit / factor));
+		}
+
+		opts.min_replace_bump = self.pool_replace_bump;
+		opts.journal = self.pool_journal.clone();
+
 		opts
 	}
 }
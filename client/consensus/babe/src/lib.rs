@@ -1175,6 +1175,11 @@ impl<Block, Client, Inner> BlockImport<Block> for BabeBlockImport<Block, Client,
 		// early exit if block already in chain, otherwise the check for
 		// epoch changes will error when trying to re-import an epoch change
 		match self.client.status(BlockId::Hash(hash)) {
+			Ok(tp_blockchain::BlockStatus::InChain) if block.import_existing => {
+				// epoch changes were processed when the block was first imported,
+				// only its state is imported now.
+				return self.inner.import_block(block, new_cache).map_err(Into::into);
+			},
 			Ok(tp_blockchain::BlockStatus::InChain) => return Ok(ImportResult::AlreadyInChain),
 			Ok(tp_blockchain::BlockStatus::Unknown) => {},
 			Err(e) => return Err(ConsensusError::ClientImport(e.to_string())),
@@ -30,6 +30,7 @@
 
 pub mod light;
 pub mod offchain;
+pub mod maintenance;
 
 #[cfg(any(feature = "with-tetsy-kvdb-rocksdb", test))]
 pub mod bench;
@@ -38,6 +39,8 @@ mod children;
 mod cache;
 mod changes_tries_storage;
 mod storage_cache;
+mod ref_counted;
+mod snapshot;
 #[cfg(any(feature = "with-tetsy-kvdb-rocksdb", test))]
 mod upgrade;
 mod utils;
@@ -68,7 +71,8 @@ use tp_trie::{MemoryDB, PrefixedMemoryDB, prefixed_key};
 use tetcore_database::Transaction;
 use tet_core::{Hasher, ChangesTrieConfiguration};
 use tet_core::offchain::OffchainOverlayedChange;
-use tet_core::storage::{well_known_keys, ChildInfo};
+use tet_core::storage::{well_known_keys, ChildInfo, ChildType, PrefixedStorageKey};
+use tet_core::hexdisplay::HexDisplay;
 use arithmetic::traits::Saturating;
 use tp_runtime::{generic::{DigestItem, BlockId}, Justification, Storage};
 use tp_runtime::traits::{
@@ -89,6 +93,7 @@ use crate::stats::StateUsageStats;
 // Re-export the Database trait so that one can pass an implementation of it.
 pub use tetcore_database::Database;
 pub use tc_state_db::PruningMode;
+pub use snapshot::SnapshotEntry;
 
 #[cfg(any(feature = "with-kvdb-rocksdb", test))]
 pub use bench::BenchmarkingState;
@@ -272,6 +277,8 @@ pub struct DatabaseSettings {
 	pub keep_blocks: KeepBlocks,
 	/// Block body/Transaction storage scheme.
 	pub transaction_storage: TransactionStorageMode,
+	/// State trie node storage scheme.
+	pub state_storage: StateStorageMode,
 }
 
 /// Block pruning settings.
@@ -293,6 +300,29 @@ pub enum TransactionStorageMode {
 	StorageChain,
 }
 
+/// State trie node storage scheme. Can only be selected when the database is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateStorageMode {
+	/// Store nodes keyed by prefixed hash. Re-inserted nodes are tracked by
+	/// the state db pruning journal.
+	Journaled,
+	/// Store each node with a reference count that is updated on insertion,
+	/// canonicalization and pruning. The nodes deleted by a block are not journaled
+	/// but found when it is pruned. Databases with native reference counting
+	/// (tetsy-db) always use this mode.
+	RefCounted,
+}
+
+impl StateStorageMode {
+	/// Identifier of the mode stored in the database metadata.
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			StateStorageMode::Journaled => "journaled",
+			StateStorageMode::RefCounted => "ref_counted",
+		}
+	}
+}
+
 /// Where to find the database..
 #[derive(Debug, Clone)]
 pub enum DatabaseSettingsSrc {
@@ -332,6 +362,17 @@ impl DatabaseSettingsSrc {
 	}
 }
 
+impl DatabaseSettings {
+	/// State storage scheme that is actually used by the database.
+	pub fn state_storage(&self) -> StateStorageMode {
+		if self.source.supports_ref_counting() {
+			StateStorageMode::RefCounted
+		} else {
+			self.state_storage
+		}
+	}
+}
+
 impl std::fmt::Display for DatabaseSettingsSrc {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		let name = match self {
@@ -676,6 +717,7 @@ pub struct BlockImportOperation<Block: BlockT> {
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
 	set_head: Option<BlockId<Block>>,
 	commit_state: bool,
+	reset_storage: bool,
 }
 
 impl<Block: BlockT> BlockImportOperation<Block> {
@@ -767,6 +809,7 @@ impl<Block: BlockT> tc_client_api::backend::BlockImportOperation<Block> for Bloc
 		self.db_updates = transaction;
 		self.changes_trie_config_update = Some(changes_trie_config);
 		self.commit_state = true;
+		self.reset_storage = true;
 		Ok(root)
 	}
 
@@ -847,6 +890,14 @@ impl<Block: BlockT> tc_state_db::NodeDb for StorageDb<Block> {
 	}
 }
 
+impl<Block: BlockT> tc_state_db::MetaDb for StorageDb<Block> {
+	type Error = io::Error;
+
+	fn get_meta(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		Ok(self.db.get(columns::STATE_META, key))
+	}
+}
+
 struct DbGenesisStorage<Block: BlockT>(pub Block::Hash);
 
 impl<Block: BlockT> DbGenesisStorage<Block> {
@@ -927,7 +978,7 @@ impl<Block: BlockT> Backend<Block> {
 	///
 	/// The pruning window is how old a block must be before the state is pruned.
 	pub fn new(config: DatabaseSettings, canonicalization_delay: u64) -> ClientResult<Self> {
-		let db = crate::utils::open_database::<Block>(&config, DatabaseType::Full)?;
+		let db = crate::utils::open_database::<Block>(&config.source, DatabaseType::Full)?;
 		Self::from_database(db as Arc<_>, canonicalization_delay, &config)
 	}
 
@@ -957,6 +1008,7 @@ impl<Block: BlockT> Backend<Block> {
 			source: DatabaseSettingsSrc::Custom(db),
 			keep_blocks: KeepBlocks::Some(keep_blocks),
 			transaction_storage,
+			state_storage: StateStorageMode::Journaled,
 		};
 
 		Self::new(db_setting, canonicalization_delay).expect("failed to create test-db")
@@ -968,18 +1020,34 @@ impl<Block: BlockT> Backend<Block> {
 		config: &DatabaseSettings,
 	) -> ClientResult<Self> {
 		let is_archive_pruning = config.state_pruning.is_archive();
+		let state_storage = config.state_storage();
+		let legacy_state_storage = if config.source.supports_ref_counting() {
+			StateStorageMode::RefCounted
+		} else {
+			StateStorageMode::Journaled
+		};
+		crate::utils::check_state_storage_mode(&*db, state_storage, legacy_state_storage)?;
+		let db = if state_storage == StateStorageMode::RefCounted && !config.source.supports_ref_counting() {
+			ref_counted::wrap(db)
+		} else {
+			db
+		};
+		let ref_counting = state_storage == StateStorageMode::RefCounted;
 		let blockchain = BlockchainDb::new(db.clone(), config.transaction_storage.clone())?;
 		let meta = blockchain.meta.clone();
 		let map_e = |e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e);
+		// With reference counting the nodes deleted by a block are found when it is pruned,
+		// see `remove_pruned_nodes`.
 		let state_db: StateDb<_, _> = StateDb::new(
 			config.state_pruning.clone(),
-			!config.source.supports_ref_counting(),
+			!ref_counting,
+			!ref_counting,
 			&StateMetaDb(&*db),
 		).map_err(map_e)?;
 		let storage_db = StorageDb {
 			db: db.clone(),
 			state_db,
-			prefix_keys: !config.source.supports_ref_counting(),
+			prefix_keys: !ref_counting,
 		};
 		let offchain_storage = offchain::LocalStorage::new(db.clone());
 		let changes_tries_storage = DbChangesTrieStorage::new(
@@ -1159,15 +1227,113 @@ impl<Block: BlockT> Backend<Block> {
 						implies existence of blocks with all numbers before it; qed")
 			};
 
+			// Blocks imported without state have nothing to canonicalize.
+			if self.storage.state_db.is_pruned(&hash, new_canonical) {
+				return Ok(())
+			}
+
 			trace!(target: "db", "Canonicalize block #{} ({:?})", new_canonical, hash);
-			let commit = self.storage.state_db.canonicalize_block(&hash)
-				.map_err(|e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e))?;
-			apply_state_commit(transaction, commit);
+			self.canonicalize_state(transaction, &hash)?;
 		};
 
 		Ok(())
 	}
 
+	/// Canonicalize the state of a block and prune the states that fall out of the window.
+	fn canonicalize_state(
+		&self,
+		transaction: &mut Transaction<DbHash>,
+		hash: &Block::Hash,
+	) -> ClientResult<()> {
+		let (commit, unjournaled) = self.storage.state_db.canonicalize_and_prune(hash, &*self.storage)
+			.map_err(|e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e))?;
+		apply_state_commit(transaction, commit);
+		for pruned in unjournaled {
+			self.remove_pruned_nodes(transaction, &pruned)?;
+		}
+		Ok(())
+	}
+
+	/// Remove the nodes deleted by a pruned block, when they are not journaled by the state db.
+	///
+	/// These are the nodes of the state of its parent that its own state doesn't reference from
+	/// the same position, which only takes reading the parts of both states that differ. Both
+	/// states are complete at this point since the parent was pruned first. With reference
+	/// counting nodes are keyed by hash alone, so child trie keyspaces don't matter.
+	fn remove_pruned_nodes(
+		&self,
+		transaction: &mut Transaction<DbHash>,
+		hash: &Block::Hash,
+	) -> ClientResult<()> {
+		type Layout<Block> = tp_trie::Layout<HashFor<Block>>;
+
+		let header = self.blockchain.expect_header(BlockId::Hash(*hash))?;
+		if header.number().is_zero() {
+			return Ok(());
+		}
+		let parent = self.blockchain.expect_header(BlockId::Hash(*header.parent_hash()))?;
+		let empty_root = tp_trie::empty_trie_root::<Layout<Block>>();
+		let (old_root, new_root) = (*parent.state_root(), *header.state_root());
+		// The parent of a block imported with its whole state, e.g. by state sync, has no state.
+		let has_parent_state = old_root == empty_root ||
+			tp_state_machine::Storage::get(&*self.storage, &old_root, tp_trie::EMPTY_PREFIX)
+				.map_err(ClientError::Backend)?
+				.is_some();
+		if !has_parent_state {
+			return Ok(());
+		}
+
+		let map_e = |e| ClientError::Backend(format!("Error pruning state of {:?}: {:?}", hash, e));
+		let old_state = DbState::<Block>::new(self.storage.clone(), old_root);
+		let new_state = DbState::<Block>::new(self.storage.clone(), new_root);
+		let mut removed = Vec::new();
+		let mut child_roots = Vec::new();
+		tp_trie::trie_removed_nodes::<Layout<Block>, _, _, _>(
+			old_state.essence(),
+			&old_root,
+			new_state.essence(),
+			&new_root,
+			|node, _| removed.push(*node),
+		).map_err(map_e)?;
+		tp_trie::trie_diff::<Layout<Block>, _, _, _>(
+			old_state.essence(),
+			&old_root,
+			new_state.essence(),
+			&new_root,
+			well_known_keys::CHILD_STORAGE_KEY_PREFIX,
+			|_, old, new| {
+				child_roots.push((old.map(|r| r.to_vec()), new.map(|r| r.to_vec())));
+				true
+			},
+		).map_err(map_e)?;
+
+		let empty_child_root = tp_trie::empty_child_trie_root::<Layout<Block>>();
+		for (old_child_root, new_child_root) in child_roots {
+			let decode_root = |root: Option<Vec<u8>>| root
+				.map(|root| Block::Hash::decode(&mut &root[..]))
+				.transpose()
+				.map_err(|e| ClientError::Backend(format!("Invalid child trie root: {}", e)));
+			let old_child_root = match decode_root(old_child_root)? {
+				Some(root) => root,
+				None => continue,
+			};
+			let new_child_root = decode_root(new_child_root)?.unwrap_or(empty_child_root);
+			tp_trie::trie_removed_nodes::<Layout<Block>, _, _, _>(
+				old_state.essence(),
+				&old_child_root,
+				new_state.essence(),
+				&new_child_root,
+				|node, _| removed.push(*node),
+			).map_err(map_e)?;
+		}
+
+		trace!(target: "db", "Removing {} state nodes of pruned block {:?}", removed.len(), hash);
+		for node in removed {
+			transaction.remove(columns::STATE, node.as_ref());
+		}
+		Ok(())
+	}
+
 	fn try_commit_operation(
 		&self,
 		mut operation: BlockImportOperation<Block>,
@@ -1300,16 +1466,35 @@ impl<Block: BlockT> Backend<Block> {
 				}
 				self.state_usage.tally_writes(ops, bytes);
 				let number_u64 = number.saturated_into::<u64>();
-				let commit = self.storage.state_db.insert_block(
-					&hash,
-					number_u64,
-					&pending_block.header.parent_hash(),
-					changeset,
-				).map_err(|e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e))?;
+				let commit = if operation.reset_storage && number_u64 != 0 {
+					// The complete state of a block downloaded by state sync. It becomes the new
+					// base of the state db, regardless of the state of its ancestors.
+					self.storage.state_db.insert_synced_block(
+						&hash,
+						number_u64,
+						&pending_block.header.parent_hash(),
+						changeset,
+					)
+				} else {
+					self.storage.state_db.insert_block(
+						&hash,
+						number_u64,
+						&pending_block.header.parent_hash(),
+						changeset,
+					)
+				}.map_err(|e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e))?;
 				apply_state_commit(&mut transaction, commit);
 
 				// Check if need to finalize. Genesis is always finalized instantly.
 				let finalized = number_u64 == 0 || pending_block.leaf_state.is_final();
+				if !finalized
+					&& number <= self.blockchain.meta.read().finalized_number
+					&& ::tc_client_api::blockchain::HeaderBackend::hash(&self.blockchain, number)? == Some(hash)
+				{
+					// The block has been finalized while it was imported without state,
+					// so its state is canonicalized right away.
+					self.canonicalize_state(&mut transaction, &hash)?;
+				}
 				finalized
 			} else {
 				false
@@ -1354,28 +1539,32 @@ impl<Block: BlockT> Backend<Block> {
 
 			debug!(target: "db", "DB Commit {:?} ({}), best = {}", hash, number, is_best);
 
-			let displaced_leaf = {
-				let mut leaves = self.blockchain.leaves.write();
-				let displaced_leaf = leaves.import(hash, number, parent_hash);
-				leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
-
-				displaced_leaf
-			};
-
 			let mut children = children::read_children(
 				&*self.storage.db,
 				columns::META,
 				meta_keys::CHILDREN_PREFIX,
 				parent_hash,
 			)?;
-			children.push(hash);
-			children::write_children(
-				&mut transaction,
-				columns::META,
-				meta_keys::CHILDREN_PREFIX,
-				parent_hash,
-				children,
-			);
+			// A block that was imported without state before is already a child of its parent
+			// and must not become a leaf again.
+			let displaced_leaf = if !children.contains(&hash) {
+				children.push(hash);
+				children::write_children(
+					&mut transaction,
+					columns::META,
+					meta_keys::CHILDREN_PREFIX,
+					parent_hash,
+					children,
+				);
+
+				let mut leaves = self.blockchain.leaves.write();
+				let displaced_leaf = leaves.import(hash, number, parent_hash);
+				leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+
+				displaced_leaf
+			} else {
+				None
+			};
 
 			meta_updates.push((hash, number, pending_block.leaf_state.is_best(), finalized));
 
@@ -1469,9 +1658,11 @@ impl<Block: BlockT> Backend<Block> {
 			let lookup_key = utils::number_and_hash_to_lookup_key(f_num, f_hash.clone())?;
 			transaction.set_from_vec(columns::META, meta_keys::FINALIZED_BLOCK, lookup_key);
 
-			let commit = self.storage.state_db.canonicalize_block(&f_hash)
-				.map_err(|e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e))?;
-			apply_state_commit(transaction, commit);
+			// Blocks imported without state, e.g. headers downloaded by fast sync, can still be
+			// finalized, but there is nothing to canonicalize.
+			if !self.storage.state_db.is_pruned(&f_hash, f_num.saturated_into::<u64>()) {
+				self.canonicalize_state(transaction, &f_hash)?;
+			}
 
 			if !f_num.is_zero() {
 				let new_changes_trie_cache_ops = self.changes_tries_storage.finalize(
@@ -1539,6 +1730,199 @@ impl<Block: BlockT> Backend<Block> {
 		}
 		Ok(())
 	}
+
+	/// Initialize an empty database from a state snapshot.
+	///
+	/// `headers` is the header chain from `genesis_hash` up to the snapshot block. Ancestors
+	/// are stored header-only, while the snapshot block gets the state made of `entries` and
+	/// becomes the best and finalized block.
+	///
+	/// `entries` are streamed straight into the state column, so they must be sorted by key
+	/// within each trie, and the entries of every child trie must come before the top trie
+	/// ones. Fails if the database is not empty or if the root of the state doesn't match the
+	/// state root of the snapshot block. Trie nodes written before a failure are left in the
+	/// database, which should be discarded.
+	pub fn import_state_snapshot(
+		&self,
+		genesis_hash: Block::Hash,
+		headers: Vec<Block::Header>,
+		entries: impl Iterator<Item = ClientResult<SnapshotEntry>>,
+	) -> ClientResult<()> {
+		let _lock = self.import_lock.write();
+		let result = self.try_import_state_snapshot(genesis_hash, headers, entries);
+		match result {
+			Ok(()) => self.storage.state_db.apply_pending(),
+			Err(_) => self.storage.state_db.revert_pending(),
+		}
+		result
+	}
+
+	fn try_import_state_snapshot(
+		&self,
+		genesis_hash: Block::Hash,
+		headers: Vec<Block::Header>,
+		entries: impl Iterator<Item = ClientResult<SnapshotEntry>>,
+	) -> ClientResult<()> {
+		use tp_trie::TrieConfiguration;
+
+		if self.blockchain.meta.read().genesis_hash != Default::default() {
+			return Err(ClientError::Backend(
+				"State snapshots can only be imported into an empty database".into()
+			));
+		}
+
+		let base = match (headers.first(), headers.last()) {
+			(Some(genesis), Some(base)) if genesis.number().is_zero() => {
+				if genesis.hash() != genesis_hash {
+					return Err(ClientError::Backend(format!(
+						"Snapshot genesis {:?} doesn't match the chain genesis {:?}",
+						genesis.hash(),
+						genesis_hash,
+					)));
+				}
+				base.clone()
+			},
+			_ => return Err(ClientError::Backend("Snapshot ancestry must start at genesis".into())),
+		};
+		for pair in headers.windows(2) {
+			if *pair[1].parent_hash() != pair[0].hash() ||
+				*pair[1].number() != *pair[0].number() + One::one()
+			{
+				return Err(ClientError::Backend(
+					format!("Snapshot ancestry is broken at block #{}", pair[1].number())
+				));
+			}
+		}
+
+		let mut nodes = snapshot::SnapshotNodes::<HashFor<Block>>::new(
+			&*self.storage.db,
+			self.storage.prefix_keys,
+		);
+		let mut entries = entries.peekable();
+		let mut error = None;
+		let mut child_roots = std::collections::BTreeMap::new();
+		loop {
+			let storage_key = match entries.peek() {
+				Some(Ok((Some(storage_key), _, _))) => storage_key.clone(),
+				_ => break,
+			};
+			let child_info = match ChildType::from_prefixed_key(PrefixedStorageKey::new_ref(&storage_key)) {
+				Some((ChildType::ParentKeyId, key)) => ChildInfo::new_default(key),
+				_ => return Err(ClientError::Backend(
+					format!("Unsupported child trie {}", HexDisplay::from(&storage_key)),
+				)),
+			};
+			if child_roots.contains_key(&storage_key) {
+				return Err(ClientError::Backend(format!(
+					"Entries of child trie {} are not contiguous",
+					HexDisplay::from(&storage_key),
+				)));
+			}
+
+			nodes.set_keyspace(child_info.keyspace());
+			let root = tp_trie::Layout::<HashFor<Block>>::trie_build(
+				&mut nodes,
+				snapshot::TrieEntries {
+					entries: &mut entries,
+					child: Some(storage_key.clone()),
+					error: &mut error,
+					last_key: None,
+				},
+			);
+			if let Some(e) = error.take() {
+				return Err(e);
+			}
+			child_roots.insert(storage_key, root.encode());
+		}
+
+		nodes.set_keyspace(&[]);
+		let top = snapshot::TrieEntries {
+			entries: &mut entries,
+			child: None,
+			error: &mut error,
+			last_key: None,
+		};
+		let root = tp_trie::Layout::<HashFor<Block>>::trie_build(
+			&mut nodes,
+			snapshot::MergeSorted { a: top.peekable(), b: child_roots.into_iter().peekable() },
+		);
+		if let Some(e) = error.take() {
+			return Err(e);
+		}
+		nodes.flush()?;
+		if root != *base.state_root() {
+			return Err(ClientError::Backend(format!(
+				"Snapshot state root {:?} doesn't match block #{} ({:?})",
+				root,
+				base.number(),
+				base.state_root(),
+			)));
+		}
+
+		let mut transaction = Transaction::new();
+		for header in &headers {
+			let hash = header.hash();
+			let number = *header.number();
+			utils::insert_hash_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+			utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+			let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
+			transaction.set_from_vec(columns::HEADER, &lookup_key, header.encode());
+			if !number.is_zero() {
+				children::write_children(
+					&mut transaction,
+					columns::META,
+					meta_keys::CHILDREN_PREFIX,
+					*header.parent_hash(),
+					vec![hash],
+				);
+			}
+		}
+
+		let hash = base.hash();
+		let number = *base.number();
+		let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
+		transaction.set(columns::META, meta_keys::GENESIS_HASH, genesis_hash.as_ref());
+		transaction.set_from_vec(columns::META, meta_keys::BEST_BLOCK, lookup_key.clone());
+		transaction.set_from_vec(columns::META, meta_keys::FINALIZED_BLOCK, lookup_key);
+
+		let commit = self.storage.state_db.insert_block(
+			&hash,
+			number.saturated_into::<u64>(),
+			base.parent_hash(),
+			Default::default(),
+		).map_err(|e: tc_state_db::Error<io::Error>| tp_blockchain::Error::from_state_db(e))?;
+		apply_state_commit(&mut transaction, commit);
+		self.canonicalize_state(&mut transaction, &hash)?;
+
+		let changes_trie_cache_ops = self.changes_tries_storage.commit(
+			&mut transaction,
+			Default::default(),
+			cache::ComplexBlockId::new(
+				*base.parent_hash(),
+				if number.is_zero() { Zero::zero() } else { number - One::one() },
+			),
+			cache::ComplexBlockId::new(hash, number),
+			&base,
+			true,
+			Some(None),
+			None,
+		)?;
+
+		{
+			let mut leaves = self.blockchain.leaves.write();
+			leaves.import(hash, number, *base.parent_hash());
+			leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+		}
+
+		self.storage.db.commit(transaction)?;
+
+		self.changes_tries_storage.post_commit(Some(changes_trie_cache_ops));
+		self.blockchain.update_meta(genesis_hash, Zero::zero(), false, false);
+		self.blockchain.update_meta(hash, number, true, true);
+		debug!(target: "db", "Imported state snapshot at #{} ({:?})", number, hash);
+
+		Ok(())
+	}
 }
 
 fn apply_state_commit(transaction: &mut Transaction<DbHash>, commit: tc_state_db::CommitSet<Vec<u8>>) {
@@ -1604,6 +1988,7 @@ impl<Block: BlockT> tc_client_api::backend::Backend<Block> for Backend<Block> {
 			finalized_blocks: Vec::new(),
 			set_head: None,
 			commit_state: false,
+			reset_storage: false,
 		})
 	}
 
@@ -2037,6 +2422,7 @@ pub(crate) mod tests {
 			source: DatabaseSettingsSrc::Custom(backing),
 			keep_blocks: KeepBlocks::All,
 			transaction_storage: TransactionStorageMode::BlockBody,
+			state_storage: StateStorageMode::Journaled,
 		}, 0).unwrap();
 		assert_eq!(backend.blockchain().info().best_number, 9);
 		for i in 0..10 {
@@ -2044,6 +2430,214 @@ pub(crate) mod tests {
 		}
 	}
 
+	#[test]
+	fn refuses_mismatched_state_storage() {
+		let backing = tetcore_database::as_database(tetsy_kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
+		let settings = |state_storage| DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			state_pruning: PruningMode::keep_blocks(1),
+			source: DatabaseSettingsSrc::Custom(backing.clone()),
+			keep_blocks: KeepBlocks::All,
+			transaction_storage: TransactionStorageMode::BlockBody,
+			state_storage,
+		};
+
+		assert!(Backend::<Block>::new(settings(StateStorageMode::RefCounted), 0).is_ok());
+		assert!(Backend::<Block>::new(settings(StateStorageMode::Journaled), 0).is_err());
+		assert!(Backend::<Block>::new(settings(StateStorageMode::RefCounted), 0).is_ok());
+	}
+
+	#[test]
+	fn ref_counted_state_is_pruned_without_journal() {
+		let backend = Backend::<Block>::new(DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			state_pruning: PruningMode::keep_blocks(1),
+			source: DatabaseSettingsSrc::Custom(
+				tetcore_database::as_database(tetsy_kvdb_memorydb::create(crate::utils::NUM_COLUMNS))
+			),
+			keep_blocks: KeepBlocks::All,
+			transaction_storage: TransactionStorageMode::BlockBody,
+			state_storage: StateStorageMode::RefCounted,
+		}, 0).unwrap();
+		let count_nodes = || {
+			let mut count = 0;
+			backend.storage.db.iter_column(columns::STATE, &mut |_, _| {
+				count += 1;
+				true
+			}).unwrap();
+			count
+		};
+
+		let mut parent_hash = Default::default();
+		let mut node_counts = Vec::new();
+		for number in 0..6u64 {
+			let mut op = backend.begin_operation().unwrap();
+			let parent = if number == 0 { BlockId::Hash(Default::default()) } else { BlockId::Number(number - 1) };
+			backend.begin_state_operation(&mut op, parent).unwrap();
+			let mut header = Header {
+				number,
+				parent_hash,
+				state_root: Default::default(),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+
+			if number == 0 {
+				let storage: Vec<_> = (0..32u8).map(|i| (vec![i, i], vec![i; 40])).collect();
+				header.state_root = op.old_state.storage_root(storage
+					.iter()
+					.map(|(k, v)| (&k[..], Some(&v[..])))
+				).0.into();
+				op.reset_storage(Storage {
+					top: storage.into_iter().collect(),
+					children_default: Default::default(),
+				}).unwrap();
+			} else {
+				let key = number as u8 * 5;
+				let storage = vec![(vec![key, key], Some(vec![number as u8 + 100; 40]))];
+				let (root, overlay) = op.old_state.storage_root(
+					storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..])))
+				);
+				op.update_db_storage(overlay).unwrap();
+				header.state_root = root.into();
+				op.update_storage(storage, Vec::new()).unwrap();
+			}
+			parent_hash = header.hash();
+			op.set_block_data(header, Some(vec![]), None, NewBlockState::Final).unwrap();
+			backend.commit_operation(op).unwrap();
+			node_counts.push(count_nodes());
+		}
+
+		// Each block replaces the same number of nodes, which are removed as soon as the state
+		// of its parent is pruned.
+		assert!(node_counts[1..].iter().all(|count| *count == node_counts[1]), "{:?}", node_counts);
+		assert!(backend.state_at(BlockId::Number(1)).is_err());
+		let state = backend.state_at(BlockId::Number(5)).unwrap();
+		assert_eq!(state.storage(&[25, 25]).unwrap(), Some(vec![105; 40]));
+		assert_eq!(state.storage(&[31, 31]).unwrap(), Some(vec![31; 40]));
+	}
+
+	#[test]
+	fn import_state_snapshot_works() {
+		let backend = Backend::<Block>::new_test(2, 0);
+		let child_info = ChildInfo::new_default(b"child");
+		let child_storage_key = child_info.prefixed_storage_key().into_inner();
+		let entries = || vec![
+			Ok((Some(child_storage_key.clone()), b"child_key".to_vec(), b"child_value".to_vec())),
+			Ok((None, b"key".to_vec(), b"value".to_vec())),
+		].into_iter();
+		let storage = Storage {
+			top: vec![(b"key".to_vec(), b"value".to_vec())].into_iter().collect(),
+			children_default: vec![(child_info.storage_key().to_vec(), tet_core::storage::StorageChild {
+				data: vec![(b"child_key".to_vec(), b"child_value".to_vec())].into_iter().collect(),
+				child_info: child_info.clone(),
+			})].into_iter().collect(),
+		};
+		let state_root = *tp_state_machine::InMemoryBackend::<BlakeTwo256>::from(storage).root();
+
+		let genesis = Header {
+			number: 0,
+			parent_hash: Default::default(),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let mut base = Header {
+			number: 1,
+			parent_hash: genesis.hash(),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		assert!(backend.import_state_snapshot(
+			genesis.hash(),
+			vec![genesis.clone(), base.clone()],
+			entries(),
+		).is_err());
+
+		base.state_root = state_root;
+		assert!(backend.import_state_snapshot(genesis.hash(), vec![base.clone()], entries()).is_err());
+		assert!(backend.import_state_snapshot(
+			Default::default(),
+			vec![genesis.clone(), base.clone()],
+			entries(),
+		).is_err());
+		assert!(backend.import_state_snapshot(
+			genesis.hash(),
+			vec![genesis.clone(), base.clone()],
+			entries().rev(),
+		).is_err());
+		backend.import_state_snapshot(genesis.hash(), vec![genesis.clone(), base.clone()], entries())
+			.unwrap();
+
+		let info = backend.blockchain().info();
+		assert_eq!(info.genesis_hash, genesis.hash());
+		assert_eq!(info.best_hash, base.hash());
+		assert_eq!(info.finalized_hash, base.hash());
+		assert_eq!(backend.blockchain().hash(0).unwrap(), Some(genesis.hash()));
+		assert!(backend.blockchain().body(BlockId::Number(0)).unwrap().is_none());
+
+		let state = backend.state_at(BlockId::Number(1)).unwrap();
+		assert_eq!(state.storage(b"key").unwrap(), Some(b"value".to_vec()));
+		assert_eq!(state.child_storage(&child_info, b"child_key").unwrap(), Some(b"child_value".to_vec()));
+		assert_eq!(state.storage_root(std::iter::empty()).0, base.state_root);
+
+		assert!(backend.import_state_snapshot(genesis.hash(), vec![genesis, base], entries()).is_err());
+	}
+
+	#[test]
+	fn import_synced_state_on_top_of_headers() {
+		let backend = Backend::<Block>::new_test(2, 0);
+		let pairs = vec![(b"key".to_vec(), b"value".to_vec())];
+		let genesis = insert_header(&backend, 0, Default::default(), None, Default::default());
+
+		let header = |number, parent_hash, state_root| Header {
+			number,
+			parent_hash,
+			state_root,
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let header1 = header(1, genesis, Default::default());
+		let header2 = header(2, header1.hash(), BlakeTwo256::trie_root(pairs.clone()));
+		let header3 = header(3, header2.hash(), Default::default());
+
+		// Headers are imported and finalized without state.
+		for header in vec![header1.clone(), header2.clone(), header3.clone()] {
+			let hash = header.hash();
+			let mut op = backend.begin_operation().unwrap();
+			op.set_block_data(header, None, None, NewBlockState::Best).unwrap();
+			backend.commit_operation(op).unwrap();
+			backend.finalize_block(BlockId::Hash(hash), None).unwrap();
+		}
+		assert!(backend.state_at(BlockId::Hash(header2.hash())).is_err());
+
+		let mut op = backend.begin_operation().unwrap();
+		backend.begin_state_operation(&mut op, BlockId::Hash(Default::default())).unwrap();
+		let root = op.reset_storage(Storage {
+			top: pairs.iter().cloned().collect(),
+			children_default: Default::default(),
+		}).unwrap();
+		assert_eq!(root, header2.state_root);
+		op.set_block_data(header2.clone(), None, None, NewBlockState::Normal).unwrap();
+		backend.commit_operation(op).unwrap();
+
+		let state = backend.state_at(BlockId::Hash(header2.hash())).unwrap();
+		assert_eq!(state.storage(b"key").unwrap(), Some(b"value".to_vec()));
+		assert_eq!(backend.blockchain().leaves().unwrap(), vec![header3.hash()]);
+		assert_eq!(backend.blockchain().children(header1.hash()).unwrap(), vec![header2.hash()]);
+		assert_eq!(backend.blockchain().info().finalized_hash, header3.hash());
+
+		// Descendants can be executed on top of the synced state.
+		let mut op = backend.begin_operation().unwrap();
+		backend.begin_state_operation(&mut op, BlockId::Hash(header2.hash())).unwrap();
+		op.set_block_data(header3.clone(), None, None, NewBlockState::Normal).unwrap();
+		backend.commit_operation(op).unwrap();
+		assert!(backend.state_at(BlockId::Hash(header3.hash())).is_ok());
+	}
+
 	#[test]
 	fn set_state_data() {
 		let db = Backend::<Block>::new_test(2, 0);
@@ -73,7 +73,7 @@ pub struct LightStorage<Block: BlockT> {
 impl<Block: BlockT> LightStorage<Block> {
 	/// Create new storage with given settings.
 	pub fn new(config: DatabaseSettings) -> ClientResult<Self> {
-		let db = crate::utils::open_database::<Block>(&config, DatabaseType::Light)?;
+		let db = crate::utils::open_database::<Block>(&config.source, DatabaseType::Light)?;
 		Self::from_kvdb(db as Arc<_>)
 	}
 
@@ -0,0 +1,729 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Offline inspection and repair of a full node database.
+//!
+//! These functions work on the raw columns of a database that is not opened by a running
+//! node. They require a database that can enumerate its keys, which tetsy-db can't do, so
+//! a tetsy-db database can only be the target of a conversion.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use codec::Decode;
+use log::{info, warn};
+use tc_client_api::leaves::LeafSet;
+use tet_core::hexdisplay::HexDisplay;
+use tetcore_database::Transaction;
+use tp_blockchain::{Error as ClientError, Result as ClientResult};
+use tp_runtime::traits::{Block as BlockT, Header as HeaderT, HashFor, NumberFor, Zero};
+use tp_trie::{empty_trie_root, Layout};
+
+use crate::utils::{self, meta_keys, DatabaseType};
+use crate::{
+	children, columns, ref_counted, Database, DatabaseSettingsSrc, DbHash, StateMetaDb, StateStorageMode,
+	DB_HASH_LEN,
+};
+
+/// Maximum number of changes committed to the database at once.
+const MAX_TRANSACTION_CHANGES: usize = 10_000;
+
+/// Columns of a full node database with their names.
+const COLUMNS: &[(u32, &str)] = &[
+	(columns::META, "meta"),
+	(columns::STATE, "state"),
+	(columns::STATE_META, "state_meta"),
+	(columns::KEY_LOOKUP, "key_lookup"),
+	(columns::HEADER, "header"),
+	(columns::BODY, "body"),
+	(columns::JUSTIFICATION, "justification"),
+	(columns::CHANGES_TRIE, "changes_trie"),
+	(columns::AUX, "aux"),
+	(columns::OFFCHAIN, "offchain"),
+	(columns::CACHE, "cache"),
+	(columns::TRANSACTION, "transaction"),
+];
+
+/// Number of entries and bytes stored in a column.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnStats {
+	/// Name of the column.
+	pub name: &'static str,
+	/// Number of entries.
+	pub entries: u64,
+	/// Total size of the keys.
+	pub key_bytes: u64,
+	/// Total size of the values.
+	pub value_bytes: u64,
+}
+
+/// Result of [`verify`].
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+	/// Number of headers in the database.
+	pub headers: u64,
+	/// Number of blocks without a body, because of block pruning or state sync.
+	pub missing_bodies: u64,
+	/// Inconsistencies found between the columns.
+	pub issues: Vec<String>,
+}
+
+/// Result of [`rebuild`].
+#[derive(Debug, Clone)]
+pub struct RebuildReport<Block: BlockT> {
+	/// Number of headers in the database.
+	pub headers: u64,
+	/// Best block of the rebuilt canonical chain.
+	pub best: (NumberFor<Block>, Block::Hash),
+	/// Last finalized block.
+	pub finalized: (NumberFor<Block>, Block::Hash),
+	/// Number of leaves of the block tree.
+	pub leaves: usize,
+}
+
+struct HeaderEntry<Block: BlockT> {
+	number: NumberFor<Block>,
+	parent_hash: Block::Hash,
+	state_root: Block::Hash,
+	lookup_key: Vec<u8>,
+}
+
+/// Open the database of a full node without touching its content.
+pub fn open_database<Block: BlockT>(
+	source: &DatabaseSettingsSrc,
+) -> ClientResult<Arc<dyn Database<DbHash>>> {
+	utils::open_database::<Block>(source, DatabaseType::Full)
+}
+
+/// Count the entries and bytes stored in each column.
+pub fn column_stats(db: &dyn Database<DbHash>) -> ClientResult<Vec<ColumnStats>> {
+	COLUMNS.iter().map(|&(col, name)| {
+		let mut stats = ColumnStats { name, ..Default::default() };
+		db.iter_column(col, &mut |key, value| {
+			stats.entries += 1;
+			stats.key_bytes += key.len() as u64;
+			stats.value_bytes += value.len() as u64;
+			true
+		})?;
+		Ok(stats)
+	}).collect()
+}
+
+/// Check that the headers, bodies, lookup keys, meta keys and state roots are consistent.
+pub fn verify<Block: BlockT>(db: &dyn Database<DbHash>) -> ClientResult<VerifyReport> {
+	let mut report = VerifyReport::default();
+	let headers = read_headers::<Block>(db, &mut report.issues)?;
+	report.headers = headers.len() as u64;
+
+	let mut number_index = HashMap::new();
+	let mut hash_lookups = HashSet::new();
+	db.iter_column(columns::KEY_LOOKUP, &mut |key, value| {
+		if key.len() == 4 {
+			number_index.insert(key.to_vec(), value.to_vec());
+			return true;
+		}
+		match Block::Hash::decode(&mut &key[..]).ok().and_then(|hash| headers.get(&hash).map(|e| (hash, e))) {
+			Some((hash, entry)) if entry.lookup_key == value => {
+				hash_lookups.insert(hash);
+			},
+			Some((hash, _)) => report.issues.push(format!("Wrong lookup key of block {:?}", hash)),
+			None => report.issues.push(format!("Lookup key of unknown block {}", HexDisplay::from(&key))),
+		}
+		true
+	})?;
+	for (hash, entry) in headers.iter() {
+		if !hash_lookups.contains(hash) {
+			report.issues.push(format!("Missing lookup key of block #{} ({:?})", entry.number, hash));
+		}
+	}
+
+	let meta_block = |key: &[u8], name: &str, issues: &mut Vec<String>| {
+		match db.get(columns::META, key) {
+			Some(lookup_key) => {
+				let hash = lookup_key.get(4..).and_then(|h| Block::Hash::decode(&mut &h[..]).ok());
+				match hash.filter(|h| headers.get(h).map_or(false, |e| e.lookup_key == lookup_key)) {
+					Some(hash) => Some(hash),
+					None => {
+						issues.push(format!("The {} block points to a missing header", name));
+						None
+					},
+				}
+			},
+			None => {
+				issues.push(format!("The {} block is not set", name));
+				None
+			},
+		}
+	};
+	let best = meta_block(meta_keys::BEST_BLOCK, "best", &mut report.issues);
+	let finalized = meta_block(meta_keys::FINALIZED_BLOCK, "finalized", &mut report.issues);
+
+	let genesis = utils::read_genesis_hash::<Block::Hash>(db)?;
+	match genesis.as_ref().and_then(|hash| headers.get(hash)) {
+		Some(entry) if entry.number.is_zero() => {},
+		_ => report.issues.push("The genesis hash doesn't point to a genesis header".into()),
+	}
+
+	if let Some(best) = best {
+		let chain = canonical_chain(&headers, best);
+		if chain.last() != genesis.as_ref() {
+			report.issues.push(format!("The chain of the best block {:?} doesn't reach the genesis block", best));
+		}
+		let mut canonical = HashSet::new();
+		for hash in chain.iter() {
+			let entry = &headers[hash];
+			let index = utils::number_index_key(entry.number)?;
+			if number_index.get(&index[..]) != Some(&entry.lookup_key) {
+				report.issues.push(format!("Wrong number index of canonical block #{}", entry.number));
+			}
+			canonical.insert(index.to_vec());
+		}
+		for (index, _) in number_index.iter().filter(|(index, _)| !canonical.contains(*index)) {
+			report.issues.push(format!(
+				"Number index of non canonical block #{}",
+				utils::lookup_key_to_number::<u32>(index)?,
+			));
+		}
+		if let Some(finalized) = finalized {
+			if !chain.contains(&finalized) {
+				report.issues.push(format!("The finalized block {:?} is not canonical", finalized));
+			}
+		}
+	}
+
+	let empty_root = empty_trie_root::<Layout<HashFor<Block>>>();
+	for (name, hash) in best.iter().map(|h| ("best", h)).chain(finalized.iter().map(|h| ("finalized", h))) {
+		let entry = &headers[hash];
+		if entry.state_root != empty_root && db.get(columns::STATE, entry.state_root.as_ref()).is_none() {
+			report.issues.push(format!("State of the {} block #{} is missing", name, entry.number));
+		}
+	}
+
+	let lookup_keys: HashSet<_> = headers.values().map(|entry| entry.lookup_key.clone()).collect();
+	let mut bodies = 0;
+	db.iter_column(columns::BODY, &mut |key, _| {
+		if lookup_keys.contains(key) {
+			bodies += 1;
+		} else {
+			report.issues.push(format!("Body without header at key {}", HexDisplay::from(&key)));
+		}
+		true
+	})?;
+	report.missing_bodies = report.headers.saturating_sub(bodies);
+	db.iter_column(columns::JUSTIFICATION, &mut |key, _| {
+		if !lookup_keys.contains(key) {
+			report.issues.push(format!("Justification without header at key {}", HexDisplay::from(&key)));
+		}
+		true
+	})?;
+
+	Ok(report)
+}
+
+/// Rebuild the lookup keys, the best and finalized blocks, the leaves and the children lists
+/// from the headers.
+///
+/// The stored best block is kept if its chain reaches the genesis block, otherwise the highest
+/// such block becomes the best block. The stored finalized block is kept if it is an ancestor of
+/// the best block, otherwise the genesis block is marked as finalized.
+pub fn rebuild<Block: BlockT>(db: &dyn Database<DbHash>) -> ClientResult<RebuildReport<Block>> {
+	let mut issues = Vec::new();
+	let headers = read_headers::<Block>(db, &mut issues)?;
+	for issue in issues {
+		warn!(target: "db", "Ignoring header: {}", issue);
+	}
+
+	let genesis = {
+		let mut genesis = headers.iter().filter(|(_, e)| e.number.is_zero()).map(|(h, _)| *h);
+		match (genesis.next(), genesis.next()) {
+			(Some(hash), None) => hash,
+			(None, _) => return Err(ClientError::Backend("No genesis header in the database".into())),
+			(Some(_), Some(_)) => utils::read_genesis_hash::<Block::Hash>(db)?
+				.filter(|hash| headers.contains_key(hash))
+				.ok_or_else(|| ClientError::Backend("Several genesis headers in the database".into()))?,
+		}
+	};
+
+	let stored_block = |key: &[u8]| db.get(columns::META, key)
+		.and_then(|lookup_key| lookup_key.get(4..).and_then(|h| Block::Hash::decode(&mut &h[..]).ok()))
+		.filter(|hash| headers.contains_key(hash));
+	let mut candidates: Vec<_> = stored_block(meta_keys::BEST_BLOCK).into_iter().collect();
+	let mut by_number: Vec<_> = headers.iter().map(|(hash, entry)| (entry.number, *hash)).collect();
+	by_number.sort_by(|a, b| b.0.cmp(&a.0));
+	candidates.extend(by_number.into_iter().map(|(_, hash)| hash));
+	let chain = candidates.into_iter()
+		.map(|hash| canonical_chain(&headers, hash))
+		.find(|chain| chain.last() == Some(&genesis))
+		.expect("the chain of the genesis block reaches the genesis block; qed");
+	let best = chain[0];
+	let finalized = stored_block(meta_keys::FINALIZED_BLOCK)
+		.filter(|hash| chain.contains(hash))
+		.unwrap_or(genesis);
+
+	let mut stale = Vec::new();
+	db.iter_column(columns::KEY_LOOKUP, &mut |key, _| {
+		stale.push(key.to_vec());
+		true
+	})?;
+	db.iter_column(columns::META, &mut |key, _| {
+		if key.starts_with(meta_keys::CHILDREN_PREFIX) {
+			stale.push(key.to_vec());
+		}
+		true
+	})?;
+	let mut batch = Batch::new(db);
+	for key in stale {
+		let col = if key.starts_with(meta_keys::CHILDREN_PREFIX) { columns::META } else { columns::KEY_LOOKUP };
+		batch.push(|tx| tx.remove(col, &key))?;
+	}
+
+	let mut children_of: HashMap<Block::Hash, Vec<Block::Hash>> = HashMap::new();
+	for (hash, entry) in headers.iter() {
+		batch.push(|tx| tx.set(columns::KEY_LOOKUP, hash.as_ref(), &entry.lookup_key))?;
+		if !entry.number.is_zero() {
+			children_of.entry(entry.parent_hash).or_default().push(*hash);
+		}
+	}
+	for hash in chain.iter() {
+		let entry = &headers[hash];
+		let index = utils::number_index_key(entry.number)?;
+		batch.push(|tx| tx.set(columns::KEY_LOOKUP, index.as_ref(), &entry.lookup_key))?;
+	}
+	for (parent_hash, child_hashes) in children_of {
+		batch.push(|tx| children::write_children(
+			tx,
+			columns::META,
+			meta_keys::CHILDREN_PREFIX,
+			parent_hash,
+			child_hashes,
+		))?;
+	}
+
+	let mut sorted: Vec<_> = headers.iter().collect();
+	sorted.sort_by_key(|(_, entry)| entry.number);
+	let mut leaves = LeafSet::new();
+	for (hash, entry) in sorted {
+		leaves.import(*hash, entry.number, entry.parent_hash);
+	}
+	let finalized_number = headers[&finalized].number;
+	// Nothing to undo, the leaves are written from scratch.
+	let _ = leaves.finalize_height(finalized_number);
+	let leaf_count = leaves.count();
+
+	let mut tx = batch.finish();
+	leaves.prepare_transaction(&mut tx, columns::META, meta_keys::LEAF_PREFIX);
+	tx.set(columns::META, meta_keys::BEST_BLOCK, &headers[&best].lookup_key);
+	tx.set(columns::META, meta_keys::FINALIZED_BLOCK, &headers[&finalized].lookup_key);
+	tx.set(columns::META, meta_keys::GENESIS_HASH, genesis.as_ref());
+	db.commit(tx)?;
+
+	let report = RebuildReport {
+		headers: headers.len() as u64,
+		best: (headers[&best].number, best),
+		finalized: (finalized_number, finalized),
+		leaves: leaf_count,
+	};
+	info!(
+		target: "db",
+		"Rebuilt lookups of {} headers, best: #{} ({:?}), finalized: #{} ({:?})",
+		report.headers,
+		report.best.0,
+		report.best.1,
+		report.finalized.0,
+		report.finalized.1,
+	);
+	Ok(report)
+}
+
+/// Copy all columns of the database at `source` to the new database at `target`.
+///
+/// The source can't be a tetsy-db database, which stores hashes of the keys of its columns: the
+/// keys of the auxiliary and offchain data can't be recovered from it. Reference counted state
+/// nodes keep their counts, whether the target database counts references natively or not.
+///
+/// When journaled state is converted to a database that counts references natively, the nodes
+/// are stored at their hash instead of their prefixed key, and each node gets a single
+/// reference. The journals of the state db are rewritten for the new keys, and the target is
+/// marked as storing reference counted state.
+///
+/// If the copy fails, the target database is removed, so the conversion can be retried.
+/// Returns the number of copied entries.
+pub fn convert<Block: BlockT>(
+	source: &DatabaseSettingsSrc,
+	target: &DatabaseSettingsSrc,
+) -> ClientResult<u64> {
+	if let DatabaseSettingsSrc::TetsyDb { .. } = source {
+		return Err(ClientError::Backend(
+			"tetsy-db can't enumerate its keys, so it can only be the target of a conversion".into()
+		));
+	}
+	if let Some(path) = target.path() {
+		if path.read_dir().map_or(false, |mut dir| dir.next().is_some()) {
+			return Err(ClientError::Backend(format!("Target database {} is not empty", path.display())));
+		}
+	}
+
+	let source_db = open_database::<Block>(source)?;
+	let source_storage = match source_db.get(columns::META, meta_keys::STATE_STORAGE) {
+		Some(stored) if stored == StateStorageMode::RefCounted.as_str().as_bytes() =>
+			StateStorageMode::RefCounted,
+		_ => StateStorageMode::Journaled,
+	};
+	let target_storage = if target.supports_ref_counting() {
+		StateStorageMode::RefCounted
+	} else {
+		source_storage
+	};
+	let result = copy_columns::<Block>(&*source_db, source_storage, target, target_storage);
+	if result.is_err() {
+		if let Some(path) = target.path() {
+			warn!(target: "db", "Conversion failed, removing {}", path.display());
+			if let Err(e) = std::fs::remove_dir_all(path) {
+				warn!(target: "db", "Failed to remove {}: {}", path.display(), e);
+			}
+		}
+	}
+	result
+}
+
+/// Copy the columns of `source_db`, whose state is stored as `source_storage`, to the database at
+/// `target`, which is closed on return. The state is stored as `target_storage` in the target.
+fn copy_columns<Block: BlockT>(
+	source_db: &dyn Database<DbHash>,
+	source_storage: StateStorageMode,
+	target: &DatabaseSettingsSrc,
+	target_storage: StateStorageMode,
+) -> ClientResult<u64> {
+	if source_storage == StateStorageMode::RefCounted && target_storage == StateStorageMode::Journaled {
+		return Err(ClientError::Backend(
+			"Reference counted state can't be converted to journaled state".into()
+		));
+	}
+	// Journaled nodes are stored at their prefixed key, reference counted ones at their hash.
+	let strip_prefixes = source_storage != target_storage;
+
+	let target_db = open_database::<Block>(target)?;
+	let target_db = if target_storage == StateStorageMode::RefCounted && !target.supports_ref_counting() {
+		ref_counted::wrap(target_db)
+	} else {
+		target_db
+	};
+
+	let mut copied = 0;
+	let mut stored_nodes = HashSet::new();
+	let mut batch = Batch::new(&*target_db);
+	for &(col, name) in COLUMNS {
+		info!(target: "db", "Copying column {}", name);
+		let mut result = Ok(());
+		source_db.iter_column(col, &mut |key, value| {
+			copied += 1;
+			result = match col {
+				columns::STATE if source_storage == StateStorageMode::RefCounted => {
+					let (node, references) = match ref_counted::split_counter(value.to_vec()) {
+						Some(node) => node,
+						None => {
+							warn!(target: "db", "Skipping corrupted state node {}", HexDisplay::from(&key));
+							return true;
+						},
+					};
+					(0..references).try_for_each(|_| batch.push(|tx| tx.set(col, key, &node)))
+				},
+				// The same node may be stored with several prefixes.
+				columns::STATE if strip_prefixes => match node_hash(key) {
+					Some(hash) if stored_nodes.insert(hash) => batch.push(|tx| tx.set(col, &hash, value)),
+					Some(_) => Ok(()),
+					None => {
+						warn!(target: "db", "Skipping state node at invalid key {}", HexDisplay::from(&key));
+						Ok(())
+					},
+				},
+				_ => batch.push(|tx| tx.set(col, key, value)),
+			};
+			result.is_ok()
+		})?;
+		result?;
+	}
+
+	let mut transaction = batch.finish();
+	if strip_prefixes {
+		// The state db of a database that counts references doesn't journal the inserted and
+		// deleted nodes of canonical blocks, see `Backend::from_database`.
+		let journals = tc_state_db::map_journal_keys::<Block::Hash, Vec<u8>, _>(
+			&StateMetaDb(source_db),
+			false,
+			false,
+			|mut key| {
+				key.drain(..key.len().saturating_sub(DB_HASH_LEN));
+				key
+			},
+		).map_err(ClientError::from_state_db)?;
+		for (key, record) in journals.inserted {
+			transaction.set_from_vec(columns::STATE_META, &key, record);
+		}
+		transaction.set(columns::META, meta_keys::STATE_STORAGE, target_storage.as_str().as_bytes());
+	}
+	target_db.commit(transaction)?;
+
+	Ok(copied)
+}
+
+/// Hash of the state node stored at the prefixed `key`.
+fn node_hash(key: &[u8]) -> Option<DbHash> {
+	let start = key.len().checked_sub(DB_HASH_LEN)?;
+	let mut hash = DbHash::default();
+	hash.copy_from_slice(&key[start..]);
+	Some(hash)
+}
+
+/// A transaction that is committed once it gets too large.
+struct Batch<'a> {
+	db: &'a dyn Database<DbHash>,
+	transaction: Transaction<DbHash>,
+}
+
+impl<'a> Batch<'a> {
+	fn new(db: &'a dyn Database<DbHash>) -> Self {
+		Batch { db, transaction: Transaction::new() }
+	}
+
+	fn push(&mut self, change: impl FnOnce(&mut Transaction<DbHash>)) -> ClientResult<()> {
+		change(&mut self.transaction);
+		if self.transaction.0.len() >= MAX_TRANSACTION_CHANGES {
+			self.db.commit(std::mem::take(&mut self.transaction))?;
+		}
+		Ok(())
+	}
+
+	/// Returns the changes that haven't been committed yet.
+	fn finish(self) -> Transaction<DbHash> {
+		self.transaction
+	}
+}
+
+fn read_headers<Block: BlockT>(
+	db: &dyn Database<DbHash>,
+	issues: &mut Vec<String>,
+) -> ClientResult<HashMap<Block::Hash, HeaderEntry<Block>>> {
+	let mut headers = HashMap::new();
+	db.iter_column(columns::HEADER, &mut |key, value| {
+		let header = match Block::Header::decode(&mut &value[..]) {
+			Ok(header) => header,
+			Err(_) => {
+				issues.push(format!("Undecodable header at key {}", HexDisplay::from(&key)));
+				return true;
+			},
+		};
+		let hash = header.hash();
+		match utils::number_and_hash_to_lookup_key(*header.number(), hash) {
+			Ok(lookup_key) if lookup_key == key => {
+				headers.insert(hash, HeaderEntry {
+					number: *header.number(),
+					parent_hash: *header.parent_hash(),
+					state_root: *header.state_root(),
+					lookup_key,
+				});
+			},
+			_ => issues.push(format!("Header {:?} is stored at the wrong key", hash)),
+		}
+		true
+	})?;
+	Ok(headers)
+}
+
+/// Walk the parents of `hash` down to the genesis block or the first missing header.
+fn canonical_chain<Block: BlockT>(
+	headers: &HashMap<Block::Hash, HeaderEntry<Block>>,
+	mut hash: Block::Hash,
+) -> Vec<Block::Hash> {
+	let mut chain = Vec::new();
+	while let Some(entry) = headers.get(&hash) {
+		chain.push(hash);
+		if entry.number.is_zero() {
+			break;
+		}
+		hash = entry.parent_hash;
+	}
+	chain
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::{insert_header, Block};
+	use crate::{Backend, DatabaseSettings, KeepBlocks, PruningMode, TransactionStorageMode};
+	use tc_client_api::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+	use tet_core::H256;
+	use tetcore_database::MemDb;
+	use tp_blockchain::HeaderBackend as _;
+	use tp_runtime::{generic::BlockId, testing::Header, Storage};
+	use tp_state_machine::Backend as _;
+
+	fn settings(source: DatabaseSettingsSrc, state_storage: StateStorageMode) -> DatabaseSettings {
+		DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			state_pruning: PruningMode::keep_blocks(10),
+			source,
+			keep_blocks: KeepBlocks::All,
+			transaction_storage: TransactionStorageMode::BlockBody,
+			state_storage,
+		}
+	}
+
+	/// Import a genesis block and a best block with some state. The best block is not
+	/// canonicalized, its state is in the journal of the state db.
+	fn insert_blocks_with_state(backend: &Backend<Block>) -> H256 {
+		let mut parent_hash = Default::default();
+		for number in 0..2u64 {
+			let mut op = backend.begin_operation().unwrap();
+			let parent = if number == 0 { BlockId::Hash(Default::default()) } else { BlockId::Number(number - 1) };
+			backend.begin_state_operation(&mut op, parent).unwrap();
+			let storage = vec![(vec![number as u8], vec![number as u8; 40]), (vec![9], vec![number as u8 + 100])];
+			let state_root = if number == 0 {
+				let root = op.old_state.storage_root(storage.iter().map(|(k, v)| (&k[..], Some(&v[..])))).0;
+				op.reset_storage(Storage {
+					top: storage.into_iter().collect(),
+					children_default: Default::default(),
+				}).unwrap();
+				root
+			} else {
+				let storage: Vec<_> = storage.into_iter().map(|(k, v)| (k, Some(v))).collect();
+				let (root, overlay) = op.old_state.storage_root(
+					storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..])))
+				);
+				op.update_db_storage(overlay).unwrap();
+				op.update_storage(storage, Vec::new()).unwrap();
+				root
+			};
+			let header = Header {
+				number,
+				parent_hash,
+				state_root,
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			parent_hash = header.hash();
+			op.set_block_data(header, Some(vec![]), None, NewBlockState::Best).unwrap();
+			backend.commit_operation(op).unwrap();
+		}
+		parent_hash
+	}
+
+	fn assert_state_is_readable(backend: &Backend<Block>, best: H256) {
+		assert_eq!(backend.blockchain().info().best_hash, best);
+		let state = backend.state_at(BlockId::Hash(best)).unwrap();
+		assert_eq!(state.storage(&[0]).unwrap(), Some(vec![0; 40]));
+		assert_eq!(state.storage(&[1]).unwrap(), Some(vec![1; 40]));
+		assert_eq!(state.storage(&[9]).unwrap(), Some(vec![101]));
+		let state = backend.state_at(BlockId::Number(0)).unwrap();
+		assert_eq!(state.storage(&[1]).unwrap(), None);
+		assert_eq!(state.storage(&[9]).unwrap(), Some(vec![100]));
+	}
+
+	#[test]
+	fn rebuild_repairs_lookups() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let block1 = insert_header(&backend, 1, genesis, None, Default::default());
+		let block2 = insert_header(&backend, 2, block1, None, Default::default());
+		let db = &*backend.storage.db;
+		assert!(verify::<Block>(db).unwrap().issues.is_empty());
+
+		let mut tx = Transaction::new();
+		utils::remove_key_mappings(&mut tx, columns::KEY_LOOKUP, 2u64, block2).unwrap();
+		tx.remove(columns::META, meta_keys::BEST_BLOCK);
+		db.commit(tx).unwrap();
+		assert_eq!(verify::<Block>(db).unwrap().issues.len(), 2);
+
+		let report = rebuild::<Block>(db).unwrap();
+		assert_eq!(report.best, (2, block2));
+		assert_eq!(report.finalized, (0, genesis));
+		assert_eq!(report.leaves, 1);
+		assert!(verify::<Block>(db).unwrap().issues.is_empty());
+		assert_eq!(
+			db.get(columns::META, meta_keys::BEST_BLOCK),
+			Some(utils::number_and_hash_to_lookup_key(2u64, block2).unwrap()),
+		);
+	}
+
+	#[test]
+	fn convert_copies_all_columns() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis = insert_header(&backend, 0, Default::default(), None, Default::default());
+		insert_header(&backend, 1, genesis, None, Default::default());
+		let source = DatabaseSettingsSrc::Custom(backend.storage.db.clone());
+		let target_db = Arc::new(MemDb::<DbHash>::default());
+		let target = DatabaseSettingsSrc::Custom(target_db.clone());
+
+		let copied = convert::<Block>(&source, &target).unwrap();
+		let stats = column_stats(&*backend.storage.db).unwrap();
+		assert_eq!(copied, stats.iter().map(|s| s.entries).sum::<u64>());
+		assert_eq!(column_stats(&*target_db).unwrap(), stats);
+	}
+
+	#[test]
+	fn convert_refuses_tetsy_db_source() {
+		let dir = tempfile::tempdir().unwrap();
+		let source = DatabaseSettingsSrc::TetsyDb { path: dir.path().join("tetsydb") };
+		let target = DatabaseSettingsSrc::Custom(Arc::new(MemDb::<DbHash>::default()));
+		assert!(convert::<Block>(&source, &target).is_err());
+		assert!(!dir.path().join("tetsydb").exists());
+	}
+
+	#[test]
+	fn convert_stores_journaled_state_by_hash() {
+		let source_db = tetcore_database::as_database(tetsy_kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
+		let source = settings(DatabaseSettingsSrc::Custom(source_db.clone()), StateStorageMode::Journaled);
+		let best = insert_blocks_with_state(&Backend::<Block>::new(source, 1).unwrap());
+
+		let target_db = Arc::new(MemDb::<DbHash>::default());
+		let target = DatabaseSettingsSrc::Custom(target_db.clone());
+		copy_columns::<Block>(&*source_db, StateStorageMode::Journaled, &target, StateStorageMode::RefCounted)
+			.unwrap();
+
+		// Each node is stored once at its hash, with a single reference.
+		let mut nodes = 0;
+		target_db.iter_column(columns::STATE, &mut |key, value| {
+			nodes += 1;
+			assert_eq!(key.len(), DB_HASH_LEN);
+			assert_eq!(ref_counted::split_counter(value.to_vec()).map(|(_, references)| references), Some(1));
+			true
+		}).unwrap();
+		assert!(nodes > 0);
+
+		let backend = Backend::<Block>::new(settings(target, StateStorageMode::RefCounted), 1).unwrap();
+		assert_state_is_readable(&backend, best);
+	}
+
+	#[cfg(feature = "with-tetsy-db")]
+	#[test]
+	fn convert_rocksdb_to_tetsy_db_keeps_state() {
+		let dir = tempfile::tempdir().unwrap();
+		let source = DatabaseSettingsSrc::RocksDb { path: dir.path().join("db"), cache_size: 16 };
+		let target = DatabaseSettingsSrc::TetsyDb { path: dir.path().join("tetsydb") };
+		let best = {
+			let backend = Backend::<Block>::new(settings(source.clone(), StateStorageMode::Journaled), 1).unwrap();
+			insert_blocks_with_state(&backend)
+		};
+
+		convert::<Block>(&source, &target).unwrap();
+
+		let backend = Backend::<Block>::new(settings(target, StateStorageMode::RefCounted), 1).unwrap();
+		assert_state_is_readable(&backend, best);
+	}
+}
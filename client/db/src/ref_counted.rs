@@ -0,0 +1,241 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A `Database` adapter that keeps a reference count with each state trie node.
+//!
+//! Values in the state column are stored as `node ++ counter`, where the counter is
+//! a little-endian `u32`. Within a transaction each `Set` of a state node increments its
+//! counter and each `Remove` decrements it. The node is deleted once the counter drops
+//! to zero. This gives databases without native reference counting the same state column
+//! semantics as tetsy-db, so the state db does not have to track re-inserted nodes.
+//!
+//! The state db doesn't journal the nodes deleted by each block either in this mode. They
+//! are found by comparing the states of a block and of its parent when the block is pruned.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use log::warn;
+use parking_lot::Mutex;
+use tetcore_database::{Database, Change, ColumnId, Transaction, error::DatabaseError};
+use crate::{columns, DbHash};
+
+const COUNTER_LEN: usize = 4;
+
+struct RefCountedState {
+	db: Arc<dyn Database<DbHash>>,
+	/// Held from reading the counters of a transaction until the transaction is written, so
+	/// that concurrent commits don't overwrite each other's updates.
+	commit_lock: Mutex<()>,
+}
+
+/// Wrap a database so that the state column keeps reference counts of the trie nodes.
+pub fn wrap(db: Arc<dyn Database<DbHash>>) -> Arc<dyn Database<DbHash>> {
+	Arc::new(RefCountedState { db, commit_lock: Mutex::new(()) })
+}
+
+pub(crate) fn split_counter(mut stored: Vec<u8>) -> Option<(Vec<u8>, u32)> {
+	if stored.len() < COUNTER_LEN {
+		return None;
+	}
+	let mut counter = [0u8; COUNTER_LEN];
+	counter.copy_from_slice(&stored[stored.len() - COUNTER_LEN..]);
+	stored.truncate(stored.len() - COUNTER_LEN);
+	Some((stored, u32::from_le_bytes(counter)))
+}
+
+fn join_counter(mut value: Vec<u8>, counter: u32) -> Vec<u8> {
+	value.extend_from_slice(&counter.to_le_bytes());
+	value
+}
+
+#[derive(Default)]
+struct NodeUpdate {
+	value: Option<Vec<u8>>,
+	delta: i64,
+}
+
+impl RefCountedState {
+	fn stored(&self, key: &[u8]) -> Option<(Vec<u8>, u32)> {
+		self.db.get(columns::STATE, key).and_then(|stored| {
+			let node = split_counter(stored);
+			if node.is_none() {
+				warn!(target: "db", "Corrupted reference counted state node {:?}", key);
+			}
+			node
+		})
+	}
+}
+
+impl Database<DbHash> for RefCountedState {
+	fn commit(&self, transaction: Transaction<DbHash>) -> Result<(), DatabaseError> {
+		let mut updates: HashMap<Vec<u8>, NodeUpdate> = HashMap::new();
+		let mut order = Vec::new();
+		let mut other = Transaction::new();
+		for change in transaction.0.into_iter() {
+			match change {
+				Change::Set(col, key, value) if col == columns::STATE => {
+					let update = updates.entry(key.clone()).or_insert_with(|| {
+						order.push(key);
+						Default::default()
+					});
+					// Re-inserted nodes may come with an empty value, only the first one counts.
+					if update.value.as_ref().map_or(true, |v| v.is_empty()) {
+						update.value = Some(value);
+					}
+					update.delta += 1;
+				},
+				Change::Remove(col, key) if col == columns::STATE => {
+					let update = updates.entry(key.clone()).or_insert_with(|| {
+						order.push(key);
+						Default::default()
+					});
+					update.delta -= 1;
+				},
+				change => other.0.push(change),
+			}
+		}
+
+		let _lock = self.commit_lock.lock();
+		for key in order {
+			let update = updates.remove(&key).expect("each key in `order` has an update; qed");
+			if update.delta == 0 {
+				continue;
+			}
+			let (value, counter) = match self.stored(&key) {
+				Some((value, counter)) => (Some(value), counter as i64),
+				None => (update.value, 0),
+			};
+			let counter = counter + update.delta;
+			if counter <= 0 {
+				if counter < 0 {
+					warn!(target: "db", "Removing state node {:?} with no references", key);
+				}
+				other.remove(columns::STATE, &key);
+			} else {
+				match value {
+					Some(value) => other.set_from_vec(
+						columns::STATE,
+						&key,
+						join_counter(value, counter as u32),
+					),
+					None => warn!(target: "db", "Referencing unknown state node {:?}", key),
+				}
+			}
+		}
+
+		self.db.commit(other)
+	}
+
+	fn get(&self, col: ColumnId, key: &[u8]) -> Option<Vec<u8>> {
+		if col == columns::STATE {
+			self.stored(key).map(|(value, _)| value)
+		} else {
+			self.db.get(col, key)
+		}
+	}
+
+	fn lookup(&self, hash: &DbHash) -> Option<Vec<u8>> {
+		self.db.lookup(hash)
+	}
+
+	fn iter_column(
+		&self,
+		col: ColumnId,
+		f: &mut dyn FnMut(&[u8], &[u8]) -> bool,
+	) -> Result<(), DatabaseError> {
+		if col == columns::STATE {
+			self.db.iter_column(col, &mut |key, stored| {
+				match split_counter(stored.to_vec()) {
+					Some((value, _)) => f(key, &value),
+					None => true,
+				}
+			})
+		} else {
+			self.db.iter_column(col, f)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tetcore_database::MemDb;
+
+	fn state_set(db: &dyn Database<DbHash>, key: &[u8], value: &[u8]) {
+		let mut tx = Transaction::new();
+		tx.set(columns::STATE, key, value);
+		db.commit(tx).unwrap();
+	}
+
+	fn state_remove(db: &dyn Database<DbHash>, key: &[u8]) {
+		let mut tx = Transaction::new();
+		tx.remove(columns::STATE, key);
+		db.commit(tx).unwrap();
+	}
+
+	#[test]
+	fn counts_references() {
+		let inner = Arc::new(MemDb::<DbHash>::default());
+		let db = wrap(inner.clone());
+
+		state_set(&*db, b"node", b"value");
+		state_set(&*db, b"node", b"");
+		assert_eq!(db.get(columns::STATE, b"node"), Some(b"value".to_vec()));
+		assert_eq!(inner.get(columns::STATE, b"node"), Some(join_counter(b"value".to_vec(), 2)));
+
+		state_remove(&*db, b"node");
+		assert_eq!(db.get(columns::STATE, b"node"), Some(b"value".to_vec()));
+		state_remove(&*db, b"node");
+		assert_eq!(db.get(columns::STATE, b"node"), None);
+		assert_eq!(inner.count(columns::STATE), 0);
+	}
+
+	#[test]
+	fn squashes_changes_within_transaction() {
+		let inner = Arc::new(MemDb::<DbHash>::default());
+		let db = wrap(inner.clone());
+
+		let mut tx = Transaction::new();
+		tx.set(columns::STATE, b"node", b"value");
+		tx.remove(columns::STATE, b"node");
+		tx.set(columns::STATE, b"other", b"other");
+		tx.set(columns::META, b"meta", b"meta");
+		db.commit(tx).unwrap();
+
+		assert_eq!(db.get(columns::STATE, b"node"), None);
+		assert_eq!(db.get(columns::STATE, b"other"), Some(b"other".to_vec()));
+		assert_eq!(inner.get(columns::META, b"meta"), Some(b"meta".to_vec()));
+	}
+
+	#[test]
+	fn concurrent_commits_keep_all_references() {
+		let inner = Arc::new(MemDb::<DbHash>::default());
+		let db = wrap(inner.clone());
+
+		let threads: Vec<_> = (0..4).map(|_| {
+			let db = db.clone();
+			std::thread::spawn(move || for _ in 0..100 {
+				state_set(&*db, b"node", b"value");
+			})
+		}).collect();
+		for thread in threads {
+			thread.join().unwrap();
+		}
+		assert_eq!(inner.get(columns::STATE, b"node"), Some(join_counter(b"value".to_vec(), 400)));
+	}
+}
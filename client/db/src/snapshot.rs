@@ -0,0 +1,219 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Streaming import of state snapshots.
+//!
+//! The entries of each trie are turned into trie nodes as they are read, in key order, and the
+//! nodes are written to the state column in batches. The state is therefore never held in
+//! memory, but the nodes are written before the state root can be checked.
+
+use std::{iter::Peekable, marker::PhantomData};
+use tetsy_hash_db::{AsHashDB, HashDB, Hasher, Prefix};
+use tetcore_database::{Database, Transaction, error::DatabaseError};
+use tp_blockchain::{Error as ClientError, Result as ClientResult};
+use tp_trie::prefixed_key;
+use tet_core::storage::well_known_keys;
+use crate::{columns, DbHash};
+
+/// Size of the trie nodes written to the database in a single transaction.
+const NODES_BATCH_BYTES: usize = 16 * 1024 * 1024;
+
+/// An entry of a state snapshot: the storage key of the default child trie holding it, `None`
+/// for the top trie, then its key and value.
+pub type SnapshotEntry = (Option<Vec<u8>>, Vec<u8>, Vec<u8>);
+
+/// Write-only `HashDB` storing the trie nodes built from a snapshot in the state column.
+pub(crate) struct SnapshotNodes<'a, H> {
+	db: &'a dyn Database<DbHash>,
+	prefix_keys: bool,
+	/// Keyspace of the trie being built, empty for the top trie.
+	keyspace: Vec<u8>,
+	transaction: Transaction<DbHash>,
+	pending_bytes: usize,
+	/// Error of a write done while building a trie.
+	error: Option<DatabaseError>,
+	_marker: PhantomData<H>,
+}
+
+impl<'a, H: Hasher> SnapshotNodes<'a, H> {
+	pub fn new(db: &'a dyn Database<DbHash>, prefix_keys: bool) -> Self {
+		SnapshotNodes {
+			db,
+			prefix_keys,
+			keyspace: Vec::new(),
+			transaction: Transaction::new(),
+			pending_bytes: 0,
+			error: None,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Set the keyspace of the trie built next.
+	pub fn set_keyspace(&mut self, keyspace: &[u8]) {
+		self.keyspace = keyspace.to_vec();
+	}
+
+	/// Write the pending nodes to the database.
+	pub fn flush(&mut self) -> ClientResult<()> {
+		if let Some(error) = self.error.take() {
+			return Err(error.into());
+		}
+		self.write()?;
+		Ok(())
+	}
+
+	fn write(&mut self) -> tetcore_database::error::Result<()> {
+		if self.pending_bytes > 0 {
+			self.pending_bytes = 0;
+			self.db.commit(std::mem::replace(&mut self.transaction, Transaction::new()))?;
+		}
+		Ok(())
+	}
+}
+
+impl<'a, H: Hasher> HashDB<H, usize> for SnapshotNodes<'a, H> {
+	fn get(&self, _key: &H::Out, _prefix: Prefix) -> Option<usize> {
+		None
+	}
+
+	fn contains(&self, _key: &H::Out, _prefix: Prefix) -> bool {
+		false
+	}
+
+	fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+		let hash = H::hash(value);
+		if self.error.is_some() {
+			return hash;
+		}
+
+		let key = if self.prefix_keys {
+			let mut keyspaced = self.keyspace.clone();
+			keyspaced.extend_from_slice(prefix.0);
+			prefixed_key::<H>(&hash, (&keyspaced, prefix.1))
+		} else {
+			hash.as_ref().to_vec()
+		};
+		self.pending_bytes += key.len() + value.len();
+		self.transaction.set(columns::STATE, &key, value);
+		if self.pending_bytes >= NODES_BATCH_BYTES {
+			if let Err(e) = self.write() {
+				self.error = Some(e);
+			}
+		}
+		hash
+	}
+
+	fn emplace(&mut self, _key: H::Out, _prefix: Prefix, _value: usize) {
+		// Only `insert` is used to build a trie.
+	}
+
+	fn remove(&mut self, _key: &H::Out, _prefix: Prefix) {
+		// Nodes are never removed while building a trie.
+	}
+}
+
+impl<'a, H: Hasher> AsHashDB<H, usize> for SnapshotNodes<'a, H> {
+	fn as_hash_db(&self) -> &dyn HashDB<H, usize> {
+		&*self
+	}
+
+	fn as_hash_db_mut<'b>(&'b mut self) -> &'b mut (dyn HashDB<H, usize> + 'b) {
+		&mut *self
+	}
+}
+
+/// Iterator over the entries of a single trie of a snapshot, in key order.
+///
+/// Stops at the first entry of another trie, and on the first error, which is then stored in
+/// `error`.
+pub(crate) struct TrieEntries<'a, I: Iterator> {
+	pub entries: &'a mut Peekable<I>,
+	/// Storage key of the child trie, `None` for the top trie.
+	pub child: Option<Vec<u8>>,
+	pub error: &'a mut Option<ClientError>,
+	pub last_key: Option<Vec<u8>>,
+}
+
+impl<'a, I> Iterator for TrieEntries<'a, I> where
+	I: Iterator<Item = ClientResult<SnapshotEntry>>,
+{
+	type Item = (Vec<u8>, Vec<u8>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.error.is_some() {
+			return None;
+		}
+		match self.entries.peek()? {
+			Ok((child, _, _)) if *child == self.child => {},
+			Ok(_) if self.child.is_none() => {
+				*self.error = Some(ClientError::Backend(
+					"Child trie entries must come before the top trie ones in a snapshot".into()
+				));
+				return None;
+			},
+			Ok(_) => return None,
+			Err(_) => {
+				if let Some(Err(e)) = self.entries.next() {
+					*self.error = Some(e);
+				}
+				return None;
+			},
+		}
+
+		let (_, key, value) = self.entries.next()?.ok()?;
+		if self.last_key.as_ref().map_or(false, |last| *last >= key) {
+			*self.error = Some(ClientError::Backend("Snapshot entries are not in key order".into()));
+			return None;
+		}
+		if self.child.is_none() && well_known_keys::is_child_storage_key(&key) {
+			*self.error = Some(ClientError::Backend(
+				"Child trie roots must not be part of the snapshot entries".into()
+			));
+			return None;
+		}
+		if self.child.is_none() && key == well_known_keys::CHANGES_TRIE_CONFIG {
+			*self.error = Some(ClientError::Backend(
+				"State snapshots of chains with changes tries are not supported".into()
+			));
+			return None;
+		}
+		self.last_key = Some(key.clone());
+		Some((key, value))
+	}
+}
+
+/// Merges two iterators of key-value pairs sorted by key and with distinct keys.
+pub(crate) struct MergeSorted<A: Iterator, B: Iterator> {
+	pub a: Peekable<A>,
+	pub b: Peekable<B>,
+}
+
+impl<A, B> Iterator for MergeSorted<A, B> where
+	A: Iterator<Item = (Vec<u8>, Vec<u8>)>,
+	B: Iterator<Item = (Vec<u8>, Vec<u8>)>,
+{
+	type Item = (Vec<u8>, Vec<u8>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match (self.a.peek(), self.b.peek()) {
+			(Some((a, _)), Some((b, _))) if b < a => self.b.next(),
+			(Some(_), _) => self.a.next(),
+			(None, _) => self.b.next(),
+		}
+	}
+}
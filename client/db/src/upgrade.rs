@@ -101,8 +101,7 @@ fn version_file_path(path: &Path) -> PathBuf {
 
 #[cfg(test)]
 mod tests {
-	use tc_state_db::PruningMode;
-	use crate::{DatabaseSettings, DatabaseSettingsSrc, KeepBlocks, TransactionStorageMode};
+	use crate::DatabaseSettingsSrc;
 	use crate::tests::Block;
 	use super::*;
 
@@ -115,14 +114,10 @@ mod tests {
 	}
 
 	fn open_database(db_path: &Path) -> tp_blockchain::Result<()> {
-		crate::utils::open_database::<Block>(&DatabaseSettings {
-			state_cache_size: 0,
-			state_cache_child_ratio: None,
-			state_pruning: PruningMode::ArchiveAll,
-			source: DatabaseSettingsSrc::RocksDb { path: db_path.to_owned(), cache_size: 128 },
-			keep_blocks: KeepBlocks::All,
-			transaction_storage: TransactionStorageMode::BlockBody,
-		}, DatabaseType::Full).map(|_| ())
+		crate::utils::open_database::<Block>(
+			&DatabaseSettingsSrc::RocksDb { path: db_path.to_owned(), cache_size: 128 },
+			DatabaseType::Full,
+		).map(|_| ())
 	}
 
 	#[test]
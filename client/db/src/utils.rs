@@ -32,7 +32,7 @@ use tp_runtime::traits::{
 	Block as BlockT, Header as HeaderT, Zero,
 	UniqueSaturatedFrom, UniqueSaturatedInto,
 };
-use crate::{DatabaseSettings, DatabaseSettingsSrc, Database, DbHash};
+use crate::{DatabaseSettingsSrc, Database, DbHash, StateStorageMode};
 
 /// Number of columns in the db. Must be the same for both full && light dbs.
 /// Otherwise RocksDb will fail to open database && check its type.
@@ -59,6 +59,8 @@ pub mod meta_keys {
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
 	/// Children prefix list key.
 	pub const CHILDREN_PREFIX: &[u8; 8] = b"children";
+	/// State storage scheme (full only).
+	pub const STATE_STORAGE: &[u8; 5] = b"state";
 }
 
 /// Database metadata.
@@ -209,7 +211,7 @@ pub fn block_id_to_lookup_key<Block>(
 
 /// Opens the configured database.
 pub fn open_database<Block: BlockT>(
-	config: &DatabaseSettings,
+	source: &DatabaseSettingsSrc,
 	db_type: DatabaseType,
 ) -> tp_blockchain::Result<Arc<dyn Database<DbHash>>> {
 	#[allow(unused)]
@@ -219,7 +221,7 @@ pub fn open_database<Block: BlockT>(
 		)
 	}
 
-	let db: Arc<dyn Database<DbHash>> = match &config.source {
+	let db: Arc<dyn Database<DbHash>> = match source {
 		#[cfg(any(feature = "with-tetsy-kvdb-rocksdb", test))]
 		DatabaseSettingsSrc::RocksDb { path, cache_size } => {
 			// first upgrade database to required version
@@ -311,6 +313,39 @@ pub fn check_database_type(db: &dyn Database<DbHash>, db_type: DatabaseType) ->
 	Ok(())
 }
 
+/// Check the state storage scheme of a full node database. Databases that were initialized before
+/// the scheme was recorded are assumed to use `legacy_mode`.
+pub fn check_state_storage_mode(
+	db: &dyn Database<DbHash>,
+	mode: StateStorageMode,
+	legacy_mode: StateStorageMode,
+) -> tp_blockchain::Result<()> {
+	let stored = match db.get(COLUMN_META, meta_keys::STATE_STORAGE) {
+		Some(stored) => stored,
+		None => {
+			if db.get(COLUMN_META, meta_keys::GENESIS_HASH).is_some() && mode != legacy_mode {
+				return Err(tp_blockchain::Error::Backend(
+					format!("Unexpected state storage mode. Expected: {}", legacy_mode.as_str())));
+			}
+			let mut transaction = Transaction::new();
+			transaction.set(COLUMN_META, meta_keys::STATE_STORAGE, mode.as_str().as_bytes());
+			db.commit(transaction)?;
+			return Ok(());
+		},
+	};
+
+	if mode.as_str().as_bytes() != &*stored {
+		return Err(tp_blockchain::Error::Backend(
+			format!(
+				"Unexpected state storage mode. Expected: {}",
+				String::from_utf8_lossy(&stored),
+			)
+		));
+	}
+
+	Ok(())
+}
+
 /// Read database column entry for the given block.
 pub fn read_db<Block>(
 	db: &dyn Database<DbHash>,
@@ -424,6 +424,12 @@ impl<BE, Block: BlockT, Client, SC> BlockImport<Block>
 		// early exit if block already in chain, otherwise the check for
 		// authority changes will error when trying to re-import a change block
 		match self.inner.status(BlockId::Hash(hash)) {
+			Ok(BlockStatus::InChain) if block.import_existing => {
+				// authority changes were processed when the block was first imported,
+				// only its state is imported now.
+				return (&*self.inner).import_block(block, new_cache)
+					.map_err(|e| ConsensusError::ClientImport(e.to_string()));
+			},
 			Ok(BlockStatus::InChain) => return Ok(ImportResult::AlreadyInChain),
 			Ok(BlockStatus::Unknown) => {},
 			Err(e) => return Err(ConsensusError::ClientImport(e.to_string())),
@@ -32,7 +32,7 @@ use tp_runtime::traits::{
 use tp_state_machine::{
 	ChangesTrieRootsStorage, ChangesTrieAnchorBlockId, ChangesTrieConfigurationRange,
 	InMemoryChangesTrieStorage, TrieBackend, read_proof_check, key_changes_proof_check_with_db,
-	read_child_proof_check,
+	read_child_proof_check, read_keys_proof_check,
 };
 pub use tp_state_machine::StorageProof;
 use tp_blockchain::{Error as ClientError, Result as ClientResult};
@@ -40,7 +40,7 @@ use tp_blockchain::{Error as ClientError, Result as ClientResult};
 pub use tc_client_api::{
 	light::{
 		RemoteCallRequest, RemoteHeaderRequest, RemoteReadRequest, RemoteReadChildRequest,
-		RemoteChangesRequest, ChangesProof, RemoteBodyRequest, Fetcher, FetchChecker,
+		RemoteReadKeysRequest, RemoteChangesRequest, ChangesProof, RemoteBodyRequest, Fetcher, FetchChecker,
 		Storage as BlockchainStorage,
 	},
 	cht,
@@ -259,6 +259,20 @@ impl<E, Block, H, S> FetchChecker<Block> for LightDataChecker<E, H, Block, S>
 		).map_err(|e| ClientError::from(e))
 	}
 
+	fn check_read_keys_proof(
+		&self,
+		request: &RemoteReadKeysRequest<Block::Header>,
+		remote_proof: StorageProof,
+	) -> ClientResult<Vec<Vec<u8>>> {
+		read_keys_proof_check::<H>(
+			convert_hash(request.header.state_root()),
+			remote_proof,
+			&request.prefix,
+			request.start_key.as_deref(),
+			request.count,
+		).map_err(|e| ClientError::from(e))
+	}
+
 	fn check_execution_proof(
 		&self,
 		request: &RemoteCallRequest<Block::Header>,
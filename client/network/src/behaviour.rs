@@ -74,6 +74,11 @@ pub struct Behaviour<B: BlockT, H: ExHashT> {
 	/// [`request_responses::RequestResponsesBehaviour`].
 	#[behaviour(ignore)]
 	block_request_protocol_name: String,
+
+	/// Protocol name used to send out state requests via
+	/// [`request_responses::RequestResponsesBehaviour`].
+	#[behaviour(ignore)]
+	state_request_protocol_name: String,
 }
 
 /// Event generated by `Behaviour`.
@@ -95,6 +100,10 @@ pub enum BehaviourOut<B: BlockT> {
 		/// If `Ok`, contains the time elapsed between when we received the request and when we
 		/// sent back the response. If `Err`, the error that happened.
 		result: Result<Duration, ResponseFailure>,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response has been sent.
+		response_size: usize,
 	},
 
 	/// A request has succeeded or failed.
@@ -109,6 +118,10 @@ pub enum BehaviourOut<B: BlockT> {
 		duration: Duration,
 		/// Result of the request.
 		result: Result<(), RequestFailure>,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response has been received.
+		response_size: usize,
 	},
 
 	/// Opened a substream with the given node with the given notifications protocol.
@@ -178,12 +191,16 @@ impl<B: BlockT, H: ExHashT> Behaviour<B, H> {
 		disco_config: DiscoveryConfig,
 		// Block request protocol config.
 		block_request_protocol_config: request_responses::ProtocolConfig,
+		// State request protocol config.
+		state_request_protocol_config: request_responses::ProtocolConfig,
 		// All remaining request protocol configs.
 		mut request_response_protocols: Vec<request_responses::ProtocolConfig>,
 	) -> Result<Self, request_responses::RegisterError> {
-		// Extract protocol name and add to `request_response_protocols`.
+		// Extract protocol names and add to `request_response_protocols`.
 		let block_request_protocol_name = block_request_protocol_config.name.to_string();
 		request_response_protocols.push(block_request_protocol_config);
+		let state_request_protocol_name = state_request_protocol_config.name.to_string();
+		request_response_protocols.push(state_request_protocol_config);
 
 		Ok(Behaviour {
 			tetcore,
@@ -196,6 +213,7 @@ impl<B: BlockT, H: ExHashT> Behaviour<B, H> {
 			role,
 
 			block_request_protocol_name,
+			state_request_protocol_name,
 		})
 	}
 
@@ -319,6 +337,21 @@ Behaviour<B, H> {
 					&target, &self.block_request_protocol_name, buf, pending_response,
 				);
 			},
+			CustomMessageOutcome::StateRequest { target, request, pending_response } => {
+				let mut buf = Vec::with_capacity(request.encoded_len());
+				if let Err(err) = request.encode(&mut buf) {
+					log::warn!(
+						target: "sync",
+						"Failed to encode state request {:?}: {:?}",
+						request, err
+					);
+					return
+				}
+
+				self.request_responses.send_request(
+					&target, &self.state_request_protocol_name, buf, pending_response,
+				);
+			},
 			CustomMessageOutcome::NotificationStreamOpened { remote, protocol, roles, notifications_sink } => {
 				let role = reported_roles_to_observed_role(&self.role, &remote, roles);
 				self.events.push_back(BehaviourOut::NotificationStreamOpened {
@@ -357,16 +390,22 @@ Behaviour<B, H> {
 impl<B: BlockT, H: ExHashT> NetworkBehaviourEventProcess<request_responses::Event> for Behaviour<B, H> {
 	fn inject_event(&mut self, event: request_responses::Event) {
 		match event {
-			request_responses::Event::InboundRequest { peer, protocol, result } => {
+			request_responses::Event::InboundRequest {
+				peer, protocol, result, request_size, response_size,
+			} => {
 				self.events.push_back(BehaviourOut::InboundRequest {
 					peer,
 					protocol,
 					result,
+					request_size,
+					response_size,
 				});
 			}
-			request_responses::Event::RequestFinished { peer, protocol, duration, result } => {
+			request_responses::Event::RequestFinished {
+				peer, protocol, duration, result, request_size, response_size,
+			} => {
 				self.events.push_back(BehaviourOut::RequestFinished {
-					peer, protocol, duration, result,
+					peer, protocol, duration, result, request_size, response_size,
 				});
 			},
 			request_responses::Event::ReputationChanges { peer, changes } => {
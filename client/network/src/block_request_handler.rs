@@ -35,7 +35,9 @@ use std::sync::{Arc};
 use std::time::Duration;
 
 const LOG_TARGET: &str = "block-request-handler";
-const MAX_BLOCKS_IN_RESPONSE: usize = 128;
+const MAX_BLOCKS_IN_RESPONSE: usize = 512;
+/// Responses stop growing once the encoded blocks exceed this size, so that they stay well
+/// within the maximum response size of the protocol.
 const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
 
 /// Generates a [`ProtocolConfig`] for the block request protocol, refusing incoming requests.
@@ -156,7 +158,7 @@ impl <B: BlockT> BlockRequestHandler<B> {
 				is_empty_justification,
 			};
 
-			total_size += block_data.body.len();
+			total_size += block_data.encoded_len();
 			blocks.push(block_data);
 
 			if blocks.len() >= max_blocks as usize || total_size > MAX_BODY_BYTES {
@@ -29,6 +29,7 @@ pub use crate::request_responses::{
 	ProtocolConfig as RequestResponseConfig,
 };
 pub use tetsy_libp2p::{identity, core::PublicKey, wasm_ext::ExtTransport, build_multiaddr};
+pub use ip_network::IpNetwork;
 
 // Note: this re-export shouldn't be part of the public API of the crate and will be removed in
 // the future.
@@ -111,6 +112,14 @@ pub struct Params<B: BlockT, H: ExHashT> {
 	/// [`block_request_handler::BlockRequestHandler::new`] allowing both outgoing and incoming
 	/// requests.
 	pub block_request_protocol_config: RequestResponseConfig,
+
+	/// Request response configuration for the state request protocol.
+	///
+	/// Can be constructed either via [`state_request_handler::generate_protocol_config`] allowing
+	/// outgoing but not incoming requests, or constructed via
+	/// [`state_request_handler::StateRequestHandler::new`] allowing both outgoing and incoming
+	/// requests.
+	pub state_request_protocol_config: RequestResponseConfig,
 }
 
 /// Role of the local node.
@@ -400,11 +409,27 @@ pub struct NetworkConfiguration {
 	pub transport: TransportConfig,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// Maximum number of blocks to ask a peer in a single request. The number of blocks
+	/// requested adapts to the throughput of each peer, up to this limit.
+	pub max_blocks_per_request: u32,
+	/// How to synchronize the chain.
+	pub sync_mode: SyncMode,
 	/// Should we insert non-global addresses into the DHT?
 	pub allow_non_globals_in_dht: bool,
 	/// Require iterative Kademlia DHT queries to use disjoint paths for increased resiliency in the
 	/// presence of potentially adversarial nodes.
 	pub kademlia_disjoint_query_paths: bool,
+	/// Filtering and limits of the connections based on the IP address of the remote.
+	pub ip_filter: IpFilterConfig,
+	/// Maximum outbound bandwidth, in bytes per second, of notifications protocols, all peers
+	/// combined. Protocols that aren't in the list aren't limited.
+	///
+	/// When the limit is reached, [`NotificationSender::ready`](crate::NotificationSender::ready)
+	/// waits until enough time has passed, notifications sent with
+	/// [`NetworkService::write_notification`](crate::NetworkService::write_notification) are
+	/// dropped, and transactions are kept for the next propagation. The block announces
+	/// protocol can't be limited.
+	pub outbound_bandwidth_caps: HashMap<Cow<'static, str>, u64>,
 
 	/// Size of Remux receive window of all substreams. `None` for the default (256kiB).
 	/// Any value less than 256kiB is invalid.
@@ -453,8 +478,12 @@ impl NetworkConfiguration {
 				wasm_external_transport: None,
 			},
 			max_parallel_downloads: 5,
+			max_blocks_per_request: 512,
+			sync_mode: SyncMode::Full,
 			allow_non_globals_in_dht: false,
 			kademlia_disjoint_query_paths: false,
+			ip_filter: Default::default(),
+			outbound_bandwidth_caps: HashMap::new(),
 			remux_window_size: None,
 		}
 	}
@@ -567,6 +596,35 @@ pub enum TransportConfig {
 	MemoryOnly,
 }
 
+/// Filtering and limits of the connections based on the IP address of the remote.
+///
+/// The filters are applied as soon as a connection is established, before any protocol is
+/// negotiated on it. Outbound connections to addresses that don't contain an IP address, such as
+/// DNS addresses, aren't filtered.
+#[derive(Clone, Debug, Default)]
+pub struct IpFilterConfig {
+	/// If non-empty, only connections with addresses within these ranges are allowed.
+	pub allow: Vec<IpNetwork>,
+	/// Connections with addresses within these ranges are denied, whether they are inbound or
+	/// outbound.
+	pub deny: Vec<IpNetwork>,
+	/// Maximum number of inbound connections from a single IP address.
+	pub max_inbound_per_ip: Option<u32>,
+	/// Maximum number of inbound connections from a single subnet, i.e. a `/24` for IPv4 and a
+	/// `/64` for IPv6 addresses.
+	pub max_inbound_per_subnet: Option<u32>,
+}
+
+/// How the chain is synchronized.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+	/// Download and execute all blocks. This is the default.
+	Full,
+	/// Download headers only, then download the state of a recent finalized block from peers
+	/// and execute the blocks after it.
+	Fast,
+}
+
 /// The policy for connections to non-reserved peers.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum NonReservedPeerMode {
@@ -67,6 +67,12 @@ pub enum Error {
 		/// Name of the protocol registered multiple times.
 		protocol: Cow<'static, str>,
 	},
+	/// An outbound bandwidth cap has been set on a protocol that can't be throttled.
+	#[display(fmt = "Outbound bandwidth of protocol {} can't be capped", protocol)]
+	UncappableProtocol {
+		/// Name of the protocol.
+		protocol: Cow<'static, str>,
+	},
 }
 
 // Make `Debug` use the `Display` implementation.
@@ -85,6 +91,7 @@ impl std::error::Error for Error {
 			Error::Prometheus(ref err) => Some(err),
 			Error::AddressesForAnotherTransport { .. } => None,
 			Error::DuplicateRequestResponseProtocol { .. } => None,
+			Error::UncappableProtocol { .. } => None,
 		}
 	}
 }
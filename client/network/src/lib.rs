@@ -263,16 +263,17 @@ pub mod config;
 pub mod error;
 pub mod gossip;
 pub mod network_state;
+pub mod state_request_handler;
 
 #[doc(inline)]
 pub use tetsy_libp2p::{multiaddr, Multiaddr, PeerId};
 pub use protocol::{event::{DhtEvent, Event, ObservedRole}, sync::SyncState, PeerInfo};
 pub use service::{
 	NetworkService, NetworkWorker, RequestFailure, OutboundFailure, NotificationSender,
-	NotificationSenderReady,
+	NotificationSenderReady, Traffic,
 };
 
-pub use tc_peerset::ReputationChange;
+pub use tc_peerset::{BannedPeer, ReputationChange};
 use tp_runtime::traits::{Block as BlockT, NumberFor};
 
 /// The maximum allowed number of established connections per peer.
@@ -71,9 +71,10 @@ use tet_core::{
 	hexdisplay::HexDisplay,
 };
 use smallvec::SmallVec;
-use tp_blockchain::{Error as ClientError};
+use tp_blockchain::{Error as ClientError, HeaderBackend};
+use tp_trie::CompactProof;
 use tp_runtime::{
-	traits::{Block, Header, NumberFor, Zero},
+	traits::{Block, Header, HashFor, NumberFor, Zero},
 	generic::BlockId,
 };
 use std::{
@@ -90,6 +91,9 @@ use wasm_timer::Instant;
 /// Reputation change for a peer when a request timed out.
 pub(crate) const TIMEOUT_REPUTATION_CHANGE: i32 = -(1 << 8);
 
+/// Maximum number of keys a remote keys read request may ask for.
+const MAX_READ_KEYS: u32 = 1000;
+
 /// Configuration options for `LightClientHandler` behaviour.
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -190,6 +194,9 @@ pub enum Error {
 	/// Encoding or decoding of some data failed.
 	#[error("codec error: {0}")]
 	Codec(#[from] codec::Error),
+	/// A compact proof could not be decoded.
+	#[error("invalid compact proof: {0}")]
+	CompactProof(String),
 }
 
 /// The possible light client requests we support.
@@ -217,6 +224,14 @@ pub enum Request<B: Block> {
 		request: light::RemoteReadChildRequest<B::Header>,
 		sender: oneshot::Sender<Result<HashMap<Vec<u8>, Option<Vec<u8>>>, ClientError>>
 	},
+	ReadChildProof {
+		request: light::RemoteReadChildRequest<B::Header>,
+		sender: oneshot::Sender<Result<StorageProof, ClientError>>
+	},
+	ReadKeys {
+		request: light::RemoteReadKeysRequest<B::Header>,
+		sender: oneshot::Sender<Result<Vec<Vec<u8>>, ClientError>>
+	},
 	Call {
 		request: light::RemoteCallRequest<B::Header>,
 		sender: oneshot::Sender<Result<Vec<u8>, ClientError>>
@@ -239,6 +254,8 @@ enum Reply<B: Block> {
 	MapVecU8OptVecU8(HashMap<Vec<u8>, Option<Vec<u8>>>),
 	Header(B::Header),
 	Extrinsics(Vec<B::Extrinsic>),
+	Proof(StorageProof),
+	Keys(Vec<Vec<u8>>),
 }
 
 /// Augments a light client request with metadata.
@@ -456,15 +473,25 @@ where
 			Some(Response::RemoteReadResponse(response)) =>
 				match request {
 					Request::Read { request, .. } => {
-						let proof = Decode::decode(&mut response.proof.as_ref())?;
+						let proof = decode_read_proof::<B>(&request.header, &response)?;
 						let reply = self.checker.check_read_proof(&request, proof)?;
 						Ok(Reply::MapVecU8OptVecU8(reply))
 					}
 					Request::ReadChild { request, .. } => {
-						let proof = Decode::decode(&mut response.proof.as_ref())?;
+						let proof = decode_read_proof::<B>(&request.header, &response)?;
 						let reply = self.checker.check_read_child_proof(&request, proof)?;
 						Ok(Reply::MapVecU8OptVecU8(reply))
 					}
+					Request::ReadChildProof { request, .. } => {
+						let proof = decode_read_proof::<B>(&request.header, &response)?;
+						self.checker.check_read_child_proof(&request, proof.clone())?;
+						Ok(Reply::Proof(proof))
+					}
+					Request::ReadKeys { request, .. } => {
+						let proof = decode_read_proof::<B>(&request.header, &response)?;
+						let reply = self.checker.check_read_keys_proof(&request, proof)?;
+						Ok(Reply::Keys(reply))
+					}
 					_ => Err(Error::UnexpectedResponse)
 				}
 			Some(Response::RemoteChangesResponse(response)) =>
@@ -600,13 +627,48 @@ where
 		};
 
 		let response = {
-			let r = schema::v1::light::RemoteReadResponse { proof: proof.encode() };
+			let r = self.read_response(peer, &block, proof, request.compact);
 			schema::v1::light::response::Response::RemoteReadResponse(r)
 		};
 
 		Ok(schema::v1::light::Response { response: Some(response) })
 	}
 
+	/// Encode a read proof, in compact form if the requester supports it.
+	///
+	/// Falls back to the plain proof if it cannot be compacted, e.g. because the header of
+	/// `block` is unknown.
+	fn read_response
+		( &self
+		, peer: &PeerId
+		, block: &B::Hash
+		, proof: StorageProof
+		, compact: bool
+		) -> schema::v1::light::RemoteReadResponse
+	{
+		if compact && !proof.is_empty() {
+			let compact_proof = self.chain.header(BlockId::Hash(*block))
+				.map_err(|e| format!("{}", e))
+				.and_then(|header| header.ok_or_else(|| "unknown block".to_string()))
+				.and_then(|header| proof.clone()
+					.into_compact_proof::<HashFor<B>>(*header.state_root())
+					.map_err(|e| format!("{:?}", e)));
+			match compact_proof {
+				Ok(compact_proof) => return schema::v1::light::RemoteReadResponse {
+					proof: compact_proof.encode(),
+					compact: true,
+				},
+				Err(error) => log::debug!(
+					"failed to compact read proof for {} at {:?}: {}",
+					peer,
+					block,
+					error,
+				),
+			}
+		}
+		schema::v1::light::RemoteReadResponse { proof: proof.encode(), compact: false }
+	}
+
 	fn on_remote_read_child_request
 		( &mut self
 		, peer: &PeerId
@@ -649,7 +711,57 @@ where
 		};
 
 		let response = {
-			let r = schema::v1::light::RemoteReadResponse { proof: proof.encode() };
+			let r = self.read_response(peer, &block, proof, request.compact);
+			schema::v1::light::response::Response::RemoteReadResponse(r)
+		};
+
+		Ok(schema::v1::light::Response { response: Some(response) })
+	}
+
+	fn on_remote_read_keys_request
+		( &mut self
+		, peer: &PeerId
+		, request: &schema::v1::light::RemoteReadKeysRequest
+		) -> Result<schema::v1::light::Response, Error>
+	{
+		if request.count > MAX_READ_KEYS {
+			log::debug!("invalid remote read keys request sent by {}", peer);
+			return Err(Error::BadRequest("remote read keys request for too many keys"))
+		}
+
+		log::trace!("remote read keys request from {} ({} keys of {} from {} at {:?})",
+			peer,
+			request.count,
+			HexDisplay::from(&request.prefix),
+			HexDisplay::from(&request.start_key),
+			request.block);
+
+		let block = Decode::decode(&mut request.block.as_ref())?;
+		let start_key = if request.start_key.is_empty() {
+			None
+		} else {
+			Some(request.start_key.as_ref())
+		};
+
+		let proof = match self.chain.read_keys_proof(
+			&BlockId::Hash(block),
+			&request.prefix,
+			start_key,
+			request.count,
+		) {
+			Ok((_, proof)) => proof,
+			Err(error) => {
+				log::trace!("remote read keys request from {} ({} at {:?}) failed with: {}",
+					peer,
+					HexDisplay::from(&request.prefix),
+					request.block,
+					error);
+				StorageProof::empty()
+			}
+		};
+
+		let response = {
+			let r = self.read_response(peer, &block, proof, request.compact);
 			schema::v1::light::response::Response::RemoteReadResponse(r)
 		};
 
@@ -835,6 +947,8 @@ where
 						self.on_remote_read_child_request(&peer, r),
 					Some(schema::v1::light::request::Request::RemoteChangesRequest(r)) =>
 						self.on_remote_changes_request(&peer, r),
+					Some(schema::v1::light::request::Request::RemoteReadKeysRequest(r)) =>
+						self.on_remote_read_keys_request(&peer, r),
 					None => {
 						log::debug!("ignoring request without request data from peer {}", peer);
 						return
@@ -1045,6 +1159,8 @@ fn required_block<B: Block>(request: &Request<B>) -> NumberFor<B> {
 		Request::Header { request, .. } => request.block,
 		Request::Read { request, .. } => *request.header.number(),
 		Request::ReadChild { request, .. } => *request.header.number(),
+		Request::ReadChildProof { request, .. } => *request.header.number(),
+		Request::ReadKeys { request, .. } => *request.header.number(),
 		Request::Call { request, .. } => *request.header.number(),
 		Request::Changes { request, .. } => request.max_block.0,
 	}
@@ -1056,6 +1172,8 @@ fn retries<B: Block>(request: &Request<B>) -> usize {
 		Request::Header { request, .. } => request.retry_count,
 		Request::Read { request, .. } => request.retry_count,
 		Request::ReadChild { request, .. } => request.retry_count,
+		Request::ReadChildProof { request, .. } => request.retry_count,
+		Request::ReadKeys { request, .. } => request.retry_count,
 		Request::Call { request, .. } => request.retry_count,
 		Request::Changes { request, .. } => request.retry_count,
 	};
@@ -1087,17 +1205,29 @@ fn serialize_request<B: Block>(request: &Request<B>) -> Result<Vec<u8>, prost::E
 			let r = schema::v1::light::RemoteReadRequest {
 				block: request.block.encode(),
 				keys: request.keys.clone(),
+				compact: true,
 			};
 			schema::v1::light::request::Request::RemoteReadRequest(r)
 		}
-		Request::ReadChild { request, .. } => {
+		Request::ReadChild { request, .. } | Request::ReadChildProof { request, .. } => {
 			let r = schema::v1::light::RemoteReadChildRequest {
 				block: request.block.encode(),
 				storage_key: request.storage_key.clone().into_inner(),
 				keys: request.keys.clone(),
+				compact: true,
 			};
 			schema::v1::light::request::Request::RemoteReadChildRequest(r)
 		}
+		Request::ReadKeys { request, .. } => {
+			let r = schema::v1::light::RemoteReadKeysRequest {
+				block: request.block.encode(),
+				prefix: request.prefix.clone(),
+				start_key: request.start_key.clone().unwrap_or_default(),
+				count: request.count,
+				compact: true,
+			};
+			schema::v1::light::request::Request::RemoteReadKeysRequest(r)
+		}
 		Request::Call { request, .. } => {
 			let r = schema::v1::light::RemoteCallRequest {
 				block: request.block.encode(),
@@ -1126,6 +1256,20 @@ fn serialize_request<B: Block>(request: &Request<B>) -> Result<Vec<u8>, prost::E
 	Ok(buf)
 }
 
+/// Decode the proof of a read response, expanding it if it is compact.
+fn decode_read_proof<B: Block>(
+	header: &B::Header,
+	response: &schema::v1::light::RemoteReadResponse,
+) -> Result<StorageProof, Error> {
+	if !response.compact {
+		return Ok(Decode::decode(&mut response.proof.as_ref())?)
+	}
+	let proof: CompactProof = Decode::decode(&mut response.proof.as_ref())?;
+	proof.to_storage_proof::<HashFor<B>>(Some(header.state_root()))
+		.map(|(proof, _root)| proof)
+		.map_err(|e| Error::CompactProof(format!("{:?}", e)))
+}
+
 fn send_reply<B: Block>(result: Result<Reply<B>, ClientError>, request: Request<B>) {
 	fn send<T>(item: T, sender: oneshot::Sender<T>) {
 		let _ = sender.send(item); // It is okay if the other end already hung up.
@@ -1151,6 +1295,16 @@ fn send_reply<B: Block>(result: Result<Reply<B>, ClientError>, request: Request<
 			Ok(Reply::MapVecU8OptVecU8(x)) => send(Ok(x), sender),
 			reply => log::error!("invalid reply for read child request: {:?}, {:?}", reply, request),
 		}
+		Request::ReadChildProof { request, sender } => match result {
+			Err(e) => send(Err(e), sender),
+			Ok(Reply::Proof(x)) => send(Ok(x), sender),
+			reply => log::error!("invalid reply for read child proof request: {:?}, {:?}", reply, request),
+		}
+		Request::ReadKeys { request, sender } => match result {
+			Err(e) => send(Err(e), sender),
+			Ok(Reply::Keys(x)) => send(Ok(x), sender),
+			reply => log::error!("invalid reply for read keys request: {:?}, {:?}", reply, request),
+		}
 		Request::Call { request, sender } => match result {
 			Err(e) => send(Err(e), sender),
 			Ok(Reply::VecU8(x)) => send(Ok(x), sender),
@@ -1415,6 +1569,17 @@ mod tests {
 			}
 		}
 
+		fn check_read_keys_proof(
+			&self,
+			request: &light::RemoteReadKeysRequest<B::Header>,
+			_: StorageProof,
+		) -> Result<Vec<Vec<u8>>, ClientError> {
+			match self.ok {
+				true => Ok(vec![request.prefix.clone()]),
+				false => Err(ClientError::Backend("Test error".into())),
+			}
+		}
+
 		fn check_execution_proof(
 			&self,
 			_: &RemoteCallRequest<B::Header>,
@@ -1495,7 +1660,10 @@ mod tests {
 			reserved_only: false,
 			reserved_nodes: Default::default(),
 		};
-		tc_peerset::Peerset::from_config(tc_peerset::PeersetConfig{ sets: vec![cfg] })
+		tc_peerset::Peerset::from_config(tc_peerset::PeersetConfig {
+			sets: vec![cfg],
+			persist_path: None,
+		})
 	}
 
 	fn make_behaviour
@@ -1707,7 +1875,7 @@ mod tests {
 		let request_id = *behaviour.outstanding.keys().next().unwrap();
 
 		let response = {
-			let r = schema::v1::light::RemoteReadResponse { proof: empty_proof() }; // Not a RemoteCallResponse!
+			let r = schema::v1::light::RemoteReadResponse { proof: empty_proof(), compact: false }; // Not a RemoteCallResponse!
 			schema::v1::light::Response {
 				response: Some(schema::v1::light::response::Response::RemoteReadResponse(r)),
 			}
@@ -1814,13 +1982,13 @@ mod tests {
 				}
 			}
 			Request::Read{..} => {
-				let r = schema::v1::light::RemoteReadResponse { proof: empty_proof() };
+				let r = schema::v1::light::RemoteReadResponse { proof: empty_proof(), compact: false };
 				schema::v1::light::Response {
 					response: Some(schema::v1::light::response::Response::RemoteReadResponse(r)),
 				}
 			}
-			Request::ReadChild{..} => {
-				let r = schema::v1::light::RemoteReadResponse { proof: empty_proof() };
+			Request::ReadChild{..} | Request::ReadChildProof{..} | Request::ReadKeys{..} => {
+				let r = schema::v1::light::RemoteReadResponse { proof: empty_proof(), compact: false };
 				schema::v1::light::Response {
 					response: Some(schema::v1::light::response::Response::RemoteReadResponse(r)),
 				}
@@ -1903,6 +2071,36 @@ mod tests {
 		assert_matches!(chan.1.try_recv(), Ok(Some(Ok(_))))
 	}
 
+	#[test]
+	fn receives_remote_read_child_proof_response() {
+		let mut chan = oneshot::channel();
+		let child_info = ChildInfo::new_default(&b":child_storage:default:sub"[..]);
+		let request = light::RemoteReadChildRequest {
+			header: dummy_header(),
+			block: Default::default(),
+			storage_key: child_info.prefixed_storage_key(),
+			keys: vec![b":key".to_vec()],
+			retry_count: None,
+		};
+		issue_request(Request::ReadChildProof { request, sender: chan.0 });
+		assert_matches!(chan.1.try_recv(), Ok(Some(Ok(_))))
+	}
+
+	#[test]
+	fn receives_remote_read_keys_response() {
+		let mut chan = oneshot::channel();
+		let request = light::RemoteReadKeysRequest {
+			header: dummy_header(),
+			block: Default::default(),
+			prefix: b":key".to_vec(),
+			start_key: None,
+			count: 10,
+			retry_count: None,
+		};
+		issue_request(Request::ReadKeys { request, sender: chan.0 });
+		assert_matches!(chan.1.try_recv(), Ok(Some(Ok(_))))
+	}
+
 	#[test]
 	fn receives_remote_header_response() {
 		let mut chan = oneshot::channel();
@@ -2058,4 +2256,36 @@ mod tests {
 				.contains(BlockAttributes::BODY)
 		);
 	}
+
+	#[test]
+	fn decodes_compact_read_proof() {
+		use tp_trie::{Layout, MemoryDB, Recorder, Trie, TrieDB, TrieDBMut, TrieMut};
+
+		let mut db = MemoryDB::<BlakeTwo256>::default();
+		let mut root = Default::default();
+		{
+			let mut trie = TrieDBMut::<Layout<BlakeTwo256>>::new(&mut db, &mut root);
+			for i in 0u8..32 {
+				trie.insert(&[i, i], &[i; 40]).unwrap();
+			}
+		}
+		let mut recorder = Recorder::new();
+		TrieDB::<Layout<BlakeTwo256>>::new(&db, &root).unwrap().get_with(&[7, 7], &mut recorder).unwrap();
+		let proof = StorageProof::new(recorder.drain().into_iter().map(|record| record.data).collect());
+		let compact = proof.clone().into_compact_proof::<BlakeTwo256>(root).unwrap();
+
+		let mut header = dummy_header();
+		header.state_root = root;
+		let response = |proof: Vec<u8>, compact| schema::v1::light::RemoteReadResponse { proof, compact };
+		let nodes = |proof: StorageProof| proof.iter_nodes().collect::<std::collections::BTreeSet<_>>();
+
+		let decoded = decode_read_proof::<Block>(&header, &response(compact.encode(), true)).unwrap();
+		assert_eq!(nodes(decoded), nodes(proof.clone()));
+		let plain = decode_read_proof::<Block>(&header, &response(proof.encode(), false)).unwrap();
+		assert_eq!(plain, proof);
+		assert_matches!(
+			decode_read_proof::<Block>(&dummy_header(), &response(compact.encode(), true)),
+			Err(super::Error::CompactProof(_))
+		);
+	}
 }
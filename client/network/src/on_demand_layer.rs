@@ -24,7 +24,8 @@ use futures::{channel::oneshot, prelude::*};
 use parking_lot::Mutex;
 use tc_client_api::{
 	FetchChecker, Fetcher, RemoteBodyRequest, RemoteCallRequest, RemoteChangesRequest,
-	RemoteHeaderRequest, RemoteReadChildRequest, RemoteReadRequest, StorageProof, ChangesProof,
+	RemoteHeaderRequest, RemoteReadChildRequest, RemoteReadKeysRequest, RemoteReadRequest,
+	StorageProof, ChangesProof,
 };
 use tetcore_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
 use tp_blockchain::Error as ClientError;
@@ -95,6 +96,14 @@ impl<Block: BlockT> FetchChecker<Block> for AlwaysBadChecker {
 		Err(ErrorAlwaysBadChecker.into())
 	}
 
+	fn check_read_keys_proof(
+		&self,
+		_request: &RemoteReadKeysRequest<Block::Header>,
+		_remote_proof: StorageProof,
+	) -> Result<Vec<Vec<u8>>, ClientError> {
+		Err(ErrorAlwaysBadChecker.into())
+	}
+
 	fn check_execution_proof(
 		&self,
 		_request: &RemoteCallRequest<Block::Header>,
@@ -165,6 +174,8 @@ where
 	type RemoteCallResult = RemoteResponse<Vec<u8>>;
 	type RemoteChangesResult = RemoteResponse<Vec<(NumberFor<B>, u32)>>;
 	type RemoteBodyResult = RemoteResponse<Vec<B::Extrinsic>>;
+	type RemoteProofResult = RemoteResponse<StorageProof>;
+	type RemoteKeysResult = RemoteResponse<Vec<Vec<u8>>>;
 
 	fn remote_header(&self, request: RemoteHeaderRequest<B::Header>) -> Self::RemoteHeaderResult {
 		let (sender, receiver) = oneshot::channel();
@@ -193,6 +204,25 @@ where
 		RemoteResponse { receiver }
 	}
 
+	fn remote_read_child_proof(
+		&self,
+		request: RemoteReadChildRequest<B::Header>,
+	) -> Self::RemoteProofResult {
+		let (sender, receiver) = oneshot::channel();
+		let _ = self
+			.requests_send
+			.unbounded_send(light_client_handler::Request::ReadChildProof { request, sender });
+		RemoteResponse { receiver }
+	}
+
+	fn remote_read_keys(&self, request: RemoteReadKeysRequest<B::Header>) -> Self::RemoteKeysResult {
+		let (sender, receiver) = oneshot::channel();
+		let _ = self
+			.requests_send
+			.unbounded_send(light_client_handler::Request::ReadKeys { request, sender });
+		RemoteResponse { receiver }
+	}
+
 	fn remote_call(&self, request: RemoteCallRequest<B::Header>) -> Self::RemoteCallResult {
 		let (sender, receiver) = oneshot::channel();
 		let _ = self
@@ -22,6 +22,7 @@ use crate::{
 	config::{self, ProtocolId, TransactionPool, TransactionImportFuture, TransactionImport},
 	error,
 	request_responses::RequestFailure,
+	service::TrafficAccounting,
 	utils::{interval, LruHashSet},
 };
 
@@ -103,6 +104,9 @@ const HARDCODED_PEERSETS_TX: tc_peerset::SetId = tc_peerset::SetId::from(1);
 /// Number of hardcoded peersets (the constants right above). Any set whose identifier is equal or
 /// superior to this value corresponds to a user-defined protocol.
 const NUM_HARDCODED_PEERSETS: usize = 2;
+/// Name of the file, within the network configuration directory, where the peerset persists the
+/// reputations and bans of the nodes.
+const PEERSET_FILE: &str = "peerset.json";
 
 /// When light node connects to the full node and the full node is behind light node
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it not useful
@@ -241,6 +245,12 @@ pub struct Protocol<B: BlockT, H: ExHashT> {
 	boot_node_ids: HashSet<PeerId>,
 	/// A cache for the data that was associated to a block announcement.
 	block_announce_data_cache: lru::LruCache<B::Hash, Vec<u8>>,
+	/// Traffic counters and outbound caps, shared with the network service.
+	traffic: Arc<TrafficAccounting>,
+	/// Name of the transactions notifications protocol.
+	transactions_protocol: Cow<'static, str>,
+	/// Name of the block announces notifications protocol.
+	block_announces_protocol: Cow<'static, str>,
 }
 
 /// Peer information
@@ -252,6 +262,8 @@ struct Peer<B: BlockT, H: ExHashT> {
 		message::BlockRequest<B>,
 		oneshot::Receiver<Result<Vec<u8>, RequestFailure>>,
 	)>,
+	/// Current state request, if any. Started by emitting [`CustomMessageOutcome::StateRequest`].
+	state_request: Option<oneshot::Receiver<Result<Vec<u8>, RequestFailure>>>,
 	/// Holds a set of transactions known to this peer.
 	known_transactions: LruHashSet<H>,
 	/// Holds a set of blocks known to this peer.
@@ -276,6 +288,10 @@ pub struct ProtocolConfig {
 	pub roles: Roles,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// Maximum number of blocks to ask a peer in a single request.
+	pub max_blocks_per_request: u32,
+	/// How to synchronize the chain.
+	pub sync_mode: config::SyncMode,
 }
 
 impl Default for ProtocolConfig {
@@ -283,6 +299,8 @@ impl Default for ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
 			max_parallel_downloads: 5,
+			max_blocks_per_request: 512,
+			sync_mode: config::SyncMode::Full,
 		}
 	}
 }
@@ -347,6 +365,7 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 		network_config: &config::NetworkConfiguration,
 		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
 		metrics_registry: Option<&Registry>,
+		traffic: Arc<TrafficAccounting>,
 	) -> error::Result<(Protocol<B, H>, tc_peerset::PeersetHandle, Vec<(PeerId, Multiaddr)>)> {
 		let info = chain.info();
 		let sync = ChainSync::new(
@@ -355,6 +374,8 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 			&info,
 			block_announce_validator,
 			config.max_parallel_downloads,
+			config.max_blocks_per_request,
+			config.sync_mode,
 		);
 
 		let boot_node_ids = {
@@ -453,6 +474,8 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 
 			tc_peerset::Peerset::from_config(tc_peerset::PeersetConfig {
 				sets,
+				persist_path: network_config.net_config_path.as_ref()
+					.map(|path| path.join(PEERSET_FILE)),
 			})
 		};
 
@@ -472,6 +495,11 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 			proto
 		});
 
+		// Block announces are sent as soon as blocks are imported, they can't be delayed.
+		if traffic.is_capped(&block_announces_protocol) {
+			return Err(error::Error::UncappableProtocol { protocol: block_announces_protocol });
+		}
+
 		let behaviour = {
 			let versions = &((MIN_VERSION as u8)..=(CURRENT_VERSION as u8)).collect::<Vec<u8>>();
 			let handshake_message = Roles::from(config_role).encode();
@@ -492,8 +520,12 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 				versions,
 				build_status_message::<B>(&config, best_number, best_hash, genesis_hash),
 				peerset,
-				iter::once((block_announces_protocol, block_announces_handshake, MAX_BLOCK_ANNOUNCE_SIZE))
-					.chain(iter::once((transactions_protocol, vec![], MAX_TRANSACTIONS_SIZE)))
+				iter::once((
+					block_announces_protocol.clone(),
+					block_announces_handshake,
+					MAX_BLOCK_ANNOUNCE_SIZE,
+				))
+					.chain(iter::once((transactions_protocol.clone(), vec![], MAX_TRANSACTIONS_SIZE)))
 					.chain(network_config.extra_sets.iter().map(|s| (
 						s.notifications_protocol.clone(),
 						handshake_message.clone(),
@@ -531,6 +563,9 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 			},
 			boot_node_ids,
 			block_announce_data_cache,
+			traffic,
+			transactions_protocol,
+			block_announces_protocol,
 		};
 
 		Ok((protocol, peerset_handle, known_addresses))
@@ -566,6 +601,11 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 		self.behaviour.peerset_debug_info()
 	}
 
+	/// Returns the list of peers banned by the peerset manager.
+	pub fn banned_peers(&mut self) -> Vec<tc_peerset::BannedPeer> {
+		self.behaviour.banned_peers()
+	}
+
 	/// Returns the number of peers we're connected to.
 	pub fn num_connected_peers(&self) -> usize {
 		self.peers.values().count()
@@ -831,10 +871,32 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 		}
 	}
 
+	/// Must be called in response to a [`CustomMessageOutcome::StateRequest`] being emitted.
+	/// Must contain the same `PeerId` that has been emitted.
+	pub fn on_state_response(
+		&mut self,
+		peer_id: PeerId,
+		response: crate::schema::v1::StateResponse,
+	) -> CustomMessageOutcome<B> {
+		match self.sync.on_state_data(&peer_id, response) {
+			Ok(sync::OnStateData::Import(origin, block)) =>
+				CustomMessageOutcome::BlockImport(origin, vec![block]),
+			Ok(sync::OnStateData::Request(peer, req)) => {
+				prepare_state_request::<B, H>(&mut self.peers, peer, req)
+			}
+			Err(sync::BadPeer(id, repu)) => {
+				self.behaviour.disconnect_peer(&id, HARDCODED_PEERSETS_SYNC);
+				self.peerset_handle.report_peer(id, repu);
+				CustomMessageOutcome::None
+			}
+		}
+	}
+
 	/// Perform time based maintenance.
 	///
 	/// > **Note**: This method normally doesn't have to be called except for testing purposes.
 	pub fn tick(&mut self) {
+		self.sync.reassign_stalled_downloads();
 		self.report_metrics()
 	}
 
@@ -912,6 +974,7 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 				best_number: status.best_number
 			},
 			block_request: None,
+			state_request: None,
 			known_transactions: LruHashSet::new(NonZeroUsize::new(MAX_KNOWN_TRANSACTIONS)
 				.expect("Constant is nonzero")),
 			known_blocks: LruHashSet::new(NonZeroUsize::new(MAX_KNOWN_BLOCKS)
@@ -1039,6 +1102,12 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 				continue;
 			}
 
+			// The transactions not sent because of the outbound cap are propagated again later.
+			if !self.traffic.has_send_capacity(&self.transactions_protocol) {
+				debug!(target: "sync", "Outbound bandwidth cap of transactions reached");
+				break;
+			}
+
 			let (hashes, to_send): (Vec<_>, Vec<_>) = transactions
 				.iter()
 				.filter(|&(ref hash, _)| peer.known_transactions.insert(hash.clone()))
@@ -1055,10 +1124,12 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 						.push(who.to_base58());
 				}
 				trace!(target: "sync", "Sending {} transactions to {}", to_send.len(), who);
+				let message = to_send.encode();
+				self.traffic.report_out(who, &self.transactions_protocol, message.len());
 				self.behaviour.write_notification(
 					who,
 					HARDCODED_PEERSETS_TX,
-					to_send.encode()
+					message
 				);
 			}
 		}
@@ -1123,10 +1194,12 @@ impl<B: BlockT, H: ExHashT> Protocol<B, H> {
 					data: Some(data.clone()),
 				};
 
+				let message = message.encode();
+				self.traffic.report_out(who, &self.block_announces_protocol, message.len());
 				self.behaviour.write_notification(
 					who,
 					HARDCODED_PEERSETS_SYNC,
-					message.encode()
+					message
 				);
 			}
 		}
@@ -1468,6 +1541,64 @@ fn prepare_block_request<B: BlockT, H: ExHashT>(
 	}
 }
 
+fn prepare_state_request<B: BlockT, H: ExHashT>(
+	peers: &mut HashMap<PeerId, Peer<B, H>>,
+	who: PeerId,
+	request: crate::schema::v1::StateRequest,
+) -> CustomMessageOutcome<B> {
+	let (tx, rx) = oneshot::channel();
+
+	if let Some(ref mut peer) = peers.get_mut(&who) {
+		peer.state_request = Some(rx);
+	}
+
+	CustomMessageOutcome::StateRequest {
+		target: who,
+		request,
+		pending_response: tx,
+	}
+}
+
+/// Report and disconnect a peer an outgoing sync request to which has failed.
+fn handle_request_failure(
+	id: &PeerId,
+	error: RequestFailure,
+	peerset_handle: &tc_peerset::PeersetHandle,
+	behaviour: &mut GenericProto,
+) {
+	match error {
+		RequestFailure::Network(OutboundFailure::Timeout) => {
+			peerset_handle.report_peer(id.clone(), rep::TIMEOUT);
+			behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
+		}
+		RequestFailure::Network(OutboundFailure::UnsupportedProtocols) => {
+			peerset_handle.report_peer(id.clone(), rep::BAD_PROTOCOL);
+			behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
+		}
+		RequestFailure::Network(OutboundFailure::DialFailure) => {
+			behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
+		}
+		RequestFailure::Refused => {
+			peerset_handle.report_peer(id.clone(), rep::REFUSED);
+			behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
+		}
+		RequestFailure::Network(OutboundFailure::ConnectionClosed)
+		| RequestFailure::NotConnected => {
+			behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
+		},
+		RequestFailure::UnknownProtocol => {
+			debug_assert!(false, "Sync request protocols should always be known.");
+		}
+		RequestFailure::Obsolete => {
+			debug_assert!(
+				false,
+				"Can not receive `RequestFailure::Obsolete` after dropping the \
+				 response receiver.",
+			);
+		}
+	}
+}
+
 /// Outcome of an incoming custom message.
 #[derive(Debug)]
 #[must_use]
@@ -1497,6 +1628,12 @@ pub enum CustomMessageOutcome<B: BlockT> {
 		request: crate::schema::v1::BlockRequest,
 		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
 	},
+	/// A new state request must be emitted.
+	StateRequest {
+		target: PeerId,
+		request: crate::schema::v1::StateRequest,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	},
 	/// Peer has a reported a new head of chain.
 	PeerNewBest(PeerId, NumberFor<B>),
 	/// Now connected to a new peer for syncing purposes.
@@ -1580,44 +1717,50 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 					Poll::Ready(Ok(Err(e))) => {
 						peer.block_request.take();
 						trace!(target: "sync", "Block request to peer {:?} failed: {:?}.", id, e);
+						handle_request_failure(id, e, &self.peerset_handle, &mut self.behaviour);
+					},
+					Poll::Ready(Err(oneshot::Canceled)) => {
+						peer.block_request.take();
+						trace!(
+							target: "sync",
+							"Block request to peer {:?} failed due to oneshot being canceled.",
+							id,
+						);
+						self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
+					},
+					Poll::Pending => {},
+				}
+			}
+		}
+		let mut finished_state_requests = Vec::new();
+		for (id, peer) in self.peers.iter_mut() {
+			if let Peer { state_request: Some(pending_response), .. } = peer {
+				match pending_response.poll_unpin(cx) {
+					Poll::Ready(Ok(Ok(resp))) => {
+						peer.state_request.take();
 
-						match e {
-							RequestFailure::Network(OutboundFailure::Timeout) => {
-								self.peerset_handle.report_peer(id.clone(), rep::TIMEOUT);
-								self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
-							}
-							RequestFailure::Network(OutboundFailure::UnsupportedProtocols) => {
-								self.peerset_handle.report_peer(id.clone(), rep::BAD_PROTOCOL);
-								self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
-							}
-							RequestFailure::Network(OutboundFailure::DialFailure) => {
-								self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
-							}
-							RequestFailure::Refused => {
-								self.peerset_handle.report_peer(id.clone(), rep::REFUSED);
-								self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
-							}
-							RequestFailure::Network(OutboundFailure::ConnectionClosed)
-							| RequestFailure::NotConnected => {
+						let protobuf_response = match crate::schema::v1::StateResponse::decode(&resp[..]) {
+							Ok(proto) => proto,
+							Err(e) => {
+								trace!(target: "sync", "Failed to decode state response from peer {:?}: {:?}.", id, e);
+								self.peerset_handle.report_peer(id.clone(), rep::BAD_MESSAGE);
 								self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
-							},
-							RequestFailure::UnknownProtocol => {
-								debug_assert!(false, "Block request protocol should always be known.");
-							}
-							RequestFailure::Obsolete => {
-								debug_assert!(
-									false,
-									"Can not receive `RequestFailure::Obsolete` after dropping the \
-									 response receiver.",
-								);
+								continue;
 							}
-						}
+						};
+
+						finished_state_requests.push((id.clone(), protobuf_response));
+					},
+					Poll::Ready(Ok(Err(e))) => {
+						peer.state_request.take();
+						trace!(target: "sync", "State request to peer {:?} failed: {:?}.", id, e);
+						handle_request_failure(id, e, &self.peerset_handle, &mut self.behaviour);
 					},
 					Poll::Ready(Err(oneshot::Canceled)) => {
-						peer.block_request.take();
+						peer.state_request.take();
 						trace!(
 							target: "sync",
-							"Block request to peer {:?} failed due to oneshot being canceled.",
+							"State request to peer {:?} failed due to oneshot being canceled.",
 							id,
 						);
 						self.behaviour.disconnect_peer(id, HARDCODED_PEERSETS_SYNC);
@@ -1626,10 +1769,15 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 				}
 			}
 		}
+
 		for (id, req, protobuf_response) in finished_block_requests {
 			let ev = self.on_block_response(id, req, protobuf_response);
 			self.pending_messages.push_back(ev);
 		}
+		for (id, protobuf_response) in finished_state_requests {
+			let ev = self.on_state_response(id, protobuf_response);
+			self.pending_messages.push_back(ev);
+		}
 
 		while let Poll::Ready(Some(())) = self.tick_timeout.poll_next_unpin(cx) {
 			self.tick();
@@ -1647,6 +1795,10 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 			let event = prepare_block_request(&mut self.peers, id, request);
 			self.pending_messages.push_back(event);
 		}
+		if let Some((id, request)) = self.sync.state_request() {
+			let event = prepare_state_request(&mut self.peers, id, request);
+			self.pending_messages.push_back(event);
+		}
 		if let Poll::Ready(Some((tx_hash, result))) = self.pending_transactions.poll_next_unpin(cx) {
 			if let Some(peers) = self.pending_transactions_peers.remove(&tx_hash) {
 				peers.into_iter().for_each(|p| self.on_handle_transaction_import(p, result));
@@ -1816,6 +1968,7 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 			GenericProtoOut::Notification { peer_id, set_id, message } =>
 				match set_id {
 					HARDCODED_PEERSETS_SYNC if self.peers.contains_key(&peer_id) => {
+						self.traffic.report_in(&peer_id, &self.block_announces_protocol, message.len());
 						if let Ok(announce) = message::BlockAnnounce::decode(&mut message.as_ref()) {
 							self.push_block_announce_validation(peer_id, announce);
 
@@ -1832,6 +1985,7 @@ impl<B: BlockT, H: ExHashT> NetworkBehaviour for Protocol<B, H> {
 						}
 					}
 					HARDCODED_PEERSETS_TX if self.peers.contains_key(&peer_id) => {
+						self.traffic.report_in(&peer_id, &self.transactions_protocol, message.len());
 						if let Ok(m) = <message::Transactions<B::Extrinsic> as Decode>::decode(
 							&mut message.as_ref(),
 						) {
@@ -628,6 +628,11 @@ impl GenericProto {
 		self.peerset.debug_info()
 	}
 
+	/// Returns the list of peers banned by the peerset manager.
+	pub fn banned_peers(&mut self) -> Vec<tc_peerset::BannedPeer> {
+		self.peerset.banned_peers()
+	}
+
 	/// Function that is called when the peerset wants us to connect to a peer.
 	fn peerset_report_connect(&mut self, peer_id: PeerId, set_id: tc_peerset::SetId) {
 		// If `PeerId` is unknown to us, insert an entry, start dialing, and return early.
@@ -28,19 +28,31 @@
 //! the network, or whenever a block has been successfully verified, call the appropriate method in
 //! order to update it.
 //!
+//! # Parallel downloads
+//!
+//! The chain is split into ranges of blocks that are downloaded from different peers at the same
+//! time. The size of the ranges follows the throughput of each peer, and the ranges of peers that
+//! stall are requested from other peers. During a major sync, the justifications of a range are
+//! requested from another peer than its headers and bodies, in parallel, and the blocks of the
+//! range are imported once both have been downloaded. Justifications that are missing once the
+//! blocks are imported are requested separately, and these requests are spread across peers.
+//!
 
 use codec::Encode;
 use blocks::BlockCollection;
 use tp_blockchain::{Error as ClientError, Info as BlockchainInfo, HeaderMetadata};
 use tp_consensus::{BlockOrigin, BlockStatus,
-	block_validation::{BlockAnnounceValidator, Validation},
+	block_validation::{AnnounceScore, BlockAnnounceValidator, Validation},
 	import_queue::{IncomingBlock, BlockImportResult, BlockImportError}
 };
 use crate::protocol::message::{
 	self, BlockAnnounce, BlockAttributes, BlockRequest, BlockResponse, Roles,
 };
+use crate::config::SyncMode;
+use crate::schema::v1::{StateRequest, StateResponse};
 use either::Either;
 use extra_requests::ExtraRequests;
+use state::{StateSync, ImportResult as StateImportResult};
 use tetsy_libp2p::PeerId;
 use log::{debug, trace, warn, info, error};
 use tp_runtime::{
@@ -56,11 +68,15 @@ use std::{
 	fmt, ops::Range, collections::{HashMap, hash_map::Entry, HashSet}, sync::Arc, pin::Pin,
 };
 use futures::{task::Poll, Future, stream::FuturesUnordered, FutureExt, StreamExt};
+use throughput::PeerThroughput;
+use wasm_timer::Instant;
 
 mod blocks;
 mod extra_requests;
+mod state;
+mod throughput;
 
-/// Maximum blocks to request in a single packet.
+/// Maximum blocks to request in a single packet before the throughput of the peer is known.
 const MAX_BLOCKS_TO_REQUEST: usize = 128;
 
 /// Maximum blocks to store in the import queue.
@@ -201,6 +217,8 @@ pub struct ChainSync<B: BlockT> {
 	block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	max_parallel_downloads: u32,
+	/// Maximum number of blocks to ask a peer in a single request.
+	max_blocks_per_request: u32,
 	/// Total number of downloaded blocks.
 	downloaded_blocks: usize,
 	/// All block announcement that are currently being validated.
@@ -209,6 +227,13 @@ pub struct ChainSync<B: BlockT> {
 	>,
 	/// Stats per peer about the number of concurrent block announce validations.
 	block_announce_validation_per_peer_stats: HashMap<PeerId, usize>,
+	/// How the chain is synchronized. Switches to `Full` once the state has been downloaded.
+	mode: SyncMode,
+	/// State download in progress, if any.
+	state_sync: Option<StateSync<B>>,
+	/// Last block executed after a state download. The blocks following it are already in the
+	/// chain without state and are downloaded again to be executed.
+	reexecute_from: Option<(B::Hash, NumberFor<B>)>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -226,6 +251,11 @@ pub struct PeerSync<B: BlockT> {
 	/// The state of syncing this peer is in for us, generally categories
 	/// into `Available` or "busy" with something as defined by `PeerSyncState`.
 	pub state: PeerSyncState<B>,
+	/// Score of the latest best block announced by this peer. Peers with higher scores are
+	/// requested blocks first.
+	pub announce_score: AnnounceScore,
+	/// Block download throughput of this peer, which determines the size of its requests.
+	pub throughput: PeerThroughput,
 }
 
 impl<B: BlockT> PeerSync<B> {
@@ -257,6 +287,9 @@ struct ForkTarget<B: BlockT> {
 	number: NumberFor<B>,
 	parent_hash: Option<B::Hash>,
 	peers: HashSet<PeerId>,
+	/// Highest score of the announcements of this target. Targets with higher scores are
+	/// downloaded first.
+	score: AnnounceScore,
 }
 
 /// The state of syncing between a Peer and ourselves.
@@ -281,6 +314,11 @@ pub enum PeerSyncState<B: BlockT> {
 	DownloadingStale(B::Hash),
 	/// Downloading justification for given block hash.
 	DownloadingJustification(B::Hash),
+	/// Downloading the justifications of the range of blocks starting at the given number, whose
+	/// bodies are downloaded from another peer.
+	DownloadingJustificationRange(NumberFor<B>),
+	/// Downloading state.
+	DownloadingState,
 }
 
 impl<B: BlockT> PeerSyncState<B> {
@@ -395,6 +433,8 @@ enum PreValidateBlockAnnounce<H> {
 	Process {
 		/// Is this the new best block of the peer?
 		is_new_best: bool,
+		/// Priority of downloading the announced block, as given by the validator.
+		score: AnnounceScore,
 		/// The id of the peer that send us the announcement.
 		who: PeerId,
 		/// The announcement.
@@ -415,6 +455,15 @@ enum PreValidateBlockAnnounce<H> {
 	Skip,
 }
 
+/// Result of [`ChainSync::on_state_data`].
+#[derive(Debug)]
+pub enum OnStateData<B: BlockT> {
+	/// The block and state that should be imported.
+	Import(BlockOrigin, IncomingBlock<B>),
+	/// A new state request needs to be made to the given peer.
+	Request(PeerId, StateRequest),
+}
+
 /// Result of [`ChainSync::on_block_justification`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OnBlockJustification<B: BlockT> {
@@ -447,10 +496,22 @@ impl<B: BlockT> ChainSync<B> {
 		info: &BlockchainInfo<B>,
 		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
 		max_parallel_downloads: u32,
+		max_blocks_per_request: u32,
+		mode: SyncMode,
 	) -> Self {
+		// Light clients have no state to download, and a node restarted after a state download
+		// already has the state of its finalized block.
+		let mode = match mode {
+			SyncMode::Fast if !role.is_full() => SyncMode::Full,
+			SyncMode::Fast if !info.finalized_number.is_zero()
+				&& client.block_status(&BlockId::Hash(info.finalized_hash)).ok()
+					== Some(BlockStatus::InChainWithState) => SyncMode::Full,
+			mode => mode,
+		};
+
 		let mut required_block_attributes = BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION;
 
-		if role.is_full() {
+		if role.is_full() && mode == SyncMode::Full {
 			required_block_attributes |= BlockAttributes::BODY
 		}
 
@@ -468,9 +529,13 @@ impl<B: BlockT> ChainSync<B> {
 			pending_requests: Default::default(),
 			block_announce_validator,
 			max_parallel_downloads,
+			max_blocks_per_request,
 			downloaded_blocks: 0,
 			block_announce_validation: Default::default(),
 			block_announce_validation_per_peer_stats: Default::default(),
+			mode,
+			state_sync: None,
+			reexecute_from: None,
 		}
 	}
 
@@ -552,6 +617,11 @@ impl<B: BlockT> ChainSync<B> {
 						best_hash,
 						best_number,
 						state: PeerSyncState::Available,
+						announce_score: 0,
+						throughput: PeerThroughput::new(
+							MAX_BLOCKS_TO_REQUEST as u32,
+							self.max_blocks_per_request,
+						),
 					});
 					return Ok(None)
 				}
@@ -593,6 +663,11 @@ impl<B: BlockT> ChainSync<B> {
 					best_hash,
 					best_number,
 					state,
+					announce_score: 0,
+					throughput: PeerThroughput::new(
+						MAX_BLOCKS_TO_REQUEST as u32,
+						self.max_blocks_per_request,
+					),
 				});
 
 				Ok(req)
@@ -604,12 +679,23 @@ impl<B: BlockT> ChainSync<B> {
 					best_hash,
 					best_number,
 				);
+				// Blocks after a downloaded state are in the chain already, but still have to be
+				// downloaded again to be executed.
+				let common_number = match self.reexecute_from {
+					Some((_, number)) => std::cmp::min(number, best_number),
+					None => best_number,
+				};
 				self.peers.insert(who.clone(), PeerSync {
 					peer_id: who.clone(),
-					common_number: best_number,
+					common_number,
 					best_hash,
 					best_number,
 					state: PeerSyncState::Available,
+					announce_score: 0,
+					throughput: PeerThroughput::new(
+						MAX_BLOCKS_TO_REQUEST as u32,
+						self.max_blocks_per_request,
+					),
 				});
 				self.pending_requests.add(&who);
 				Ok(None)
@@ -682,6 +768,9 @@ impl<B: BlockT> ChainSync<B> {
 				number,
 				peers: Default::default(),
 				parent_hash: None,
+				// Explicitly requested forks are needed by consensus, so they are downloaded
+				// before any announced one.
+				score: AnnounceScore::max_value(),
 			})
 			.peers.extend(peers);
 	}
@@ -720,6 +809,15 @@ impl<B: BlockT> ChainSync<B> {
 			return Either::Left(std::iter::empty())
 		}
 		let major_sync = self.status().state == SyncState::Downloading;
+		// During a major sync, the justifications of a range are downloaded from another peer than
+		// its bodies, if there is one which has the range.
+		let split_justifications = major_sync && self.peers.len() > 1 && self.required_block_attributes
+			.contains(BlockAttributes::BODY | BlockAttributes::JUSTIFICATION);
+		let peer_bests: Vec<(PeerId, NumberFor<B>)> = if split_justifications {
+			self.peers.iter().map(|(id, peer)| (id.clone(), peer.best_number)).collect()
+		} else {
+			Vec::new()
+		};
 		let blocks = &mut self.blocks;
 		let attrs = &self.required_block_attributes;
 		let fork_targets = &mut self.fork_targets;
@@ -728,8 +826,13 @@ impl<B: BlockT> ChainSync<B> {
 		let client = &self.client;
 		let queue = &self.queue_blocks;
 		let pending_requests = self.pending_requests.take();
+		let new_pending_requests = &mut self.pending_requests;
 		let max_parallel = if major_sync { 1 } else { self.max_parallel_downloads };
-		let iter = self.peers.iter_mut().filter_map(move |(id, peer)| {
+		// Peers that announced their best block with the highest score are served first, and
+		// thus get the first ranges of blocks to download.
+		let mut peers = self.peers.iter_mut().collect::<Vec<_>>();
+		peers.sort_by_key(|(_, peer)| std::cmp::Reverse(peer.announce_score));
+		let iter = peers.into_iter().filter_map(move |(id, peer)| {
 			if !peer.state.is_available() || !pending_requests.contains(id) {
 				return None
 			}
@@ -748,7 +851,16 @@ impl<B: BlockT> ChainSync<B> {
 					state: AncestorSearchState::ExponentialBackoff(One::one()),
 				};
 				Some((id, ancestry_request::<B>(current)))
-			} else if let Some((range, req)) = peer_block_request(
+			} else if let Some(range) = blocks.needed_justifications(id, peer.best_number) {
+				peer.state = PeerSyncState::DownloadingJustificationRange(range.start);
+				peer.throughput.on_request(
+					(range.end - range.start).saturated_into::<u32>(),
+					Instant::now(),
+				);
+				let req = justification_range_request::<B>(range);
+				trace!(target: "sync", "New justification range request for {}: {:?}", id, req);
+				Some((id, req))
+			} else if let Some((range, mut req)) = peer_block_request(
 				id,
 				peer,
 				blocks,
@@ -757,7 +869,18 @@ impl<B: BlockT> ChainSync<B> {
 				last_finalized,
 				best_queued,
 			) {
+				if peer_bests.iter().any(|(other, best)| other != id && *best >= range.end - One::one())
+					&& blocks.schedule_justifications(range.clone(), id.clone())
+				{
+					req.fields.remove(BlockAttributes::JUSTIFICATION);
+					// The other peers may have been skipped already.
+					new_pending_requests.set_all();
+				}
 				peer.state = PeerSyncState::DownloadingNew(range.start);
+				peer.throughput.on_request(
+					(range.end - range.start).saturated_into::<u32>(),
+					Instant::now(),
+				);
 				trace!(
 					target: "sync",
 					"New block request for {}, (best:{}, common:{}) {:?}",
@@ -789,6 +912,24 @@ impl<B: BlockT> ChainSync<B> {
 		Either::Right(iter)
 	}
 
+	/// Get a state request, if any.
+	pub fn state_request(&mut self) -> Option<(PeerId, StateRequest)> {
+		if self.peers.values().any(|peer| peer.state == PeerSyncState::DownloadingState) {
+			// Only one pending state request is allowed.
+			return None;
+		}
+		let state_sync = self.state_sync.as_ref()?;
+		let target = state_sync.target_number();
+		for (id, peer) in self.peers.iter_mut() {
+			if peer.state.is_available() && peer.common_number >= target {
+				trace!(target: "sync", "New state request to {}", id);
+				peer.state = PeerSyncState::DownloadingState;
+				return Some((id.clone(), state_sync.next_request()));
+			}
+		}
+		None
+	}
+
 	/// Handle a response from the remote to a block request that we made.
 	///
 	/// `request` must be the original request that triggered `response`.
@@ -803,6 +944,7 @@ impl<B: BlockT> ChainSync<B> {
 		response: BlockResponse<B>
 	) -> Result<OnBlockData<B>, BadPeer> {
 		self.downloaded_blocks += response.blocks.len();
+		let import_existing = self.reexecute_from.is_some();
 		let mut new_blocks: Vec<IncomingBlock<B>> =
 			if let Some(peer) = self.peers.get_mut(who) {
 				let mut blocks = response.blocks;
@@ -815,24 +957,31 @@ impl<B: BlockT> ChainSync<B> {
 					match &mut peer.state {
 						PeerSyncState::DownloadingNew(start_block) => {
 							self.blocks.clear_peer_download(who);
+							peer.throughput.on_response(blocks.len(), Instant::now());
+							// The request is descending from the end of the range. If the peer
+							// sent fewer blocks than requested, because of the limits of its
+							// responses, the missing ones are at the start of the range and are
+							// requested again later.
+							let missing = request.max.unwrap_or(0).saturating_sub(blocks.len() as u32);
+							let start_block = *start_block + missing.into();
+							peer.state = PeerSyncState::Available;
+							validate_blocks::<B>(&blocks, who, Some(request.clone()))?;
+							if request.fields.contains(BlockAttributes::JUSTIFICATION) {
+								// A range reassigned from a stalled peer may have been requested
+								// with its justifications before they were split.
+								let end = start_block + (blocks.len() as u32).into();
+								self.blocks.justifications_downloaded(start_block .. end);
+							}
+							self.blocks.insert(start_block, blocks, who.clone());
+							drain_blocks(&mut self.blocks, self.best_queued_number + One::one(), import_existing)
+						}
+						PeerSyncState::DownloadingJustificationRange(start_block) => {
 							let start_block = *start_block;
+							peer.throughput.on_response_unmeasured();
 							peer.state = PeerSyncState::Available;
 							validate_blocks::<B>(&blocks, who, Some(request))?;
-							self.blocks.insert(start_block, blocks, who.clone());
-							self.blocks
-								.drain(self.best_queued_number + One::one())
-								.into_iter()
-								.map(|block_data| {
-									IncomingBlock {
-										hash: block_data.block.hash,
-										header: block_data.block.header,
-										body: block_data.block.body,
-										justification: block_data.block.justification,
-										origin: block_data.origin,
-										allow_missing_state: true,
-										import_existing: false,
-									}
-								}).collect()
+							self.blocks.insert_justifications(start_block, blocks);
+							drain_blocks(&mut self.blocks, self.best_queued_number + One::one(), import_existing)
 						}
 						PeerSyncState::DownloadingStale(_) => {
 							peer.state = PeerSyncState::Available;
@@ -849,7 +998,8 @@ impl<B: BlockT> ChainSync<B> {
 									justification: b.justification,
 									origin: Some(who.clone()),
 									allow_missing_state: true,
-									import_existing: false,
+									import_existing,
+									state: None,
 								}
 							}).collect()
 						}
@@ -936,6 +1086,7 @@ impl<B: BlockT> ChainSync<B> {
 											number: peer.best_number,
 											parent_hash: None,
 											peers: Default::default(),
+											score: peer.announce_score,
 										})
 									.peers.insert(who.clone());
 								}
@@ -945,7 +1096,8 @@ impl<B: BlockT> ChainSync<B> {
 						}
 
 						| PeerSyncState::Available
-						| PeerSyncState::DownloadingJustification(..) => Vec::new()
+						| PeerSyncState::DownloadingJustification(..)
+						| PeerSyncState::DownloadingState => Vec::new()
 					}
 				} else {
 					// When request.is_none() this is a block announcement. Just accept blocks.
@@ -958,7 +1110,8 @@ impl<B: BlockT> ChainSync<B> {
 							justification: b.justification,
 							origin: Some(who.clone()),
 							allow_missing_state: true,
-							import_existing: false,
+							import_existing,
+							state: None,
 						}
 					}).collect()
 				}
@@ -995,6 +1148,64 @@ impl<B: BlockT> ChainSync<B> {
 		Ok(OnBlockData::Import(origin, new_blocks))
 	}
 
+	/// Handle a response from the remote to a state request that we made.
+	///
+	/// Returns the block to import once the state is complete, or the next request.
+	pub fn on_state_data(
+		&mut self,
+		who: &PeerId,
+		response: StateResponse,
+	) -> Result<OnStateData<B>, BadPeer> {
+		if let Some(peer) = self.peers.get_mut(who) {
+			if let PeerSyncState::DownloadingState = peer.state {
+				peer.state = PeerSyncState::Available;
+			}
+		}
+
+		let state_sync = match self.state_sync.as_mut() {
+			Some(state_sync) => state_sync,
+			None => {
+				debug!(target: "sync", "Ignored obsolete state response from {}", who);
+				return Err(BadPeer(who.clone(), rep::NOT_REQUESTED));
+			}
+		};
+		let progress = state_sync.progress();
+		trace!(
+			target: "sync",
+			"Importing state data from {} with {} entries, {} entries ({} bytes) downloaded so far",
+			who,
+			response.entries.len(),
+			progress.entries,
+			progress.size,
+		);
+		match state_sync.import(response) {
+			StateImportResult::Import(hash, header, state) => {
+				debug!(target: "sync", "State download of {:?} is complete, importing", hash);
+				self.queue_blocks.insert(hash);
+				Ok(OnStateData::Import(BlockOrigin::NetworkInitialSync, IncomingBlock {
+					hash,
+					header: Some(header),
+					body: None,
+					justification: None,
+					origin: None,
+					allow_missing_state: true,
+					import_existing: true,
+					state: Some(state),
+				}))
+			},
+			StateImportResult::Continue(request) => {
+				if let Some(peer) = self.peers.get_mut(who) {
+					peer.state = PeerSyncState::DownloadingState;
+				}
+				Ok(OnStateData::Request(who.clone(), request))
+			},
+			StateImportResult::BadResponse => {
+				debug!(target: "sync", "Bad state data received from {}", who);
+				Err(BadPeer(who.clone(), rep::BAD_BLOCK))
+			},
+		}
+	}
+
 	/// Handle a response from the remote to a justification request that we made.
 	///
 	/// `request` must be the original request that triggered `response`.
@@ -1076,6 +1287,34 @@ impl<B: BlockT> ChainSync<B> {
 				has_error = true;
 			}
 
+			let state_target = self.state_sync.as_ref()
+				.filter(|state_sync| state_sync.target() == hash)
+				.map(|state_sync| state_sync.target_number());
+			if let Some(number) = state_target {
+				self.state_sync = None;
+				match result {
+					Ok(_) => {
+						info!("✅ Imported state of block #{} ({}), executing the following blocks", number, hash);
+						self.mode = SyncMode::Full;
+						if self.role.is_full() {
+							self.required_block_attributes |= BlockAttributes::BODY;
+						}
+						self.reexecute_from = Some((hash, number));
+						output.extend(self.restart());
+					},
+					Err(e) => {
+						warn!(target: "sync", "💔 Error importing state of block {:?}: {:?}", hash, e);
+					},
+				}
+				continue;
+			}
+
+			if let Ok(BlockImportResult::ImportedKnown(number, _))
+				| Ok(BlockImportResult::ImportedUnknown(number, _, _)) = &result
+			{
+				self.on_block_reexecuted(hash, *number);
+			}
+
 			match result {
 				Ok(BlockImportResult::ImportedKnown(number, who)) => {
 					if let Some(peer) = who.and_then(|p| self.peers.get_mut(&p)) {
@@ -1189,6 +1428,36 @@ impl<B: BlockT> ChainSync<B> {
 				err,
 			);
 		}
+
+		// The state of the latest finalized block is downloaded once the headers are caught up,
+		// so that only a few blocks have to be executed afterwards.
+		if self.mode == SyncMode::Fast
+			&& self.state_sync.is_none()
+			&& !number.is_zero()
+			&& self.status().state == SyncState::Idle
+		{
+			match self.client.header(BlockId::Hash(*hash)) {
+				Ok(Some(header)) => {
+					info!("⬇️  Downloading state of block #{} ({})", number, hash);
+					self.state_sync = Some(StateSync::new(header));
+				},
+				Ok(None) | Err(_) => {
+					warn!(target: "sync", "💔 Missing header of finalized block {:?}", hash);
+				},
+			}
+		}
+	}
+
+	/// Called when a block following a state download has been executed.
+	fn on_block_reexecuted(&mut self, hash: B::Hash, number: NumberFor<B>) {
+		if let Some((_, last)) = self.reexecute_from {
+			if number >= self.client.info().best_number {
+				debug!(target: "sync", "Executed all blocks following the state download");
+				self.reexecute_from = None;
+			} else if number > last {
+				self.reexecute_from = Some((hash, number));
+			}
+		}
 	}
 
 	/// Called when a block has been queued for import.
@@ -1332,8 +1601,9 @@ impl<B: BlockT> ChainSync<B> {
 
 		self.block_announce_validation.push(async move {
 			match future.await {
-				Ok(Validation::Success { is_new_best }) => PreValidateBlockAnnounce::Process {
+				Ok(Validation::Success { is_new_best, score }) => PreValidateBlockAnnounce::Process {
 					is_new_best: is_new_best || is_best,
+					score,
 					announce,
 					who,
 				},
@@ -1422,12 +1692,12 @@ impl<B: BlockT> ChainSync<B> {
 			pre_validation_result,
 		);
 
-		let (announce, is_best, who) = match pre_validation_result {
+		let (announce, is_best, score, who) = match pre_validation_result {
 			PreValidateBlockAnnounce::Failure { who, disconnect } => {
 				return PollBlockAnnounceValidation::Failure { who, disconnect }
 			},
-			PreValidateBlockAnnounce::Process { announce, is_new_best, who } => {
-				(announce, is_new_best, who)
+			PreValidateBlockAnnounce::Process { announce, is_new_best, score, who } => {
+				(announce, is_new_best, score, who)
 			},
 			PreValidateBlockAnnounce::Error { .. } | PreValidateBlockAnnounce::Skip =>
 				return PollBlockAnnounceValidation::Skip,
@@ -1451,6 +1721,7 @@ impl<B: BlockT> ChainSync<B> {
 			// update their best block
 			peer.best_number = number;
 			peer.best_hash = hash;
+			peer.announce_score = score;
 		}
 
 		if let PeerSyncState::AncestorSearch {..} = peer.state {
@@ -1476,6 +1747,7 @@ impl<B: BlockT> ChainSync<B> {
 			trace!(target: "sync", "Known block announce from {}: {}", who, hash);
 			if let Some(target) = self.fork_targets.get_mut(&hash) {
 				target.peers.insert(who.clone());
+				target.score = target.score.max(score);
 			}
 			return PollBlockAnnounceValidation::Nothing { is_best, who, announce }
 		}
@@ -1511,14 +1783,16 @@ impl<B: BlockT> ChainSync<B> {
 				hash,
 				announce.header,
 			);
-			self.fork_targets
+			let target = self.fork_targets
 				.entry(hash.clone())
 				.or_insert_with(|| ForkTarget {
 					number,
 					parent_hash: Some(*announce.header.parent_hash()),
 					peers: Default::default(),
-				})
-				.peers.insert(who.clone());
+					score,
+				});
+			target.peers.insert(who.clone());
+			target.score = target.score.max(score);
 		}
 
 		trace!(target: "sync", "Announce validation result is nothing");
@@ -1527,12 +1801,53 @@ impl<B: BlockT> ChainSync<B> {
 
 	/// Call when a peer has disconnected.
 	pub fn peer_disconnected(&mut self, who: &PeerId) {
+		if let Some(PeerSyncState::DownloadingJustificationRange(start)) = self.peers.get(who).map(|p| p.state) {
+			self.blocks.clear_justification_download(start, who);
+		}
 		self.blocks.clear_peer_download(who);
 		self.peers.remove(who);
 		self.extra_justifications.peer_disconnected(who);
 		self.pending_requests.set_all();
 	}
 
+	/// Frees the ranges of blocks requested from peers that are much slower to answer than
+	/// expected, so that they can be requested from other peers.
+	///
+	/// The response of the slow peer is still processed if it eventually arrives.
+	pub fn reassign_stalled_downloads(&mut self) {
+		self.reassign_stalled_downloads_at(Instant::now())
+	}
+
+	fn reassign_stalled_downloads_at(&mut self, now: Instant) {
+		for (id, peer) in self.peers.iter_mut() {
+			match peer.state {
+				PeerSyncState::DownloadingNew(start) if peer.throughput.is_stalled(now) => {
+					debug!(
+						target: "sync",
+						"Block request to {} from #{} stalled, requesting the blocks from other peers",
+						id,
+						start,
+					);
+					peer.throughput.on_stall();
+					self.blocks.clear_peer_download(id);
+					self.pending_requests.set_all();
+				},
+				PeerSyncState::DownloadingJustificationRange(start) if peer.throughput.is_stalled(now) => {
+					debug!(
+						target: "sync",
+						"Justification request to {} from #{} stalled, requesting them from other peers",
+						id,
+						start,
+					);
+					peer.throughput.on_stall();
+					self.blocks.clear_justification_download(start, id);
+					self.pending_requests.set_all();
+				},
+				_ => {},
+			}
+		}
+	}
+
 	/// Restart the sync process. This will reset all pending block requests and return an iterator
 	/// of new block requests to make to peers. Peers that were downloading finality data (i.e.
 	/// their state was `DownloadingJustification`) are unaffected and will stay in the same state.
@@ -1540,9 +1855,15 @@ impl<B: BlockT> ChainSync<B> {
 		&'a mut self,
 	) -> impl Iterator<Item = Result<(PeerId, BlockRequest<B>), BadPeer>> + 'a {
 		self.blocks.clear();
-		let info = self.client.info();
-		self.best_queued_hash = info.best_hash;
-		self.best_queued_number = info.best_number;
+		let (best_hash, best_number) = match self.reexecute_from {
+			Some(reexecute_from) => reexecute_from,
+			None => {
+				let info = self.client.info();
+				(info.best_hash, info.best_number)
+			},
+		};
+		self.best_queued_hash = best_hash;
+		self.best_queued_number = best_number;
 		self.pending_requests.set_all();
 		debug!(target:"sync", "Restarted with {} ({})", self.best_queued_number, self.best_queued_hash);
 		let old_peers = std::mem::take(&mut self.peers);
@@ -1700,7 +2021,7 @@ fn peer_block_request<B: BlockT>(
 	}
 	let range = blocks.needed_blocks(
 		id.clone(),
-		MAX_BLOCKS_TO_REQUEST,
+		peer.throughput.request_size() as usize,
 		peer.best_number,
 		peer.common_number,
 		max_parallel_downloads,
@@ -1728,7 +2049,45 @@ fn peer_block_request<B: BlockT>(
 	Some((range, request))
 }
 
+/// Get a request for the justifications of `range`, whose bodies are downloaded from another peer.
+///
+/// The headers are requested along with the justifications to validate the response.
+fn justification_range_request<B: BlockT>(range: Range<NumberFor<B>>) -> BlockRequest<B> {
+	message::generic::BlockRequest {
+		id: 0,
+		fields: BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION,
+		from: message::FromBlock::Number(range.end.saturating_sub(One::one())),
+		to: None,
+		direction: message::Direction::Descending,
+		max: Some((range.end - range.start).saturated_into::<u32>()),
+	}
+}
+
+/// Drain the blocks ready to be imported from `blocks`, starting at `from`.
+fn drain_blocks<B: BlockT>(
+	blocks: &mut BlockCollection<B>,
+	from: NumberFor<B>,
+	import_existing: bool,
+) -> Vec<IncomingBlock<B>> {
+	blocks.drain(from)
+		.into_iter()
+		.map(|block_data| {
+			IncomingBlock {
+				hash: block_data.block.hash,
+				header: block_data.block.header,
+				body: block_data.block.body,
+				justification: block_data.block.justification,
+				origin: block_data.origin,
+				allow_missing_state: true,
+				import_existing,
+				state: None,
+			}
+		}).collect()
+}
+
 /// Get pending fork sync targets for a peer.
+///
+/// The target with the highest score among the ones the peer can serve is picked first.
 fn fork_sync_request<B: BlockT>(
 	id: &PeerId,
 	targets: &mut HashMap<B::Hash, ForkTarget<B>>,
@@ -1748,30 +2107,25 @@ fn fork_sync_request<B: BlockT>(
 		}
 		true
 	});
-	for (hash, r) in targets {
-		if !r.peers.contains(id) {
-			continue
-		}
-		if r.number <= best_num {
-			let parent_status = r.parent_hash.as_ref().map_or(BlockStatus::Unknown, check_block);
-			let count = if parent_status == BlockStatus::Unknown {
-				(r.number - finalized).saturated_into::<u32>() // up to the last finalized block
-			} else {
-				// request only single block
-				1
-			};
-			trace!(target: "sync", "Downloading requested fork {:?} from {}, {} blocks", hash, id, count);
-			return Some((hash.clone(), message::generic::BlockRequest {
-				id: 0,
-				fields: attributes.clone(),
-				from: message::FromBlock::Hash(hash.clone()),
-				to: None,
-				direction: message::Direction::Descending,
-				max: Some(count),
-			}))
-		}
-	}
-	None
+	let (hash, r) = targets.iter()
+		.filter(|(_, r)| r.peers.contains(id) && r.number <= best_num)
+		.max_by_key(|(_, r)| r.score)?;
+	let parent_status = r.parent_hash.as_ref().map_or(BlockStatus::Unknown, check_block);
+	let count = if parent_status == BlockStatus::Unknown {
+		(r.number - finalized).saturated_into::<u32>() // up to the last finalized block
+	} else {
+		// request only single block
+		1
+	};
+	trace!(target: "sync", "Downloading requested fork {:?} from {}, {} blocks", hash, id, count);
+	Some((hash.clone(), message::generic::BlockRequest {
+		id: 0,
+		fields: attributes.clone(),
+		from: message::FromBlock::Hash(hash.clone()),
+		to: None,
+		direction: message::Direction::Descending,
+		max: Some(count),
+	}))
 }
 
 /// Returns `true` if the given `block` is a descendent of `base`.
@@ -1923,6 +2277,8 @@ mod test {
 			&info,
 			block_announce_validator,
 			1,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
 		);
 
 		let (a1_hash, a1_number) = {
@@ -1994,6 +2350,8 @@ mod test {
 			&info,
 			Box::new(DefaultBlockAnnounceValidator),
 			1,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
 		);
 
 		let peer_id1 = PeerId::random();
@@ -2160,6 +2518,8 @@ mod test {
 			&info,
 			Box::new(DefaultBlockAnnounceValidator),
 			5,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
 		);
 
 		let peer_id1 = PeerId::random();
@@ -2274,6 +2634,8 @@ mod test {
 			&info,
 			Box::new(DefaultBlockAnnounceValidator),
 			5,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
 		);
 
 		let peer_id1 = PeerId::random();
@@ -2344,8 +2706,26 @@ mod test {
 
 		let peer1_from = unwrap_from_block_number(peer1_req.unwrap().from);
 
-		// As we are on the same chain, we should directly continue with requesting blocks from
-		// peer 2 as well.
+		// As we are on the same chain, peer 2 downloads the justifications of the blocks requested
+		// from peer 1.
+		let request = get_block_request(
+			&mut sync,
+			FromBlock::Number(peer1_from),
+			MAX_BLOCKS_TO_REQUEST as u32,
+			&peer_id2,
+		);
+		assert_eq!(BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION, request.fields);
+		let response = create_block_response(
+			blocks[(peer1_from - MAX_BLOCKS_TO_REQUEST as u64) as usize..peer1_from as usize]
+				.iter()
+				.rev()
+				.cloned()
+				.collect(),
+		);
+		let res = sync.on_block_data(&peer_id2, Some(request), response).unwrap();
+		assert!(imported_numbers(res).is_empty());
+
+		// We should then directly continue with requesting blocks from peer 2 as well.
 		get_block_request(
 			&mut sync,
 			FromBlock::Number(peer1_from + MAX_BLOCKS_TO_REQUEST as u64),
@@ -2393,6 +2773,8 @@ mod test {
 			&info,
 			Box::new(DefaultBlockAnnounceValidator),
 			5,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
 		);
 
 		let finalized_block = blocks[MAX_BLOCKS_TO_LOOK_BACKWARDS as usize * 2 - 1].clone();
@@ -2489,4 +2871,236 @@ mod test {
 			&peer_id1,
 		);
 	}
+
+	#[test]
+	fn fork_sync_request_prefers_highest_score() {
+		let peer_id = PeerId::random();
+		let (low, high, unrelated) = (Hash::random(), Hash::random(), Hash::random());
+		let target = |score, peers: &[PeerId]| ForkTarget::<Block> {
+			number: 5,
+			parent_hash: None,
+			peers: peers.iter().cloned().collect(),
+			score,
+		};
+
+		let mut targets = HashMap::new();
+		targets.insert(low, target(0, &[peer_id.clone()]));
+		targets.insert(high, target(1, &[peer_id.clone()]));
+		targets.insert(unrelated, target(2, &[PeerId::random()]));
+
+		let (hash, _) = fork_sync_request::<Block>(
+			&peer_id,
+			&mut targets,
+			10,
+			0,
+			&BlockAttributes::HEADER,
+			|_| BlockStatus::Unknown,
+		).unwrap();
+		assert_eq!(hash, high);
+	}
+
+	/// Numbers of the blocks of an import, or an empty list for a request.
+	fn imported_numbers(res: OnBlockData<Block>) -> Vec<u64> {
+		match res {
+			OnBlockData::Import(_, blocks) =>
+				blocks.iter().map(|b| *b.header.as_ref().unwrap().number()).collect(),
+			OnBlockData::Request(..) => Vec::new(),
+		}
+	}
+
+	#[test]
+	fn truncated_response_requests_the_start_of_the_range_again() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let mut remote = Arc::new(TestClientBuilder::new().build());
+		let info = client.info();
+		let blocks = (0..10).map(|_| build_block(&mut remote, None, false)).collect::<Vec<_>>();
+
+		let mut sync = ChainSync::new(
+			Roles::AUTHORITY,
+			client.clone(),
+			&info,
+			Box::new(DefaultBlockAnnounceValidator),
+			1,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
+		);
+
+		let peer_id = PeerId::random();
+		sync.new_peer(peer_id.clone(), blocks[9].hash(), 10).unwrap();
+		let request = get_block_request(&mut sync, FromBlock::Hash(blocks[9].hash()), 10, &peer_id);
+
+		// The response stops after the first half of the descending range.
+		let response = create_block_response(blocks[5..].iter().rev().cloned().collect());
+		let res = sync.on_block_data(&peer_id, Some(request), response).unwrap();
+		assert!(imported_numbers(res).is_empty());
+
+		// The start of the range is requested again, and the blocks are imported at their heights.
+		let request = get_block_request(&mut sync, FromBlock::Number(5), 5, &peer_id);
+		let response = create_block_response(blocks[..5].iter().rev().cloned().collect());
+		let res = sync.on_block_data(&peer_id, Some(request), response).unwrap();
+		assert_eq!(imported_numbers(res), (1..=10).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn late_response_after_reassignment_is_imported_once() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let mut remote = Arc::new(TestClientBuilder::new().build());
+		let info = client.info();
+		let blocks = (0..10).map(|_| build_block(&mut remote, None, false)).collect::<Vec<_>>();
+
+		let mut sync = ChainSync::new(
+			Roles::AUTHORITY,
+			client.clone(),
+			&info,
+			Box::new(DefaultBlockAnnounceValidator),
+			1,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
+		);
+
+		let slow_peer = PeerId::random();
+		let other_peer = PeerId::random();
+		sync.new_peer(slow_peer.clone(), blocks[9].hash(), 10).unwrap();
+		let slow_request = get_block_request(&mut sync, FromBlock::Hash(blocks[9].hash()), 10, &slow_peer);
+		sync.new_peer(other_peer.clone(), blocks[9].hash(), 10).unwrap();
+		assert!(sync.block_requests().next().is_none());
+
+		// Once the request to the slow peer stalls, its range is requested from the other peer.
+		sync.reassign_stalled_downloads_at(Instant::now() + std::time::Duration::from_secs(60));
+		let other_request = get_block_request(&mut sync, FromBlock::Hash(blocks[9].hash()), 10, &other_peer);
+
+		// The late response is imported, and the blocks are not imported a second time.
+		let response = create_block_response(blocks.iter().rev().cloned().collect());
+		let res = sync.on_block_data(&slow_peer, Some(slow_request), response).unwrap();
+		assert_eq!(imported_numbers(res), (1..=10).collect::<Vec<_>>());
+
+		let response = create_block_response(blocks.iter().rev().cloned().collect());
+		let res = sync.on_block_data(&other_peer, Some(other_request), response).unwrap();
+		assert!(imported_numbers(res).is_empty());
+	}
+
+	/// Split the requests of `sync` into the request for the bodies of a range and the request for
+	/// its justifications, along with the peers they are sent to.
+	fn body_and_justification_requests(
+		sync: &mut ChainSync<Block>,
+	) -> ((PeerId, BlockRequest<Block>), (PeerId, BlockRequest<Block>)) {
+		let mut requests = sync.block_requests()
+			.map(|(peer, request)| (peer.clone(), request))
+			.collect::<Vec<_>>();
+		assert_eq!(2, requests.len());
+		requests.sort_by_key(|(_, request)| !request.fields.contains(BlockAttributes::BODY));
+		let justification_request = requests.pop().unwrap();
+		let body_request = requests.pop().unwrap();
+
+		assert_eq!(BlockAttributes::HEADER | BlockAttributes::BODY, body_request.1.fields);
+		assert_eq!(BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION, justification_request.1.fields);
+		assert_ne!(body_request.0, justification_request.0);
+		(body_request, justification_request)
+	}
+
+	/// A response to a justification request, with a justification for the `justified` block.
+	fn create_justification_response(blocks: Vec<Block>, justified: Hash) -> BlockResponse<Block> {
+		BlockResponse::<Block> {
+			id: 0,
+			blocks: blocks.into_iter().map(|b|
+				BlockData::<Block> {
+					hash: b.hash(),
+					header: Some(b.header().clone()),
+					body: None,
+					receipt: None,
+					message_queue: None,
+					justification: if b.hash() == justified { Some(vec![1, 2, 3]) } else { None },
+				}
+			).collect(),
+		}
+	}
+
+	#[test]
+	fn bodies_and_justifications_are_downloaded_from_different_peers() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let mut remote = Arc::new(TestClientBuilder::new().build());
+		let info = client.info();
+		let blocks = (0..10).map(|_| build_block(&mut remote, None, false)).collect::<Vec<_>>();
+
+		let mut sync = ChainSync::new(
+			Roles::AUTHORITY,
+			client.clone(),
+			&info,
+			Box::new(DefaultBlockAnnounceValidator),
+			1,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
+		);
+
+		let peer_id1 = PeerId::random();
+		let peer_id2 = PeerId::random();
+		sync.new_peer(peer_id1.clone(), blocks[9].hash(), 10).unwrap();
+		sync.new_peer(peer_id2.clone(), blocks[9].hash(), 10).unwrap();
+
+		let ((body_peer, body_request), (justification_peer, justification_request)) =
+			body_and_justification_requests(&mut sync);
+		assert_eq!(FromBlock::Hash(blocks[9].hash()), body_request.from);
+		assert_eq!(FromBlock::Number(10), justification_request.from);
+		assert_eq!(Some(10), justification_request.max);
+
+		// The blocks are not imported before their justifications are downloaded.
+		let response = create_block_response(blocks.iter().rev().cloned().collect());
+		let res = sync.on_block_data(&body_peer, Some(body_request), response).unwrap();
+		assert!(imported_numbers(res).is_empty());
+
+		let response = create_justification_response(blocks.iter().rev().cloned().collect(), blocks[4].hash());
+		let res = sync.on_block_data(&justification_peer, Some(justification_request), response).unwrap();
+		match res {
+			OnBlockData::Import(_, imported) => {
+				assert_eq!(
+					imported.iter().map(|b| *b.header.as_ref().unwrap().number()).collect::<Vec<_>>(),
+					(1..=10).collect::<Vec<_>>(),
+				);
+				assert!(imported.iter().all(|b| b.body.is_some()));
+				assert_eq!(
+					imported.iter().map(|b| b.justification.is_some()).collect::<Vec<_>>(),
+					(1..=10).map(|n| n == 5).collect::<Vec<_>>(),
+				);
+			},
+			OnBlockData::Request(..) => panic!("Expected blocks to import"),
+		}
+	}
+
+	#[test]
+	fn justifications_are_requested_again_when_their_peer_disconnects() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let mut remote = Arc::new(TestClientBuilder::new().build());
+		let info = client.info();
+		let blocks = (0..10).map(|_| build_block(&mut remote, None, false)).collect::<Vec<_>>();
+
+		let mut sync = ChainSync::new(
+			Roles::AUTHORITY,
+			client.clone(),
+			&info,
+			Box::new(DefaultBlockAnnounceValidator),
+			1,
+			MAX_BLOCKS_TO_REQUEST as u32,
+			SyncMode::Full,
+		);
+
+		let peer_id1 = PeerId::random();
+		let peer_id2 = PeerId::random();
+		sync.new_peer(peer_id1.clone(), blocks[9].hash(), 10).unwrap();
+		sync.new_peer(peer_id2.clone(), blocks[9].hash(), 10).unwrap();
+
+		let ((body_peer, body_request), (justification_peer, _)) = body_and_justification_requests(&mut sync);
+		let response = create_block_response(blocks.iter().rev().cloned().collect());
+		let res = sync.on_block_data(&body_peer, Some(body_request), response).unwrap();
+		assert!(imported_numbers(res).is_empty());
+
+		// Once the peer downloading the justifications is gone, they are requested from the peer
+		// which sent the bodies.
+		sync.peer_disconnected(&justification_peer);
+		let request = get_block_request(&mut sync, FromBlock::Number(10), 10, &body_peer);
+		assert_eq!(BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION, request.fields);
+
+		let response = create_justification_response(blocks.iter().rev().cloned().collect(), blocks[9].hash());
+		let res = sync.on_block_data(&body_peer, Some(request), response).unwrap();
+		assert_eq!(imported_numbers(res), (1..=10).collect::<Vec<_>>());
+	}
 }
@@ -21,7 +21,7 @@ use std::ops::Range;
 use std::collections::{HashMap, BTreeMap};
 use log::trace;
 use tetsy_libp2p::PeerId;
-use tp_runtime::traits::{Block as BlockT, NumberFor, One};
+use tp_runtime::{Justification, traits::{Block as BlockT, NumberFor, One}};
 use crate::protocol::message;
 
 /// Block data with origin.
@@ -51,12 +51,32 @@ impl<B: BlockT> BlockRangeState<B> {
 	}
 }
 
+#[derive(Debug)]
+enum JustificationRangeState<B: BlockT> {
+	Needed,
+	Downloading(PeerId),
+	Complete(HashMap<B::Hash, Justification>),
+}
+
+/// Justifications of a range of blocks, downloaded from another peer than their bodies.
+#[derive(Debug)]
+struct JustificationRange<B: BlockT> {
+	len: NumberFor<B>,
+	/// The peer downloading the bodies of the range, which is not asked for its justifications
+	/// until it has answered.
+	body_peer: Option<PeerId>,
+	state: JustificationRangeState<B>,
+}
+
 /// A collection of blocks being downloaded.
 #[derive(Default)]
 pub struct BlockCollection<B: BlockT> {
 	/// Downloaded blocks.
 	blocks: BTreeMap<NumberFor<B>, BlockRangeState<B>>,
 	peer_requests: HashMap<PeerId, NumberFor<B>>,
+	/// Justifications downloaded separately, by the first block number of their range. The
+	/// blocks of a range are not drained before its justifications are downloaded.
+	justifications: BTreeMap<NumberFor<B>, JustificationRange<B>>,
 }
 
 impl<B: BlockT> BlockCollection<B> {
@@ -65,6 +85,7 @@ impl<B: BlockT> BlockCollection<B> {
 		BlockCollection {
 			blocks: BTreeMap::new(),
 			peer_requests: HashMap::new(),
+			justifications: BTreeMap::new(),
 		}
 	}
 
@@ -72,6 +93,7 @@ impl<B: BlockT> BlockCollection<B> {
 	pub fn clear(&mut self) {
 		self.blocks.clear();
 		self.peer_requests.clear();
+		self.justifications.clear();
 	}
 
 	/// Insert a set of blocks into collection.
@@ -161,6 +183,9 @@ impl<B: BlockT> BlockCollection<B> {
 	}
 
 	/// Get a valid chain of blocks ordered in descending order and ready for importing into blockchain.
+	///
+	/// Blocks whose justifications are still being downloaded are not drained. The downloaded
+	/// justifications are added to the drained blocks.
 	pub fn drain(&mut self, from: NumberFor<B>) -> Vec<BlockData<B>> {
 		let mut drained = Vec::new();
 		let mut ranges = Vec::new();
@@ -169,7 +194,22 @@ impl<B: BlockT> BlockCollection<B> {
 		for (start, range_data) in &mut self.blocks {
 			match range_data {
 				BlockRangeState::Complete(blocks) if *start <= prev => {
-					prev = *start + (blocks.len() as u32).into();
+					let end = *start + (blocks.len() as u32).into();
+					if justifications_pending(&self.justifications, *start .. end) {
+						break;
+					}
+					let mut number = *start;
+					for block in blocks.iter_mut() {
+						if block.block.justification.is_none() {
+							block.block.justification = take_justification(
+								&mut self.justifications,
+								number,
+								&block.block.hash,
+							);
+						}
+						number += One::one();
+					}
+					prev = end;
 					// Remove all elements from `blocks` and add them to `drained`
 					drained.append(blocks);
 					ranges.push(*start);
@@ -181,11 +221,92 @@ impl<B: BlockT> BlockCollection<B> {
 		for r in ranges {
 			self.blocks.remove(&r);
 		}
+		// The justifications of the blocks that are already imported are no longer needed.
+		let obsolete = self.justifications.iter()
+			.take_while(|(start, range)| **start + range.len <= prev)
+			.map(|(start, _)| *start)
+			.collect::<Vec<_>>();
+		for start in obsolete {
+			self.justifications.remove(&start);
+		}
 		trace!(target: "sync", "Drained {} blocks", drained.len());
 		drained
 	}
 
+	/// Schedule the download of the justifications of `range` from another peer than
+	/// `body_peer`, which downloads the bodies of the range without their justifications.
+	///
+	/// Returns false, and does nothing, if the justifications of part of the range are already
+	/// scheduled.
+	pub fn schedule_justifications(&mut self, range: Range<NumberFor<B>>, body_peer: PeerId) -> bool {
+		if overlapping(&self.justifications, range.clone()).next().is_some() {
+			return false;
+		}
+		self.justifications.insert(range.start, JustificationRange {
+			len: range.end - range.start,
+			body_peer: Some(body_peer),
+			state: JustificationRangeState::Needed,
+		});
+		true
+	}
+
+	/// Returns a range of blocks whose justifications `who` should download, up to `peer_best`.
+	/// The returned range is marked as being downloaded.
+	pub fn needed_justifications(
+		&mut self,
+		who: &PeerId,
+		peer_best: NumberFor<B>,
+	) -> Option<Range<NumberFor<B>>> {
+		let (start, range) = self.justifications.iter_mut().find(|(start, range)| {
+			matches!(range.state, JustificationRangeState::Needed)
+				&& range.body_peer.as_ref() != Some(who)
+				&& **start + range.len <= peer_best + One::one()
+		})?;
+		range.state = JustificationRangeState::Downloading(who.clone());
+		Some(*start .. *start + range.len)
+	}
+
+	/// Insert the justifications of the range starting at `start`. Blocks without a
+	/// justification in `blocks` don't have any.
+	pub fn insert_justifications(&mut self, start: NumberFor<B>, blocks: Vec<message::BlockData<B>>) {
+		match self.justifications.get_mut(&start) {
+			Some(range) if !matches!(range.state, JustificationRangeState::Complete(_)) => {
+				range.state = JustificationRangeState::Complete(blocks.into_iter()
+					.filter_map(|b| b.justification.map(|j| (b.hash, j)))
+					.collect());
+			},
+			_ => trace!(target: "sync", "Ignored justifications not needed anymore: {}", start),
+		}
+	}
+
+	/// Forget about the justifications of the ranges within `range`, which were downloaded along
+	/// with its blocks.
+	pub fn justifications_downloaded(&mut self, range: Range<NumberFor<B>>) {
+		let starts = overlapping(&self.justifications, range.clone())
+			.filter(|(start, r)| **start >= range.start && **start + r.len <= range.end)
+			.map(|(start, _)| *start)
+			.collect::<Vec<_>>();
+		for start in starts {
+			self.justifications.remove(&start);
+		}
+	}
+
+	/// Release the justifications of the range starting at `start` if `who` is downloading them,
+	/// so that they are requested from another peer.
+	pub fn clear_justification_download(&mut self, start: NumberFor<B>, who: &PeerId) {
+		if let Some(range) = self.justifications.get_mut(&start) {
+			if matches!(&range.state, JustificationRangeState::Downloading(peer) if peer == who) {
+				range.state = JustificationRangeState::Needed;
+			}
+		}
+	}
+
 	pub fn clear_peer_download(&mut self, who: &PeerId) {
+		for range in self.justifications.values_mut() {
+			if range.body_peer.as_ref() == Some(who) {
+				range.body_peer = None;
+			}
+		}
 		if let Some(start) = self.peer_requests.remove(who) {
 			let remove = match self.blocks.get_mut(&start) {
 				Some(&mut BlockRangeState::Downloading { ref mut downloading, .. }) if *downloading > 1 => {
@@ -206,6 +327,37 @@ impl<B: BlockT> BlockCollection<B> {
 	}
 }
 
+/// Iterate over the justification ranges overlapping `range`.
+fn overlapping<'a, B: BlockT>(
+	justifications: &'a BTreeMap<NumberFor<B>, JustificationRange<B>>,
+	range: Range<NumberFor<B>>,
+) -> impl Iterator<Item = (&'a NumberFor<B>, &'a JustificationRange<B>)> {
+	// The ranges don't overlap, their ends are in the same order as their starts.
+	justifications.range(.. range.end).rev().take_while(move |(start, r)| **start + r.len > range.start)
+}
+
+/// Returns true if justifications of blocks of `range` are still being downloaded.
+fn justifications_pending<B: BlockT>(
+	justifications: &BTreeMap<NumberFor<B>, JustificationRange<B>>,
+	range: Range<NumberFor<B>>,
+) -> bool {
+	overlapping(justifications, range)
+		.any(|(_, r)| !matches!(r.state, JustificationRangeState::Complete(_)))
+}
+
+/// Take the downloaded justification of the block `number` with the given `hash`, if any.
+fn take_justification<B: BlockT>(
+	justifications: &mut BTreeMap<NumberFor<B>, JustificationRange<B>>,
+	number: NumberFor<B>,
+	hash: &B::Hash,
+) -> Option<Justification> {
+	match justifications.range_mut(..= number).next_back() {
+		Some((start, JustificationRange { len, state: JustificationRangeState::Complete(downloaded), .. }))
+			if *start + *len > number => downloaded.remove(hash),
+		_ => None,
+	}
+}
+
 #[cfg(test)]
 mod test {
 	use super::{BlockCollection, BlockData, BlockRangeState};
@@ -217,7 +369,8 @@ mod test {
 
 	fn is_empty(bc: &BlockCollection<Block>) -> bool {
 		bc.blocks.is_empty() &&
-		bc.peer_requests.is_empty()
+		bc.peer_requests.is_empty() &&
+		bc.justifications.is_empty()
 	}
 
 	fn generate_blocks(n: usize) -> Vec<message::BlockData<Block>> {
@@ -304,4 +457,53 @@ mod test {
 		assert_eq!(bc.needed_blocks(peer0.clone(), 128, 10000, 600, 1, 200), None); // too far ahead
 		assert_eq!(bc.needed_blocks(peer0.clone(), 128, 10000, 600, 1, 200000), Some(100 + 128 .. 100 + 128 + 128));
 	}
+
+	#[test]
+	fn drain_waits_for_justifications() {
+		let mut bc = BlockCollection::new();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+
+		let blocks = generate_blocks(11);
+		assert_eq!(bc.needed_blocks(peer0.clone(), 10, 10, 0, 1, 200), Some(1 .. 11));
+		assert!(bc.schedule_justifications(1 .. 11, peer0.clone()));
+		assert!(!bc.schedule_justifications(5 .. 15, peer1.clone()));
+
+		// The peer downloading the bodies and peers without the range are not asked.
+		assert_eq!(bc.needed_justifications(&peer0, 10), None);
+		assert_eq!(bc.needed_justifications(&peer1, 9), None);
+		assert_eq!(bc.needed_justifications(&peer1, 10), Some(1 .. 11));
+		assert_eq!(bc.needed_justifications(&peer1, 10), None);
+
+		bc.clear_peer_download(&peer0);
+		bc.insert(1, blocks[1..11].to_vec(), peer0.clone());
+		assert_eq!(bc.drain(1), vec![]);
+
+		let mut justified = blocks[1..11].to_vec();
+		justified[9].justification = Some(vec![1]);
+		bc.insert_justifications(1, justified.clone());
+		let drained = bc.drain(1);
+		assert_eq!(drained.into_iter().map(|b| b.block).collect::<Vec<_>>(), justified);
+		assert!(is_empty(&bc));
+	}
+
+	#[test]
+	fn stalled_justifications_are_requested_again() {
+		let mut bc = BlockCollection::<Block>::new();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		let peer2 = PeerId::random();
+
+		assert!(bc.schedule_justifications(1 .. 11, peer0.clone()));
+		assert_eq!(bc.needed_justifications(&peer1, 10), Some(1 .. 11));
+		assert_eq!(bc.needed_justifications(&peer2, 10), None);
+		bc.clear_justification_download(1, &peer2);
+		assert_eq!(bc.needed_justifications(&peer2, 10), None);
+		bc.clear_justification_download(1, &peer1);
+		assert_eq!(bc.needed_justifications(&peer2, 10), Some(1 .. 11));
+
+		// Justifications downloaded along with the blocks are not requested anymore.
+		bc.justifications_downloaded(1 .. 11);
+		assert!(bc.justifications.is_empty());
+	}
 }
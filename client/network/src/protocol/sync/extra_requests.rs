@@ -343,7 +343,7 @@ impl<'a, B: BlockT> Matcher<'a, B> {
 
 #[cfg(test)]
 mod tests {
-	use crate::protocol::sync::PeerSync;
+	use crate::protocol::sync::{PeerSync, throughput::PeerThroughput};
 	use tp_blockchain::Error as ClientError;
 	use quickcheck::{Arbitrary, Gen, QuickCheck, StdThreadGen};
 	use rand::Rng;
@@ -550,6 +550,8 @@ mod tests {
 				best_hash: Hash::random(),
 				best_number: g.gen(),
 				state: ArbitraryPeerSyncState::arbitrary(g).0,
+				announce_score: g.gen(),
+				throughput: PeerThroughput::new(128, 128),
 			};
 			ArbitraryPeerSync(ps)
 		}
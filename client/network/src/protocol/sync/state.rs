@@ -0,0 +1,269 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Download of the complete state of a single block.
+//!
+//! The top trie is downloaded first, in key order and in chunks of limited size. Every chunk
+//! comes with a proof that is checked against the state root of the target header. Child tries
+//! found in the top trie are queued and downloaded the same way once the top trie is complete.
+//! Their proofs are also checked against the state root of the target header, as they include
+//! the path from the top trie to the child root.
+
+use codec::Encode;
+use crate::schema::v1::{StateRequest, StateResponse};
+use log::debug;
+use tet_core::storage::{ChildInfo, Storage, StorageChild, well_known_keys};
+use tp_consensus::ImportedState;
+use tp_runtime::traits::{Block as BlockT, Header as HeaderT, HashFor};
+use tp_state_machine::StorageProof;
+
+/// State sync status and statistics.
+#[derive(Clone, Debug)]
+pub struct StateDownloadProgress {
+	/// Number of state entries downloaded so far.
+	pub entries: u64,
+	/// Number of key and value bytes downloaded so far.
+	pub size: u64,
+}
+
+/// Result of [`StateSync::import`].
+pub enum ImportResult<B: BlockT> {
+	/// The state is complete and ready to be imported together with the target header.
+	Import(B::Hash, B::Header, ImportedState<B>),
+	/// The chunk was valid, request the next one.
+	Continue(StateRequest),
+	/// The response was invalid and the peer should be punished.
+	BadResponse,
+}
+
+/// Downloads the state of the target block.
+pub struct StateSync<B: BlockT> {
+	target_block: B::Hash,
+	target_header: B::Header,
+	/// Trie being downloaded, `None` stands for the top trie.
+	current_child: Option<ChildInfo>,
+	/// Last key received for the current trie, `None` if nothing has been received yet.
+	last_key: Option<Vec<u8>>,
+	/// Child tries found in the top trie that are still to be downloaded.
+	pending_children: Vec<ChildInfo>,
+	state: Storage,
+	progress: StateDownloadProgress,
+}
+
+impl<B: BlockT> StateSync<B> {
+	/// Create a new instance downloading the state of the block with the given header.
+	pub fn new(target_header: B::Header) -> Self {
+		StateSync {
+			target_block: target_header.hash(),
+			target_header,
+			current_child: None,
+			last_key: None,
+			pending_children: Vec::new(),
+			state: Default::default(),
+			progress: StateDownloadProgress { entries: 0, size: 0 },
+		}
+	}
+
+	/// Hash of the block which state is downloaded.
+	pub fn target(&self) -> B::Hash {
+		self.target_block
+	}
+
+	/// Number of the block which state is downloaded.
+	pub fn target_number(&self) -> <B::Header as HeaderT>::Number {
+		*self.target_header.number()
+	}
+
+	/// Returns the download progress.
+	pub fn progress(&self) -> StateDownloadProgress {
+		self.progress.clone()
+	}
+
+	/// Produce the request for the next chunk of state.
+	pub fn next_request(&self) -> StateRequest {
+		StateRequest {
+			block: self.target_block.encode(),
+			child: self.current_child.as_ref()
+				.map(|info| info.storage_key().to_vec())
+				.unwrap_or_default(),
+			start: self.last_key.clone().unwrap_or_default(),
+		}
+	}
+
+	/// Validate and import a state response.
+	pub fn import(&mut self, response: StateResponse) -> ImportResult<B> {
+		let proof = match StorageProof::decode(&mut response.proof.as_ref()) {
+			Ok(proof) => proof,
+			Err(e) => {
+				debug!(target: "sync", "Failed to decode state proof: {:?}", e);
+				return ImportResult::BadResponse;
+			}
+		};
+		let entries: Vec<_> = response.entries.into_iter().map(|e| (e.key, e.value)).collect();
+		if entries.is_empty() && !response.complete {
+			debug!(target: "sync", "Empty incomplete state response");
+			return ImportResult::BadResponse;
+		}
+		if let Err(e) = tp_state_machine::read_range_proof_check::<HashFor<B>>(
+			*self.target_header.state_root(),
+			proof,
+			self.current_child.as_ref(),
+			self.last_key.as_ref().map(|k| &k[..]),
+			&entries,
+			response.complete,
+		) {
+			debug!(target: "sync", "Bad state response for {:?}: {}", self.target_block, e);
+			return ImportResult::BadResponse;
+		}
+
+		self.last_key = entries.last().map(|(k, _)| k.clone()).or(self.last_key.take());
+		for (key, value) in entries {
+			self.progress.entries += 1;
+			self.progress.size += (key.len() + value.len()) as u64;
+			match &self.current_child {
+				Some(info) => {
+					self.state.children_default
+						.entry(info.storage_key().to_vec())
+						.or_insert_with(|| StorageChild {
+							data: Default::default(),
+							child_info: info.clone(),
+						})
+						.data
+						.insert(key, value);
+				},
+				None if well_known_keys::is_child_storage_key(&key) => {
+					let prefix = well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
+					if !key.starts_with(prefix) {
+						debug!(target: "sync", "Unsupported child trie {:?}", key);
+						return ImportResult::BadResponse;
+					}
+					self.pending_children.push(ChildInfo::new_default(&key[prefix.len()..]));
+				},
+				None => {
+					self.state.top.insert(key, value);
+				},
+			}
+		}
+
+		if response.complete {
+			self.current_child = self.pending_children.pop();
+			self.last_key = None;
+			if self.current_child.is_none() {
+				return ImportResult::Import(
+					self.target_block,
+					self.target_header.clone(),
+					ImportedState {
+						block: self.target_block,
+						state: std::mem::take(&mut self.state),
+					},
+				);
+			}
+		}
+		ImportResult::Continue(self.next_request())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ImportResult, StateSync};
+	use crate::schema::v1::{StateEntry, StateRequest, StateResponse};
+	use codec::Encode;
+	use tet_core::storage::{ChildInfo, Storage, StorageChild};
+	use tp_runtime::traits::{BlakeTwo256, Header as HeaderT};
+	use tp_state_machine::{InMemoryBackend, prove_range_read_with_size};
+	use tetcore_test_runtime_client::runtime::{Block, Header};
+
+	/// Answers a request the same way as the state request handler, with tiny chunks.
+	fn respond(backend: &InMemoryBackend<BlakeTwo256>, request: &StateRequest) -> StateResponse {
+		let child_info = if request.child.is_empty() {
+			None
+		} else {
+			Some(ChildInfo::new_default(&request.child))
+		};
+		let start = if request.start.is_empty() { None } else { Some(&request.start[..]) };
+		let (entries, complete, proof) = prove_range_read_with_size(
+			backend.clone(),
+			child_info.as_ref(),
+			start,
+			1,
+		).unwrap();
+		StateResponse {
+			entries: entries.into_iter().map(|(key, value)| StateEntry { key, value }).collect(),
+			complete,
+			proof: proof.encode(),
+		}
+	}
+
+	#[test]
+	fn downloads_state_with_child_tries() {
+		let child_info = ChildInfo::new_default(b"child");
+		let mut storage = Storage::default();
+		storage.top.insert(b"a".to_vec(), vec![1]);
+		storage.top.insert(b"b".to_vec(), vec![2; 40]);
+		storage.top.insert(b"c".to_vec(), vec![3]);
+		storage.children_default.insert(child_info.storage_key().to_vec(), StorageChild {
+			data: vec![(b"x".to_vec(), vec![4]), (b"y".to_vec(), vec![5])].into_iter().collect(),
+			child_info: child_info.clone(),
+		});
+		let backend = InMemoryBackend::<BlakeTwo256>::from(storage.clone());
+		let header = Header::new(
+			1,
+			Default::default(),
+			*backend.root(),
+			Default::default(),
+			Default::default(),
+		);
+
+		let mut sync = StateSync::<Block>::new(header.clone());
+		let mut request = sync.next_request();
+		let (hash, imported_header, imported) = loop {
+			match sync.import(respond(&backend, &request)) {
+				ImportResult::Continue(next) => request = next,
+				ImportResult::Import(hash, header, state) => break (hash, header, state),
+				ImportResult::BadResponse => panic!("Valid state response refused"),
+			}
+		};
+
+		assert_eq!(hash, header.hash());
+		assert_eq!(imported_header, header);
+		assert_eq!(imported.block, header.hash());
+		assert_eq!(imported.state.top, storage.top);
+		assert_eq!(imported.state.children_default, storage.children_default);
+		// The child trie root stored in the top trie counts as an entry.
+		assert_eq!(sync.progress().entries, 6);
+	}
+
+	#[test]
+	fn refuses_entries_not_matching_the_proof() {
+		let mut storage = Storage::default();
+		storage.top.insert(b"a".to_vec(), vec![1]);
+		let backend = InMemoryBackend::<BlakeTwo256>::from(storage);
+		let header = Header::new(
+			1,
+			Default::default(),
+			*backend.root(),
+			Default::default(),
+			Default::default(),
+		);
+
+		let mut sync = StateSync::<Block>::new(header);
+		let mut response = respond(&backend, &sync.next_request());
+		response.entries[0].value = vec![2];
+		assert!(matches!(sync.import(response), ImportResult::BadResponse));
+	}
+}
@@ -0,0 +1,211 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Measurement of the block download throughput of the peers.
+//!
+//! The number of blocks asked to a peer in a single request is adjusted so that its responses
+//! arrive after roughly [`TARGET_RESPONSE_TIME`]: fast peers, or peers behind high-latency links,
+//! get large requests, while slow peers get small ones so that they don't hold back the import
+//! of the blocks downloaded from the other peers. A request that takes much longer than expected
+//! is considered stalled, so that its range of blocks can be requested from another peer.
+
+use std::time::Duration;
+use wasm_timer::Instant;
+
+/// Minimum number of blocks to request in a single packet.
+const MIN_BLOCKS_TO_REQUEST: u32 = 8;
+
+/// Time after which we aim to receive the responses to block requests.
+const TARGET_RESPONSE_TIME: Duration = Duration::from_secs(4);
+
+/// Minimum time without response before a block request is considered stalled.
+const MIN_STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A block request is considered stalled when its response takes this many times longer than
+/// expected from the throughput of the peer.
+const STALL_FACTOR: f64 = 4.0;
+
+/// Weight of the latest response in the moving average of the throughput.
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+/// Block download throughput of a peer.
+#[derive(Debug, Clone)]
+pub struct PeerThroughput {
+	/// Number of blocks to ask in the next request.
+	request_size: u32,
+	/// Upper bound of `request_size`.
+	max_request_size: u32,
+	/// Moving average of the number of blocks per second received from the peer, once at least
+	/// one response has been received.
+	blocks_per_sec: Option<f64>,
+	/// The pending block request, if any.
+	pending: Option<PendingRequest>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingRequest {
+	/// When the request has been sent.
+	started: Instant,
+	/// Number of blocks requested.
+	size: u32,
+	/// Whether the request has already been reported as stalled.
+	stalled: bool,
+}
+
+impl PeerThroughput {
+	/// Creates the throughput of a new peer, whose first request asks for `initial_request_size`
+	/// blocks. Requests never ask for more than `max_request_size` blocks.
+	pub fn new(initial_request_size: u32, max_request_size: u32) -> Self {
+		let max_request_size = max_request_size.max(MIN_BLOCKS_TO_REQUEST);
+		PeerThroughput {
+			request_size: initial_request_size.max(MIN_BLOCKS_TO_REQUEST).min(max_request_size),
+			max_request_size,
+			blocks_per_sec: None,
+			pending: None,
+		}
+	}
+
+	/// Number of blocks to ask in the next request.
+	pub fn request_size(&self) -> u32 {
+		self.request_size
+	}
+
+	/// Must be called when a request for `size` blocks is sent to the peer.
+	pub fn on_request(&mut self, size: u32, now: Instant) {
+		self.pending = Some(PendingRequest { started: now, size, stalled: false });
+	}
+
+	/// Must be called when the response to the pending request is received, with the number of
+	/// blocks it contains.
+	pub fn on_response(&mut self, received: usize, now: Instant) {
+		let pending = match self.pending.take() {
+			Some(pending) => pending,
+			None => return,
+		};
+
+		let elapsed = if now > pending.started { now - pending.started } else { Duration::from_secs(0) };
+		// Avoid dividing by zero for responses faster than the resolution of the clock.
+		let rate = received as f64 / elapsed.as_secs_f64().max(0.001);
+		let blocks_per_sec = match self.blocks_per_sec {
+			Some(average) => average * (1.0 - THROUGHPUT_SMOOTHING) + rate * THROUGHPUT_SMOOTHING,
+			None => rate,
+		};
+		self.blocks_per_sec = Some(blocks_per_sec);
+
+		// Grow by at most a factor of two per response, so that a single lucky response doesn't
+		// lead to a huge request.
+		let target = (blocks_per_sec * TARGET_RESPONSE_TIME.as_secs_f64())
+			.min(u32::max_value() as f64) as u32;
+		let mut request_size = target.min(self.request_size.saturating_mul(2));
+
+		// Peers limit the size of their responses, either in number of blocks or in bytes. Asking
+		// for more than they serve only leads to more requests for the remaining blocks.
+		if received > 0 && (received as u32) < pending.size {
+			request_size = request_size.min(received as u32);
+		}
+
+		self.request_size = request_size.max(MIN_BLOCKS_TO_REQUEST).min(self.max_request_size);
+	}
+
+	/// Must be called when the response to a pending request whose size doesn't reflect the
+	/// block download throughput, like a request for justifications only, is received.
+	pub fn on_response_unmeasured(&mut self) {
+		self.pending = None;
+	}
+
+	/// Returns true if the pending request has taken much longer than expected from the
+	/// throughput of the peer, and hasn't been reported as stalled yet.
+	pub fn is_stalled(&self, now: Instant) -> bool {
+		let pending = match &self.pending {
+			Some(pending) if !pending.stalled => pending,
+			_ => return false,
+		};
+
+		let timeout = self.blocks_per_sec
+			.filter(|blocks_per_sec| *blocks_per_sec > 0.0)
+			.map(|blocks_per_sec| {
+				let expected = pending.size as f64 / blocks_per_sec * STALL_FACTOR;
+				Duration::from_secs_f64(expected.min(u32::max_value() as f64)).max(MIN_STALL_TIMEOUT)
+			})
+			.unwrap_or(MIN_STALL_TIMEOUT);
+
+		now > pending.started && now - pending.started > timeout
+	}
+
+	/// Must be called when the pending request is reported as stalled. Halves the size of the
+	/// next requests.
+	pub fn on_stall(&mut self) {
+		if let Some(pending) = &mut self.pending {
+			pending.stalled = true;
+		}
+		self.request_size = (self.request_size / 2).max(MIN_BLOCKS_TO_REQUEST);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{PeerThroughput, MIN_BLOCKS_TO_REQUEST, MIN_STALL_TIMEOUT};
+	use std::time::Duration;
+	use wasm_timer::Instant;
+
+	#[test]
+	fn request_size_follows_throughput() {
+		let start = Instant::now();
+		let mut throughput = PeerThroughput::new(128, 512);
+		assert_eq!(throughput.request_size(), 128);
+
+		// Fast responses double the request size, up to the maximum.
+		throughput.on_request(128, start);
+		throughput.on_response(128, start + Duration::from_millis(100));
+		assert_eq!(throughput.request_size(), 256);
+		throughput.on_request(256, start);
+		throughput.on_response(256, start + Duration::from_millis(100));
+		assert_eq!(throughput.request_size(), 512);
+		throughput.on_request(512, start);
+		throughput.on_response(512, start + Duration::from_millis(100));
+		assert_eq!(throughput.request_size(), 512);
+
+		// Partial responses cap the request size.
+		throughput.on_request(512, start);
+		throughput.on_response(128, start + Duration::from_millis(100));
+		assert_eq!(throughput.request_size(), 128);
+
+		// Slow responses shrink it.
+		let mut throughput = PeerThroughput::new(128, 512);
+		throughput.on_request(128, start);
+		throughput.on_response(128, start + Duration::from_secs(64));
+		assert_eq!(throughput.request_size(), MIN_BLOCKS_TO_REQUEST);
+	}
+
+	#[test]
+	fn stalled_requests_are_reported_once() {
+		let start = Instant::now();
+		let mut throughput = PeerThroughput::new(128, 128);
+		throughput.on_request(128, start);
+		assert!(!throughput.is_stalled(start + MIN_STALL_TIMEOUT / 2));
+		assert!(throughput.is_stalled(start + MIN_STALL_TIMEOUT * 2));
+
+		throughput.on_stall();
+		assert_eq!(throughput.request_size(), 64);
+		assert!(!throughput.is_stalled(start + MIN_STALL_TIMEOUT * 2));
+
+		// The late response is still accounted.
+		throughput.on_response(128, start + MIN_STALL_TIMEOUT * 2);
+		assert!(!throughput.is_stalled(start + MIN_STALL_TIMEOUT * 4));
+	}
+}
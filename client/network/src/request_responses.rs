@@ -154,6 +154,10 @@ pub enum Event {
 		/// When successful contains the time elapsed between when we received the request and when
 		/// we sent back the response. When unsuccessful contains the failure reason.
 		result: Result<Duration, ResponseFailure>,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response has been sent.
+		response_size: usize,
 	},
 
 	/// A request initiated using [`RequestResponsesBehaviour::send_request`] has succeeded or
@@ -168,7 +172,11 @@ pub enum Event {
 		/// Duration the request took.
 		duration: Duration,
 		/// Result of the request.
-		result: Result<(), RequestFailure>
+		result: Result<(), RequestFailure>,
+		/// Size in bytes of the request.
+		request_size: usize,
+		/// Size in bytes of the response, or 0 if no response has been received.
+		response_size: usize,
 	},
 
 	/// A request protocol handler issued reputation changes for the given peer.
@@ -207,9 +215,10 @@ pub struct RequestResponsesBehaviour {
 	>,
 
 	/// Pending requests, passed down to a [`RequestResponse`] behaviour, awaiting a reply.
+	/// Also contains the size in bytes of the request.
 	pending_requests: HashMap<
 			ProtocolRequestId,
-			(Instant, oneshot::Sender<Result<Vec<u8>, RequestFailure>>),
+			(Instant, usize, oneshot::Sender<Result<Vec<u8>, RequestFailure>>),
 		>,
 
 	/// Whenever an incoming request arrives, a `Future` is added to this list and will yield the
@@ -218,8 +227,18 @@ pub struct RequestResponsesBehaviour {
 		Pin<Box<dyn Future<Output = Option<RequestProcessingOutcome>> + Send>>
 	>,
 
-	/// Whenever an incoming request arrives, the arrival [`Instant`] is recorded here.
-	pending_responses_arrival_time: HashMap<ProtocolRequestId, Instant>,
+	/// Whenever an incoming request arrives, its arrival [`Instant`] and size are recorded here.
+	pending_responses_info: HashMap<ProtocolRequestId, InboundRequestInfo>,
+}
+
+/// Information about an inbound request whose response hasn't been sent yet.
+struct InboundRequestInfo {
+	/// When the request has been received.
+	arrival_time: Instant,
+	/// Size in bytes of the request.
+	request_size: usize,
+	/// Size in bytes of the response, once it has been handed over to the remote.
+	response_size: usize,
 }
 
 /// Generated by the response builder and waiting to be processed.
@@ -263,7 +282,7 @@ impl RequestResponsesBehaviour {
 			protocols,
 			pending_requests: Default::default(),
 			pending_responses: Default::default(),
-			pending_responses_arrival_time: Default::default(),
+			pending_responses_info: Default::default(),
 		})
 	}
 
@@ -280,10 +299,11 @@ impl RequestResponsesBehaviour {
 	) {
 		if let Some((protocol, _)) = self.protocols.get_mut(protocol_name) {
 			if protocol.is_connected(target) {
+				let request_size = request.len();
 				let request_id = protocol.send_request(target, request);
 				self.pending_requests.insert(
 					(protocol_name.to_string().into(), request_id).into(),
-					(Instant::now(), pending_response),
+					(Instant::now(), request_size, pending_response),
 				);
 			} else {
 				if pending_response.send(Err(RequestFailure::NotConnected)).is_err() {
@@ -449,6 +469,12 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 				};
 
 				if let Ok(payload) = result {
+					if let Some(info) = self.pending_responses_info
+						.get_mut(&(protocol_name.clone(), request_id).into())
+					{
+						info.response_size = payload.len();
+					}
+
 					if let Some((protocol, _)) = self.protocols.get_mut(&*protocol_name) {
 						if let Err(_) = protocol.send_response(inner_channel, Ok(payload)) {
 							// Note: Failure is handled further below when receiving
@@ -518,9 +544,13 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 							peer,
 							message: RequestResponseMessage::Request { request_id, request, channel, .. },
 						} => {
-							self.pending_responses_arrival_time.insert(
+							self.pending_responses_info.insert(
 								(protocol.clone(), request_id.clone()).into(),
-								Instant::now(),
+								InboundRequestInfo {
+									arrival_time: Instant::now(),
+									request_size: request.len(),
+									response_size: 0,
+								},
 							);
 
 							let (tx, rx) = oneshot::channel();
@@ -568,14 +598,15 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 							},
 							..
 						} => {
-							let (started, delivered) = match self.pending_requests.remove(
+							let response_size = response.as_ref().map_or(0, |response| response.len());
+							let (started, request_size, delivered) = match self.pending_requests.remove(
 								&(protocol.clone(), request_id).into(),
 							) {
-								Some((started, pending_response)) => {
+								Some((started, request_size, pending_response)) => {
 									let delivered = pending_response.send(
 										response.map_err(|()| RequestFailure::Refused),
 									).map_err(|_| RequestFailure::Obsolete);
-									(started, delivered)
+									(started, request_size, delivered)
 								}
 								None => {
 									log::warn!(
@@ -593,6 +624,8 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 								protocol: protocol.clone(),
 								duration: started.elapsed(),
 								result: delivered,
+								request_size,
+								response_size,
 							};
 
 							return Poll::Ready(NetworkBehaviourAction::GenerateEvent(out));
@@ -605,8 +638,10 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 							error,
 							..
 						} => {
-							let started = match self.pending_requests.remove(&(protocol.clone(), request_id).into()) {
-								Some((started, pending_response)) => {
+							let (started, request_size) = match self.pending_requests
+								.remove(&(protocol.clone(), request_id).into())
+							{
+								Some((started, request_size, pending_response)) => {
 									if pending_response.send(
 										Err(RequestFailure::Network(error.clone())),
 									).is_err() {
@@ -617,7 +652,7 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 											request_id,
 										);
 									}
-									started
+									(started, request_size)
 								}
 								None => {
 									log::warn!(
@@ -635,6 +670,8 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 								protocol: protocol.clone(),
 								duration: started.elapsed(),
 								result: Err(RequestFailure::Network(error)),
+								request_size,
+								response_size: 0,
 							};
 
 							return Poll::Ready(NetworkBehaviourAction::GenerateEvent(out));
@@ -643,23 +680,24 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 						// An inbound request failed, either while reading the request or due to failing
 						// to send a response.
 						RequestResponseEvent::InboundFailure { request_id, peer, error, .. } => {
-							self.pending_responses_arrival_time.remove(
+							let request_size = self.pending_responses_info.remove(
 								&(protocol.clone(), request_id).into(),
-							);
+							).map_or(0, |info| info.request_size);
 							let out = Event::InboundRequest {
 								peer,
 								protocol: protocol.clone(),
 								result: Err(ResponseFailure::Network(error)),
+								request_size,
+								response_size: 0,
 							};
 							return Poll::Ready(NetworkBehaviourAction::GenerateEvent(out));
 						}
 
 						// A response to an inbound request has been sent.
 						RequestResponseEvent::ResponseSent { request_id, peer } => {
-							let arrival_time = self.pending_responses_arrival_time.remove(
+							let info = self.pending_responses_info.remove(
 								&(protocol.clone(), request_id).into(),
 							)
-								.map(|t| t.elapsed())
 								.expect(
 									"Time is added for each inbound request on arrival and only \
 									 removed on success (`ResponseSent`) or failure \
@@ -671,7 +709,9 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 							let out = Event::InboundRequest {
 								peer,
 								protocol: protocol.clone(),
-								result: Ok(arrival_time),
+								result: Ok(info.arrival_time.elapsed()),
+								request_size: info.request_size,
+								response_size: info.response_size,
 							};
 							return Poll::Ready(NetworkBehaviourAction::GenerateEvent(out));
 
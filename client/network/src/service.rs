@@ -76,9 +76,10 @@ use tetsy_libp2p::swarm::{
 use log::{error, info, trace, warn};
 use metrics::{Metrics, MetricSources, Histogram, HistogramVec};
 use parking_lot::Mutex;
-use tc_peerset::PeersetHandle;
+use tc_peerset::{BannedPeer, PeersetHandle};
 use tp_consensus::import_queue::{BlockImportError, BlockImportResult, ImportQueue, Link};
 use tp_runtime::traits::{Block as BlockT, NumberFor};
+pub(crate) use traffic::TrafficAccounting;
 use tetcore_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
 use std::{
 	borrow::Cow,
@@ -96,12 +97,15 @@ use std::{
 		Arc,
 	},
 	task::Poll,
+	time::Duration,
 };
 
 pub use behaviour::{ResponseFailure, InboundFailure, RequestFailure, OutboundFailure};
+pub use traffic::Traffic;
 
 mod metrics;
 mod out_events;
+mod traffic;
 #[cfg(test)]
 mod tests;
 
@@ -128,6 +132,8 @@ pub struct NetworkService<B: BlockT + 'static, H: ExHashT> {
 	/// Field extracted from the [`Metrics`] struct and necessary to report the
 	/// notifications-related metrics.
 	notifications_sizes_metric: Option<HistogramVec>,
+	/// Bytes exchanged with each peer on each protocol, and outbound bandwidth caps.
+	traffic: Arc<TrafficAccounting>,
 	/// Marker to pin the `H` generic. Serves no purpose except to not break backwards
 	/// compatibility.
 	_marker: PhantomData<H>,
@@ -180,10 +186,16 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			local_peer_id.to_base58(),
 		);
 
+		let traffic = Arc::new(TrafficAccounting::new(
+			params.network_config.outbound_bandwidth_caps.clone()
+		));
+
 		let (protocol, peerset_handle, mut known_addresses) = Protocol::new(
 			protocol::ProtocolConfig {
 				roles: From::from(&params.role),
 				max_parallel_downloads: params.network_config.max_parallel_downloads,
+				max_blocks_per_request: params.network_config.max_blocks_per_request,
+				sync_mode: params.network_config.sync_mode,
 			},
 			params.chain.clone(),
 			params.transaction_pool,
@@ -192,6 +204,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			&params.network_config,
 			params.block_announce_validator,
 			params.metrics_registry.as_ref(),
+			traffic.clone(),
 		)?;
 
 		// List of multiaddresses that we know in the network.
@@ -329,7 +342,8 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 					config_mem,
 					config_wasm,
 					params.network_config.remux_window_size,
-					remux_maximum_buffer_size
+					remux_maximum_buffer_size,
+					params.network_config.ip_filter.clone(),
 				)
 			};
 
@@ -342,6 +356,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 					light_client_handler,
 					discovery_config,
 					params.block_request_protocol_config,
+					params.state_request_protocol_config,
 					params.network_config.request_response_protocols,
 				);
 
@@ -382,6 +397,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 					bandwidth: bandwidth.clone(),
 					major_syncing: is_major_syncing.clone(),
 					connected_peers: num_connected.clone(),
+					traffic: traffic.clone(),
 				})?)
 			}
 			None => None
@@ -413,6 +429,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			peers_notifications_sinks: peers_notifications_sinks.clone(),
 			notifications_sizes_metric:
 				metrics.as_ref().map(|metrics| metrics.notifications_sizes.clone()),
+			traffic: traffic.clone(),
 			_marker: PhantomData,
 		});
 
@@ -427,6 +444,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			light_client_rqs: params.on_demand.and_then(|od| od.extract_receiver()),
 			event_streams: out_events::OutChannels::new(params.metrics_registry.as_ref())?,
 			peers_notifications_sinks,
+			traffic,
 			metrics,
 			boot_node_ids,
 		})
@@ -606,6 +624,11 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 		self.service.remove_reserved_peer(peer);
 	}
 
+	/// Returns the list of peers currently banned.
+	pub fn banned_peers(&mut self) -> Vec<BannedPeer> {
+		self.network_service.user_protocol_mut().banned_peers()
+	}
+
 	/// Adds a `PeerId` and its address as reserved. The string should encode the address
 	/// and peer ID of the remote node.
 	pub fn add_reserved_peer(&self, peer: String) -> Result<(), String> {
@@ -647,6 +670,10 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 	/// happens if you call this method at a higher rate than the rate at which the peer processes
 	/// these notifications, or if the available network bandwidth is too low.
 	///
+	/// The notification is also silently dropped if the protocol has an outbound bandwidth cap
+	/// (see [`NetworkConfiguration::outbound_bandwidth_caps`](crate::config::NetworkConfiguration::outbound_bandwidth_caps))
+	/// and its budget is exhausted.
+	///
 	/// For this reason, this method is considered soft-deprecated. You are encouraged to use
 	/// [`NetworkService::notification_sender`] instead.
 	///
@@ -677,11 +704,23 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 			}
 		};
 
+		if !self.traffic.has_send_capacity(&protocol) {
+			// Notification discarded, as documented.
+			log::debug!(
+				target: "sub-libp2p",
+				"Outbound bandwidth cap of {:?} reached, dropping notification to {:?}",
+				protocol,
+				target,
+			);
+			return;
+		}
+
 		if let Some(notifications_sizes_metric) = self.notifications_sizes_metric.as_ref() {
 			notifications_sizes_metric
 				.with_label_values(&["out", &protocol])
 				.observe(message.len() as f64);
 		}
+		self.traffic.report_out(&target, &protocol, message.len());
 
 		// Sending is communicated to the `NotificationsSink`.
 		trace!(
@@ -786,9 +825,18 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 			sink,
 			protocol_name: protocol,
 			notification_size_metric,
+			traffic: self.traffic.clone(),
 		})
 	}
 
+	/// Returns the number of bytes exchanged with the given peer on each notifications and
+	/// request-response protocol, ordered by protocol name.
+	///
+	/// Only the peers we are connected to are tracked.
+	pub fn peer_traffic(&self, peer_id: &PeerId) -> Vec<(Cow<'static, str>, Traffic)> {
+		self.traffic.peer_traffic(peer_id)
+	}
+
 	/// Returns a stream containing the events that happen on the network.
 	///
 	/// If this method is called multiple times, the events are duplicated.
@@ -874,6 +922,20 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 		self.peerset.report_peer(who, cost_benefit);
 	}
 
+	/// Ban a node, for the given duration or until it is unbanned if `None`.
+	///
+	/// The node is disconnected, and no connection with it is accepted or opened while it is
+	/// banned, unless it is a reserved node. Bans are persisted across restarts if the network
+	/// has a configuration directory.
+	pub fn ban_peer(&self, who: PeerId, duration: Option<Duration>) {
+		self.peerset.ban_peer(who, duration);
+	}
+
+	/// Lift the ban of a node.
+	pub fn unban_peer(&self, who: PeerId) {
+		self.peerset.unban_peer(who);
+	}
+
 	/// Disconnect from a node as soon as possible.
 	///
 	/// This triggers the same effects as if the connection had closed itself spontaneously.
@@ -1165,11 +1227,19 @@ pub struct NotificationSender {
 	/// Field extracted from the [`Metrics`] struct and necessary to report the
 	/// notifications-related metrics.
 	notification_size_metric: Option<Histogram>,
+
+	/// Traffic accounting and outbound bandwidth caps.
+	traffic: Arc<TrafficAccounting>,
 }
 
 impl NotificationSender {
 	/// Returns a future that resolves when the `NotificationSender` is ready to send a notification.
+	///
+	/// If the protocol has an outbound bandwidth cap, the future also waits until sending is
+	/// allowed by the cap.
 	pub async fn ready<'a>(&'a self) -> Result<NotificationSenderReady<'a>, NotificationSenderError> {
+		self.traffic.wait_send_capacity(&self.protocol_name).await;
+
 		Ok(NotificationSenderReady {
 			ready: match self.sink.reserve_notification().await {
 				Ok(r) => r,
@@ -1178,6 +1248,7 @@ impl NotificationSender {
 			peer_id: self.sink.peer_id(),
 			protocol_name: &self.protocol_name,
 			notification_size_metric: self.notification_size_metric.clone(),
+			traffic: &self.traffic,
 		})
 	}
 }
@@ -1196,6 +1267,9 @@ pub struct NotificationSenderReady<'a> {
 	/// Field extracted from the [`Metrics`] struct and necessary to report the
 	/// notifications-related metrics.
 	notification_size_metric: Option<Histogram>,
+
+	/// Traffic accounting and outbound bandwidth caps.
+	traffic: &'a TrafficAccounting,
 }
 
 impl<'a> NotificationSenderReady<'a> {
@@ -1206,6 +1280,7 @@ impl<'a> NotificationSenderReady<'a> {
 		if let Some(notification_size_metric) = &self.notification_size_metric {
 			notification_size_metric.observe(notification.len() as f64);
 		}
+		self.traffic.report_out(self.peer_id, self.protocol_name, notification.len());
 
 		trace!(
 			target: "sub-libp2p",
@@ -1296,6 +1371,8 @@ pub struct NetworkWorker<B: BlockT + 'static, H: ExHashT> {
 	/// For each peer and protocol combination, an object that allows sending notifications to
 	/// that peer. Shared with the [`NetworkService`].
 	peers_notifications_sinks: Arc<Mutex<HashMap<(PeerId, Cow<'static, str>), NotificationsSink>>>,
+	/// Bytes exchanged with each peer on each protocol. Shared with the [`NetworkService`].
+	traffic: Arc<TrafficAccounting>,
 }
 
 impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
@@ -1419,7 +1496,12 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 					}
 					this.import_queue.import_justification(origin, hash, nb, justification);
 				},
-				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::InboundRequest { protocol, result, .. })) => {
+				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::InboundRequest {
+					peer, protocol, result, request_size, response_size,
+				})) => {
+					this.traffic.report_in(&peer, &protocol, request_size);
+					this.traffic.report_out(&peer, &protocol, response_size);
+
 					if let Some(metrics) = this.metrics.as_ref() {
 						match result {
 							Ok(serve_time) => {
@@ -1446,8 +1528,11 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 					}
 				},
 				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::RequestFinished {
-					protocol, duration, result, ..
+					peer, protocol, duration, result, request_size, response_size,
 				})) => {
+					this.traffic.report_out(&peer, &protocol, request_size);
+					this.traffic.report_in(&peer, &protocol, response_size);
+
 					if let Some(metrics) = this.metrics.as_ref() {
 						match result {
 							Ok(_) => {
@@ -1551,6 +1636,9 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 					}
 				},
 				Poll::Ready(SwarmEvent::Behaviour(BehaviourOut::NotificationsReceived { remote, messages })) => {
+					for (protocol, message) in &messages {
+						this.traffic.report_in(&remote, protocol, message.len());
+					}
 					if let Some(metrics) = this.metrics.as_ref() {
 						for (protocol, message) in &messages {
 							metrics.notifications_sizes
@@ -1585,6 +1673,9 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 				},
 				Poll::Ready(SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established }) => {
 					trace!(target: "sub-libp2p", "Libp2p => Connected({:?})", peer_id);
+					if num_established.get() == 1 {
+						this.traffic.add_peer(&peer_id);
+					}
 
 					if let Some(metrics) = this.metrics.as_ref() {
 						let direction = match endpoint {
@@ -1624,6 +1715,10 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 							metrics.distinct_peers_connections_closed_total.inc();
 						}
 					}
+
+					if num_established == 0 {
+						this.traffic.remove_peer(&peer_id);
+					}
 				},
 				Poll::Ready(SwarmEvent::NewListenAddr(addr)) => {
 					trace!(target: "sub-libp2p", "Libp2p => NewListenAddr({})", addr);
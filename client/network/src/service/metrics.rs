@@ -17,6 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::transport::BandwidthSinks;
+use super::traffic::TrafficAccounting;
 use prometheus_endpoint::{
 	self as prometheus,
 	Counter, CounterVec, Gauge, GaugeVec, HistogramOpts,
@@ -38,6 +39,8 @@ pub fn register(registry: &Registry, sources: MetricSources) -> Result<Metrics,
 	BandwidthCounters::register(registry, sources.bandwidth)?;
 	MajorSyncingGauge::register(registry, sources.major_syncing)?;
 	NumConnectedGauge::register(registry, sources.connected_peers)?;
+	ProtocolTrafficCounters::register(registry, sources.traffic.clone())?;
+	PeerTrafficCounters::register(registry, sources.traffic)?;
 	Metrics::register(registry)
 }
 
@@ -46,8 +49,13 @@ pub struct MetricSources {
 	pub bandwidth: Arc<BandwidthSinks>,
 	pub major_syncing: Arc<AtomicBool>,
 	pub connected_peers: Arc<AtomicUsize>,
+	pub traffic: Arc<TrafficAccounting>,
 }
 
+/// Maximum number of peers reported by the per-peer traffic metric, in order to bound the
+/// number of time series.
+const MAX_PEERS_TRAFFIC_METRICS: usize = 16;
+
 /// Dedicated metrics.
 pub struct Metrics {
 	// This list is ordered alphabetically
@@ -343,3 +351,72 @@ impl MetricSource for NumConnectedGauge {
 		set(&[], self.0.load(Ordering::Relaxed) as u64);
 	}
 }
+
+/// The per-protocol traffic metric.
+#[derive(Clone)]
+pub struct ProtocolTrafficCounters(Arc<TrafficAccounting>);
+
+impl ProtocolTrafficCounters {
+	/// Registers the `ProtocolTrafficCounters` metric whose values are
+	/// obtained from the given accounting.
+	fn register(registry: &Registry, traffic: Arc<TrafficAccounting>) -> Result<(), PrometheusError> {
+		prometheus::register(SourcedCounter::new(
+			&Opts::new(
+				"sub_libp2p_protocol_bytes_total",
+				"Total bytes of notifications, requests and responses exchanged per protocol"
+			).variable_label("direction").variable_label("protocol"),
+			ProtocolTrafficCounters(traffic),
+		)?, registry)?;
+
+		Ok(())
+	}
+}
+
+impl MetricSource for ProtocolTrafficCounters {
+	type N = u64;
+
+	fn collect(&self, mut set: impl FnMut(&[&str], Self::N)) {
+		for (protocol, traffic) in self.0.protocols_traffic() {
+			set(&["in", &protocol], traffic.bytes_in);
+			set(&["out", &protocol], traffic.bytes_out);
+		}
+	}
+}
+
+/// The per-peer traffic metric, restricted to the peers with the most traffic.
+#[derive(Clone)]
+pub struct PeerTrafficCounters(Arc<TrafficAccounting>);
+
+impl PeerTrafficCounters {
+	/// Registers the `PeerTrafficCounters` metric whose values are
+	/// obtained from the given accounting.
+	fn register(registry: &Registry, traffic: Arc<TrafficAccounting>) -> Result<(), PrometheusError> {
+		prometheus::register(SourcedCounter::new(
+			&Opts::new(
+				"sub_libp2p_peer_protocol_bytes_total",
+				"Total bytes exchanged per protocol with the connected peers that have the most \
+				traffic"
+			)
+				.variable_label("direction")
+				.variable_label("protocol")
+				.variable_label("peer"),
+			PeerTrafficCounters(traffic),
+		)?, registry)?;
+
+		Ok(())
+	}
+}
+
+impl MetricSource for PeerTrafficCounters {
+	type N = u64;
+
+	fn collect(&self, mut set: impl FnMut(&[&str], Self::N)) {
+		for (peer, protocols) in self.0.top_peers_traffic(MAX_PEERS_TRAFFIC_METRICS) {
+			let peer = peer.to_base58();
+			for (protocol, traffic) in protocols {
+				set(&["in", &protocol, &peer], traffic.bytes_in);
+				set(&["out", &protocol, &peer], traffic.bytes_out);
+			}
+		}
+	}
+}
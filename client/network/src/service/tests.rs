@@ -18,6 +18,7 @@
 
 use crate::{config, Event, NetworkService, NetworkWorker};
 use crate::block_request_handler::BlockRequestHandler;
+use crate::state_request_handler::StateRequestHandler;
 
 use tetsy_libp2p::PeerId;
 use futures::prelude::*;
@@ -103,6 +104,15 @@ fn build_test_full_node(config: config::NetworkConfiguration)
 		protocol_config
 	};
 
+	let state_request_protocol_config = {
+		let (handler, protocol_config) = StateRequestHandler::new(
+			protocol_id.clone(),
+			client.clone(),
+		);
+		async_std::task::spawn(handler.run().boxed());
+		protocol_config
+	};
+
 	let worker = NetworkWorker::new(config::Params {
 		role: config::Role::Full,
 		executor: None,
@@ -117,6 +127,7 @@ fn build_test_full_node(config: config::NetworkConfiguration)
 		),
 		metrics_registry: None,
 		block_request_protocol_config,
+		state_request_protocol_config,
 	})
 	.unwrap();
 
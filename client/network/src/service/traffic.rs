@@ -0,0 +1,334 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Accounting of the bytes exchanged with each peer on each notifications or request-response
+//! protocol, and enforcement of the per-protocol outbound bandwidth caps.
+//!
+//! Only the payloads of the notifications, requests and responses are counted. The overhead of
+//! the transport (encryption, multiplexing, handshakes, ...) is only reported by the global
+//! bandwidth counters of the transport.
+//!
+//! The outbound caps are enforced differently depending on how notifications are sent:
+//! [`NotificationSender::ready`](crate::NotificationSender::ready) waits for the budget,
+//! [`NetworkService::write_notification`](crate::NetworkService::write_notification) drops
+//! the notifications exceeding it, and transactions are kept for the next propagation. Block
+//! announces can't be delayed, so their protocol can't be capped.
+
+use tetsy_libp2p::PeerId;
+use parking_lot::Mutex;
+use std::{borrow::Cow, collections::HashMap, time::Duration};
+use wasm_timer::Instant;
+
+/// Number of bytes exchanged on a protocol.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Traffic {
+	/// Number of bytes received from the remote.
+	pub bytes_in: u64,
+	/// Number of bytes sent to the remote.
+	pub bytes_out: u64,
+}
+
+impl Traffic {
+	/// Total number of bytes in both directions.
+	pub fn total(&self) -> u64 {
+		self.bytes_in.saturating_add(self.bytes_out)
+	}
+}
+
+/// Per-peer and per-protocol traffic counters, shared between the [`NetworkWorker`] and the
+/// [`NetworkService`].
+///
+/// [`NetworkWorker`]: crate::NetworkWorker
+/// [`NetworkService`]: crate::NetworkService
+pub struct TrafficAccounting {
+	/// Traffic of the connected peers. Entries are added when we connect to the peer and
+	/// removed when we disconnect from it, the traffic of other peers is not recorded.
+	peers: Mutex<HashMap<PeerId, HashMap<Cow<'static, str>, Traffic>>>,
+	/// Traffic of each protocol since the start of the node, all peers combined.
+	protocols: Mutex<HashMap<Cow<'static, str>, Traffic>>,
+	/// Outbound bandwidth caps of the protocols that have one.
+	caps: HashMap<Cow<'static, str>, Mutex<TokenBucket>>,
+}
+
+impl TrafficAccounting {
+	/// Builds a new `TrafficAccounting`, with the given outbound caps in bytes per second.
+	///
+	/// Caps of 0 are ignored.
+	pub fn new(caps: impl IntoIterator<Item = (Cow<'static, str>, u64)>) -> Self {
+		let now = Instant::now();
+		TrafficAccounting {
+			peers: Mutex::new(HashMap::new()),
+			protocols: Mutex::new(HashMap::new()),
+			caps: caps.into_iter()
+				.filter(|(_, bytes_per_sec)| *bytes_per_sec != 0)
+				.map(|(protocol, bytes_per_sec)| (protocol, Mutex::new(TokenBucket::new(bytes_per_sec, now))))
+				.collect(),
+		}
+	}
+
+	/// Records bytes received from `peer` on `protocol`.
+	pub fn report_in(&self, peer: &PeerId, protocol: &Cow<'static, str>, bytes: usize) {
+		self.report(peer, protocol, |traffic| &mut traffic.bytes_in, bytes);
+	}
+
+	/// Records bytes sent to `peer` on `protocol`.
+	///
+	/// If the protocol has an outbound cap, the bytes are deducted from its budget, which might
+	/// delay the next [`TrafficAccounting::wait_send_capacity`].
+	pub fn report_out(&self, peer: &PeerId, protocol: &Cow<'static, str>, bytes: usize) {
+		self.report(peer, protocol, |traffic| &mut traffic.bytes_out, bytes);
+		if let Some(bucket) = self.caps.get(protocol) {
+			bucket.lock().consume(bytes as u64, Instant::now());
+		}
+	}
+
+	fn report(
+		&self,
+		peer: &PeerId,
+		protocol: &Cow<'static, str>,
+		counter: impl Fn(&mut Traffic) -> &mut u64,
+		bytes: usize,
+	) {
+		let bytes = bytes as u64;
+
+		if let Some(protocols) = self.peers.lock().get_mut(peer) {
+			let counter = counter(protocols.entry(protocol.clone()).or_default());
+			*counter = counter.saturating_add(bytes);
+		}
+
+		let mut protocols = self.protocols.lock();
+		let counter = counter(protocols.entry(protocol.clone()).or_default());
+		*counter = counter.saturating_add(bytes);
+	}
+
+	/// Starts recording the traffic of the given peer. Must be called when we connect to it.
+	pub fn add_peer(&self, peer: &PeerId) {
+		self.peers.lock().entry(peer.clone()).or_default();
+	}
+
+	/// Forgets about the traffic of the given peer. Must be called when we disconnect from it.
+	pub fn remove_peer(&self, peer: &PeerId) {
+		self.peers.lock().remove(peer);
+	}
+
+	/// Returns the traffic of the given peer on each protocol, ordered by protocol name.
+	pub fn peer_traffic(&self, peer: &PeerId) -> Vec<(Cow<'static, str>, Traffic)> {
+		let mut traffic = self.peers.lock()
+			.get(peer)
+			.map(|protocols| protocols.iter().map(|(p, t)| (p.clone(), *t)).collect::<Vec<_>>())
+			.unwrap_or_default();
+		traffic.sort_by(|a, b| a.0.cmp(&b.0));
+		traffic
+	}
+
+	/// Returns the traffic of each protocol, all peers combined.
+	pub fn protocols_traffic(&self) -> Vec<(Cow<'static, str>, Traffic)> {
+		self.protocols.lock().iter().map(|(p, t)| (p.clone(), *t)).collect()
+	}
+
+	/// Returns the traffic of the `max` peers that exchanged the most bytes with us, in
+	/// decreasing order.
+	pub fn top_peers_traffic(&self, max: usize) -> Vec<(PeerId, Vec<(Cow<'static, str>, Traffic)>)> {
+		let peers = self.peers.lock();
+		let mut top = peers.iter()
+			.map(|(peer, protocols)| {
+				let total = protocols.values().fold(0u64, |acc, t| acc.saturating_add(t.total()));
+				(total, peer)
+			})
+			.collect::<Vec<_>>();
+		top.sort_by(|a, b| b.0.cmp(&a.0));
+
+		top.into_iter()
+			.take(max)
+			.map(|(_, peer)| {
+				let protocols = peers[peer].iter().map(|(p, t)| (p.clone(), *t)).collect();
+				(peer.clone(), protocols)
+			})
+			.collect()
+	}
+
+	/// Returns true if `protocol` doesn't have an outbound cap, or if its budget allows sending
+	/// data right now.
+	pub fn has_send_capacity(&self, protocol: &str) -> bool {
+		self.caps.get(protocol).map_or(true, |bucket| {
+			bucket.lock().delay_until_available(Instant::now()) == Duration::from_secs(0)
+		})
+	}
+
+	/// Returns true if `protocol` has an outbound cap.
+	pub fn is_capped(&self, protocol: &str) -> bool {
+		self.caps.contains_key(protocol)
+	}
+
+	/// Waits until the outbound budget of `protocol` allows sending data again.
+	///
+	/// Returns immediately if the protocol doesn't have an outbound cap.
+	pub async fn wait_send_capacity(&self, protocol: &str) {
+		let bucket = match self.caps.get(protocol) {
+			Some(bucket) => bucket,
+			None => return,
+		};
+
+		loop {
+			let delay = bucket.lock().delay_until_available(Instant::now());
+			if delay == Duration::from_secs(0) {
+				return;
+			}
+			futures_timer::Delay::new(delay).await;
+		}
+	}
+}
+
+/// Outbound budget of a protocol.
+///
+/// The budget is refilled at a constant rate, up to one second worth of data. Sending is allowed
+/// as long as the budget isn't negative, and each sent message is deducted from it, even if the
+/// budget goes negative as a result. Large messages therefore delay the following ones instead of
+/// being refused.
+struct TokenBucket {
+	/// Refill rate, in bytes per second. Also the maximum budget.
+	bytes_per_sec: u64,
+	/// Current budget, in bytes.
+	budget: i64,
+	/// Last time the budget has been refilled.
+	last_refill: Instant,
+}
+
+impl TokenBucket {
+	fn new(bytes_per_sec: u64, now: Instant) -> Self {
+		TokenBucket {
+			bytes_per_sec,
+			budget: bytes_per_sec.min(i64::max_value() as u64) as i64,
+			last_refill: now,
+		}
+	}
+
+	fn refill(&mut self, now: Instant) {
+		if now <= self.last_refill {
+			return;
+		}
+
+		let elapsed = now - self.last_refill;
+		let refill = (elapsed.as_secs_f64() * self.bytes_per_sec as f64) as i64;
+		if refill > 0 {
+			let max = self.bytes_per_sec.min(i64::max_value() as u64) as i64;
+			self.budget = self.budget.saturating_add(refill).min(max);
+			self.last_refill = now;
+		}
+	}
+
+	fn consume(&mut self, bytes: u64, now: Instant) {
+		self.refill(now);
+		self.budget = self.budget.saturating_sub(bytes.min(i64::max_value() as u64) as i64);
+	}
+
+	fn delay_until_available(&mut self, now: Instant) -> Duration {
+		self.refill(now);
+		if self.budget >= 0 {
+			Duration::from_secs(0)
+		} else {
+			Duration::from_secs_f64(-(self.budget as f64) / self.bytes_per_sec as f64)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{TokenBucket, Traffic, TrafficAccounting};
+	use tetsy_libp2p::PeerId;
+	use std::{borrow::Cow, time::Duration};
+	use wasm_timer::Instant;
+
+	#[test]
+	fn traffic_is_accounted_per_peer_and_protocol() {
+		let accounting = TrafficAccounting::new(Vec::new());
+		let (peer1, peer2) = (PeerId::random(), PeerId::random());
+		let (proto_a, proto_b) = (Cow::Borrowed("/a"), Cow::Borrowed("/b"));
+		accounting.add_peer(&peer1);
+		accounting.add_peer(&peer2);
+
+		accounting.report_in(&peer1, &proto_a, 10);
+		accounting.report_out(&peer1, &proto_a, 5);
+		accounting.report_out(&peer1, &proto_b, 7);
+		accounting.report_in(&peer2, &proto_a, 100);
+
+		assert_eq!(accounting.peer_traffic(&peer1), vec![
+			(proto_a.clone(), Traffic { bytes_in: 10, bytes_out: 5 }),
+			(proto_b.clone(), Traffic { bytes_in: 0, bytes_out: 7 }),
+		]);
+
+		let top = accounting.top_peers_traffic(1);
+		assert_eq!(top.len(), 1);
+		assert_eq!(top[0].0, peer2);
+
+		accounting.remove_peer(&peer2);
+		assert!(accounting.peer_traffic(&peer2).is_empty());
+
+		// Traffic reported after the disconnection only counts towards the protocol totals.
+		accounting.report_in(&peer2, &proto_a, 1);
+		assert!(accounting.peer_traffic(&peer2).is_empty());
+		assert_eq!(accounting.top_peers_traffic(10).len(), 1);
+
+		let mut protocols = accounting.protocols_traffic();
+		protocols.sort_by(|a, b| a.0.cmp(&b.0));
+		assert_eq!(protocols, vec![
+			(proto_a, Traffic { bytes_in: 111, bytes_out: 5 }),
+			(proto_b, Traffic { bytes_in: 0, bytes_out: 7 }),
+		]);
+	}
+
+	#[test]
+	fn send_capacity_follows_the_cap() {
+		let accounting = TrafficAccounting::new(vec![(Cow::Borrowed("/a"), 1000)]);
+		let (peer, proto_a, proto_b) = (PeerId::random(), Cow::Borrowed("/a"), Cow::Borrowed("/b"));
+		assert!(accounting.is_capped("/a"));
+		assert!(!accounting.is_capped("/b"));
+
+		accounting.report_out(&peer, &proto_a, 1000);
+		assert!(accounting.has_send_capacity("/a"));
+		accounting.report_out(&peer, &proto_a, 1000);
+		assert!(!accounting.has_send_capacity("/a"));
+
+		accounting.report_out(&peer, &proto_b, 10_000);
+		assert!(accounting.has_send_capacity("/b"));
+	}
+
+	#[test]
+	fn token_bucket_delays_after_debt() {
+		let start = Instant::now();
+		let mut bucket = TokenBucket::new(1000, start);
+		assert_eq!(bucket.delay_until_available(start), Duration::from_secs(0));
+
+		// Going 500 bytes into debt requires waiting half a second.
+		bucket.consume(1500, start);
+		assert_eq!(bucket.delay_until_available(start), Duration::from_millis(500));
+		assert_eq!(
+			bucket.delay_until_available(start + Duration::from_millis(250)),
+			Duration::from_millis(250),
+		);
+		assert_eq!(
+			bucket.delay_until_available(start + Duration::from_millis(500)),
+			Duration::from_secs(0),
+		);
+
+		// The budget never exceeds one second worth of data.
+		let later = start + Duration::from_secs(10);
+		bucket.consume(1500, later);
+		assert_eq!(bucket.delay_until_available(later), Duration::from_millis(500));
+	}
+}
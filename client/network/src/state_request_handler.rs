@@ -0,0 +1,154 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Tetcore.
+
+// Tetcore is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Tetcore is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Tetcore.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helper for handling (i.e. answering) state requests from a remote peer via the
+//! [`crate::request_responses::RequestResponsesBehaviour`].
+
+use codec::{Encode, Decode};
+use crate::chain::Client;
+use crate::config::ProtocolId;
+use crate::request_responses::{IncomingRequest, OutgoingResponse, ProtocolConfig};
+use crate::schema::v1::{StateEntry, StateRequest, StateResponse};
+use futures::channel::{mpsc, oneshot};
+use futures::stream::StreamExt;
+use log::debug;
+use prost::Message;
+use tet_core::storage::ChildInfo;
+use tp_runtime::generic::BlockId;
+use tp_runtime::traits::Block as BlockT;
+use std::sync::Arc;
+use std::time::Duration;
+
+const LOG_TARGET: &str = "state-request-handler";
+/// Maximum size of the key-value pairs in a response. The proof comes on top.
+const MAX_RESPONSE_BYTES: usize = 2 * 1024 * 1024;
+
+/// Generates a [`ProtocolConfig`] for the state request protocol, refusing incoming requests.
+pub fn generate_protocol_config(protocol_id: ProtocolId) -> ProtocolConfig {
+	ProtocolConfig {
+		name: generate_protocol_name(protocol_id).into(),
+		max_request_size: 1024 * 1024,
+		max_response_size: 16 * 1024 * 1024,
+		request_timeout: Duration::from_secs(40),
+		inbound_queue: None,
+	}
+}
+
+/// Generate the state protocol name from chain specific protocol identifier.
+fn generate_protocol_name(protocol_id: ProtocolId) -> String {
+	let mut s = String::new();
+	s.push_str("/");
+	s.push_str(protocol_id.as_ref());
+	s.push_str("/state/1");
+	s
+}
+
+/// Handler for incoming state requests from a remote peer.
+pub struct StateRequestHandler<B> {
+	client: Arc<dyn Client<B>>,
+	request_receiver: mpsc::Receiver<IncomingRequest>,
+}
+
+impl<B: BlockT> StateRequestHandler<B> {
+	/// Create a new [`StateRequestHandler`].
+	pub fn new(protocol_id: ProtocolId, client: Arc<dyn Client<B>>) -> (Self, ProtocolConfig) {
+		// State requests are only sent by nodes doing a fast sync, so they are much rarer than
+		// block requests. Use the same queue length nevertheless.
+		let (tx, request_receiver) = mpsc::channel(20);
+
+		let mut protocol_config = generate_protocol_config(protocol_id);
+		protocol_config.inbound_queue = Some(tx);
+
+		(Self { client, request_receiver }, protocol_config)
+	}
+
+	fn handle_request(
+		&self,
+		payload: Vec<u8>,
+		pending_response: oneshot::Sender<OutgoingResponse>
+	) -> Result<(), HandleRequestError> {
+		let request = StateRequest::decode(&payload[..])?;
+		let block: B::Hash = Decode::decode(&mut request.block.as_ref())?;
+		let child_info = if request.child.is_empty() {
+			None
+		} else {
+			Some(ChildInfo::new_default(&request.child))
+		};
+		let start = if request.start.is_empty() {
+			None
+		} else {
+			Some(&request.start[..])
+		};
+
+		let (entries, complete, proof) = self.client.read_range_proof(
+			&BlockId::Hash(block),
+			child_info.as_ref(),
+			start,
+			MAX_RESPONSE_BYTES,
+		)?;
+
+		log::trace!(
+			target: LOG_TARGET,
+			"Serving {} state entries of {:?}, complete: {}",
+			entries.len(),
+			block,
+			complete,
+		);
+
+		let res = StateResponse {
+			entries: entries.into_iter().map(|(key, value)| StateEntry { key, value }).collect(),
+			complete,
+			proof: proof.encode(),
+		};
+
+		let mut data = Vec::with_capacity(res.encoded_len());
+		res.encode(&mut data)?;
+
+		pending_response.send(OutgoingResponse {
+			result: Ok(data),
+			reputation_changes: Vec::new(),
+		}).map_err(|_| HandleRequestError::SendResponse)
+	}
+
+	/// Run [`StateRequestHandler`].
+	pub async fn run(mut self) {
+		while let Some(request) = self.request_receiver.next().await {
+			let IncomingRequest { peer, payload, pending_response } = request;
+
+			match self.handle_request(payload, pending_response) {
+				Ok(()) => debug!(target: LOG_TARGET, "Handled state request from {}.", peer),
+				Err(e) => debug!(
+					target: LOG_TARGET,
+					"Failed to handle state request from {}: {}",
+					peer, e,
+				),
+			}
+		}
+	}
+}
+
+#[derive(derive_more::Display, derive_more::From)]
+enum HandleRequestError {
+	#[display(fmt = "Failed to decode request: {}.", _0)]
+	DecodeProto(prost::DecodeError),
+	#[display(fmt = "Failed to encode response: {}.", _0)]
+	EncodeProto(prost::EncodeError),
+	#[display(fmt = "Failed to decode block hash: {}.", _0)]
+	DecodeScale(codec::Error),
+	Client(tp_blockchain::Error),
+	#[display(fmt = "Failed to send response.")]
+	SendResponse,
+}
@@ -16,17 +16,30 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::config::{IpFilterConfig, IpNetwork};
+use futures::{future, io::{AsyncRead, AsyncWrite, IoSlice, IoSliceMut}};
+use log::debug;
+use parking_lot::Mutex;
+use pin_project::pin_project;
 use tetsy_libp2p::{
-	PeerId, Transport,
+	Multiaddr, PeerId, Transport,
 	core::{
-		self, either::EitherTransport, muxing::StreamMuxerBox,
+		self, ConnectedPoint, either::EitherTransport, multiaddr::Protocol, muxing::StreamMuxerBox,
 		transport::{Boxed, OptionalTransport}, upgrade
 	},
 	mplex, identity, bandwidth, wasm_ext, noise
 };
 #[cfg(not(target_os = "unknown"))]
 use tetsy_libp2p::{tcp, dns, websocket};
-use std::{sync::Arc, time::Duration};
+use std::{
+	collections::HashMap,
+	io,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr},
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Duration,
+};
 
 pub use self::bandwidth::BandwidthSinks;
 
@@ -43,6 +56,8 @@ pub use self::bandwidth::BandwidthSinks;
 /// high-level protocols combined, or to some generously high value if you are sure that a maximum
 /// size is enforced on all high-level protocols.
 ///
+/// `ip_filter` is applied to all the connections, as soon as they are established.
+///
 /// Returns a `BandwidthSinks` object that allows querying the average bandwidth produced by all
 /// the connections spawned with this transport.
 pub fn build_transport(
@@ -51,6 +66,7 @@ pub fn build_transport(
 	wasm_external_transport: Option<wasm_ext::ExtTransport>,
 	remux_window_size: Option<u32>,
 	remux_maximum_buffer_size: usize,
+	ip_filter: IpFilterConfig,
 ) -> (Boxed<(PeerId, StreamMuxerBox)>, Arc<BandwidthSinks>) {
 	// Build the base layer of the transport.
 	let transport = if let Some(t) = wasm_external_transport {
@@ -78,6 +94,15 @@ pub fn build_transport(
 		OptionalTransport::none()
 	});
 
+	// Reject the connections denied by the filter before anything is negotiated on them.
+	let ip_filter = IpFilter::new(ip_filter);
+	let transport = transport.and_then(move |connection, endpoint| {
+		future::ready(ip_filter.check(&endpoint).map(|guard| FilteredConnection {
+			inner: connection,
+			_guard: guard,
+		}))
+	});
+
 	let (transport, bandwidth) = bandwidth::BandwidthLogging::new(transport);
 
 	let authentication_config = {
@@ -124,3 +149,252 @@ pub fn build_transport(
 
 	(transport, bandwidth)
 }
+
+/// Reason for rejecting a connection.
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectionDenied {
+	/// The address isn't allowed by the configuration.
+	#[error("Connections with {0} aren't allowed")]
+	NotAllowed(IpAddr),
+	/// There are already too many inbound connections from the address.
+	#[error("Too many inbound connections from {0}")]
+	TooManyFromIp(IpAddr),
+	/// There are already too many inbound connections from the subnet of the address.
+	#[error("Too many inbound connections from the subnet of {0}")]
+	TooManyFromSubnet(IpAddr),
+}
+
+/// Applies an [`IpFilterConfig`] to the connections of a transport.
+#[derive(Clone)]
+struct IpFilter {
+	config: Arc<IpFilterConfig>,
+	inbound: Arc<Mutex<InboundConnections>>,
+}
+
+/// Number of inbound connections currently open.
+#[derive(Default)]
+struct InboundConnections {
+	per_ip: HashMap<IpAddr, u32>,
+	per_subnet: HashMap<IpAddr, u32>,
+}
+
+impl IpFilter {
+	fn new(config: IpFilterConfig) -> Self {
+		IpFilter {
+			config: Arc::new(config),
+			inbound: Default::default(),
+		}
+	}
+
+	/// Checks whether a new connection is allowed.
+	///
+	/// Returns a guard accounting for inbound connections that must be kept for as long as the
+	/// connection is open.
+	fn check(&self, endpoint: &ConnectedPoint) -> Result<Option<InboundGuard>, ConnectionDenied> {
+		let result = match endpoint {
+			ConnectedPoint::Dialer { address } => match ip_address(address) {
+				Some(ip) => self.check_allowed(ip).map(|()| None),
+				None => Ok(None),
+			},
+			ConnectedPoint::Listener { send_back_addr, .. } => match ip_address(send_back_addr) {
+				Some(ip) => self.check_allowed(ip).and_then(|()| self.accept_inbound(ip)).map(Some),
+				None => Ok(None),
+			},
+		};
+
+		if let Err(err) = &result {
+			debug!(target: "sub-libp2p", "Rejected connection {:?}: {}", endpoint, err);
+		}
+		result
+	}
+
+	fn check_allowed(&self, ip: IpAddr) -> Result<(), ConnectionDenied> {
+		let contains = |networks: &[IpNetwork]| networks.iter().any(|network| network.contains(ip));
+		if contains(&self.config.deny) ||
+			(!self.config.allow.is_empty() && !contains(&self.config.allow))
+		{
+			return Err(ConnectionDenied::NotAllowed(ip))
+		}
+		Ok(())
+	}
+
+	fn accept_inbound(&self, ip: IpAddr) -> Result<InboundGuard, ConnectionDenied> {
+		let subnet = subnet(ip);
+		let mut inbound = self.inbound.lock();
+
+		let exceeds = |counts: &HashMap<IpAddr, u32>, key, max: Option<u32>| {
+			max.map_or(false, |max| counts.get(key).copied().unwrap_or(0) >= max)
+		};
+		if exceeds(&inbound.per_ip, &ip, self.config.max_inbound_per_ip) {
+			return Err(ConnectionDenied::TooManyFromIp(ip))
+		}
+		if exceeds(&inbound.per_subnet, &subnet, self.config.max_inbound_per_subnet) {
+			return Err(ConnectionDenied::TooManyFromSubnet(ip))
+		}
+
+		*inbound.per_ip.entry(ip).or_default() += 1;
+		*inbound.per_subnet.entry(subnet).or_default() += 1;
+		Ok(InboundGuard { ip, subnet, inbound: self.inbound.clone() })
+	}
+}
+
+/// Accounts for an inbound connection until dropped.
+struct InboundGuard {
+	ip: IpAddr,
+	subnet: IpAddr,
+	inbound: Arc<Mutex<InboundConnections>>,
+}
+
+impl Drop for InboundGuard {
+	fn drop(&mut self) {
+		fn release(counts: &mut HashMap<IpAddr, u32>, key: &IpAddr) {
+			if let Some(count) = counts.get_mut(key) {
+				*count -= 1;
+				if *count == 0 {
+					counts.remove(key);
+				}
+			}
+		}
+
+		let mut inbound = self.inbound.lock();
+		release(&mut inbound.per_ip, &self.ip);
+		release(&mut inbound.per_subnet, &self.subnet);
+	}
+}
+
+/// Connection accepted by an [`IpFilter`].
+#[pin_project]
+struct FilteredConnection<T> {
+	#[pin]
+	inner: T,
+	_guard: Option<InboundGuard>,
+}
+
+impl<T: AsyncRead> AsyncRead for FilteredConnection<T> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+		self.project().inner.poll_read(cx, buf)
+	}
+
+	fn poll_read_vectored(
+		self: Pin<&mut Self>,
+		cx: &mut Context,
+		bufs: &mut [IoSliceMut],
+	) -> Poll<io::Result<usize>> {
+		self.project().inner.poll_read_vectored(cx, bufs)
+	}
+}
+
+impl<T: AsyncWrite> AsyncWrite for FilteredConnection<T> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+		self.project().inner.poll_write(cx, buf)
+	}
+
+	fn poll_write_vectored(
+		self: Pin<&mut Self>,
+		cx: &mut Context,
+		bufs: &[IoSlice],
+	) -> Poll<io::Result<usize>> {
+		self.project().inner.poll_write_vectored(cx, bufs)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+		self.project().inner.poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+		self.project().inner.poll_close(cx)
+	}
+}
+
+/// Returns the IP address of a multiaddress, if it starts with one.
+///
+/// IPv4 addresses mapped to IPv6 are returned as IPv4 addresses.
+fn ip_address(address: &Multiaddr) -> Option<IpAddr> {
+	match address.iter().next()? {
+		Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+		Protocol::Ip6(ip) => match ip.segments() {
+			[0, 0, 0, 0, 0, 0xffff, ..] => {
+				let [.., a, b, c, d] = ip.octets();
+				Some(IpAddr::V4(Ipv4Addr::new(a, b, c, d)))
+			}
+			_ => Some(IpAddr::V6(ip)),
+		},
+		_ => None,
+	}
+}
+
+/// Returns the subnet of an IP address, i.e. the `/24` for IPv4 and the `/64` for IPv6.
+fn subnet(ip: IpAddr) -> IpAddr {
+	match ip {
+		IpAddr::V4(ip) => {
+			let [a, b, c, _] = ip.octets();
+			IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+		}
+		IpAddr::V6(ip) => {
+			let [a, b, c, d, ..] = ip.segments();
+			IpAddr::V6(Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn listener(address: &str) -> ConnectedPoint {
+		ConnectedPoint::Listener {
+			local_addr: "/ip4/127.0.0.1/tcp/30333".parse().unwrap(),
+			send_back_addr: address.parse().unwrap(),
+		}
+	}
+
+	fn dialer(address: &str) -> ConnectedPoint {
+		ConnectedPoint::Dialer { address: address.parse().unwrap() }
+	}
+
+	#[test]
+	fn filters_denied_and_not_allowed_addresses() {
+		let filter = IpFilter::new(IpFilterConfig {
+			allow: vec![IpNetwork::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), 8).unwrap()],
+			deny: vec![IpNetwork::new(IpAddr::V4(Ipv4Addr::new(10, 1, 0, 0)), 16).unwrap()],
+			..Default::default()
+		});
+
+		assert!(filter.check(&listener("/ip4/10.0.0.1/tcp/1000")).is_ok());
+		assert!(filter.check(&dialer("/ip4/10.0.0.1/tcp/30333")).is_ok());
+		assert!(filter.check(&listener("/ip6/::ffff:10.0.0.1/tcp/1000")).is_ok());
+		assert!(filter.check(&listener("/ip4/10.1.0.1/tcp/1000")).is_err());
+		assert!(filter.check(&dialer("/ip4/10.1.0.1/tcp/30333")).is_err());
+		assert!(filter.check(&listener("/ip4/192.168.0.1/tcp/1000")).is_err());
+		assert!(filter.check(&dialer("/dns/example.com/tcp/30333")).is_ok());
+	}
+
+	#[test]
+	fn limits_inbound_connections_per_ip_and_subnet() {
+		let filter = IpFilter::new(IpFilterConfig {
+			max_inbound_per_ip: Some(2),
+			max_inbound_per_subnet: Some(3),
+			..Default::default()
+		});
+
+		let first = filter.check(&listener("/ip4/1.2.3.4/tcp/1000")).unwrap();
+		let _second = filter.check(&listener("/ip4/1.2.3.4/tcp/1001")).unwrap();
+		assert!(matches!(
+			filter.check(&listener("/ip4/1.2.3.4/tcp/1002")),
+			Err(ConnectionDenied::TooManyFromIp(_)),
+		));
+		// Outbound connections aren't limited.
+		assert!(filter.check(&dialer("/ip4/1.2.3.4/tcp/30333")).is_ok());
+
+		let _third = filter.check(&listener("/ip4/1.2.3.5/tcp/1000")).unwrap();
+		assert!(matches!(
+			filter.check(&listener("/ip4/1.2.3.6/tcp/1000")),
+			Err(ConnectionDenied::TooManyFromSubnet(_)),
+		));
+		assert!(filter.check(&listener("/ip4/1.2.4.6/tcp/1000")).is_ok());
+
+		// Closing a connection frees its slot.
+		drop(first);
+		assert!(filter.check(&listener("/ip4/1.2.3.4/tcp/1003")).is_ok());
+	}
+}
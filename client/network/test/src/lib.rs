@@ -30,6 +30,7 @@ use std::{
 use tetsy_libp2p::build_multiaddr;
 use log::trace;
 use tc_network::block_request_handler::{self, BlockRequestHandler};
+use tc_network::state_request_handler::{self, StateRequestHandler};
 use tp_blockchain::{
 	HeaderBackend, Result as ClientResult,
 	well_known_cache_keys::{self, Id as CacheKeyId},
@@ -731,6 +732,12 @@ pub trait TestNetFactory: Sized {
 			protocol_config
 		};
 
+		let state_request_protocol_config = {
+			let (handler, protocol_config) = StateRequestHandler::new(protocol_id.clone(), client.clone());
+			self.spawn_task(handler.run().boxed());
+			protocol_config
+		};
+
 		let network = NetworkWorker::new(tc_network::config::Params {
 			role: Role::Full,
 			executor: None,
@@ -744,6 +751,7 @@ pub trait TestNetFactory: Sized {
 				.unwrap_or_else(|| Box::new(DefaultBlockAnnounceValidator)),
 			metrics_registry: None,
 			block_request_protocol_config,
+			state_request_protocol_config,
 		}).unwrap();
 
 		trace!(target: "test_network", "Peer identifier: {}", network.service().local_peer_id());
@@ -817,6 +825,9 @@ pub trait TestNetFactory: Sized {
 		let block_request_protocol_config = block_request_handler::generate_protocol_config(
 			protocol_id.clone(),
 		);
+		let state_request_protocol_config = state_request_handler::generate_protocol_config(
+			protocol_id.clone(),
+		);
 
 		let network = NetworkWorker::new(tc_network::config::Params {
 			role: Role::Light,
@@ -830,6 +841,7 @@ pub trait TestNetFactory: Sized {
 			block_announce_validator: Box::new(DefaultBlockAnnounceValidator),
 			metrics_registry: None,
 			block_request_protocol_config,
+			state_request_protocol_config,
 		}).unwrap();
 
 		self.mut_peers(|peers| {
@@ -714,7 +714,7 @@ impl BlockAnnounceValidator<Block> for NewBestBlockAnnounceValidator {
 		_: &Header,
 		_: &[u8],
 	) -> Pin<Box<dyn Future<Output = Result<Validation, Box<dyn std::error::Error + Send>>> + Send>> {
-		async { Ok(Validation::Success { is_new_best: true }) }.boxed()
+		async { Ok(Validation::Success { is_new_best: true, score: 0 }) }.boxed()
 	}
 }
 
@@ -754,7 +754,7 @@ impl BlockAnnounceValidator<Block> for DeferredBlockAnnounceValidator {
 	) -> Pin<Box<dyn Future<Output = Result<Validation, Box<dyn std::error::Error + Send>>> + Send>> {
 		async {
 			futures_timer::Delay::new(std::time::Duration::from_millis(500)).await;
-			Ok(Validation::Success { is_new_best: false })
+			Ok(Validation::Success { is_new_best: false, score: 0 })
 		}.boxed()
 	}
 }
@@ -858,7 +858,7 @@ fn block_announce_data_is_propagated() {
 			let correct = data.get(0) == Some(&137);
 			async move {
 				if correct {
-					Ok(Validation::Success { is_new_best: true })
+					Ok(Validation::Success { is_new_best: true, score: 0 })
 				} else {
 					Ok(Validation::Failure { disconnect: false })
 				}
@@ -913,7 +913,7 @@ fn continue_to_sync_after_some_block_announcement_verifications_failed() {
 				if number < 100 {
 					Err(Box::<dyn std::error::Error + Send + Sync>::from(String::from("error")) as Box<_>)
 				} else {
-					Ok(Validation::Success { is_new_best: false })
+					Ok(Validation::Success { is_new_best: false, score: 0 })
 				}
 			}.boxed()
 		}
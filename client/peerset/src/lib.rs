@@ -31,15 +31,19 @@
 //!
 //! In addition, for each, set, the peerset also holds a list of reserved nodes towards which it
 //! will at all time try to maintain a connection with.
+//!
+//! The reputations of the nodes and the nodes explicitly banned can optionally be persisted to
+//! a file, in order to survive restarts.
 
 mod peersstate;
+mod persistence;
 
 use std::{collections::HashSet, collections::VecDeque};
 use futures::prelude::*;
-use log::{debug, error, trace};
+use log::{debug, error, trace, warn};
 use serde_json::json;
-use std::{collections::HashMap, pin::Pin, task::{Context, Poll}, time::Duration};
-use wasm_timer::Instant;
+use std::{collections::HashMap, path::PathBuf, pin::Pin, task::{Context, Poll}, time::Duration};
+use wasm_timer::{Instant, SystemTime, UNIX_EPOCH};
 use tetcore_utils::mpsc::{tracing_unbounded, TracingUnboundedSender, TracingUnboundedReceiver};
 
 pub use tetsy_libp2p::PeerId;
@@ -51,6 +55,8 @@ const DISCONNECT_REPUTATION_CHANGE: i32 = -256;
 /// Amount of time between the moment we disconnect from a node and the moment we remove it from
 /// the list.
 const FORGET_AFTER: Duration = Duration::from_secs(3600);
+/// Interval between two saves of the reputations to [`PeersetConfig::persist_path`].
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Debug)]
 enum Action {
@@ -61,6 +67,8 @@ enum Action {
 	ReportPeer(PeerId, ReputationChange),
 	AddToPeersSet(SetId, PeerId),
 	RemoveFromPeersSet(SetId, PeerId),
+	BanPeer(PeerId, Option<Duration>),
+	UnbanPeer(PeerId),
 }
 
 /// Identifier of a set in the peerset.
@@ -161,6 +169,30 @@ impl PeersetHandle {
 	pub fn remove_from_peers_set(&self, set_id: SetId, peer_id: PeerId) {
 		let _ = self.tx.unbounded_send(Action::RemoveFromPeersSet(set_id, peer_id));
 	}
+
+	/// Bans a peer, for the given duration or until it is unbanned if `None`.
+	///
+	/// The peer is disconnected from all the sets it isn't a reserved peer of, and no connection
+	/// with it is accepted or opened while it is banned.
+	pub fn ban_peer(&self, peer_id: PeerId, duration: Option<Duration>) {
+		let _ = self.tx.unbounded_send(Action::BanPeer(peer_id, duration));
+	}
+
+	/// Lifts the ban of a peer.
+	///
+	/// Has no effect if the peer isn't banned.
+	pub fn unban_peer(&self, peer_id: PeerId) {
+		let _ = self.tx.unbounded_send(Action::UnbanPeer(peer_id));
+	}
+}
+
+/// A peer banned through [`PeersetHandle::ban_peer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannedPeer {
+	/// Identity of the peer.
+	pub peer_id: PeerId,
+	/// When the ban expires, if ever.
+	pub until: Option<SystemTime>,
 }
 
 /// Message that can be sent by the peer set manager (PSM).
@@ -203,6 +235,10 @@ impl From<u64> for IncomingIndex {
 pub struct PeersetConfig {
 	/// List of sets of nodes the peerset manages.
 	pub sets: Vec<SetConfig>,
+
+	/// File to load the reputations and bans from, and to save them to. If `None`, they are
+	/// only kept in memory.
+	pub persist_path: Option<PathBuf>,
 }
 
 /// Configuration for a single set of nodes.
@@ -252,6 +288,12 @@ pub struct Peerset {
 	created: Instant,
 	/// Last time when we updated the reputations of connected nodes.
 	latest_time_update: Instant,
+	/// Banned nodes, with the time when the ban expires, if ever.
+	bans: HashMap<PeerId, Option<SystemTime>>,
+	/// Writer of the file the reputations and bans are persisted to.
+	persistence: Option<persistence::Writer>,
+	/// Last time when we saved the reputations and bans to `persist_path`.
+	latest_save: Instant,
 }
 
 impl Peerset {
@@ -279,15 +321,31 @@ impl Peerset {
 				message_queue: VecDeque::new(),
 				created: now,
 				latest_time_update: now,
+				bans: HashMap::new(),
+				persistence: config.persist_path.and_then(|path| match persistence::Writer::new(path) {
+					Ok(writer) => Some(writer),
+					Err(err) => {
+						warn!(target: "peerset", "Failed to start saving the peerset state: {}", err);
+						None
+					}
+				}),
+				latest_save: now,
 			}
 		};
 
+		peerset.load();
+
 		for (set, set_config) in config.sets.into_iter().enumerate() {
 			for node in set_config.reserved_nodes {
 				peerset.data.add_no_slot_node(set, node);
 			}
 
 			for peer_id in set_config.bootnodes {
+				if peerset.is_banned(&peer_id) {
+					debug!(target: "peerset", "Ignoring banned bootnode: {:?}", peer_id);
+					continue;
+				}
+
 				if let peersstate::Peer::Unknown(entry) = peerset.data.peer(set, &peer_id) {
 					entry.discover();
 				} else {
@@ -300,6 +358,129 @@ impl Peerset {
 		(peerset, handle)
 	}
 
+	/// Loads the reputations and bans from [`PeersetConfig::persist_path`].
+	///
+	/// The reputations decay for the time elapsed since they were saved.
+	fn load(&mut self) {
+		let path = match &self.persistence {
+			Some(writer) => writer.path(),
+			None => return,
+		};
+
+		let persisted = match persistence::PersistedPeers::load(path) {
+			Ok(persisted) => persisted,
+			Err(err) => {
+				warn!(target: "peerset", "Failed to load peerset state from {:?}: {}", path, err);
+				return;
+			}
+		};
+
+		let now = persistence::unix_time();
+		let elapsed = now.saturating_sub(persisted.saved_at);
+		for (peer_id, reputation) in persisted.reputations {
+			self.data.peer_reputation(peer_id).set_reputation(reputation_decay(reputation, elapsed));
+		}
+
+		for (peer_id, until) in persisted.bans {
+			match until {
+				Some(until) if until <= now => {}
+				until => {
+					self.bans.insert(peer_id, until.map(|until| UNIX_EPOCH + Duration::from_secs(until)));
+				}
+			}
+		}
+
+		debug!(target: "peerset", "Loaded {} reputations and {} bans from {:?}",
+			self.data.peers().len(), self.bans.len(), path);
+	}
+
+	/// Saves the reputations and bans to [`PeersetConfig::persist_path`].
+	///
+	/// The file is written in the background.
+	fn save(&mut self) {
+		self.latest_save = Instant::now();
+		self.remove_expired_bans();
+
+		let writer = match &self.persistence {
+			Some(writer) => writer,
+			None => return,
+		};
+
+		let persisted = persistence::PersistedPeers {
+			saved_at: persistence::unix_time(),
+			reputations: self.data.reputations()
+				.filter(|(_, reputation)| *reputation != 0)
+				.map(|(peer_id, reputation)| (peer_id.clone(), reputation))
+				.collect(),
+			bans: self.bans.iter()
+				.map(|(peer_id, until)| (peer_id.clone(), until.and_then(|until|
+					until.duration_since(UNIX_EPOCH).ok().map(|until| until.as_secs())
+				)))
+				.collect(),
+		};
+
+		writer.save(persisted);
+	}
+
+	/// Removes the bans that have expired.
+	fn remove_expired_bans(&mut self) {
+		let now = SystemTime::now();
+		self.bans.retain(|_, until| until.map_or(true, |until| until > now));
+	}
+
+	/// Returns `true` if the given peer is currently banned.
+	fn is_banned(&self, peer_id: &PeerId) -> bool {
+		match self.bans.get(peer_id) {
+			Some(Some(until)) => *until > SystemTime::now(),
+			Some(None) => true,
+			None => false,
+		}
+	}
+
+	fn on_ban_peer(&mut self, peer_id: PeerId, duration: Option<Duration>) {
+		debug!(target: "peerset", "Banning {} for {:?}", peer_id, duration);
+		// A ban whose end can't be represented never expires.
+		let until = duration.and_then(|duration| SystemTime::now().checked_add(duration));
+		self.bans.insert(peer_id.clone(), until);
+
+		for set_index in 0..self.data.num_sets() {
+			// Reserved nodes are kept connected.
+			if self.reserved_nodes[set_index].0.contains(&peer_id) {
+				continue;
+			}
+
+			match self.data.peer(set_index, &peer_id) {
+				peersstate::Peer::Connected(peer) => {
+					peer.disconnect().forget_peer();
+					self.message_queue.push_back(Message::Drop {
+						set_id: SetId(set_index),
+						peer_id: peer_id.clone(),
+					});
+				}
+				peersstate::Peer::NotConnected(peer) => { peer.forget_peer(); }
+				peersstate::Peer::Unknown(_) => {}
+			}
+		}
+
+		self.save();
+		self.alloc_slots();
+	}
+
+	fn on_unban_peer(&mut self, peer_id: PeerId) {
+		if self.bans.remove(&peer_id).is_some() {
+			debug!(target: "peerset", "Unbanning {}", peer_id);
+			self.save();
+		}
+	}
+
+	/// Returns the list of peers currently banned.
+	pub fn banned_peers(&mut self) -> Vec<BannedPeer> {
+		self.remove_expired_bans();
+		self.bans.iter()
+			.map(|(peer_id, until)| BannedPeer { peer_id: peer_id.clone(), until: *until })
+			.collect()
+	}
+
 	fn on_add_reserved_peer(&mut self, set_id: SetId, peer_id: PeerId) {
 		let newly_inserted = self.reserved_nodes[set_id.0].0.insert(peer_id.clone());
 		if !newly_inserted {
@@ -381,6 +562,10 @@ impl Peerset {
 	///
 	/// > **Note**: This has the same effect as [`PeersetHandle::add_to_peers_set`].
 	pub fn add_to_peers_set(&mut self, set_id: SetId, peer_id: PeerId) {
+		if self.is_banned(&peer_id) {
+			return;
+		}
+
 		if let peersstate::Peer::Unknown(entry) = self.data.peer(set_id.0, &peer_id) {
 			entry.discover();
 			self.alloc_slots();
@@ -451,23 +636,8 @@ impl Peerset {
 		};
 
 		// For each elapsed second, move the node reputation towards zero.
-		// If we multiply each second the reputation by `k` (where `k` is between 0 and 1), it
-		// takes `ln(0.5) / ln(k)` seconds to reduce the reputation by half. Use this formula to
-		// empirically determine a value of `k` that looks correct.
 		for _ in 0..secs_diff {
 			for peer_id in self.data.peers().cloned().collect::<Vec<_>>() {
-				// We use `k = 0.98`, so we divide by `50`. With that value, it takes 34.3 seconds
-				// to reduce the reputation by half.
-				fn reput_tick(reput: i32) -> i32 {
-					let mut diff = reput / 50;
-					if diff == 0 && reput < 0 {
-						diff = -1;
-					} else if diff == 0 && reput > 0 {
-						diff = 1;
-					}
-					reput.saturating_sub(diff)
-				}
-
 				let mut peer_reputation = self.data.peer_reputation(peer_id.clone());
 
 				let before = peer_reputation.reputation();
@@ -498,6 +668,10 @@ impl Peerset {
 				}
 			}
 		}
+
+		if self.persistence.is_some() && self.latest_save + SAVE_INTERVAL <= now {
+			self.save();
+		}
 	}
 
 	/// Try to fill available out slots with nodes.
@@ -579,6 +753,12 @@ impl Peerset {
 			}
 		}
 
+		if self.is_banned(&peer_id) && !self.reserved_nodes[set_id.0].0.contains(&peer_id) {
+			trace!(target: "peerset", "Rejecting banned {:?}", peer_id);
+			self.message_queue.push_back(Message::Reject(index));
+			return;
+		}
+
 		let not_connected = match self.data.peer(set_id.0, &peer_id) {
 			// If we're already connected, don't answer, as the docs mention.
 			peersstate::Peer::Connected(_) => return,
@@ -703,11 +883,49 @@ impl Stream for Peerset {
 					self.add_to_peers_set(sets_name, peer_id),
 				Action::RemoveFromPeersSet(sets_name, peer_id) =>
 					self.on_remove_from_peers_set(sets_name, peer_id),
+				Action::BanPeer(peer_id, duration) =>
+					self.on_ban_peer(peer_id, duration),
+				Action::UnbanPeer(peer_id) =>
+					self.on_unban_peer(peer_id),
 			}
 		}
 	}
 }
 
+impl Drop for Peerset {
+	fn drop(&mut self) {
+		if self.persistence.is_some() {
+			self.save();
+		}
+	}
+}
+
+/// Moves the reputation one second towards zero.
+///
+/// If we multiply each second the reputation by `k` (where `k` is between 0 and 1), it takes
+/// `ln(0.5) / ln(k)` seconds to reduce the reputation by half. We use `k = 0.98`, so we divide
+/// by `50`. With that value, it takes 34.3 seconds to reduce the reputation by half.
+fn reput_tick(reput: i32) -> i32 {
+	let mut diff = reput / 50;
+	if diff == 0 && reput < 0 {
+		diff = -1;
+	} else if diff == 0 && reput > 0 {
+		diff = 1;
+	}
+	reput.saturating_sub(diff)
+}
+
+/// Moves the reputation towards zero for the given number of seconds.
+fn reputation_decay(mut reput: i32, secs: u64) -> i32 {
+	for _ in 0..secs {
+		if reput == 0 {
+			break;
+		}
+		reput = reput_tick(reput);
+	}
+	reput
+}
+
 /// Reason for calling [`Peerset::dropped`].
 pub enum DropReason {
 	/// Substream or connection has been closed for an unknown reason.
@@ -723,8 +941,11 @@ pub enum DropReason {
 mod tests {
 	use tetsy_libp2p::PeerId;
 	use futures::prelude::*;
-	use super::{PeersetConfig, Peerset, Message, IncomingIndex, ReputationChange, SetConfig, SetId, BANNED_THRESHOLD};
-	use std::{pin::Pin, task::Poll, thread, time::Duration};
+	use super::{
+		PeersetConfig, Peerset, Message, IncomingIndex, ReputationChange, SetConfig, SetId,
+		BANNED_THRESHOLD, reputation_decay,
+	};
+	use std::{path::PathBuf, pin::Pin, task::Poll, thread, time::Duration};
 
 	fn assert_messages(mut peerset: Peerset, messages: Vec<Message>) -> Peerset {
 		for expected_message in messages {
@@ -754,6 +975,7 @@ mod tests {
 				reserved_nodes: Default::default(),
 				reserved_only: true,
 			}],
+			persist_path: None,
 		};
 
 		let (peerset, handle) = Peerset::from_config(config);
@@ -784,6 +1006,7 @@ mod tests {
 				reserved_nodes: Default::default(),
 				reserved_only: false,
 			}],
+			persist_path: None,
 		};
 
 		let (mut peerset, _handle) = Peerset::from_config(config);
@@ -812,6 +1035,7 @@ mod tests {
 				reserved_nodes: Default::default(),
 				reserved_only: true,
 			}],
+			persist_path: None,
 		};
 
 		let (mut peerset, _) = Peerset::from_config(config);
@@ -835,6 +1059,7 @@ mod tests {
 				reserved_nodes: Default::default(),
 				reserved_only: false,
 			}],
+			persist_path: None,
 		};
 
 		let (mut peerset, _handle) = Peerset::from_config(config);
@@ -858,6 +1083,7 @@ mod tests {
 				reserved_nodes: Default::default(),
 				reserved_only: false,
 			}],
+			persist_path: None,
 		});
 
 		// We ban a node by setting its reputation under the threshold.
@@ -890,4 +1116,104 @@ mod tests {
 
 		futures::executor::block_on(fut);
 	}
+
+	fn banning_config(persist_path: Option<PathBuf>) -> PeersetConfig {
+		PeersetConfig {
+			sets: vec![SetConfig {
+				in_peers: 25,
+				out_peers: 25,
+				bootnodes: vec![],
+				reserved_nodes: Default::default(),
+				reserved_only: false,
+			}],
+			persist_path,
+		}
+	}
+
+	#[test]
+	fn test_peerset_ban_and_unban() {
+		let (mut peerset, handle) = Peerset::from_config(banning_config(None));
+		let peer_id = PeerId::random();
+		handle.ban_peer(peer_id.clone(), None);
+
+		let fut = futures::future::poll_fn(move |cx| {
+			// We need one polling for the message to be processed.
+			assert_eq!(Stream::poll_next(Pin::new(&mut peerset), cx), Poll::Pending);
+			assert_eq!(peerset.banned_peers().len(), 1);
+
+			peerset.incoming(SetId::from(0), peer_id.clone(), IncomingIndex(1));
+			assert_eq!(
+				Stream::poll_next(Pin::new(&mut peerset), cx),
+				Poll::Ready(Some(Message::Reject(IncomingIndex(1)))),
+			);
+
+			handle.unban_peer(peer_id.clone());
+			assert_eq!(Stream::poll_next(Pin::new(&mut peerset), cx), Poll::Pending);
+			assert!(peerset.banned_peers().is_empty());
+
+			peerset.incoming(SetId::from(0), peer_id.clone(), IncomingIndex(2));
+			assert_eq!(
+				Stream::poll_next(Pin::new(&mut peerset), cx),
+				Poll::Ready(Some(Message::Accept(IncomingIndex(2)))),
+			);
+
+			Poll::Ready(())
+		});
+
+		futures::executor::block_on(fut);
+	}
+
+	#[test]
+	fn test_peerset_ban_overflowing_duration_is_permanent() {
+		let (mut peerset, handle) = Peerset::from_config(banning_config(None));
+		handle.ban_peer(PeerId::random(), Some(Duration::from_secs(u64::max_value())));
+
+		futures::executor::block_on(futures::future::poll_fn(|cx| {
+			assert_eq!(Stream::poll_next(Pin::new(&mut peerset), cx), Poll::Pending);
+			Poll::Ready(())
+		}));
+		let banned_peers = peerset.banned_peers();
+		assert_eq!(banned_peers.len(), 1);
+		assert!(banned_peers[0].until.is_none());
+	}
+
+	#[test]
+	fn test_peerset_persists_bans_and_reputations() {
+		let dir = std::env::temp_dir().join(format!("peerset-{}", PeerId::random()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("peerset.json");
+
+		let banned = PeerId::random();
+		let reported = PeerId::random();
+		{
+			let (mut peerset, handle) = Peerset::from_config(banning_config(Some(path.clone())));
+			handle.ban_peer(banned.clone(), Some(Duration::from_secs(3600)));
+			handle.report_peer(reported.clone(), ReputationChange::new_fatal(""));
+			futures::executor::block_on(futures::future::poll_fn(|cx| {
+				assert_eq!(Stream::poll_next(Pin::new(&mut peerset), cx), Poll::Pending);
+				Poll::Ready(())
+			}));
+		}
+
+		let (mut peerset, _) = Peerset::from_config(banning_config(Some(path)));
+		let banned_peers = peerset.banned_peers();
+		assert_eq!(banned_peers.len(), 1);
+		assert_eq!(banned_peers[0].peer_id, banned);
+		assert!(banned_peers[0].until.is_some());
+
+		// The reported node is still banned by its reputation after the restart.
+		peerset.incoming(SetId::from(0), reported, IncomingIndex(1));
+		assert_messages(peerset, vec![Message::Reject(IncomingIndex(1))]);
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn reputation_decays_to_zero() {
+		assert_eq!(reputation_decay(0, 10), 0);
+		assert_eq!(reputation_decay(100, 1), 98);
+		assert_eq!(reputation_decay(-100, 1), -98);
+		assert_eq!(reputation_decay(i32::min_value(), 3600), 0);
+		assert_eq!(reputation_decay(i32::max_value(), u64::max_value()), 0);
+	}
 }
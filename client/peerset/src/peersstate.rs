@@ -219,6 +219,11 @@ impl PeersState {
 		self.nodes.keys()
 	}
 
+	/// Returns the list of all the peers we know of, with their reputation.
+	pub fn reputations(&self) -> impl Iterator<Item = (&PeerId, i32)> {
+		self.nodes.iter().map(|(peer_id, node)| (peer_id, node.reputation))
+	}
+
 	/// Returns the list of peers we are connected to in the context of a specific set.
 	///
 	/// # Panic
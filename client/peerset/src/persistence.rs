@@ -0,0 +1,218 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Storage of the reputations and bans of the peerset across restarts.
+//!
+//! The state is stored as a small JSON file of the form:
+//!
+//! ```json
+//! {
+//!   "saved_at": 1610000000,
+//!   "reputations": { "12D3KooW...": -1024 },
+//!   "bans": { "12D3KooW...": 1610003600, "12D3KooX...": null }
+//! }
+//! ```
+//!
+//! where timestamps are UNIX times in seconds, and a `null` ban never expires.
+//!
+//! The file is written by a background thread, see [`Writer`].
+
+use tetsy_libp2p::PeerId;
+use log::warn;
+use serde_json::{json, Value};
+use std::{
+	collections::HashMap, fs, io, path::{Path, PathBuf}, sync::mpsc, thread,
+};
+use wasm_timer::{SystemTime, UNIX_EPOCH};
+
+/// Reputations and bans of the peerset, as stored on disk.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PersistedPeers {
+	/// UNIX time in seconds when the state was saved.
+	pub saved_at: u64,
+	/// Non-zero reputations of the peers.
+	pub reputations: HashMap<PeerId, i32>,
+	/// Banned peers, with the UNIX time in seconds when the ban expires, if ever.
+	pub bans: HashMap<PeerId, Option<u64>>,
+}
+
+impl PersistedPeers {
+	/// Loads the state from the given file.
+	///
+	/// Returns an empty state if the file doesn't exist. Invalid entries are skipped.
+	pub fn load(path: &Path) -> io::Result<Self> {
+		let content = match fs::read(path) {
+			Ok(content) => content,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+			Err(err) => return Err(err),
+		};
+		let value: Value = serde_json::from_slice(&content)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+		let entries = |field: &str| value.get(field)
+			.and_then(Value::as_object)
+			.into_iter()
+			.flat_map(|entries| entries.iter())
+			.filter_map(|(peer_id, value)| match peer_id.parse::<PeerId>() {
+				Ok(peer_id) => Some((peer_id, value)),
+				Err(_) => {
+					warn!(target: "peerset", "Invalid peer id in persisted peerset: {}", peer_id);
+					None
+				}
+			});
+
+		Ok(PersistedPeers {
+			saved_at: value.get("saved_at").and_then(Value::as_u64).unwrap_or_default(),
+			reputations: entries("reputations")
+				.filter_map(|(peer_id, reputation)| {
+					let reputation = reputation.as_i64()?;
+					Some((peer_id, reputation.max(i32::min_value().into()).min(i32::max_value().into()) as i32))
+				})
+				.collect(),
+			bans: entries("bans")
+				.filter(|(_, until)| until.is_null() || until.is_u64())
+				.map(|(peer_id, until)| (peer_id, until.as_u64()))
+				.collect(),
+		})
+	}
+
+	/// Saves the state to the given file.
+	///
+	/// The state is first written to a temporary file, which then replaces the given one, so
+	/// that an interrupted write doesn't lose the previous state.
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let value = json!({
+			"saved_at": self.saved_at,
+			"reputations": self.reputations.iter()
+				.map(|(peer_id, reputation)| (peer_id.to_base58(), json!(reputation)))
+				.collect::<serde_json::Map<_, _>>(),
+			"bans": self.bans.iter()
+				.map(|(peer_id, until)| (peer_id.to_base58(), json!(until)))
+				.collect::<serde_json::Map<_, _>>(),
+		});
+
+		let tmp_path = path.with_extension("tmp");
+		fs::write(&tmp_path, value.to_string())?;
+		fs::rename(tmp_path, path)
+	}
+}
+
+/// Saves the state to a file from a background thread, so that saving never blocks the caller.
+///
+/// If several states are queued while one is being written, only the latest one is written
+/// next. The queued state is written before the writer is dropped.
+pub struct Writer {
+	path: PathBuf,
+	sender: Option<mpsc::Sender<PersistedPeers>>,
+	thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Writer {
+	/// Starts the thread saving the state to the given file.
+	pub fn new(path: PathBuf) -> io::Result<Self> {
+		let (sender, receiver) = mpsc::channel::<PersistedPeers>();
+		let thread_path = path.clone();
+		let thread = thread::Builder::new()
+			.name("peerset-persistence".into())
+			.spawn(move || {
+				while let Ok(mut peers) = receiver.recv() {
+					// Only the latest state matters.
+					while let Ok(newer) = receiver.try_recv() {
+						peers = newer;
+					}
+					if let Err(err) = peers.save(&thread_path) {
+						warn!(target: "peerset", "Failed to save peerset state to {:?}: {}", thread_path, err);
+					}
+				}
+			})?;
+
+		Ok(Writer { path, sender: Some(sender), thread: Some(thread) })
+	}
+
+	/// The file the state is saved to.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Queues the given state to be saved.
+	pub fn save(&self, peers: PersistedPeers) {
+		if let Some(sender) = &self.sender {
+			let _ = sender.send(peers);
+		}
+	}
+}
+
+impl Drop for Writer {
+	fn drop(&mut self) {
+		// Closing the channel stops the thread once the queued state is written.
+		self.sender = None;
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+	}
+}
+
+/// Returns the current UNIX time in seconds.
+pub fn unix_time() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{PersistedPeers, Writer};
+	use tetsy_libp2p::PeerId;
+
+	#[test]
+	fn save_and_load() {
+		let dir = std::env::temp_dir().join(format!("peerset-persistence-{}", PeerId::random()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("peerset.json");
+
+		assert_eq!(PersistedPeers::load(&path).unwrap(), PersistedPeers::default());
+
+		let mut peers = PersistedPeers::default();
+		peers.saved_at = 1_000;
+		peers.reputations.insert(PeerId::random(), -1024);
+		peers.reputations.insert(PeerId::random(), i32::min_value());
+		peers.bans.insert(PeerId::random(), None);
+		peers.bans.insert(PeerId::random(), Some(5_000));
+		peers.save(&path).unwrap();
+
+		assert_eq!(PersistedPeers::load(&path).unwrap(), peers);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn writer_saves_latest_state_before_drop() {
+		let dir = std::env::temp_dir().join(format!("peerset-persistence-{}", PeerId::random()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("peerset.json");
+
+		let writer = Writer::new(path.clone()).unwrap();
+		let mut peers = PersistedPeers::default();
+		for saved_at in 0..10 {
+			peers.saved_at = saved_at;
+			peers.bans.insert(PeerId::random(), Some(saved_at));
+			writer.save(peers.clone());
+		}
+		drop(writer);
+
+		assert_eq!(PersistedPeers::load(&path).unwrap(), peers);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,52 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2019-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+//! Batch submission types for author RPC module.
+
+use serde::{Serialize, Deserialize};
+use tetsy_jsonrpc_core as rpc;
+use tp_transaction_pool::TransactionStatus;
+
+/// Result of submitting one extrinsic of a batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmitResult<Hash> {
+	/// The extrinsic was imported to the pool under this hash.
+	Hash(Hash),
+	/// The extrinsic was rejected.
+	Error(rpc::Error),
+}
+
+/// Notification about one extrinsic of a watched batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchUpdate<Hash, BlockHash> {
+	/// The extrinsic was rejected and will not be watched.
+	Rejected {
+		/// Position of the extrinsic in the batch.
+		index: usize,
+		/// Reason of the rejection.
+		error: rpc::Error,
+	},
+	/// The status of the extrinsic changed.
+	Status {
+		/// Position of the extrinsic in the batch.
+		index: usize,
+		/// New status of the extrinsic.
+		status: TransactionStatus<Hash, BlockHash>,
+	},
+}
@@ -99,6 +99,10 @@ const POOL_CYCLE_DETECTED: i64 = POOL_INVALID_TX + 5;
 const POOL_IMMEDIATELY_DROPPED: i64 = POOL_INVALID_TX + 6;
 /// The key type crypto is not known.
 const UNSUPPORTED_KEY_TYPE: i64 = POOL_INVALID_TX + 7;
+/// The transaction was not included to the pool because its sender exceeded the quota.
+const POOL_SENDER_QUOTA_EXCEEDED: i64 = POOL_INVALID_TX + 8;
+/// The transaction would not replace the given one.
+const POOL_NO_REPLACEMENT: i64 = POOL_INVALID_TX + 9;
 
 impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
@@ -158,6 +162,16 @@ impl From<Error> for rpc::Error {
 				message: "Immediately Dropped".into(),
 				data: Some("The transaction couldn't enter the pool because of the limit".into()),
 			},
+			Error::Pool(PoolError::SenderQuotaExceeded) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(POOL_SENDER_QUOTA_EXCEEDED),
+				message: "Sender Quota Exceeded".into(),
+				data: Some("The transaction couldn't enter the pool because of the sender quota".into()),
+			},
+			Error::Pool(PoolError::NoReplacement) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(POOL_NO_REPLACEMENT),
+				message: "No Replacement".into(),
+				data: Some("The transaction would not replace the given ready transaction".into()),
+			},
 			Error::UnsupportedKeyType => rpc::Error {
 				code: rpc::ErrorCode::ServerError(UNSUPPORTED_KEY_TYPE),
 				message: "Unknown key type crypto" .into(),
@@ -18,13 +18,15 @@
 
 //! Tetcore block-author/full-node API.
 
+pub mod batch;
 pub mod error;
 pub mod hash;
+pub mod pool;
 
 use tetsy_jsonrpc_derive::rpc;
 use tetsy_jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use tet_core::Bytes;
-use tp_transaction_pool::TransactionStatus;
+use tp_transaction_pool::{PoolEvent, TransactionStatus};
 use self::error::{FutureResult, Result};
 
 pub use self::gen_client::Client as AuthorClient;
@@ -39,6 +41,20 @@ pub trait AuthorApi<Hash, BlockHash> {
 	#[rpc(name = "author_submitExtrinsic")]
 	fn submit_extrinsic(&self, extrinsic: Bytes) -> FutureResult<Hash>;
 
+	/// Submit a batch of hex-encoded extrinsics for inclusion in block.
+	///
+	/// The extrinsics are imported in the given order, so they may depend on each other.
+	/// Returns the hash or the error of each extrinsic, in the same order.
+	#[rpc(name = "author_submitExtrinsics")]
+	fn submit_extrinsics(&self, extrinsics: Vec<Bytes>) -> FutureResult<Vec<batch::SubmitResult<Hash>>>;
+
+	/// Submit hex-encoded extrinsic replacing the pending extrinsic with the given hash.
+	///
+	/// The extrinsic is refused unless it provides the same tags as the replaced one
+	/// and has a high enough priority to replace it.
+	#[rpc(name = "author_replaceExtrinsic")]
+	fn replace_extrinsic(&self, extrinsic: Bytes, replaces: Hash) -> FutureResult<Hash>;
+
 	/// Insert a key into the keystore.
 	#[rpc(name = "author_insertKey")]
 	fn insert_key(
@@ -70,6 +86,19 @@ pub trait AuthorApi<Hash, BlockHash> {
 	#[rpc(name = "author_pendingExtrinsics")]
 	fn pending_extrinsics(&self) -> Result<Vec<Bytes>>;
 
+	/// Returns the number and size of transactions in the ready and future queues.
+	///
+	/// This method is considered unsafe.
+	#[rpc(name = "author_poolStatus")]
+	fn pool_status(&self) -> Result<pool::PoolStatus>;
+
+	/// Returns the ready and future transactions in the pool along with their tags.
+	///
+	/// Future transactions list the required tags they are still waiting for.
+	/// This method is considered unsafe.
+	#[rpc(name = "author_poolContent")]
+	fn pool_content(&self, filter: Option<pool::PoolFilter>) -> Result<pool::PoolContent<Hash>>;
+
 	/// Remove given extrinsic from the pool and temporarily ban it to prevent reimporting.
 	#[rpc(name = "author_removeExtrinsic")]
 	fn remove_extrinsic(&self,
@@ -101,4 +130,56 @@ pub trait AuthorApi<Hash, BlockHash> {
 		metadata: Option<Self::Metadata>,
 		id: SubscriptionId
 	) -> Result<bool>;
+
+	/// Submit a batch of extrinsics to watch.
+	///
+	/// Notifications carry the position of the extrinsic in the batch. Extrinsics that
+	/// could not be imported are reported once as rejected.
+	#[pubsub(
+		subscription = "author_extrinsicsUpdate",
+		subscribe,
+		name = "author_submitAndWatchExtrinsics"
+	)]
+	fn watch_extrinsics(&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<batch::BatchUpdate<Hash, BlockHash>>,
+		extrinsics: Vec<Bytes>
+	);
+
+	/// Unsubscribe from batch extrinsic watching.
+	#[pubsub(
+		subscription = "author_extrinsicsUpdate",
+		unsubscribe,
+		name = "author_unwatchExtrinsics"
+	)]
+	fn unwatch_extrinsics(&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId
+	) -> Result<bool>;
+
+	/// Subscribe to events about all transactions in the pool.
+	///
+	/// Streams imports to the ready and future queues, promotions, replacements, drops,
+	/// invalidations, bans and prunes of every transaction, not only the ones submitted
+	/// by the subscriber.
+	#[pubsub(
+		subscription = "author_poolEvent",
+		subscribe,
+		name = "author_subscribePoolEvents"
+	)]
+	fn subscribe_pool_events(&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<PoolEvent<Hash, BlockHash>>,
+	);
+
+	/// Unsubscribe from pool events.
+	#[pubsub(
+		subscription = "author_poolEvent",
+		unsubscribe,
+		name = "author_unsubscribePoolEvents"
+	)]
+	fn unsubscribe_pool_events(&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId
+	) -> Result<bool>;
 }
@@ -0,0 +1,99 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2019-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+//! Transaction pool inspection types for author RPC module.
+
+use tet_core::Bytes;
+use serde::{Serialize, Deserialize};
+
+/// Transaction pool queue sizes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStatus {
+	/// Number of transactions in the ready queue.
+	pub ready: usize,
+	/// Sum of bytes of ready transaction encodings.
+	pub ready_bytes: usize,
+	/// Number of transactions in the future queue.
+	pub future: usize,
+	/// Sum of bytes of future transaction encodings.
+	pub future_bytes: usize,
+}
+
+/// Filter for the transactions returned by `author_poolContent`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolFilter {
+	/// Only return transactions that require or provide this tag.
+	pub tag: Option<Bytes>,
+	/// Only return transactions with a required or provided tag starting with these bytes.
+	///
+	/// Runtimes usually tag transactions with the encoded `(sender, nonce)`, so the encoded
+	/// sender account selects its transactions.
+	pub sender: Option<Bytes>,
+}
+
+impl PoolFilter {
+	/// Returns true if a transaction with given tags passes the filter.
+	pub fn matches(&self, requires: &[Vec<u8>], provides: &[Vec<u8>]) -> bool {
+		let mut tags = requires.iter().chain(provides);
+		let tag = self.tag.as_ref().map_or(true, |tag| tags.clone().any(|t| t[..] == tag[..]));
+		let sender = self.sender.as_ref().map_or(true, |sender| tags.any(|t| t.starts_with(&sender[..])));
+		tag && sender
+	}
+}
+
+/// Transaction in the pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolTransaction<Hash> {
+	/// Hash of the extrinsic.
+	pub hash: Hash,
+	/// Encoded extrinsic.
+	pub extrinsic: Bytes,
+	/// Priority of the transaction.
+	pub priority: u64,
+	/// Number of blocks the transaction is valid for after it was validated.
+	pub longevity: u64,
+	/// Tags required by the transaction.
+	pub requires: Vec<Bytes>,
+	/// Tags provided by the transaction.
+	pub provides: Vec<Bytes>,
+	/// Whether the transaction is propagated to other peers.
+	pub propagate: bool,
+}
+
+/// Transaction waiting in the future queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FutureTransaction<Hash> {
+	/// The transaction.
+	#[serde(flatten)]
+	pub transaction: PoolTransaction<Hash>,
+	/// Required tags that are not provided by any ready transaction yet.
+	pub missing: Vec<Bytes>,
+}
+
+/// Content of the transaction pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolContent<Hash> {
+	/// Ready transactions, ordered by priority.
+	pub ready: Vec<PoolTransaction<Hash>>,
+	/// Transactions waiting for the tags they are missing.
+	pub future: Vec<FutureTransaction<Hash>>,
+}
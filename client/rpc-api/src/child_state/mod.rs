@@ -18,9 +18,12 @@
 
 //! Tetcore state API.
 
+use tetsy_jsonrpc_core::Result as RpcResult;
 use tetsy_jsonrpc_derive::rpc;
+use tetsy_jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use tet_core::storage::{StorageKey, PrefixedStorageKey, StorageData};
 use crate::state::error::FutureResult;
+use crate::state::{ReadProof, StoragePairs};
 
 pub use self::gen_client::Client as ChildStateClient;
 
@@ -42,6 +45,19 @@ pub trait ChildStateApi<Hash> {
 		hash: Option<Hash>
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns the keys with prefix from a child storage with pagination support.
+	/// Up to `count` keys will be returned.
+	/// If `start_key` is passed, return next keys in storage in lexicographic order.
+	#[rpc(name = "childstate_getKeysPaged", alias("childstate_getKeysPagedAt"))]
+	fn storage_keys_paged(
+		&self,
+		child_storage_key: PrefixedStorageKey,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<StorageKey>>;
+
 	/// Returns a child storage entry at a specific block's state.
 	#[rpc(name = "childstate_getStorage")]
 	fn storage(
@@ -68,4 +84,43 @@ pub trait ChildStateApi<Hash> {
 		key: StorageKey,
 		hash: Option<Hash>
 	) -> FutureResult<Option<u64>>;
+
+	/// Returns proof of child storage entries at a specific block's state.
+	///
+	/// The proof also covers the path from the state root to the child trie root.
+	#[rpc(name = "childstate_getReadProof")]
+	fn read_child_proof(
+		&self,
+		child_storage_key: PrefixedStorageKey,
+		keys: Vec<StorageKey>,
+		hash: Option<Hash>
+	) -> FutureResult<ReadProof<Hash>>;
+	/// Stream the key-value pairs with the given prefix from a child storage in batches of at
+	/// most `batch_size` pairs, leave the prefix empty to get all the pairs.
+	///
+	/// The subscription ends after the batch marked as complete.
+	#[pubsub(
+		subscription = "childstate_storagePairs",
+		subscribe,
+		name = "childstate_subscribeStoragePairs"
+	)]
+	fn subscribe_storage_pairs(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<StoragePairs<Hash>>,
+		child_storage_key: PrefixedStorageKey,
+		prefix: StorageKey,
+		batch_size: u32,
+		hash: Option<Hash>,
+	);
+
+	/// Unsubscribe from child storage pairs subscription
+	#[pubsub(
+		subscription = "childstate_storagePairs",
+		unsubscribe,
+		name = "childstate_unsubscribeStoragePairs"
+	)]
+	fn unsubscribe_storage_pairs(
+		&self, metadata: Option<Self::Metadata>, id: SubscriptionId
+	) -> RpcResult<bool>;
 }
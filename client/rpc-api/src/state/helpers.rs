@@ -19,6 +19,7 @@
 //! Tetcore state API helpers.
 
 use tet_core::Bytes;
+use tet_core::storage::{StorageKey, StorageData};
 use serde::{Serialize, Deserialize};
 
 /// ReadProof struct returned by the RPC
@@ -30,3 +31,85 @@ pub struct ReadProof<Hash> {
 	/// A proof used to prove that storage entries are included in the storage trie
 	pub proof: Vec<Bytes>,
 }
+
+/// A batch of key-value pairs sent by the storage pairs subscriptions.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoragePairs<Hash> {
+	/// Block hash of the state the pairs are read from
+	pub at: Hash,
+	/// Key-value pairs, in lexicographic key order
+	pub pairs: Vec<(StorageKey, StorageData)>,
+	/// Whether this is the last batch of the subscription
+	pub complete: bool,
+}
+
+/// A storage entry whose value differs between two blocks.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDiffEntry {
+	/// Storage key
+	pub key: StorageKey,
+	/// Value at the first block, `None` if the key did not exist
+	pub from: Option<StorageData>,
+	/// Value at the second block, `None` if the key was removed
+	pub to: Option<StorageData>,
+}
+
+/// Spans and events recorded while re-executing a block, returned by `state_traceBlock`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTrace<Hash> {
+	/// Hash of the traced block
+	pub block_hash: Hash,
+	/// Hash of the parent block, whose state the block was executed on
+	pub parent_hash: Hash,
+	/// Targets the spans and events were filtered with
+	pub targets: String,
+	/// Traces of the block initialization
+	pub initialize: Traces,
+	/// Traces of each extrinsic, in block order
+	pub extrinsics: Vec<Traces>,
+	/// Traces of the block finalization
+	pub finalize: Traces,
+}
+
+/// Spans and events recorded during one step of a block execution.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Traces {
+	/// Spans, in the order they were exited
+	pub spans: Vec<TraceSpan>,
+	/// Events, in the order they were emitted
+	pub events: Vec<TraceEvent>,
+}
+
+/// A span recorded by `state_traceBlock`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSpan {
+	/// Id of the span, unique while the span is alive
+	pub id: u64,
+	/// Id of the parent span, if any
+	pub parent_id: Option<u64>,
+	/// Name of the span
+	pub name: String,
+	/// Target of the span, typically the module
+	pub target: String,
+	/// Time spent inside of the span, in nanoseconds
+	pub time: u64,
+	/// Values recorded to the span
+	pub values: serde_json::Map<String, serde_json::Value>,
+}
+
+/// An event recorded by `state_traceBlock`, storage accesses are events of the `state` target.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEvent {
+	/// Target of the event
+	pub target: String,
+	/// Id of the span the event was emitted in, if any
+	pub parent_id: Option<u64>,
+	/// Values of the event
+	pub values: serde_json::Map<String, serde_json::Value>,
+}
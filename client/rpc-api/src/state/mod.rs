@@ -30,7 +30,9 @@ use tp_version::RuntimeVersion;
 use self::error::FutureResult;
 
 pub use self::gen_client::Client as StateClient;
-pub use self::helpers::ReadProof;
+pub use self::helpers::{
+	ReadProof, StoragePairs, StorageDiffEntry, BlockTrace, Traces, TraceSpan, TraceEvent,
+};
 
 /// Tetcore state API
 #[rpc]
@@ -104,8 +106,45 @@ pub trait StateApi<Hash> {
 	) -> FutureResult<Vec<StorageChangeSet<Hash>>>;
 
 	/// Returns proof of storage entries at a specific block's state.
+	///
+	/// With `compact` set, the proof nodes are compact encoded in trie traversal order and the
+	/// child hashes are omitted, see `tp_trie::CompactProof`.
 	#[rpc(name = "state_getReadProof")]
-	fn read_proof(&self, keys: Vec<StorageKey>, hash: Option<Hash>) -> FutureResult<ReadProof<Hash>>;
+	fn read_proof(
+		&self,
+		keys: Vec<StorageKey>,
+		hash: Option<Hash>,
+		compact: Option<bool>,
+	) -> FutureResult<ReadProof<Hash>>;
+
+	/// Returns every storage entry under `prefix` whose value differs between the states of
+	/// the `from` and `to` blocks, in lexicographic key order.
+	///
+	/// Unlike `state_queryStorage` this does not need the keys in advance, the two state tries
+	/// are compared directly. Both states must still be available, i.e. not pruned.
+	#[rpc(name = "state_traceStorageDiff")]
+	fn trace_storage_diff(
+		&self,
+		from: Hash,
+		to: Hash,
+		prefix: StorageKey,
+	) -> FutureResult<Vec<StorageDiffEntry>>;
+
+	/// Re-executes a block on the state of its parent and returns the spans and events
+	/// recorded along the way, grouped by extrinsic.
+	///
+	/// `targets` is a comma separated list of targets with an optional level, e.g.
+	/// `"noble=debug,state"`, and defaults to `"noble,fabric,state"`. Storage accesses are
+	/// reported as events of the `state` target and can be narrowed down to the keys starting
+	/// with one of `storage_keys`. Runtime spans are only available if the runtime was built
+	/// with the `with-tracing` feature.
+	#[rpc(name = "state_traceBlock")]
+	fn trace_block(
+		&self,
+		block: Hash,
+		targets: Option<String>,
+		storage_keys: Option<Vec<StorageKey>>,
+	) -> FutureResult<BlockTrace<Hash>>;
 
 	/// New runtime version subscription
 	#[pubsub(
@@ -136,4 +175,23 @@ pub trait StateApi<Hash> {
 	fn unsubscribe_storage(
 		&self, metadata: Option<Self::Metadata>, id: SubscriptionId
 	) -> RpcResult<bool>;
+	/// Stream the key-value pairs with the given prefix in batches of at most `batch_size`
+	/// pairs, leave the prefix empty to get all the pairs.
+	///
+	/// The subscription ends after the batch marked as complete.
+	#[pubsub(subscription = "state_storagePairs", subscribe, name = "state_subscribeStoragePairs")]
+	fn subscribe_storage_pairs(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<StoragePairs<Hash>>,
+		prefix: StorageKey,
+		batch_size: u32,
+		hash: Option<Hash>,
+	);
+
+	/// Unsubscribe from storage pairs subscription
+	#[pubsub(subscription = "state_storagePairs", unsubscribe, name = "state_unsubscribeStoragePairs")]
+	fn unsubscribe_storage_pairs(
+		&self, metadata: Option<Self::Metadata>, id: SubscriptionId
+	) -> RpcResult<bool>;
 }
@@ -71,6 +71,32 @@ pub struct PeerInfo<Hash, Number> {
 	pub best_hash: Hash,
 	/// Peer best block number
 	pub best_number: Number,
+	/// Bytes exchanged with the peer on each notifications and request-response protocol
+	#[serde(default = "Default::default", skip_serializing_if = "Vec::is_empty")]
+	pub protocols: Vec<ProtocolTraffic>,
+}
+
+/// Bytes exchanged with a peer on a protocol.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolTraffic {
+	/// Name of the protocol
+	pub protocol: String,
+	/// Number of bytes received from the peer
+	pub bytes_in: u64,
+	/// Number of bytes sent to the peer
+	pub bytes_out: u64,
+}
+
+/// A peer banned by the node.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerBan {
+	/// Peer ID
+	pub peer_id: String,
+	/// UNIX time in seconds when the ban expires. Missing if the ban never expires.
+	#[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+	pub expires: Option<u64>,
 }
 
 /// The role the node is running as
@@ -122,9 +148,44 @@ mod tests {
 				roles: "a".into(),
 				best_hash: 5u32,
 				best_number: 6u32,
+				protocols: vec![],
 			}).unwrap(),
 			r#"{"peerId":"2","roles":"a","bestHash":5,"bestNumber":6}"#,
 		);
+
+		assert_eq!(
+			::serde_json::to_string(&PeerInfo {
+				peer_id: "2".into(),
+				roles: "a".into(),
+				best_hash: 5u32,
+				best_number: 6u32,
+				protocols: vec![ProtocolTraffic {
+					protocol: "/foo".into(),
+					bytes_in: 7,
+					bytes_out: 8,
+				}],
+			}).unwrap(),
+			r#"{"peerId":"2","roles":"a","bestHash":5,"bestNumber":6,"protocols":[{"protocol":"/foo","bytesIn":7,"bytesOut":8}]}"#,
+		);
+	}
+
+	#[test]
+	fn should_serialize_peer_ban() {
+		assert_eq!(
+			::serde_json::to_string(&PeerBan {
+				peer_id: "2".into(),
+				expires: Some(1610000000),
+			}).unwrap(),
+			r#"{"peerId":"2","expires":1610000000}"#,
+		);
+
+		assert_eq!(
+			::serde_json::to_string(&PeerBan {
+				peer_id: "2".into(),
+				expires: None,
+			}).unwrap(),
+			r#"{"peerId":"2"}"#,
+		);
 	}
 
 	#[test]
@@ -27,7 +27,7 @@ use futures::{future::BoxFuture, compat::Compat};
 
 use self::error::Result as SystemResult;
 
-pub use self::helpers::{SystemInfo, Health, PeerInfo, NodeRole, SyncState};
+pub use self::helpers::{SystemInfo, Health, PeerInfo, PeerBan, ProtocolTraffic, NodeRole, SyncState};
 pub use self::gen_client::Client as SystemClient;
 
 /// Tetcore system RPC API
@@ -100,6 +100,26 @@ pub trait SystemApi<Hash, Number> {
 	fn system_remove_reserved_peer(&self, peer_id: String)
 		-> Compat<BoxFuture<'static, Result<(), tetsy_jsonrpc_core::Error>>>;
 
+	/// Bans a peer, for the given number of seconds or until it is unbanned if no duration is
+	/// given. The string should encode only the PeerId e.g.
+	/// `QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV`.
+	///
+	/// The peer is disconnected and no connection with it is accepted or opened while it is
+	/// banned, unless it is a reserved peer. Bans are kept across restarts.
+	#[rpc(name = "system_banPeer", returns = "()")]
+	fn system_ban_peer(&self, peer_id: String, duration: Option<u64>)
+		-> Compat<BoxFuture<'static, Result<(), tetsy_jsonrpc_core::Error>>>;
+
+	/// Lifts the ban of a peer. The string should encode only the PeerId.
+	#[rpc(name = "system_unbanPeer", returns = "()")]
+	fn system_unban_peer(&self, peer_id: String)
+		-> Compat<BoxFuture<'static, Result<(), tetsy_jsonrpc_core::Error>>>;
+
+	/// Returns the peers currently banned.
+	#[rpc(name = "system_listBans", returns = "Vec<PeerBan>")]
+	fn system_list_bans(&self)
+		-> Compat<BoxFuture<'static, tetsy_jsonrpc_core::Result<Vec<PeerBan>>>>;
+
 	/// Returns the roles the node is running as.
 	#[rpc(name = "system_nodeRoles", returns = "Vec<NodeRole>")]
 	fn system_node_roles(&self) -> Receiver<Vec<NodeRole>>;
@@ -0,0 +1,167 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2020-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Filtering of the RPC methods exposed by a server.
+
+use serde::Deserialize;
+
+/// Aliases of the RPC methods, together with the name they are declared with.
+///
+/// Must be kept in sync with the `alias` attributes of the RPC traits.
+const METHOD_ALIASES: &[(&str, &str)] = &[
+	("chain_getHead", "chain_getBlockHash"),
+	("chain_getFinalisedHead", "chain_getFinalizedHead"),
+	("subscribe_newHead", "chain_subscribeNewHeads"),
+	("chain_subscribeNewHead", "chain_subscribeNewHeads"),
+	("unsubscribe_newHead", "chain_unsubscribeNewHeads"),
+	("chain_unsubscribeNewHead", "chain_unsubscribeNewHeads"),
+	("chain_subscribeFinalisedHeads", "chain_subscribeFinalizedHeads"),
+	("chain_unsubscribeFinalisedHeads", "chain_unsubscribeFinalizedHeads"),
+	("childstate_getKeysPagedAt", "childstate_getKeysPaged"),
+	("state_callAt", "state_call"),
+	("state_getKeysPagedAt", "state_getKeysPaged"),
+	("state_getStorageAt", "state_getStorage"),
+	("state_getStorageHashAt", "state_getStorageHash"),
+	("state_getStorageSizeAt", "state_getStorageSize"),
+	("chain_getRuntimeVersion", "state_getRuntimeVersion"),
+	("chain_subscribeRuntimeVersion", "state_subscribeRuntimeVersion"),
+	("chain_unsubscribeRuntimeVersion", "state_unsubscribeRuntimeVersion"),
+];
+
+/// Returns the name the given method is declared with, which differs from `method` if it is
+/// an alias.
+pub fn canonical_method_name(method: &str) -> &str {
+	METHOD_ALIASES.iter()
+		.find(|(alias, _)| *alias == method)
+		.map(|(_, name)| *name)
+		.unwrap_or(method)
+}
+
+/// Lists of the methods allowed and denied by an RPC server.
+///
+/// Entries are either method names, e.g. `state_getStorage`, or patterns ending with `*`
+/// matching all the methods starting with the given prefix, e.g. `chain_*`.
+/// Denied methods take precedence over the allowed ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MethodFilter {
+	/// Methods allowed, all of them if `None`.
+	pub allow: Option<Vec<String>>,
+	/// Methods denied.
+	pub deny: Vec<String>,
+}
+
+impl MethodFilter {
+	/// Returns `true` if the filter lets all the methods through.
+	pub fn is_empty(&self) -> bool {
+		self.allow.is_none() && self.deny.is_empty()
+	}
+
+	/// Returns `true` if the method is allowed by the filter.
+	///
+	/// Aliases are matched both by their own name and by the name of the method they stand for,
+	/// so that denying a method also denies its aliases.
+	pub fn is_allowed(&self, method: &str) -> bool {
+		let names = [method, canonical_method_name(method)];
+		let matches_any = |pattern: &String| names.iter().any(|name| matches(pattern, name));
+
+		if self.deny.iter().any(matches_any) {
+			return false
+		}
+
+		match &self.allow {
+			Some(allow) => allow.iter().any(matches_any),
+			None => true,
+		}
+	}
+}
+
+/// Method filters of the HTTP and WS servers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcMethodFilters {
+	/// Filter of the HTTP server.
+	pub http: MethodFilter,
+	/// Filter of the WS server.
+	pub ws: MethodFilter,
+}
+
+fn matches(pattern: &str, method: &str) -> bool {
+	match pattern.strip_suffix('*') {
+		Some(prefix) => method.starts_with(prefix),
+		None => pattern == method,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_allow_listed_methods_only() {
+		let filter = MethodFilter {
+			allow: Some(vec!["chain_*".into(), "state_getStorage".into()]),
+			deny: vec!["chain_getBlock".into()],
+		};
+
+		assert!(filter.is_allowed("chain_getHeader"));
+		assert!(filter.is_allowed("state_getStorage"));
+		assert!(!filter.is_allowed("state_getStorageHash"));
+		assert!(!filter.is_allowed("chain_getBlock"));
+		assert!(!filter.is_allowed("author_submitExtrinsic"));
+	}
+
+	#[test]
+	fn should_deny_listed_methods() {
+		let filter = MethodFilter { allow: None, deny: vec!["state_getPairs".into(), "offchain_*".into()] };
+
+		assert!(filter.is_allowed("state_getStorage"));
+		assert!(!filter.is_allowed("state_getPairs"));
+		assert!(!filter.is_allowed("offchain_localStorageGet"));
+		assert!(MethodFilter::default().is_allowed("state_getPairs"));
+	}
+
+	#[test]
+	fn should_deny_aliases_of_denied_methods() {
+		let filter = MethodFilter {
+			allow: None,
+			deny: vec!["state_call".into(), "state_getKeysPaged".into(), "chain_subscribe*".into()],
+		};
+
+		assert!(!filter.is_allowed("state_callAt"));
+		assert!(!filter.is_allowed("state_getKeysPagedAt"));
+		assert!(!filter.is_allowed("subscribe_newHead"));
+		assert!(filter.is_allowed("state_getStorageAt"));
+
+		let filter = MethodFilter { allow: Some(vec!["state_getStorage".into()]), deny: Vec::new() };
+		assert!(filter.is_allowed("state_getStorageAt"));
+		assert!(!filter.is_allowed("state_callAt"));
+	}
+
+	#[test]
+	fn should_deserialize_filters() {
+		let filters: RpcMethodFilters = serde_json::from_str(r#"{
+			"http": { "allow": ["chain_*", "state_getStorage"] },
+			"ws": { "deny": ["state_getPairs"] }
+		}"#).unwrap();
+
+		assert_eq!(filters.http.allow, Some(vec!["chain_*".into(), "state_getStorage".into()]));
+		assert!(filters.http.deny.is_empty());
+		assert_eq!(filters.ws, MethodFilter { allow: None, deny: vec!["state_getPairs".into()] });
+	}
+}
@@ -20,6 +20,8 @@
 
 #![warn(missing_docs)]
 
+mod filter;
+mod limits;
 mod middleware;
 
 use std::io;
@@ -37,6 +39,8 @@ const WS_MAX_CONNECTIONS: usize = 100;
 pub type RpcHandler<T> = pubsub::PubSubHandler<T, RpcMiddleware>;
 
 pub use self::inner::*;
+pub use filter::{MethodFilter, RpcMethodFilters};
+pub use limits::{RateLimit, RpcLimits, SubscriptionFuture, bind_to_subscription};
 pub use middleware::{RpcMiddleware, RpcMetrics};
 
 /// Construct rpc `IoHandler`
@@ -44,12 +48,16 @@ pub fn rpc_handler<M: PubSubMetadata>(
 	extension: impl IoHandlerExtension<M>,
 	rpc_middleware: RpcMiddleware,
 ) -> RpcHandler<M> {
+	let filter = rpc_middleware.method_filter();
 	let io_handler = MetaIoHandler::with_middleware(rpc_middleware);
 	let mut io = pubsub::PubSubHandler::new(io_handler);
 	extension.augment(&mut io);
 
 	// add an endpoint to list all available methods.
-	let mut methods = io.iter().map(|x| x.0.clone()).collect::<Vec<String>>();
+	let mut methods = io.iter()
+		.map(|x| x.0.clone())
+		.filter(|method| filter.is_allowed(method))
+		.collect::<Vec<String>>();
 	io.add_method("rpc_methods", {
 		methods.sort();
 		let methods = serde_json::to_value(&methods)
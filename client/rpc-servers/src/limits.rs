@@ -0,0 +1,329 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2020-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Limits applied to the calls of RPC connections.
+
+use std::{
+	cell::RefCell, collections::HashMap, sync::Arc, time::Instant,
+	sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use futures::{Future, Poll};
+
+use crate::filter::canonical_method_name;
+
+thread_local! {
+	/// Subscription slot of the call being handled on this thread.
+	static CURRENT_SLOT: RefCell<Option<Arc<SubscriptionSlot>>> = RefCell::new(None);
+}
+
+/// A token bucket rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+	/// Number of calls allowed per second, on average.
+	pub per_second: u32,
+	/// Maximum number of calls allowed in a burst.
+	pub burst: u32,
+}
+
+impl RateLimit {
+	/// Create a rate limit allowing `per_second` calls per second, in bursts of the same size.
+	pub fn per_second(per_second: u32) -> Self {
+		RateLimit { per_second, burst: per_second }
+	}
+}
+
+/// Limits applied to the calls of each RPC connection.
+///
+/// The per-connection limits only apply to the transports with sessions (i.e. WS). The HTTP
+/// server doesn't expose the address of its clients, so HTTP requests are only subject to
+/// `max_batch_size`.
+#[derive(Debug, Clone, Default)]
+pub struct RpcLimits {
+	/// Rate limit of all the calls of a connection.
+	pub calls: Option<RateLimit>,
+	/// Rate limits of the calls of a connection to specific methods.
+	///
+	/// A limit also applies to the aliases of the method.
+	pub methods: HashMap<String, RateLimit>,
+	/// Maximum number of concurrent subscriptions of a connection.
+	pub max_subscriptions: Option<usize>,
+	/// Maximum number of calls in a batch request.
+	pub max_batch_size: Option<usize>,
+}
+
+impl RpcLimits {
+	/// Returns `true` if any of the per-connection limits is set.
+	pub fn has_connection_limits(&self) -> bool {
+		self.calls.is_some() || !self.methods.is_empty() || self.max_subscriptions.is_some()
+	}
+}
+
+/// Token bucket tracking the calls made against a `RateLimit`.
+#[derive(Debug)]
+pub(crate) struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+impl Bucket {
+	/// Create a full bucket.
+	pub fn new(limit: &RateLimit, now: Instant) -> Self {
+		Bucket { tokens: limit.burst as f64, updated: now }
+	}
+
+	/// Take a token from the bucket, returns `false` if it is empty.
+	pub fn try_take(&mut self, limit: &RateLimit, now: Instant) -> bool {
+		let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * limit.per_second as f64).min(limit.burst as f64);
+		self.updated = now;
+
+		if self.tokens >= 1.0 {
+			self.tokens -= 1.0;
+			true
+		} else {
+			false
+		}
+	}
+}
+
+/// A subscription of a connection, counted in its number of open subscriptions until dropped.
+#[derive(Debug)]
+pub struct SubscriptionSlot {
+	subscriptions: Arc<AtomicUsize>,
+	/// Whether a future driving the subscription has been bound to the slot.
+	bound: AtomicBool,
+}
+
+impl SubscriptionSlot {
+	/// Whether a future has been bound to the slot with `bind_to_subscription`.
+	pub(crate) fn is_bound(&self) -> bool {
+		self.bound.load(Ordering::SeqCst)
+	}
+}
+
+impl Drop for SubscriptionSlot {
+	fn drop(&mut self) {
+		self.subscriptions.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Runs `f` with `slot` as the subscription slot of the call being handled on this thread.
+pub(crate) fn with_subscription_slot<R>(
+	slot: Option<Arc<SubscriptionSlot>>,
+	f: impl FnOnce() -> R,
+) -> R {
+	let previous = CURRENT_SLOT.with(|current| current.replace(slot));
+	let result = f();
+	CURRENT_SLOT.with(|current| current.replace(previous));
+	result
+}
+
+/// Binds `future` to the subscription slot of the RPC call being handled on the current thread,
+/// if any.
+///
+/// The slot is released once all the futures bound to it are dropped, including the ones
+/// spawned while polling `future`. Executors of subscription tasks must bind them, so that the
+/// subscriptions ending on the server side, e.g. a finalized extrinsic watch, release their
+/// slot. Subscriptions whose tasks aren't bound are only released when explicitly closed.
+pub fn bind_to_subscription<F: Future>(future: F) -> SubscriptionFuture<F> {
+	let slot = CURRENT_SLOT.with(|current| current.borrow().clone());
+	if let Some(slot) = &slot {
+		slot.bound.store(true, Ordering::SeqCst);
+	}
+	SubscriptionFuture { inner: future, slot }
+}
+
+/// Future bound to a subscription slot, see `bind_to_subscription`.
+pub struct SubscriptionFuture<F> {
+	inner: F,
+	slot: Option<Arc<SubscriptionSlot>>,
+}
+
+impl<F: Future> Future for SubscriptionFuture<F> {
+	type Item = F::Item;
+	type Error = F::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let inner = &mut self.inner;
+		with_subscription_slot(self.slot.clone(), || inner.poll())
+	}
+}
+
+/// State of a single connection.
+#[derive(Debug, Default)]
+pub(crate) struct Connection {
+	calls: Option<Bucket>,
+	methods: HashMap<String, Bucket>,
+	/// Number of subscriptions currently open.
+	subscriptions: Arc<AtomicUsize>,
+	/// Slots of the open subscriptions whose tasks aren't bound to their slot, by subscription
+	/// id.
+	unbound_subscriptions: HashMap<String, Arc<SubscriptionSlot>>,
+}
+
+impl Connection {
+	/// Account a call to `method`, returns `false` if the call exceeds the rate limits.
+	pub fn try_call(&mut self, limits: &RpcLimits, method: &str, now: Instant) -> bool {
+		if let Some(limit) = limits.calls.as_ref() {
+			let bucket = self.calls.get_or_insert_with(|| Bucket::new(limit, now));
+			if !bucket.try_take(limit, now) {
+				return false
+			}
+		}
+
+		let method = canonical_method_name(method);
+		if let Some(limit) = limits.methods.get(method) {
+			let bucket = self.methods.entry(method.into()).or_insert_with(|| Bucket::new(limit, now));
+			if !bucket.try_take(limit, now) {
+				return false
+			}
+		}
+
+		true
+	}
+
+	/// Reserve a subscription, returns `None` if the connection already has `max` of them.
+	pub fn try_subscribe(&mut self, max: usize) -> Option<Arc<SubscriptionSlot>> {
+		if self.subscriptions.load(Ordering::SeqCst) >= max {
+			return None
+		}
+		self.subscriptions.fetch_add(1, Ordering::SeqCst);
+		Some(Arc::new(SubscriptionSlot {
+			subscriptions: self.subscriptions.clone(),
+			bound: AtomicBool::new(false),
+		}))
+	}
+
+	/// Keep the slot of an open subscription whose tasks aren't bound to it, until
+	/// `unsubscribed` is called with its id.
+	pub fn keep_unbound(&mut self, id: String, slot: Arc<SubscriptionSlot>) {
+		self.unbound_subscriptions.insert(id, slot);
+	}
+
+	/// Release the slot of a subscription closed by the client, if it isn't bound to its tasks.
+	pub fn unsubscribed(&mut self, id: &str) {
+		self.unbound_subscriptions.remove(id);
+	}
+
+	/// Number of subscriptions currently open.
+	pub fn subscriptions(&self) -> usize {
+		self.subscriptions.load(Ordering::SeqCst)
+	}
+}
+
+/// Returns the method name without its namespace.
+fn method_name(method: &str) -> &str {
+	method.splitn(2, '_').nth(1).unwrap_or(method)
+}
+
+/// Whether the method opens a subscription, following the naming of the RPC APIs.
+pub(crate) fn is_subscribe(method: &str) -> bool {
+	let name = method_name(method);
+	method.starts_with("subscribe_") ||
+		name.starts_with("subscribe") ||
+		name.starts_with("submitAndWatch")
+}
+
+/// Whether the method closes a subscription, following the naming of the RPC APIs.
+pub(crate) fn is_unsubscribe(method: &str) -> bool {
+	let name = method_name(method);
+	method.starts_with("unsubscribe_") ||
+		name.starts_with("unsubscribe") ||
+		name.starts_with("unwatch")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn bucket_should_refill_over_time() {
+		let limit = RateLimit { per_second: 2, burst: 4 };
+		let now = Instant::now();
+		let mut bucket = Bucket::new(&limit, now);
+
+		for _ in 0..4 {
+			assert!(bucket.try_take(&limit, now));
+		}
+		assert!(!bucket.try_take(&limit, now));
+
+		let later = now + Duration::from_millis(500);
+		assert!(bucket.try_take(&limit, later));
+		assert!(!bucket.try_take(&limit, later));
+
+		// the bucket never holds more than the burst.
+		let much_later = later + Duration::from_secs(60);
+		for _ in 0..4 {
+			assert!(bucket.try_take(&limit, much_later));
+		}
+		assert!(!bucket.try_take(&limit, much_later));
+	}
+
+	#[test]
+	fn connection_should_apply_method_limits() {
+		let mut limits = RpcLimits::default();
+		limits.calls = Some(RateLimit::per_second(3));
+		limits.methods.insert("state_getKeysPaged".into(), RateLimit::per_second(1));
+		let now = Instant::now();
+		let mut connection = Connection::default();
+
+		assert!(connection.try_call(&limits, "state_getKeysPaged", now));
+		assert!(!connection.try_call(&limits, "state_getKeysPaged", now));
+		// aliases share the limit of the method.
+		assert!(!connection.try_call(&limits, "state_getKeysPagedAt", now));
+		assert!(connection.try_call(&limits, "system_health", now));
+		assert!(!connection.try_call(&limits, "system_health", now));
+	}
+
+	#[test]
+	fn subscription_slots_should_be_released_with_their_tasks() {
+		let mut connection = Connection::default();
+		let slot = connection.try_subscribe(1).unwrap();
+		assert!(connection.try_subscribe(1).is_none());
+
+		// the subscription task ends on the server side.
+		let task = with_subscription_slot(Some(slot.clone()), || {
+			bind_to_subscription(futures::future::ok::<(), ()>(()))
+		});
+		assert!(slot.is_bound());
+		drop(slot);
+		assert_eq!(connection.subscriptions(), 1);
+		assert_eq!(task.wait(), Ok(()));
+		assert_eq!(connection.subscriptions(), 0);
+
+		// unbound subscriptions are released when the client closes them.
+		let slot = connection.try_subscribe(1).unwrap();
+		connection.keep_unbound("1".into(), slot);
+		assert!(connection.try_subscribe(1).is_none());
+		connection.unsubscribed("1");
+		assert!(connection.try_subscribe(1).is_some());
+	}
+
+	#[test]
+	fn should_detect_subscription_methods() {
+		assert!(is_subscribe("chain_subscribeNewHeads"));
+		assert!(is_subscribe("subscribe_newHead"));
+		assert!(is_subscribe("author_submitAndWatchExtrinsic"));
+		assert!(!is_subscribe("author_submitExtrinsic"));
+		assert!(is_unsubscribe("chain_unsubscribeNewHeads"));
+		assert!(is_unsubscribe("unsubscribe_newHead"));
+		assert!(is_unsubscribe("author_unwatchExtrinsic"));
+		assert!(!is_unsubscribe("chain_subscribeNewHeads"));
+	}
+}
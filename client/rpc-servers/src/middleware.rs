@@ -18,21 +18,38 @@
 
 //! Middleware for RPC requests.
 
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
 use tetsy_jsonrpc_core::{
-	Middleware as RequestMiddleware, Metadata,
-	Request, Response, FutureResponse, FutureOutput
+	Middleware as RequestMiddleware, Call, Error, ErrorCode, Output, Params,
+	Request, Response, FutureResponse, FutureOutput, Version,
 };
+use parking_lot::Mutex;
 use prometheus_endpoint::{
-	Registry, CounterVec, PrometheusError,
-	Opts, register, U64
+	Registry, CounterVec, HistogramOpts, HistogramVec, PrometheusError,
+	Opts, exponential_buckets, register, U64
 };
+use pubsub::PubSubMetadata;
+
+use futures::{future::{self, Either}, Future};
 
-use futures::{future::Either, Future};
+use crate::filter::{MethodFilter, canonical_method_name};
+use crate::limits::{
+	Connection, RpcLimits, SubscriptionSlot, is_subscribe, is_unsubscribe, with_subscription_slot,
+};
+
+/// Error code returned for calls rejected because of the connection limits.
+const LIMIT_EXCEEDED: i64 = -32005;
+/// Method label used for calls to methods that do not exist.
+const UNKNOWN_METHOD: &str = "unknown";
 
 /// Metrics for RPC middleware
 #[derive(Debug, Clone)]
 pub struct RpcMetrics {
 	rpc_calls: Option<CounterVec<U64>>,
+	rpc_calls_time: Option<HistogramVec>,
+	rpc_calls_response_size: Option<HistogramVec>,
+	rpc_calls_rejected: Option<CounterVec<U64>>,
 }
 
 impl RpcMetrics {
@@ -51,14 +68,64 @@ impl RpcMetrics {
 					r,
 				)
 			).transpose()?,
+			rpc_calls_time: metrics_registry.map(|r|
+				register(
+					HistogramVec::new(
+						HistogramOpts {
+							common_opts: Opts::new(
+								"rpc_calls_time",
+								"Time taken to process rpc calls, in seconds",
+							),
+							buckets: exponential_buckets(0.001, 4.0, 9)
+								.expect("parameters are always valid values; qed"),
+						},
+						&["protocol", "method"]
+					)?,
+					r,
+				)
+			).transpose()?,
+			rpc_calls_response_size: metrics_registry.map(|r|
+				register(
+					HistogramVec::new(
+						HistogramOpts {
+							common_opts: Opts::new(
+								"rpc_calls_response_size",
+								"Approximate size of the responses to rpc calls, in bytes",
+							),
+							buckets: exponential_buckets(64.0, 4.0, 10)
+								.expect("parameters are always valid values; qed"),
+						},
+						&["protocol", "method"]
+					)?,
+					r,
+				)
+			).transpose()?,
+			rpc_calls_rejected: metrics_registry.map(|r|
+				register(
+					CounterVec::new(
+						Opts::new(
+							"rpc_calls_rejected_total",
+							"Number of rpc calls rejected because of the method filter or the connection limits",
+						),
+						&["protocol", "reason"]
+					)?,
+					r,
+				)
+			).transpose()?,
 		})
 	}
 }
 
+/// Identifies a connection of a transport with sessions.
+type ConnectionId = usize;
+
 /// Middleware for RPC calls
 pub struct RpcMiddleware {
 	metrics: RpcMetrics,
 	transport_label: String,
+	limits: Arc<RpcLimits>,
+	connections: Arc<Mutex<HashMap<ConnectionId, Connection>>>,
+	filter: Arc<MethodFilter>,
 }
 
 impl RpcMiddleware {
@@ -70,11 +137,137 @@ impl RpcMiddleware {
 		RpcMiddleware {
 			metrics,
 			transport_label: String::from(transport_label),
+			limits: Default::default(),
+			connections: Default::default(),
+			filter: Default::default(),
 		}
 	}
+
+	/// Apply the given limits to the calls of each connection.
+	pub fn with_limits(mut self, mut limits: RpcLimits) -> Self {
+		limits.methods = limits.methods.into_iter()
+			.map(|(method, limit)| (canonical_method_name(&method).to_owned(), limit))
+			.collect();
+		self.limits = Arc::new(limits);
+		self
+	}
+
+	/// Only let the methods allowed by the given filter through.
+	pub fn with_method_filter(mut self, filter: MethodFilter) -> Self {
+		self.filter = Arc::new(filter);
+		self
+	}
+
+	/// Returns the filter of the methods exposed.
+	pub(crate) fn method_filter(&self) -> Arc<MethodFilter> {
+		self.filter.clone()
+	}
+
+	fn reject(&self, reason: &str) {
+		if let Some(ref rpc_calls_rejected) = self.metrics.rpc_calls_rejected {
+			rpc_calls_rejected.with_label_values(&[self.transport_label.as_str(), reason]).inc();
+		}
+	}
+
+	/// Returns the id of the connection the call has been made on, registering the connection
+	/// on first use.
+	///
+	/// Returns `None` for the transports without sessions, which don't expose the address of
+	/// their clients either. Their calls are not subject to the per-connection limits.
+	fn connection<M: PubSubMetadata>(&self, meta: &M) -> Option<ConnectionId> {
+		let session = meta.session()?;
+
+		let id = Arc::as_ptr(&session) as ConnectionId;
+		let mut connections = self.connections.lock();
+		if !connections.contains_key(&id) {
+			connections.insert(id, Connection::default());
+			let connections = self.connections.clone();
+			session.on_drop(move || {
+				connections.lock().remove(&id);
+			});
+		}
+		Some(id)
+	}
+
+	/// Account the call against the limits of its connection.
+	///
+	/// Returns the slot reserved for the subscription opened by the call, if any, or the error
+	/// to respond with if the call is rejected.
+	fn check_limits(
+		&self,
+		connection: ConnectionId,
+		method: &str,
+	) -> Result<Option<Arc<SubscriptionSlot>>, Error> {
+		let mut connections = self.connections.lock();
+		let state = connections.entry(connection).or_default();
+
+		if !state.try_call(&self.limits, method, Instant::now()) {
+			self.reject("rate");
+			return Err(Error {
+				code: ErrorCode::ServerError(LIMIT_EXCEEDED),
+				message: "Too many requests".into(),
+				data: None,
+			})
+		}
+
+		match self.limits.max_subscriptions {
+			Some(max) if is_subscribe(method) => match state.try_subscribe(max) {
+				Some(slot) => Ok(Some(slot)),
+				None => {
+					self.reject("subscriptions");
+					Err(Error {
+						code: ErrorCode::ServerError(LIMIT_EXCEEDED),
+						message: format!("Too many subscriptions, at most {} allowed", max),
+						data: None,
+					})
+				}
+			},
+			_ => Ok(None),
+		}
+	}
+}
+
+/// Returns the subscription id passed to an unsubscribe call.
+fn subscription_id_param(call: &Call) -> Option<String> {
+	match call {
+		Call::MethodCall(call) => match &call.params {
+			Params::Array(params) => params.first().map(|id| id.to_string()),
+			_ => None,
+		},
+		_ => None,
+	}
 }
 
-impl<M: Metadata> RequestMiddleware<M> for RpcMiddleware {
+/// Estimate of the size of the serialized `value`, without serializing it.
+///
+/// String escapes are ignored and numbers are assumed to take 8 bytes.
+fn encoded_len_estimate(value: &serde_json::Value) -> usize {
+	use serde_json::Value;
+	match value {
+		Value::Null => 4,
+		Value::Bool(_) => 5,
+		Value::Number(_) => 8,
+		Value::String(s) => s.len() + 2,
+		Value::Array(values) => 2 + values.iter().map(|v| encoded_len_estimate(v) + 1).sum::<usize>(),
+		Value::Object(values) => 2 + values.iter()
+			.map(|(key, v)| key.len() + 4 + encoded_len_estimate(v))
+			.sum::<usize>(),
+	}
+}
+
+/// Estimate of the size of the serialized `output`, see `encoded_len_estimate`.
+fn output_len_estimate(output: &Output) -> usize {
+	// `{"jsonrpc":"2.0","id":...,"result":...}` and the id.
+	const ENVELOPE: usize = 40;
+	match output {
+		Output::Success(success) => ENVELOPE + encoded_len_estimate(&success.result),
+		Output::Failure(failure) => ENVELOPE +
+			failure.error.message.len() +
+			failure.error.data.as_ref().map(encoded_len_estimate).unwrap_or(0),
+	}
+}
+
+impl<M: PubSubMetadata> RequestMiddleware<M> for RpcMiddleware {
 	type Future = FutureResponse;
 	type CallFuture = FutureOutput;
 
@@ -87,6 +280,106 @@ impl<M: Metadata> RequestMiddleware<M> for RpcMiddleware {
 			rpc_calls.with_label_values(&[self.transport_label.as_str()]).inc();
 		}
 
+		if let (Request::Batch(calls), Some(max)) = (&request, self.limits.max_batch_size) {
+			if calls.len() > max {
+				self.reject("batch");
+				let error = Error {
+					code: ErrorCode::InvalidRequest,
+					message: format!("Batch of {} calls exceeds the limit of {}", calls.len(), max),
+					data: None,
+				};
+				return Either::A(Box::new(future::ok(Some(Response::from(error, Some(Version::V2))))))
+			}
+		}
+
 		Either::B(next(request, meta))
 	}
+
+	fn on_call<F, X>(&self, call: Call, meta: M, next: F) -> Either<FutureOutput, X>
+	where
+		F: Fn(Call, M) -> X + Send + Sync,
+		X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+	{
+		let (method, id, jsonrpc) = match &call {
+			Call::MethodCall(call) => (call.method.clone(), Some(call.id.clone()), call.jsonrpc),
+			Call::Notification(notification) => (notification.method.clone(), None, notification.jsonrpc),
+			Call::Invalid { .. } => return Either::B(next(call, meta)),
+		};
+
+		if !self.filter.is_allowed(&method) {
+			self.reject("method");
+			let error = Error {
+				code: ErrorCode::MethodNotFound,
+				message: "Method not allowed".into(),
+				data: None,
+			};
+			let output = id.map(|id| Output::from(Err(error), id, jsonrpc));
+			return Either::A(Box::new(future::ok(output)))
+		}
+
+		let connection = if self.limits.has_connection_limits() {
+			self.connection(&meta)
+		} else {
+			None
+		};
+		let slot = match connection.map(|connection| self.check_limits(connection, &method)) {
+			Some(Ok(slot)) => slot,
+			Some(Err(error)) => {
+				let output = id.map(|id| Output::from(Err(error), id, jsonrpc));
+				return Either::A(Box::new(future::ok(output)))
+			},
+			None => None,
+		};
+		let unsubscribed = if connection.is_some() && is_unsubscribe(&method) {
+			subscription_id_param(&call)
+		} else {
+			None
+		};
+
+		let metrics = self.metrics.clone();
+		let transport_label = self.transport_label.clone();
+		let connections = self.connections.clone();
+		let started = Instant::now();
+
+		// The subscription tasks spawned while handling the call are bound to the slot, which
+		// is released once they all end. A failed subscription releases it right away.
+		let response = with_subscription_slot(slot.clone(), || next(call, meta));
+
+		Either::A(Box::new(response.map(move |output| {
+			if let Some(connection) = connection {
+				if let Some(state) = connections.lock().get_mut(&connection) {
+					match (&output, slot) {
+						(Some(Output::Success(success)), Some(slot)) if !slot.is_bound() =>
+							state.keep_unbound(success.result.to_string(), slot),
+						_ => (),
+					}
+					let closed = match &output {
+						Some(Output::Success(success)) => success.result == serde_json::Value::Bool(true),
+						_ => false,
+					};
+					if let (Some(id), true) = (&unsubscribed, closed) {
+						state.unsubscribed(id);
+					}
+				}
+			}
+
+			let method = match &output {
+				Some(Output::Failure(failure)) if failure.error.code == ErrorCode::MethodNotFound =>
+					UNKNOWN_METHOD,
+				_ => method.as_str(),
+			};
+			if let Some(ref rpc_calls_time) = metrics.rpc_calls_time {
+				rpc_calls_time
+					.with_label_values(&[transport_label.as_str(), method])
+					.observe(started.elapsed().as_secs_f64());
+			}
+			if let (Some(rpc_calls_response_size), Some(output)) = (&metrics.rpc_calls_response_size, &output) {
+				rpc_calls_response_size
+					.with_label_values(&[transport_label.as_str(), method])
+					.observe(output_len_estimate(output) as f64);
+			}
+
+			output
+		})))
+	}
 }
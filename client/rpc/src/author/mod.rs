@@ -40,7 +40,7 @@ use tp_keystore::{SyncCryptoStorePtr, SyncCryptoStore};
 use tp_api::ProvideRuntimeApi;
 use tp_runtime::generic;
 use tp_transaction_pool::{
-	TransactionPool, InPoolTransaction, TransactionStatus, TransactionSource,
+	TransactionPool, InPoolTransaction, TransactionStatus, TransactionSource, PoolEvent,
 	BlockHash, TxHash, TransactionFor, error::IntoPoolError,
 };
 use tp_session::SessionKeys;
@@ -156,10 +156,75 @@ impl<P, Client> AuthorApi<TxHash<P>, BlockHash<P>> for Author<P, Client>
 		)
 	}
 
+	fn submit_extrinsics(&self, exts: Vec<Bytes>) -> FutureResult<Vec<batch::SubmitResult<TxHash<P>>>> {
+		let (xts, decoded) = decode_batch::<TransactionFor<P>>(exts);
+		let best_block_hash = self.client.info().best_hash;
+		Box::new(self.pool
+			.submit_at(&generic::BlockId::hash(best_block_hash), TX_SOURCE, xts)
+			.map(move |result| result.map(|results| {
+				merge_batch(decoded, results.into_iter().map(|result| result.map_err(pool_error)))
+					.into_iter()
+					.map(|result| match result {
+						Ok(hash) => batch::SubmitResult::Hash(hash),
+						Err(err) => batch::SubmitResult::Error(err.into()),
+					})
+					.collect()
+			}))
+			.compat()
+			.map_err(pool_error)
+		)
+	}
+
+	fn replace_extrinsic(&self, ext: Bytes, replaces: TxHash<P>) -> FutureResult<TxHash<P>> {
+		let xt = match Decode::decode(&mut &ext[..]) {
+			Ok(xt) => xt,
+			Err(err) => return Box::new(result(Err(err.into()))),
+		};
+		let best_block_hash = self.client.info().best_hash;
+		Box::new(self.pool
+			.replace_at(&generic::BlockId::hash(best_block_hash), TX_SOURCE, xt, replaces)
+			.compat()
+			.map_err(|e| e.into_pool_error()
+				.map(Into::into)
+				.unwrap_or_else(|e| error::Error::Verification(Box::new(e)).into()))
+		)
+	}
+
 	fn pending_extrinsics(&self) -> Result<Vec<Bytes>> {
 		Ok(self.pool.ready().map(|tx| tx.data().encode().into()).collect())
 	}
 
+	fn pool_status(&self) -> Result<pool::PoolStatus> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let status = self.pool.status();
+		Ok(pool::PoolStatus {
+			ready: status.ready,
+			ready_bytes: status.ready_bytes,
+			future: status.future,
+			future_bytes: status.future_bytes,
+		})
+	}
+
+	fn pool_content(&self, filter: Option<pool::PoolFilter>) -> Result<pool::PoolContent<TxHash<P>>> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let filter = filter.unwrap_or_default();
+		let ready = self.pool.ready()
+			.filter(|tx| filter.matches(tx.requires(), tx.provides()))
+			.map(|tx| pool_transaction(&*tx))
+			.collect();
+		let future = self.pool.futures().into_iter()
+			.filter(|(tx, _)| filter.matches(tx.requires(), tx.provides()))
+			.map(|(tx, missing)| pool::FutureTransaction {
+				transaction: pool_transaction(&*tx),
+				missing: missing.into_iter().map(Into::into).collect(),
+			})
+			.collect();
+
+		Ok(pool::PoolContent { ready, future })
+	}
+
 	fn remove_extrinsic(
 		&self,
 		bytes_or_hash: Vec<hash::ExtrinsicOrHash<TxHash<P>>>,
@@ -237,4 +302,130 @@ impl<P, Client> AuthorApi<TxHash<P>, BlockHash<P>> for Author<P, Client>
 	fn unwatch_extrinsic(&self, _metadata: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
 		Ok(self.subscriptions.cancel(id))
 	}
+
+	fn watch_extrinsics(&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<batch::BatchUpdate<TxHash<P>, BlockHash<P>>>,
+		exts: Vec<Bytes>,
+	) {
+		let (xts, decoded) = decode_batch::<TransactionFor<P>>(exts);
+		let best_block_hash = self.client.info().best_hash;
+		let submit = self.pool.submit_and_watch_at(&generic::BlockId::hash(best_block_hash), TX_SOURCE, xts);
+
+		let subscriptions = self.subscriptions.clone();
+		let future = submit.map(move |result| match result {
+			Ok(watchers) => {
+				let mut rejected = Vec::new();
+				let mut watchers_with_index = Vec::new();
+				let results = merge_batch(decoded, watchers.into_iter().map(|result| result.map_err(pool_error)));
+				for (index, result) in results.into_iter().enumerate() {
+					match result {
+						Ok(watcher) => watchers_with_index.push(
+							watcher.map(move |status| batch::BatchUpdate::Status { index, status })
+						),
+						Err(err) => rejected.push(batch::BatchUpdate::Rejected { index, error: err.into() }),
+					}
+				}
+
+				let updates = futures::stream::iter(rejected)
+					.chain(futures::stream::select_all(watchers_with_index))
+					.map(|v| Ok::<_, ()>(Ok(v)));
+				subscriptions.add(subscriber, move |sink| {
+					sink
+						.sink_map_err(|e| log::debug!("Subscription sink failed: {:?}", e))
+						.send_all(Compat::new(updates))
+						.map(|_| ())
+				});
+			},
+			Err(err) => {
+				let err = pool_error(err);
+				warn!("Failed to submit extrinsics: {}", err);
+				// reject the subscriber (ignore errors - we don't care if subscriber is no longer there).
+				let _ = subscriber.reject(err.into());
+			},
+		});
+
+		let res = self.subscriptions.executor()
+			.execute(Box::new(Compat::new(future.map(|_| Ok(())))));
+		if res.is_err() {
+			warn!("Error spawning subscription RPC task.");
+		}
+	}
+
+	fn unwatch_extrinsics(&self, _metadata: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+
+	fn subscribe_pool_events(&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<PoolEvent<TxHash<P>, BlockHash<P>>>,
+	) {
+		let stream = self.pool.pool_event_stream()
+			.map(|event| Ok::<_, ()>(Ok(event)));
+
+		self.subscriptions.add(subscriber, |sink| {
+			sink
+				.sink_map_err(|e| log::debug!("Subscription sink failed: {:?}", e))
+				.send_all(Compat::new(stream))
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_pool_events(&self, _metadata: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
+
+/// Converts a pool error into an RPC error.
+fn pool_error<E: IntoPoolError + 'static>(e: E) -> Error {
+	e.into_pool_error()
+		.map(Into::into)
+		.unwrap_or_else(|e| Error::Verification(Box::new(e)))
+}
+
+/// Decodes a batch of extrinsics.
+///
+/// Returns the decoded extrinsics and, for every encoded one, the decoding error if any.
+fn decode_batch<T: Decode>(exts: Vec<Bytes>) -> (Vec<T>, Vec<Option<Error>>) {
+	let mut xts = Vec::with_capacity(exts.len());
+	let decoded = exts.into_iter()
+		.map(|ext| match T::decode(&mut &ext[..]) {
+			Ok(xt) => {
+				xts.push(xt);
+				None
+			},
+			Err(err) => Some(err.into()),
+		})
+		.collect();
+	(xts, decoded)
+}
+
+/// Merges the results of submitting decoded extrinsics with the decoding errors,
+/// restoring the order of the batch.
+fn merge_batch<T>(
+	decoded: Vec<Option<Error>>,
+	mut submitted: impl Iterator<Item=std::result::Result<T, Error>>,
+) -> Vec<std::result::Result<T, Error>> {
+	decoded.into_iter()
+		.filter_map(|decoding_error| match decoding_error {
+			Some(err) => Some(Err(err)),
+			None => submitted.next(),
+		})
+		.collect()
+}
+
+fn pool_transaction<T, H>(tx: &T) -> pool::PoolTransaction<H> where
+	T: InPoolTransaction<Hash = H>,
+	T::Transaction: Encode,
+	H: Clone,
+{
+	pool::PoolTransaction {
+		hash: tx.hash().clone(),
+		extrinsic: tx.data().encode().into(),
+		priority: *tx.priority(),
+		longevity: *tx.longevity(),
+		requires: tx.requires().iter().cloned().map(Into::into).collect(),
+		provides: tx.provides().iter().cloned().map(Into::into).collect(),
+		propagate: tx.is_propagable(),
+	}
 }
@@ -200,6 +200,94 @@ fn should_return_pending_extrinsics() {
 	);
 }
 
+#[test]
+fn should_submit_extrinsics_in_order() {
+	let setup = TestSetup::default();
+	let p = setup.author();
+
+	let xt0 = uxt(AccountKeyring::Alice, 0);
+	let xt1 = uxt(AccountKeyring::Alice, 1);
+	let results = p.submit_extrinsics(vec![
+		xt1.encode().into(),
+		vec![0u8; 3].into(),
+		xt0.encode().into(),
+		xt1.encode().into(),
+	]).wait().unwrap();
+
+	assert_eq!(results.len(), 4);
+	assert_eq!(results[0], batch::SubmitResult::Hash(setup.pool.hash_of(&xt1)));
+	assert_matches!(results[1], batch::SubmitResult::Error(ref e) if e.code == rpc::ErrorCode::ServerError(1001));
+	assert_eq!(results[2], batch::SubmitResult::Hash(setup.pool.hash_of(&xt0)));
+	assert_matches!(results[3], batch::SubmitResult::Error(ref e) if e.code == rpc::ErrorCode::ServerError(1013));
+	assert_eq!(setup.pool.status().ready, 2);
+}
+
+#[test]
+fn should_replace_extrinsic() {
+	let setup = TestSetup::default();
+	let p = setup.author();
+
+	let transfer = |amount| Transfer {
+		amount,
+		nonce: 0,
+		from: AccountKeyring::Alice.into(),
+		to: Default::default(),
+	}.into_signed_tx();
+	let hash = p.submit_extrinsic(transfer(100).encode().into()).wait().unwrap();
+
+	// not enough priority bump
+	assert!(p.replace_extrinsic(transfer(105).encode().into(), hash).wait().is_err());
+	// not replacing the given transaction
+	assert!(p.replace_extrinsic(uxt(AccountKeyring::Alice, 1).encode().into(), hash).wait().is_err());
+	assert_eq!(setup.pool.status().ready, 1);
+
+	let replacement = p.replace_extrinsic(transfer(110).encode().into(), hash).wait().unwrap();
+	assert_eq!(
+		setup.pool.ready().map(|tx| tx.hash().clone()).collect::<Vec<_>>(),
+		vec![replacement],
+	);
+}
+
+#[test]
+fn should_return_pool_content() {
+	let setup = TestSetup::default();
+	let mut p = setup.author();
+
+	let alice: tetcore_test_runtime_client::runtime::AccountId = AccountKeyring::Alice.into();
+	p.submit_extrinsic(uxt(AccountKeyring::Alice, 0).encode().into()).wait().unwrap();
+	let future = p.submit_extrinsic(uxt(AccountKeyring::Alice, 2).encode().into()).wait().unwrap();
+	p.submit_extrinsic(uxt(AccountKeyring::Bob, 0).encode().into()).wait().unwrap();
+
+	assert_eq!(
+		p.pool_status().unwrap(),
+		pool::PoolStatus { ready: 2, ready_bytes: setup.pool.status().ready_bytes, future: 1, future_bytes: setup.pool.status().future_bytes },
+	);
+
+	let content = p.pool_content(None).unwrap();
+	assert_eq!(content.ready.len(), 2);
+	assert_eq!(content.future.len(), 1);
+	assert_eq!(content.future[0].transaction.hash, future);
+	assert_eq!(content.future[0].missing, vec![Bytes((&alice, 1u64).encode())]);
+
+	let content = p.pool_content(Some(pool::PoolFilter {
+		sender: Some(Bytes(alice.encode())),
+		..Default::default()
+	})).unwrap();
+	assert_eq!(content.ready.len(), 1);
+	assert_eq!(content.future.len(), 1);
+
+	let content = p.pool_content(Some(pool::PoolFilter {
+		tag: Some(Bytes((&alice, 1u64).encode())),
+		..Default::default()
+	})).unwrap();
+	assert_eq!(content.ready.len(), 0);
+	assert_eq!(content.future.len(), 1);
+
+	p.deny_unsafe = DenyUnsafe::Yes;
+	assert!(p.pool_status().is_err());
+	assert!(p.pool_content(None).is_err());
+}
+
 #[test]
 fn should_remove_extrinsics() {
 	let setup = TestSetup::default();
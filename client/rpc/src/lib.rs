@@ -55,6 +55,8 @@ impl Executor<Box<dyn Future<Item = (), Error = ()> + Send>> for SubscriptionTas
 		&self,
 		future: Box<dyn Future<Item = (), Error = ()> + Send>,
 	) -> Result<(), ExecuteError<Box<dyn Future<Item = (), Error = ()> + Send>>> {
+		// Subscriptions ending on the server side release their slot in the connection limits.
+		let future = tc_rpc_server::bind_to_subscription(future);
 		self.0.spawn("tetcore-rpc-subscription", future.compat().map(drop).boxed());
 		Ok(())
 	}
@@ -40,7 +40,10 @@ use self::error::{Error, FutureResult};
 
 pub use tc_rpc_api::state::*;
 pub use tc_rpc_api::child_state::*;
-use tc_client_api::{ExecutorProvider, StorageProvider, BlockchainEvents, Backend, ProofProvider};
+use tc_client_api::{
+	ExecutorProvider, StorageProvider, BlockchainEvents, Backend, ProofProvider, BlockBackend,
+};
+use tc_block_builder::BlockBuilderApi;
 use tp_blockchain::{HeaderMetadata, HeaderBackend};
 
 const STORAGE_KEYS_PAGED_MAX_COUNT: u32 = 1000;
@@ -130,13 +133,31 @@ pub trait StateBackend<Block: BlockT, Client>: Send + Sync + 'static
 		at: Option<Block::Hash>
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>>;
 
-	/// Returns proof of storage entries at a specific block's state.
+	/// Returns proof of storage entries at a specific block's state, in compact form if
+	/// `compact` is set.
 	fn read_proof(
 		&self,
 		block: Option<Block::Hash>,
 		keys: Vec<StorageKey>,
+		compact: bool,
 	) -> FutureResult<ReadProof<Block::Hash>>;
 
+	/// Returns the storage entries under `prefix` that differ between two blocks.
+	fn trace_storage_diff(
+		&self,
+		from: Block::Hash,
+		to: Block::Hash,
+		prefix: StorageKey,
+	) -> FutureResult<Vec<StorageDiffEntry>>;
+
+	/// Re-executes a block and returns the spans and events recorded along the way.
+	fn trace_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+		storage_keys: Option<Vec<StorageKey>>,
+	) -> FutureResult<BlockTrace<Block::Hash>>;
+
 	/// New runtime version subscription
 	fn subscribe_runtime_version(
 		&self,
@@ -165,6 +186,23 @@ pub trait StateBackend<Block: BlockT, Client>: Send + Sync + 'static
 		_meta: Option<crate::Metadata>,
 		id: SubscriptionId,
 	) -> RpcResult<bool>;
+
+	/// New storage pairs subscription
+	fn subscribe_storage_pairs(
+		&self,
+		_meta: crate::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		batch_size: u32,
+	);
+
+	/// Unsubscribe from storage pairs subscription
+	fn unsubscribe_storage_pairs(
+		&self,
+		_meta: Option<crate::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool>;
 }
 
 /// Create new state API that works on full node.
@@ -178,15 +216,16 @@ pub fn new_full<BE, Block: BlockT, Client>(
 		BE: Backend<Block> + 'static,
 		Client: ExecutorProvider<Block> + StorageProvider<Block, BE> + ProofProvider<Block> + HeaderBackend<Block>
 			+ HeaderMetadata<Block, Error = tp_blockchain::Error> + BlockchainEvents<Block>
-			+ CallApiAt<Block, Error = tp_blockchain::Error>
+			+ CallApiAt<Block, Error = tp_blockchain::Error> + BlockBackend<Block>
 			+ ProvideRuntimeApi<Block> + Send + Sync + 'static,
-		Client::Api: Metadata<Block, Error = tp_blockchain::Error>,
+		Client::Api: Metadata<Block, Error = tp_blockchain::Error>
+			+ BlockBuilderApi<Block, Error = tp_blockchain::Error>,
 {
 	let child_backend = Box::new(
 		self::state_full::FullState::new(client.clone(), subscriptions.clone())
 	);
 	let backend = Box::new(self::state_full::FullState::new(client, subscriptions));
-	(State { backend, deny_unsafe }, ChildState { backend: child_backend })
+	(State { backend, deny_unsafe }, ChildState { backend: child_backend, deny_unsafe })
 }
 
 /// Create new state API that works on light node.
@@ -219,7 +258,7 @@ pub fn new_light<BE, Block: BlockT, Client, F: Fetcher<Block>>(
 			remote_blockchain,
 			fetcher,
 	));
-	(State { backend, deny_unsafe }, ChildState { backend: child_backend })
+	(State { backend, deny_unsafe }, ChildState { backend: child_backend, deny_unsafe })
 }
 
 /// State API with subscriptions support.
@@ -315,8 +354,39 @@ impl<Block, Client> StateApi<Block::Hash> for State<Block, Client>
 		self.backend.query_storage_at(keys, at)
 	}
 
-	fn read_proof(&self, keys: Vec<StorageKey>, block: Option<Block::Hash>) -> FutureResult<ReadProof<Block::Hash>> {
-		self.backend.read_proof(block, keys)
+	fn read_proof(
+		&self,
+		keys: Vec<StorageKey>,
+		block: Option<Block::Hash>,
+		compact: Option<bool>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		self.backend.read_proof(block, keys, compact.unwrap_or(false))
+	}
+
+	fn trace_storage_diff(
+		&self,
+		from: Block::Hash,
+		to: Block::Hash,
+		prefix: StorageKey,
+	) -> FutureResult<Vec<StorageDiffEntry>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(result(Err(err.into())))
+		}
+
+		self.backend.trace_storage_diff(from, to, prefix)
+	}
+
+	fn trace_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+		storage_keys: Option<Vec<StorageKey>>,
+	) -> FutureResult<BlockTrace<Block::Hash>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(result(Err(err.into())))
+		}
+
+		self.backend.trace_block(block, targets, storage_keys)
 	}
 
 	fn subscribe_storage(
@@ -332,6 +402,33 @@ impl<Block, Client> StateApi<Block::Hash> for State<Block, Client>
 		self.backend.unsubscribe_storage(meta, id)
 	}
 
+	fn subscribe_storage_pairs(
+		&self,
+		meta: Self::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		prefix: StorageKey,
+		batch_size: u32,
+		block: Option<Block::Hash>,
+	) {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			let _ = subscriber.reject(err.into());
+			return;
+		}
+		if let Err(err) = check_batch_size(batch_size) {
+			let _ = subscriber.reject(err.into());
+			return;
+		}
+		self.backend.subscribe_storage_pairs(meta, subscriber, block, prefix, batch_size)
+	}
+
+	fn unsubscribe_storage_pairs(
+		&self,
+		meta: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		self.backend.unsubscribe_storage_pairs(meta, id)
+	}
+
 	fn runtime_version(&self, at: Option<Block::Hash>) -> FutureResult<RuntimeVersion> {
 		self.backend.runtime_version(at)
 	}
@@ -364,6 +461,16 @@ pub trait ChildStateBackend<Block: BlockT, Client>: Send + Sync + 'static
 		prefix: StorageKey,
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns the keys with prefix from a child storage with pagination support.
+	fn storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>>;
+
 	/// Returns a child storage entry at a specific block's state.
 	fn storage(
 		&self,
@@ -390,11 +497,39 @@ pub trait ChildStateBackend<Block: BlockT, Client>: Send + Sync + 'static
 		Box::new(self.storage(block, storage_key, key)
 			.map(|x| x.map(|x| x.0.len() as u64)))
 	}
+
+	/// Returns proof of child storage entries at a specific block's state.
+	fn read_child_proof(
+		&self,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>>;
+
+	/// New child storage pairs subscription
+	fn subscribe_storage_pairs(
+		&self,
+		_meta: crate::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		prefix: StorageKey,
+		batch_size: u32,
+	);
+
+	/// Unsubscribe from child storage pairs subscription
+	fn unsubscribe_storage_pairs(
+		&self,
+		_meta: Option<crate::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool>;
 }
 
 /// Child state API with subscriptions support.
 pub struct ChildState<Block, Client> {
 	backend: Box<dyn ChildStateBackend<Block, Client>>,
+	/// Whether to deny unsafe calls
+	deny_unsafe: DenyUnsafe,
 }
 
 impl<Block, Client> ChildStateApi<Block::Hash> for ChildState<Block, Client>
@@ -422,6 +557,25 @@ impl<Block, Client> ChildStateApi<Block::Hash> for ChildState<Block, Client>
 		self.backend.storage_keys(block, storage_key, key_prefix)
 	}
 
+	fn storage_keys_paged(
+		&self,
+		storage_key: PrefixedStorageKey,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<StorageKey>> {
+		if count > STORAGE_KEYS_PAGED_MAX_COUNT {
+			return Box::new(result(Err(
+				Error::InvalidCount {
+					value: count,
+					max: STORAGE_KEYS_PAGED_MAX_COUNT,
+				}
+			)));
+		}
+		self.backend.storage_keys_paged(block, storage_key, prefix, count, start_key)
+	}
+
 	fn storage_hash(
 		&self,
 		storage_key: PrefixedStorageKey,
@@ -439,6 +593,53 @@ impl<Block, Client> ChildStateApi<Block::Hash> for ChildState<Block, Client>
 	) -> FutureResult<Option<u64>> {
 		self.backend.storage_size(block, storage_key, key)
 	}
+
+	fn read_child_proof(
+		&self,
+		child_storage_key: PrefixedStorageKey,
+		keys: Vec<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		self.backend.read_child_proof(block, child_storage_key, keys)
+	}
+
+	fn subscribe_storage_pairs(
+		&self,
+		meta: Self::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		child_storage_key: PrefixedStorageKey,
+		prefix: StorageKey,
+		batch_size: u32,
+		block: Option<Block::Hash>,
+	) {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			let _ = subscriber.reject(err.into());
+			return;
+		}
+		if let Err(err) = check_batch_size(batch_size) {
+			let _ = subscriber.reject(err.into());
+			return;
+		}
+		self.backend.subscribe_storage_pairs(meta, subscriber, block, child_storage_key, prefix, batch_size)
+	}
+
+	fn unsubscribe_storage_pairs(
+		&self,
+		meta: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		self.backend.unsubscribe_storage_pairs(meta, id)
+	}
+}
+
+fn check_batch_size(batch_size: u32) -> Result<(), Error> {
+	if batch_size == 0 || batch_size > STORAGE_KEYS_PAGED_MAX_COUNT {
+		return Err(Error::InvalidCount {
+			value: batch_size,
+			max: STORAGE_KEYS_PAGED_MAX_COUNT,
+		});
+	}
+	Ok(())
 }
 
 fn client_err(err: tp_blockchain::Error) -> Error {
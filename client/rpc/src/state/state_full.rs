@@ -26,24 +26,29 @@ use log::warn;
 use tetsy_jsonrpc_pubsub::{typed::Subscriber, SubscriptionId, manager::SubscriptionManager};
 use rpc::{Result as RpcResult, futures::{stream, Future, Sink, Stream, future::result}};
 
-use tc_rpc_api::state::ReadProof;
+use tc_rpc_api::state::{
+	ReadProof, StoragePairs, StorageDiffEntry, BlockTrace, Traces, TraceSpan, TraceEvent,
+};
 use tc_client_api::backend::Backend;
 use tp_blockchain::{Result as ClientResult, Error as ClientError, HeaderMetadata, CachedHeaderMetadata, HeaderBackend};
 use tc_client_api::BlockchainEvents;
 use tet_core::{
-	Bytes, storage::{well_known_keys, StorageKey, StorageData, StorageChangeSet,
+	Bytes, hexdisplay::HexDisplay, storage::{well_known_keys, StorageKey, StorageData, StorageChangeSet,
 	ChildInfo, ChildType, PrefixedStorageKey},
 };
 use tp_version::RuntimeVersion;
 use tp_runtime::{
-	generic::BlockId, traits::{Block as BlockT, NumberFor, SaturatedConversion, CheckedSub},
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, SaturatedConversion, CheckedSub, HashFor},
 };
 
-use tp_api::{Metadata, ProvideRuntimeApi, CallApiAt};
+use tp_api::{Metadata, ProvideRuntimeApi, CallApiAt, Core};
 
 use super::{StateBackend, ChildStateBackend, error::{FutureResult, Error, Result}, client_err};
 use std::marker::PhantomData;
-use tc_client_api::{CallExecutor, StorageProvider, ExecutorProvider, ProofProvider};
+use tc_client_api::{CallExecutor, StorageProvider, ExecutorProvider, ProofProvider, BlockBackend};
+use tc_block_builder::BlockBuilderApi;
+use tc_tracing::block::{self as block_tracing, STORAGE_TARGET};
 
 /// Ranges to query in state_queryStorage.
 struct QueryStorageRange<Block: BlockT> {
@@ -83,6 +88,75 @@ impl<BE, Block: BlockT, Client> FullState<BE, Block, Client>
 		Ok(hash.unwrap_or_else(|| self.client.info().best_hash))
 	}
 
+	/// Sends the key-value pairs with the given prefix to the subscriber in batches.
+	///
+	/// Each batch is read by the subscription task when the subscriber is ready to receive it,
+	/// resuming after the last key sent, so nothing is read once the subscriber is gone.
+	fn stream_storage_pairs(
+		&self,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		block: Option<Block::Hash>,
+		child_info: Option<ChildInfo>,
+		prefix: StorageKey,
+		batch_size: u32,
+	) where
+		BE: 'static,
+		Client: Send + Sync + 'static,
+	{
+		let block = match self.block_or_best(block).and_then(|block| {
+			self.client.header(BlockId::Hash(block))?
+				.ok_or_else(|| ClientError::UnknownBlock(format!("{:?}", block)))?;
+			Ok(block)
+		}) {
+			Ok(block) => block,
+			Err(err) => {
+				let _ = subscriber.reject(client_err(err).into());
+				return;
+			},
+		};
+
+		let client = self.client.clone();
+		let batch_size = batch_size as usize;
+		// `None` once the last batch has been read, otherwise the key to resume after.
+		let batches = futures::stream::unfold(Some(None), move |start_key: Option<Option<StorageKey>>| {
+			let start_key = match start_key {
+				Some(start_key) => start_key,
+				None => return future::ready(None),
+			};
+			let mut pairs = Vec::with_capacity(batch_size);
+			let result = client.storage_pairs_while(
+				&BlockId::Hash(block),
+				child_info.as_ref(),
+				&prefix,
+				start_key.as_ref(),
+				&mut |key, value| {
+					pairs.push((key, value));
+					pairs.len() < batch_size
+				},
+			);
+			future::ready(Some(match result {
+				Ok(()) => {
+					let complete = pairs.len() < batch_size;
+					let next = if complete { None } else { pairs.last().map(|(key, _)| Some(key.clone())) };
+					(Ok(StoragePairs { at: block, pairs, complete }), next)
+				},
+				Err(err) => (Err(client_err(err)), None),
+			}))
+		});
+
+		self.subscriptions.add(subscriber, |sink| {
+			let stream = batches
+				.map(|batch: Result<StoragePairs<Block::Hash>>| Ok::<_, ()>(batch.map_err(Into::into)))
+				.compat();
+
+			sink
+				.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+				.send_all(stream)
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		});
+	}
+
 	/// Splits the `query_storage` block range into 'filtered' and 'unfiltered' subranges.
 	/// Blocks that contain changes within filtered subrange could be filtered using changes tries.
 	/// Blocks that contain changes within unfiltered subrange must be filtered manually.
@@ -224,8 +298,9 @@ impl<BE, Block, Client> StateBackend<Block, Client> for FullState<BE, Block, Cli
 	Client: ExecutorProvider<Block> + StorageProvider<Block, BE> + ProofProvider<Block> + HeaderBackend<Block>
 		+ HeaderMetadata<Block, Error = tp_blockchain::Error> + BlockchainEvents<Block>
 		+ CallApiAt<Block, Error = tp_blockchain::Error> + ProvideRuntimeApi<Block>
-		+ Send + Sync + 'static,
-	Client::Api: Metadata<Block, Error = tp_blockchain::Error>,
+		+ BlockBackend<Block> + Send + Sync + 'static,
+	Client::Api: Metadata<Block, Error = tp_blockchain::Error>
+		+ BlockBuilderApi<Block, Error = tp_blockchain::Error>,
 {
 	fn call(
 		&self,
@@ -387,22 +462,103 @@ impl<BE, Block, Client> StateBackend<Block, Client> for FullState<BE, Block, Cli
 		&self,
 		block: Option<Block::Hash>,
 		keys: Vec<StorageKey>,
+		compact: bool,
 	) -> FutureResult<ReadProof<Block::Hash>> {
 		Box::new(result(
 			self.block_or_best(block)
 				.and_then(|block| {
-					self.client
-						.read_proof(
-							&BlockId::Hash(block),
-							&mut keys.iter().map(|key| key.0.as_ref()),
-						)
-						.map(|proof| proof.iter_nodes().map(|node| node.into()).collect())
-						.map(|proof| ReadProof { at: block, proof })
+					let id = BlockId::Hash(block);
+					let proof = self.client.read_proof(&id, &mut keys.iter().map(|key| key.0.as_ref()))?;
+					let proof = if compact {
+						let state_root = *self.client.header(id)?
+							.ok_or_else(|| ClientError::UnknownBlock(format!("{:?}", block)))?
+							.state_root();
+						proof.into_compact_proof::<HashFor<Block>>(state_root)
+							.map_err(|e| ClientError::Backend(format!("{:?}", e)))?
+							.encoded_nodes
+					} else {
+						proof.iter_nodes().collect()
+					};
+					Ok(ReadProof { at: block, proof: proof.into_iter().map(Into::into).collect() })
 				})
 				.map_err(client_err),
 		))
 	}
 
+	fn trace_storage_diff(
+		&self,
+		from: Block::Hash,
+		to: Block::Hash,
+		prefix: StorageKey,
+	) -> FutureResult<Vec<StorageDiffEntry>> {
+		let mut changes = Vec::new();
+		let diff = self.client.storage_diff(
+			&BlockId::Hash(from),
+			&BlockId::Hash(to),
+			&prefix,
+			&mut |key, from, to| {
+				changes.push(StorageDiffEntry { key, from, to });
+				true
+			},
+		);
+		Box::new(result(diff.map(|()| changes).map_err(client_err)))
+	}
+
+	fn trace_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+		storage_keys: Option<Vec<StorageKey>>,
+	) -> FutureResult<BlockTrace<Block::Hash>> {
+		let targets = targets.unwrap_or_else(|| block_tracing::DEFAULT_TARGETS.into());
+		let storage_keys = storage_keys.unwrap_or_default()
+			.iter()
+			.map(|key| HexDisplay::from(&key.0).to_string())
+			.collect::<Vec<_>>();
+		let trace = || -> ClientResult<BlockTrace<Block::Hash>> {
+			let (mut header, extrinsics) = self.client.block(&BlockId::Hash(block))?
+				.ok_or_else(|| ClientError::UnknownBlock(format!("{}", block)))?
+				.block
+				.deconstruct();
+			let parent_hash = *header.parent_hash();
+			let parent = BlockId::Hash(parent_hash);
+			// The seal is added after the execution, the runtime has never seen it.
+			header.digest_mut().logs.retain(|log| log.as_seal().is_none());
+
+			let api = self.client.runtime_api();
+			let (initialized, initialize) = block_tracing::collect(
+				&targets,
+				|| api.initialize_block(&parent, &header),
+			);
+			initialized?;
+			let mut extrinsic_traces = Vec::with_capacity(extrinsics.len());
+			for extrinsic in extrinsics {
+				// Failed dispatches are part of the block, only execution errors abort the trace.
+				let (applied, traces) = block_tracing::collect(
+					&targets,
+					|| api.apply_extrinsic(&parent, extrinsic),
+				);
+				applied?;
+				extrinsic_traces.push(rpc_traces(traces, &storage_keys));
+			}
+			let (finalized, finalize) = block_tracing::collect(
+				&targets,
+				|| api.finalize_block(&parent),
+			);
+			finalized?;
+
+			Ok(BlockTrace {
+				block_hash: block,
+				parent_hash,
+				targets: targets.clone(),
+				initialize: rpc_traces(initialize, &storage_keys),
+				extrinsics: extrinsic_traces,
+				finalize: rpc_traces(finalize, &storage_keys),
+			})
+		};
+		Box::new(result(trace().map_err(client_err)))
+	}
+
 	fn subscribe_runtime_version(
 		&self,
 		_meta: crate::Metadata,
@@ -521,12 +677,31 @@ impl<BE, Block, Client> StateBackend<Block, Client> for FullState<BE, Block, Cli
 	) -> RpcResult<bool> {
 		Ok(self.subscriptions.cancel(id))
 	}
+
+	fn subscribe_storage_pairs(
+		&self,
+		_meta: crate::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		batch_size: u32,
+	) {
+		self.stream_storage_pairs(subscriber, block, None, prefix, batch_size)
+	}
+
+	fn unsubscribe_storage_pairs(
+		&self,
+		_meta: Option<crate::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
 }
 
 impl<BE, Block, Client> ChildStateBackend<Block, Client> for FullState<BE, Block, Client> where
 	Block: BlockT + 'static,
 	BE: Backend<Block> + 'static,
-	Client: ExecutorProvider<Block> + StorageProvider<Block, BE> + HeaderBackend<Block>
+	Client: ExecutorProvider<Block> + StorageProvider<Block, BE> + ProofProvider<Block> + HeaderBackend<Block>
 		+ HeaderMetadata<Block, Error = tp_blockchain::Error> + BlockchainEvents<Block>
 		+ CallApiAt<Block, Error = tp_blockchain::Error> + ProvideRuntimeApi<Block>
 		+ Send + Sync + 'static,
@@ -554,6 +729,29 @@ impl<BE, Block, Client> ChildStateBackend<Block, Client> for FullState<BE, Block
 				.map_err(client_err)))
 	}
 
+	fn storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| {
+					let child_info = match ChildType::from_prefixed_key(&storage_key) {
+						Some((ChildType::ParentKeyId, storage_key)) => ChildInfo::new_default(storage_key),
+						None => return Err(tp_blockchain::Error::InvalidChildStorageKey),
+					};
+					self.client.child_storage_keys_iter(
+						&BlockId::Hash(block), child_info, prefix.as_ref(), start_key.as_ref(),
+					)
+				})
+				.map(|v| v.take(count as usize).collect())
+				.map_err(client_err)))
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -597,6 +795,93 @@ impl<BE, Block, Client> ChildStateBackend<Block, Client> for FullState<BE, Block
 				})
 				.map_err(client_err)))
 	}
+
+	fn read_child_proof(
+		&self,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| {
+					let child_info = match ChildType::from_prefixed_key(&storage_key) {
+						Some((ChildType::ParentKeyId, storage_key)) => ChildInfo::new_default(storage_key),
+						None => return Err(tp_blockchain::Error::InvalidChildStorageKey),
+					};
+					self.client
+						.read_child_proof(
+							&BlockId::Hash(block),
+							&child_info,
+							&mut keys.iter().map(|key| key.0.as_ref()),
+						)
+						.map(|proof| proof.iter_nodes().map(|node| node.into()).collect())
+						.map(|proof| ReadProof { at: block, proof })
+				})
+				.map_err(client_err),
+		))
+	}
+	fn subscribe_storage_pairs(
+		&self,
+		_meta: crate::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		prefix: StorageKey,
+		batch_size: u32,
+	) {
+		let child_info = match ChildType::from_prefixed_key(&storage_key) {
+			Some((ChildType::ParentKeyId, storage_key)) => ChildInfo::new_default(storage_key),
+			None => {
+				let _ = subscriber.reject(client_err(tp_blockchain::Error::InvalidChildStorageKey).into());
+				return;
+			},
+		};
+		self.stream_storage_pairs(subscriber, block, Some(child_info), prefix, batch_size)
+	}
+
+	fn unsubscribe_storage_pairs(
+		&self,
+		_meta: Option<crate::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
+
+/// Convert the traces recorded by `tc_tracing` into their RPC representation, dropping the
+/// storage events of keys that don't start with any of the hex encoded `storage_keys`.
+///
+/// The events clearing a prefix are kept if they may clear any key starting with `storage_keys`.
+fn rpc_traces(traces: block_tracing::Traces, storage_keys: &[String]) -> Traces {
+	let values = |values: &tc_tracing::Values| match serde_json::to_value(values) {
+		Ok(serde_json::Value::Object(values)) => values,
+		_ => Default::default(),
+	};
+	let key_matches = |event: &tc_tracing::TraceEvent| {
+		let clears_prefix = event.values.string_values.get("method").map_or(false, |method| {
+			["KillChild", "ClearPrefix", "ClearChildPrefix"].contains(&method.as_str())
+		});
+		storage_keys.is_empty() || event.target != STORAGE_TARGET ||
+			event.values.string_values.get("key").map_or(true, |key| storage_keys.iter().any(|prefix| {
+				key.starts_with(prefix) || (clears_prefix && prefix.starts_with(key.as_str()))
+			}))
+	};
+	Traces {
+		spans: traces.spans.into_iter().map(|span| TraceSpan {
+			id: span.id.into_u64(),
+			parent_id: span.parent_id.map(|id| id.into_u64()),
+			time: span.overall_time.as_nanos().saturated_into(),
+			values: values(&span.values),
+			name: span.name,
+			target: span.target,
+		}).collect(),
+		events: traces.events.into_iter().filter(key_matches).map(|event| TraceEvent {
+			parent_id: event.parent_id.map(|id| id.into_u64()),
+			values: values(&event.values),
+			target: event.target,
+		}).collect(),
+	}
 }
 
 /// Splits passed range into two subranges where:
@@ -40,12 +40,12 @@ use rpc::{
 	futures::stream::Stream,
 };
 
-use tc_rpc_api::state::ReadProof;
+use tc_rpc_api::state::{ReadProof, StoragePairs, StorageDiffEntry, BlockTrace};
 use tp_blockchain::{Error as ClientError, HeaderBackend};
 use tc_client_api::{
 	BlockchainEvents,
 	light::{
-		RemoteCallRequest, RemoteReadRequest, RemoteReadChildRequest,
+		RemoteCallRequest, RemoteReadRequest, RemoteReadChildRequest, RemoteReadKeysRequest,
 		RemoteBlockchain, Fetcher, future_header,
 	},
 };
@@ -54,13 +54,19 @@ use tet_core::{
 	storage::{StorageKey, PrefixedStorageKey, StorageData, StorageChangeSet},
 };
 use tp_version::RuntimeVersion;
-use tp_runtime::{generic::BlockId, traits::{Block as BlockT, HashFor}};
+use tp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT, HashFor}};
 
-use super::{StateBackend, ChildStateBackend, error::{FutureResult, Error}, client_err};
+use super::{
+	StateBackend, ChildStateBackend, STORAGE_KEYS_PAGED_MAX_COUNT,
+	error::{FutureResult, Error}, client_err,
+};
 
 /// Storage data map of storage keys => (optional) storage value.
 type StorageMap = HashMap<StorageKey, Option<StorageData>>;
 
+/// Maximum number of blocks read at once by `state_queryStorage`.
+const QUERY_STORAGE_PARALLEL_READS: usize = 8;
+
 /// State API backend for light nodes.
 #[derive(Clone)]
 pub struct LightState<Block: BlockT, F: Fetcher<Block>, Client> {
@@ -167,6 +173,42 @@ impl<Block: BlockT, F: Fetcher<Block> + 'static, Client> LightState<Block, F, Cl
 	fn block_or_best(&self, hash: Option<Block::Hash>) -> Block::Hash {
 		hash.unwrap_or_else(|| self.client.info().best_hash)
 	}
+
+	/// Returns the headers of the blocks from `from` to `to` (or best block if `None`),
+	/// in ascending order.
+	fn query_storage_range(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+	) -> Result<Vec<Block::Header>, Error> {
+		let to = self.block_or_best(to);
+		let invalid_range = |details: String| Error::InvalidBlockRange {
+			from: format!("{:?}", from),
+			to: format!("{:?}", to),
+			details,
+		};
+		let header = |hash: Block::Hash| self.client.header(BlockId::Hash(hash))
+			.map_err(|e| invalid_range(e.to_string()))?
+			.ok_or_else(|| invalid_range(format!("unknown block {:?}", hash)));
+
+		let from_header = header(from)?;
+		let mut last = header(to)?;
+		if from_header.number() > last.number() {
+			return Err(invalid_range("from number > to number".into()))
+		}
+
+		// check if we can get from `to` to `from` by going through parent_hashes.
+		let mut headers = vec![last.clone()];
+		while last.number() > from_header.number() {
+			last = header(*last.parent_hash())?;
+			headers.push(last.clone());
+		}
+		if last.hash() != from {
+			return Err(invalid_range("from and to are on different forks".into()))
+		}
+		headers.reverse();
+		Ok(headers)
+	}
 }
 
 impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Client>
@@ -192,36 +234,68 @@ impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Clie
 
 	fn storage_keys(
 		&self,
-		_block: Option<Block::Hash>,
-		_prefix: StorageKey,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
 	) -> FutureResult<Vec<StorageKey>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		Box::new(all_storage_keys(
+			&*self.remote_blockchain,
+			self.fetcher.clone(),
+			self.block_or_best(block),
+			prefix.0,
+		).boxed().compat())
 	}
 
 	fn storage_pairs(
 		&self,
-		_block: Option<Block::Hash>,
-		_prefix: StorageKey,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
 	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		let remote_blockchain = self.remote_blockchain.clone();
+		let fetcher = self.fetcher.clone();
+		let block = self.block_or_best(block);
+		Box::new(async move {
+			let keys = all_storage_keys(&*remote_blockchain, fetcher.clone(), block, prefix.0).await?;
+			let mut pairs = Vec::with_capacity(keys.len());
+			for chunk in keys.chunks(STORAGE_KEYS_PAGED_MAX_COUNT as usize) {
+				let mut values = storage(
+					&*remote_blockchain,
+					fetcher.clone(),
+					block,
+					chunk.iter().map(|key| key.0.clone()).collect(),
+				).await?;
+				pairs.extend(chunk.iter().filter_map(|key|
+					values.remove(key).flatten().map(|value| (key.clone(), value))
+				));
+			}
+			Ok(pairs)
+		}.boxed().compat())
 	}
 
 	fn storage_keys_paged(
 		&self,
-		_block: Option<Block::Hash>,
-		_prefix: Option<StorageKey>,
-		_count: u32,
-		_start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
 	) -> FutureResult<Vec<StorageKey>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		Box::new(storage_keys_paged(
+			&*self.remote_blockchain,
+			self.fetcher.clone(),
+			self.block_or_best(block),
+			prefix.map(|prefix| prefix.0).unwrap_or_default(),
+			start_key.map(|start_key| start_key.0),
+			count,
+		).boxed().compat())
 	}
 
 	fn storage_size(
 		&self,
-		_: Option<Block::Hash>,
-		_: StorageKey,
+		block: Option<Block::Hash>,
+		key: StorageKey,
 	) -> FutureResult<Option<u64>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		Box::new(StateBackend::storage(self, block, key)
+			.map(|maybe_storage| maybe_storage.map(|storage| storage.0.len() as u64))
+		)
 	}
 
 	fn storage(
@@ -274,29 +348,53 @@ impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Clie
 
 	fn query_storage(
 		&self,
-		_from: Block::Hash,
-		_to: Option<Block::Hash>,
-		_keys: Vec<StorageKey>,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		keys: Vec<StorageKey>,
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		let headers = match self.query_storage_range(from, to) {
+			Ok(headers) => headers,
+			Err(error) => return Box::new(result(Err(error))),
+		};
+		Box::new(query_storage(self.fetcher.clone(), headers, keys).boxed().compat())
 	}
 
 	fn query_storage_at(
 		&self,
-		_keys: Vec<StorageKey>,
-		_at: Option<Block::Hash>
+		keys: Vec<StorageKey>,
+		at: Option<Block::Hash>
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		let at = self.block_or_best(at);
+		self.query_storage(at, Some(at), keys)
 	}
 
 	fn read_proof(
 		&self,
 		_block: Option<Block::Hash>,
 		_keys: Vec<StorageKey>,
+		_compact: bool,
 	) -> FutureResult<ReadProof<Block::Hash>> {
 		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
 	}
 
+	fn trace_storage_diff(
+		&self,
+		_from: Block::Hash,
+		_to: Block::Hash,
+		_prefix: StorageKey,
+	) -> FutureResult<Vec<StorageDiffEntry>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
+	fn trace_block(
+		&self,
+		_block: Block::Hash,
+		_targets: Option<String>,
+		_storage_keys: Option<Vec<StorageKey>>,
+	) -> FutureResult<BlockTrace<Block::Hash>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
 	fn subscribe_storage(
 		&self,
 		_meta: crate::Metadata,
@@ -474,6 +572,24 @@ impl<Block, F, Client> StateBackend<Block, Client> for LightState<Block, F, Clie
 	) -> RpcResult<bool> {
 		Ok(self.subscriptions.cancel(id))
 	}
+	fn subscribe_storage_pairs(
+		&self,
+		_meta: crate::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		_block: Option<Block::Hash>,
+		_prefix: StorageKey,
+		_batch_size: u32,
+	) {
+		let _ = subscriber.reject(client_err(ClientError::NotAvailableOnLightClient).into());
+	}
+
+	fn unsubscribe_storage_pairs(
+		&self,
+		_meta: Option<crate::Metadata>,
+		_id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(false)
+	}
 }
 
 impl<Block, F, Client> ChildStateBackend<Block, Client> for LightState<Block, F, Client>
@@ -491,6 +607,17 @@ impl<Block, F, Client> ChildStateBackend<Block, Client> for LightState<Block, F,
 		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
 	}
 
+	fn storage_keys_paged(
+		&self,
+		_block: Option<Block::Hash>,
+		_storage_key: PrefixedStorageKey,
+		_prefix: Option<StorageKey>,
+		_count: u32,
+		_start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -533,6 +660,53 @@ impl<Block, F, Client> ChildStateBackend<Block, Client> for LightState<Block, F,
 			)
 		)
 	}
+	fn read_child_proof(
+		&self,
+		block: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProof<Block::Hash>> {
+		let block = self.block_or_best(block);
+		let fetcher = self.fetcher.clone();
+		let proof = resolve_header(&*self.remote_blockchain, &*self.fetcher, block)
+			.then(move |result| match result {
+				Ok(header) => Either::Left(fetcher.remote_read_child_proof(RemoteReadChildRequest {
+					block,
+					header,
+					storage_key,
+					keys: keys.into_iter().map(|key| key.0).collect(),
+					retry_count: Default::default(),
+				}).then(move |result| ready(result
+					.map(|proof| ReadProof {
+						at: block,
+						proof: proof.iter_nodes().map(|node| node.into()).collect(),
+					})
+					.map_err(client_err)
+				))),
+				Err(error) => Either::Right(ready(Err(error))),
+			});
+
+		Box::new(proof.boxed().compat())
+	}
+	fn subscribe_storage_pairs(
+		&self,
+		_meta: crate::Metadata,
+		subscriber: Subscriber<StoragePairs<Block::Hash>>,
+		_block: Option<Block::Hash>,
+		_storage_key: PrefixedStorageKey,
+		_prefix: StorageKey,
+		_batch_size: u32,
+	) {
+		let _ = subscriber.reject(client_err(ClientError::NotAvailableOnLightClient).into());
+	}
+
+	fn unsubscribe_storage_pairs(
+		&self,
+		_meta: Option<crate::Metadata>,
+		_id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(false)
+	}
 }
 
 /// Resolve header by hash.
@@ -619,6 +793,106 @@ fn storage<Block: BlockT, F: Fetcher<Block>>(
 		})
 }
 
+/// Get at most `count` storage keys starting with `prefix` and following `start_key`
+/// at given block.
+fn storage_keys_paged<Block: BlockT, F: Fetcher<Block>>(
+	remote_blockchain: &dyn RemoteBlockchain<Block>,
+	fetcher: Arc<F>,
+	block: Block::Hash,
+	prefix: Vec<u8>,
+	start_key: Option<Vec<u8>>,
+	count: u32,
+) -> impl std::future::Future<Output = Result<Vec<StorageKey>, Error>> {
+	resolve_header(remote_blockchain, &*fetcher, block)
+		.then(move |result| match result {
+			Ok(header) => Either::Left(fetcher.remote_read_keys(RemoteReadKeysRequest {
+				block,
+				header,
+				prefix,
+				start_key,
+				count,
+				retry_count: Default::default(),
+			}).then(|result| ready(result
+				.map(|keys| keys.into_iter().map(StorageKey).collect())
+				.map_err(client_err)
+			))),
+			Err(error) => Either::Right(ready(Err(error))),
+		})
+}
+
+/// Get all the storage keys starting with `prefix` at given block, page by page.
+fn all_storage_keys<Block: BlockT, F: Fetcher<Block>>(
+	remote_blockchain: &dyn RemoteBlockchain<Block>,
+	fetcher: Arc<F>,
+	block: Block::Hash,
+	prefix: Vec<u8>,
+) -> impl std::future::Future<Output = Result<Vec<StorageKey>, Error>> {
+	let header = resolve_header(remote_blockchain, &*fetcher, block);
+	async move {
+		let header = header.await?;
+		let mut keys = Vec::new();
+		let mut start_key = None;
+		loop {
+			let page = fetcher.remote_read_keys(RemoteReadKeysRequest {
+				block,
+				header: header.clone(),
+				prefix: prefix.clone(),
+				start_key: start_key.take(),
+				count: STORAGE_KEYS_PAGED_MAX_COUNT,
+				retry_count: Default::default(),
+			}).await.map_err(client_err)?;
+			let complete = page.len() < STORAGE_KEYS_PAGED_MAX_COUNT as usize;
+			start_key = page.last().cloned();
+			keys.extend(page.into_iter().map(StorageKey));
+			if complete {
+				return Ok(keys)
+			}
+		}
+	}
+}
+
+/// Get the changes of the values of `keys` over the blocks of given headers.
+///
+/// The values at the first block are always reported, then only the changed values.
+async fn query_storage<Block: BlockT, F: Fetcher<Block>>(
+	fetcher: Arc<F>,
+	headers: Vec<Block::Header>,
+	keys: Vec<StorageKey>,
+) -> Result<Vec<StorageChangeSet<Block::Hash>>, Error> {
+	if keys.is_empty() {
+		return Ok(Vec::new())
+	}
+
+	let raw_keys = keys.iter().map(|key| key.0.clone()).collect::<Vec<_>>();
+	let mut reads = futures::stream::iter(headers.into_iter().map(|header| {
+		let block = header.hash();
+		fetcher.remote_read(RemoteReadRequest {
+			block,
+			header,
+			keys: raw_keys.clone(),
+			retry_count: Default::default(),
+		}).map(move |values| values.map(|values| (block, values)))
+	})).buffered(QUERY_STORAGE_PARALLEL_READS);
+
+	let mut changes = Vec::new();
+	let mut last_values = HashMap::new();
+	while let Some(read) = reads.next().await {
+		let (block, values) = read.map_err(client_err)?;
+		let mut block_changes = StorageChangeSet { block, changes: Vec::new() };
+		for key in &keys {
+			let data = values.get(&key.0).cloned().flatten().map(StorageData);
+			if last_values.get(key) != Some(&data) {
+				block_changes.changes.push((key.clone(), data.clone()));
+				last_values.insert(key.clone(), data);
+			}
+		}
+		if !block_changes.changes.is_empty() {
+			changes.push(block_changes);
+		}
+	}
+	Ok(changes)
+}
+
 /// Returns subscription stream that issues request on every imported block and
 /// if value has changed from previous block, emits (stream) item.
 fn subscription_stream<
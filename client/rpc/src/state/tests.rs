@@ -136,6 +136,129 @@ fn should_return_child_storage() {
 	);
 }
 
+#[test]
+fn should_return_child_read_proof() {
+	let child_info = ChildInfo::new_default(STORAGE_KEY);
+	let client = Arc::new(tetcore_test_runtime_client::TestClientBuilder::new()
+		.add_child_storage(&child_info, "key", vec![42_u8])
+		.build());
+	let genesis_hash = client.genesis_hash();
+	let (_client, child) = new_full(
+		client,
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+	);
+	let key = StorageKey(b"key".to_vec());
+
+	assert_matches!(
+		child.read_child_proof(
+			prefixed_storage_key(),
+			vec![key.clone()],
+			None,
+		).wait(),
+		Ok(ReadProof { at, ref proof }) if at == genesis_hash && !proof.is_empty()
+	);
+	assert_matches!(
+		child.read_child_proof(
+			PrefixedStorageKey::new(b"invalid".to_vec()),
+			vec![key],
+			None,
+		).wait(),
+		Err(Error::Client(_))
+	);
+}
+
+#[test]
+fn should_return_compact_read_proof() {
+	use tp_blockchain::HeaderBackend;
+	use tp_runtime::traits::Header as HeaderT;
+
+	let client = Arc::new(tetcore_test_runtime_client::new());
+	let genesis_hash = client.genesis_hash();
+	let state_root = *client.header(BlockId::Hash(genesis_hash)).unwrap().unwrap().state_root();
+	let (api, _child) = new_full(
+		client,
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+	);
+	let key = StorageKey(tet_core::storage::well_known_keys::CODE.to_vec());
+
+	let full = api.read_proof(vec![key.clone()], None, None).wait().unwrap();
+	let compact = api.read_proof(vec![key.clone()], None, Some(true)).wait().unwrap();
+	assert_eq!(compact.at, genesis_hash);
+	let size = |proof: &ReadProof<H256>| proof.proof.iter().map(|node| node.0.len()).sum::<usize>();
+	assert!(size(&compact) < size(&full));
+
+	let compact = tp_trie::CompactProof {
+		encoded_nodes: compact.proof.into_iter().map(|node| node.0).collect(),
+	};
+	let (proof, root) = compact.to_storage_proof::<tp_runtime::traits::BlakeTwo256>(Some(&state_root))
+		.unwrap();
+	assert_eq!(root, state_root);
+	let value = tp_trie::read_trie_value::<tp_trie::Layout<tp_runtime::traits::BlakeTwo256>, _>(
+		&proof.into_memory_db(),
+		&state_root,
+		&key.0,
+	).unwrap();
+	assert!(value.is_some());
+}
+
+#[test]
+fn should_page_and_stream_child_storage() {
+	let child_info = ChildInfo::new_default(STORAGE_KEY);
+	let client = Arc::new(tetcore_test_runtime_client::TestClientBuilder::new()
+		.add_child_storage(&child_info, "key1", vec![1_u8])
+		.add_child_storage(&child_info, "key2", vec![2_u8])
+		.add_child_storage(&child_info, "key3", vec![3_u8])
+		.build());
+	let (_client, child) = new_full(
+		client,
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+	);
+
+	assert_matches!(
+		child.storage_keys_paged(prefixed_storage_key(), None, 2, None, None).wait(),
+		Ok(ref keys) if keys == &[StorageKey(b"key1".to_vec()), StorageKey(b"key2".to_vec())]
+	);
+	assert_matches!(
+		child.storage_keys_paged(
+			prefixed_storage_key(),
+			None,
+			2,
+			Some(StorageKey(b"key2".to_vec())),
+			None,
+		).wait(),
+		Ok(ref keys) if keys == &[StorageKey(b"key3".to_vec())]
+	);
+
+	let (subscriber, id, transport) = Subscriber::new_test("test");
+	child.subscribe_storage_pairs(
+		Default::default(),
+		subscriber,
+		prefixed_storage_key(),
+		StorageKey(b"key".to_vec()),
+		2,
+		None,
+	);
+
+	// assert id assigned
+	assert!(matches!(
+		executor::block_on(id.compat()),
+		Ok(Ok(SubscriptionId::String(_)))
+	));
+
+	// "key1" and "key2", then "key3" resumed after the last key of the first batch.
+	let (notification, next) = executor::block_on(transport.into_future().compat()).unwrap();
+	let notification = notification.unwrap();
+	assert!(notification.contains(r#""complete":false"#));
+	assert!(notification.contains("0x6b657931") && notification.contains("0x6b657932"));
+	let (notification, _) = executor::block_on(next.into_future().compat()).unwrap();
+	let notification = notification.unwrap();
+	assert!(notification.contains(r#""complete":true"#));
+	assert!(notification.contains("0x6b657933") && !notification.contains("0x6b657932"));
+}
+
 #[test]
 fn should_call_contract() {
 	let client = Arc::new(tetcore_test_runtime_client::new());
@@ -446,6 +569,103 @@ fn should_query_storage() {
 	);
 }
 
+#[test]
+fn should_trace_storage_diff() {
+	let mut client = Arc::new(tetcore_test_runtime_client::new());
+	let (api, _child) = new_full(
+		client.clone(),
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+	);
+
+	let mut add_block = |changes: Vec<(Vec<u8>, Option<Vec<u8>>)>| {
+		let mut builder = client.new_block(Default::default()).unwrap();
+		for (key, value) in changes {
+			builder.push_storage_change(key, value).unwrap();
+		}
+		let block = builder.build().unwrap().block;
+		let hash = block.header.hash();
+		client.import(BlockOrigin::Own, block).unwrap();
+		hash
+	};
+	let block1_hash = add_block(vec![
+		(vec![0xff, 1], Some(vec![1])),
+		(vec![0xff, 2], Some(vec![2])),
+		(vec![0xff, 3], Some(vec![3])),
+	]);
+	let block2_hash = add_block(vec![
+		(vec![0xff, 1], Some(vec![1])),
+		(vec![0xff, 2], None),
+		(vec![0xff, 3], Some(vec![33])),
+		(vec![0xff, 4], Some(vec![4])),
+	]);
+
+	assert_eq!(
+		api.trace_storage_diff(block1_hash, block2_hash, StorageKey(vec![0xff])).wait().unwrap(),
+		vec![
+			StorageDiffEntry {
+				key: StorageKey(vec![0xff, 2]),
+				from: Some(StorageData(vec![2])),
+				to: None,
+			},
+			StorageDiffEntry {
+				key: StorageKey(vec![0xff, 3]),
+				from: Some(StorageData(vec![3])),
+				to: Some(StorageData(vec![33])),
+			},
+			StorageDiffEntry {
+				key: StorageKey(vec![0xff, 4]),
+				from: None,
+				to: Some(StorageData(vec![4])),
+			},
+		],
+	);
+	assert_eq!(
+		api.trace_storage_diff(block2_hash, block2_hash, StorageKey(vec![0xff])).wait().unwrap(),
+		vec![],
+	);
+	assert_matches!(
+		api.trace_storage_diff(block1_hash, H256::repeat_byte(1), StorageKey(vec![0xff])).wait(),
+		Err(Error::Client(_))
+	);
+}
+
+#[test]
+fn should_trace_block() {
+	let mut client = Arc::new(tetcore_test_runtime_client::new());
+	let (api, _child) = new_full(
+		client.clone(),
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+	);
+
+	let mut builder = client.new_block(Default::default()).unwrap();
+	builder.push_storage_change(vec![0xff, 1], Some(vec![1])).unwrap();
+	builder.push_storage_change(vec![0xfe, 2], Some(vec![2])).unwrap();
+	let block = builder.build().unwrap().block;
+	let block_hash = block.header.hash();
+	client.import(BlockOrigin::Own, block).unwrap();
+
+	let trace = api.trace_block(block_hash, Some("state".into()), Some(vec![StorageKey(vec![0xff])]))
+		.wait()
+		.unwrap();
+	assert_eq!(trace.block_hash, block_hash);
+	assert_eq!(trace.parent_hash, client.genesis_hash());
+	assert_eq!(trace.extrinsics.len(), 2);
+
+	let puts = |traces: &Traces| traces.events.iter()
+		.filter(|event| event.values.get("method") == Some(&"Put".into()))
+		.map(|event| event.values["key"].clone())
+		.collect::<Vec<_>>();
+	assert_eq!(puts(&trace.extrinsics[0]), vec![serde_json::Value::from("ff01")]);
+	assert!(puts(&trace.extrinsics[1]).is_empty());
+
+	assert_matches!(
+		api.trace_block(H256::repeat_byte(1), None, None).wait(),
+		Err(Error::Client(_))
+	);
+}
+
 #[test]
 fn should_split_ranges() {
 	assert_eq!(split_range(1, None), (0..1, None));
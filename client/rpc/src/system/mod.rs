@@ -31,7 +31,7 @@ use tp_runtime::traits::{self, Header as HeaderT};
 use self::error::Result;
 
 pub use tc_rpc_api::system::*;
-pub use self::helpers::{SystemInfo, Health, PeerInfo, NodeRole, SyncState};
+pub use self::helpers::{SystemInfo, Health, PeerInfo, PeerBan, ProtocolTraffic, NodeRole, SyncState};
 pub use self::gen_client::Client as SystemClient;
 
 macro_rules! bail_if_unsafe {
@@ -66,6 +66,12 @@ pub enum Request<B: traits::Block> {
 	NetworkAddReservedPeer(String, oneshot::Sender<Result<()>>),
 	/// Must return any potential parse error.
 	NetworkRemoveReservedPeer(String, oneshot::Sender<Result<()>>),
+	/// Must return any potential parse error.
+	NetworkBanPeer(String, Option<u64>, oneshot::Sender<Result<()>>),
+	/// Must return any potential parse error.
+	NetworkUnbanPeer(String, oneshot::Sender<Result<()>>),
+	/// Must return the peers currently banned.
+	NetworkBannedPeers(oneshot::Sender<Vec<PeerBan>>),
 	/// Must return the node role.
 	NodeRoles(oneshot::Sender<Vec<NodeRole>>),
 	/// Must return the state of the node syncing.
@@ -187,6 +193,51 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 		}.boxed().compat()
 	}
 
+	fn system_ban_peer(&self, peer_id: String, duration: Option<u64>)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkBanPeer(peer_id, duration, tx));
+		async move {
+			match rx.await {
+				Ok(Ok(())) => Ok(()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}.boxed().compat()
+	}
+
+	fn system_unban_peer(&self, peer_id: String)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkUnbanPeer(peer_id, tx));
+		async move {
+			match rx.await {
+				Ok(Ok(())) => Ok(()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}.boxed().compat()
+	}
+
+	fn system_list_bans(&self)
+		-> Compat<BoxFuture<'static, rpc::Result<Vec<PeerBan>>>>
+	{
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkBannedPeers(tx));
+
+		async move {
+			rx.await.map_err(|_| rpc::Error::internal_error())
+		}.boxed().compat()
+	}
+
 	fn system_node_roles(&self) -> Receiver<Vec<NodeRole>> {
 		let (tx, rx) = oneshot::channel();
 		let _ = self.send_back.unbounded_send(Request::NodeRoles(tx));
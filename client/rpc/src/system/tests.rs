@@ -78,6 +78,11 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 							roles: format!("{}", Role::Full),
 							best_hash: Default::default(),
 							best_number: 1,
+							protocols: vec![ProtocolTraffic {
+								protocol: "/foo".into(),
+								bytes_in: 10,
+								bytes_out: 20,
+							}],
 						});
 					}
 					let _ = sender.send(peers);
@@ -104,6 +109,18 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 						Err(s) => sender.send(Err(error::Error::MalformattedPeerArg(s.to_string()))),
 					};
 				}
+				Request::NetworkBanPeer(peer, _, sender) | Request::NetworkUnbanPeer(peer, sender) => {
+					let _ = match peer.parse::<PeerId>() {
+						Ok(_) => sender.send(Ok(())),
+						Err(s) => sender.send(Err(error::Error::MalformattedPeerArg(s.to_string()))),
+					};
+				}
+				Request::NetworkBannedPeers(sender) => {
+					let _ = sender.send(vec![PeerBan {
+						peer_id: "QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV".to_string(),
+						expires: None,
+					}]);
+				}
 				Request::NodeRoles(sender) => {
 					let _ = sender.send(vec![NodeRole::Authority]);
 				}
@@ -270,6 +287,11 @@ fn system_peers() {
 			roles: "FULL".into(),
 			best_hash: Default::default(),
 			best_number: 1u64,
+			protocols: vec![ProtocolTraffic {
+				protocol: "/foo".into(),
+				bytes_in: 10,
+				bytes_out: 20,
+			}],
 		}]
 	);
 }
@@ -337,6 +359,36 @@ fn system_network_remove_reserved() {
 	assert!(runtime.block_on(bad_fut).is_err());
 }
 
+#[test]
+fn system_network_ban_and_unban() {
+	let good_peer_id = "QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let bad_peer_id = "/ip4/198.51.100.19/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let good_fut = api(None).system_ban_peer(good_peer_id.into(), Some(60));
+	let bad_fut = api(None).system_ban_peer(bad_peer_id.into(), None);
+	assert_eq!(runtime.block_on(good_fut), Ok(()));
+	assert!(runtime.block_on(bad_fut).is_err());
+
+	let good_fut = api(None).system_unban_peer(good_peer_id.into());
+	let bad_fut = api(None).system_unban_peer(bad_peer_id.into());
+	assert_eq!(runtime.block_on(good_fut), Ok(()));
+	assert!(runtime.block_on(bad_fut).is_err());
+}
+
+#[test]
+fn system_list_bans() {
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	assert_eq!(
+		runtime.block_on(api(None).system_list_bans()).unwrap(),
+		vec![PeerBan {
+			peer_id: "QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV".to_string(),
+			expires: None,
+		}],
+	);
+}
+
 #[test]
 fn test_add_reset_log_filter() {
 	const EXPECTED_BEFORE_ADD: &'static str = "EXPECTED_BEFORE_ADD";
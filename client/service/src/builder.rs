@@ -43,6 +43,7 @@ use log::{info, warn};
 use tc_network::config::{Role, OnDemand};
 use tc_network::NetworkService;
 use tc_network::block_request_handler::{self, BlockRequestHandler};
+use tc_network::state_request_handler::{self, StateRequestHandler};
 use tp_runtime::generic::BlockId;
 use tp_runtime::traits::{
 	Block as BlockT, HashFor, Zero, BlockIdTo,
@@ -296,6 +297,39 @@ pub fn new_full_client<TBl, TRtApi, TExecDisp>(
 	new_full_parts(config).map(|parts| parts.0)
 }
 
+/// Delay before the state of non-finalized blocks is canonicalized.
+const CANONICALIZATION_DELAY: u64 = 4096;
+
+fn database_settings(config: &Configuration) -> DatabaseSettings {
+	DatabaseSettings {
+		state_cache_size: config.state_cache_size,
+		state_cache_child_ratio:
+			config.state_cache_child_ratio.map(|v| (v, 100)),
+		state_pruning: config.state_pruning.clone(),
+		source: config.database.clone(),
+		keep_blocks: config.keep_blocks.clone(),
+		transaction_storage: config.transaction_storage.clone(),
+		state_storage: config.state_storage,
+	}
+}
+
+/// Open the database backend of a full node.
+///
+/// Unlike [`new_full_parts`] this doesn't create a client, so the genesis block of an empty
+/// database is not initialized.
+pub fn new_db_backend<TBl: BlockT>(
+	config: &Configuration,
+) -> Result<Arc<TFullBackend<TBl>>, Error> {
+	Ok(Arc::new(Backend::new(database_settings(config), CANONICALIZATION_DELAY)?))
+}
+
+/// Hash of the genesis block built from the chain spec of `config`.
+pub fn chain_spec_genesis_hash<TBl: BlockT>(config: &Configuration) -> Result<TBl::Hash, Error> {
+	let storage = config.chain_spec.as_storage_builder().build_storage()?;
+	let state_root = *tp_state_machine::InMemoryBackend::<HashFor<TBl>>::from(storage).root();
+	Ok(crate::client::genesis::construct_genesis_block::<TBl>(state_root).hash())
+}
+
 /// Create the initial parts of a full node.
 pub fn new_full_parts<TBl, TRtApi, TExecDisp>(
 	config: &Configuration,
@@ -326,15 +360,7 @@ pub fn new_full_parts<TBl, TRtApi, TExecDisp>(
 		.unwrap_or_default();
 
 	let (client, backend) = {
-		let db_config = tc_client_db::DatabaseSettings {
-			state_cache_size: config.state_cache_size,
-			state_cache_child_ratio:
-			config.state_cache_child_ratio.map(|v| (v, 100)),
-			state_pruning: config.state_pruning.clone(),
-			source: config.database.clone(),
-			keep_blocks: config.keep_blocks.clone(),
-			transaction_storage: config.transaction_storage.clone(),
-		};
+		let db_config = database_settings(config);
 
 		let extensions = tc_client_api::execution_extensions::ExecutionExtensions::new(
 			config.execution_strategies.clone(),
@@ -386,16 +412,7 @@ pub fn new_light_parts<TBl, TRtApi, TExecDisp>(
 	);
 
 	let db_storage = {
-		let db_settings = tc_client_db::DatabaseSettings {
-			state_cache_size: config.state_cache_size,
-			state_cache_child_ratio:
-				config.state_cache_child_ratio.map(|v| (v, 100)),
-			state_pruning: config.state_pruning.clone(),
-			source: config.database.clone(),
-			keep_blocks: config.keep_blocks.clone(),
-			transaction_storage: config.transaction_storage.clone(),
-		};
-		tc_client_db::light::LightStorage::new(db_settings)?
+		tc_client_db::light::LightStorage::new(database_settings(config))?
 	};
 	let light_blockchain = tc_light::new_light_blockchain(db_storage);
 	let fetch_checker = Arc::new(
@@ -444,8 +461,6 @@ pub fn new_client<E, Block, RA>(
 		Block: BlockT,
 		E: CodeExecutor + RuntimeInfo,
 {
-	const CANONICALIZATION_DELAY: u64 = 4096;
-
 	let backend = Arc::new(Backend::new(settings, CANONICALIZATION_DELAY)?);
 	let executor = crate::client::LocalCallExecutor::new(backend.clone(), executor, spawn_handle, config.clone())?;
 	Ok((
@@ -548,6 +563,7 @@ pub fn spawn_tasks<TBl, TBackend, TExPool, TRpc, TCl>(
 			tc_offchain::OffchainWorkerApi<TBl> +
 			tp_transaction_pool::runtime_api::TaggedTransactionQueue<TBl> +
 			tp_session::SessionKeys<TBl> +
+			tp_block_builder::BlockBuilder<TBl> +
 			tp_api::ApiErrorExt<Error = tp_blockchain::Error> +
 			tp_api::ApiExt<TBl, StateBackend = TBackend::State>,
 		TBl: BlockT,
@@ -735,7 +751,8 @@ fn gen_handler<TBl, TBackend, TExPool, TRpc, TCl>(
 		TRpc: tc_rpc::RpcExtension<tc_rpc::Metadata>,
 		<TCl as ProvideRuntimeApi<TBl>>::Api:
 			tp_session::SessionKeys<TBl> +
-			tp_api::Metadata<TBl, Error = tp_blockchain::Error>,
+			tp_api::Metadata<TBl, Error = tp_blockchain::Error> +
+			tp_block_builder::BlockBuilder<TBl, Error = tp_blockchain::Error>,
 {
 	use tc_rpc::{chain, state, author, system, offchain};
 
@@ -825,6 +842,11 @@ pub struct BuildNetworkParams<'a, TBl: BlockT, TExPool, TImpQu, TCl> {
 	pub block_announce_validator_builder: Option<Box<
 		dyn FnOnce(Arc<TCl>) -> Box<dyn BlockAnnounceValidator<TBl> + Send> + Send
 	>>,
+	/// Builds the data attached to the announcements of the blocks authored by this node, for
+	/// example an encoded `AuthorshipClaim`.
+	pub block_announce_data_builder: Option<Box<
+		dyn Fn(&TBl::Header) -> Option<Vec<u8>> + Send
+	>>,
 }
 
 /// Build the network service, the network status sinks and an RPC sender.
@@ -849,7 +871,7 @@ pub fn build_network<TBl, TExPool, TImpQu, TCl>(
 {
 	let BuildNetworkParams {
 		config, client, transaction_pool, spawn_handle, import_queue, on_demand,
-		block_announce_validator_builder,
+		block_announce_validator_builder, block_announce_data_builder,
 	} = params;
 
 	let transaction_pool_adapter = Arc::new(TransactionPoolAdapter {
@@ -881,6 +903,21 @@ pub fn build_network<TBl, TExPool, TImpQu, TCl>(
 		}
 	};
 
+	let state_request_protocol_config = {
+		if matches!(config.role, Role::Light) {
+			// Allow outgoing requests but deny incoming requests.
+			state_request_handler::generate_protocol_config(protocol_id.clone())
+		} else {
+			// Allow both outgoing and incoming requests.
+			let (handler, protocol_config) = StateRequestHandler::new(
+				protocol_id.clone(),
+				client.clone(),
+			);
+			spawn_handle.spawn("state_request_handler", handler.run());
+			protocol_config
+		}
+	};
+
 	let network_params = tc_network::config::Params {
 		role: config.role.clone(),
 		executor: {
@@ -898,6 +935,7 @@ pub fn build_network<TBl, TExPool, TImpQu, TCl>(
 		block_announce_validator,
 		metrics_registry: config.prometheus_config.as_ref().map(|config| config.registry.clone()),
 		block_request_protocol_config,
+		state_request_protocol_config,
 	};
 
 	let has_bootnodes = !network_params.network_config.boot_nodes.is_empty();
@@ -915,6 +953,7 @@ pub fn build_network<TBl, TExPool, TImpQu, TCl>(
 		system_rpc_rx,
 		has_bootnodes,
 		config.announce_block,
+		block_announce_data_builder,
 	);
 
 	// TODO: Normally, one is supposed to pass a list of notifications protocols supported by the
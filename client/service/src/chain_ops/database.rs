@@ -0,0 +1,54 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Offline maintenance of the database of a full node.
+
+use crate::error::Error;
+use crate::config::DatabaseConfig;
+use tc_client_db::maintenance;
+use tp_runtime::traits::Block as BlockT;
+
+pub use tc_client_db::maintenance::{ColumnStats, RebuildReport, VerifyReport};
+
+/// Check the consistency of the headers, bodies, lookup keys, meta keys and state roots.
+pub fn verify_database<B: BlockT>(database: &DatabaseConfig) -> Result<VerifyReport, Error> {
+	let db = maintenance::open_database::<B>(database)?;
+	Ok(maintenance::verify::<B>(&*db)?)
+}
+
+/// Rebuild the lookup keys, the leaves and the best and finalized blocks from the headers.
+pub fn rebuild_database<B: BlockT>(database: &DatabaseConfig) -> Result<RebuildReport<B>, Error> {
+	let db = maintenance::open_database::<B>(database)?;
+	Ok(maintenance::rebuild::<B>(&*db)?)
+}
+
+/// Count the entries and bytes stored in each column of the database.
+pub fn database_column_stats<B: BlockT>(database: &DatabaseConfig) -> Result<Vec<ColumnStats>, Error> {
+	let db = maintenance::open_database::<B>(database)?;
+	Ok(maintenance::column_stats(&*db)?)
+}
+
+/// Copy the database at `source` to the new database at `target`.
+///
+/// Returns the number of copied entries.
+pub fn convert_database<B: BlockT>(
+	source: &DatabaseConfig,
+	target: &DatabaseConfig,
+) -> Result<u64, Error> {
+	Ok(maintenance::convert::<B>(source, target)?)
+}
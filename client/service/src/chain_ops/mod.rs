@@ -19,13 +19,17 @@
 //! Chain utilities.
 
 mod check_block;
+mod database;
 mod export_blocks;
 mod export_raw_state;
 mod import_blocks;
 mod revert_chain;
+mod snapshot;
 
 pub use check_block::*;
+pub use database::*;
 pub use export_blocks::*;
 pub use export_raw_state::*;
 pub use import_blocks::*;
 pub use revert_chain::*;
+pub use snapshot::*;
@@ -0,0 +1,291 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2020-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Offline state snapshots.
+//!
+//! A snapshot is a SCALE encoded stream made of:
+//!
+//! - [`SNAPSHOT_MAGIC`],
+//! - the headers from genesis up to the snapshot block, encoded as a `Vec`,
+//! - the key-value pairs of every default child trie, then of the top trie without the child
+//!   trie roots, as a sequence of `Some(SnapshotChunk)` terminated by `None`.
+//!
+//! Each chunk carries a read proof of its entries against the state root of the snapshot
+//! block, so a corrupted chunk is detected as soon as it is read.
+
+use crate::error::Error;
+use crate::TFullBackend;
+use log::info;
+use codec::{Compact, Decode, Encode, IoReader as CodecIoReader};
+use tp_blockchain::{Error as ClientError, HeaderBackend};
+use tp_runtime::traits::{Block as BlockT, Header as HeaderT, HashFor, One, Zero, SaturatedConversion};
+use tp_runtime::generic::BlockId;
+use tet_core::hexdisplay::HexDisplay;
+use tet_core::storage::{StorageKey, well_known_keys, ChildInfo};
+use tc_client_api::{ProofProvider, StorageProof, StorageProvider};
+
+use std::{io::{Read, Write}, sync::Arc};
+
+/// Magic bytes at the start of every state snapshot.
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"tcsnap01";
+
+/// A chunk of key-value pairs of one trie in a state snapshot.
+#[derive(Debug, Encode, Decode)]
+pub struct SnapshotChunk {
+	/// Storage key of the default child trie holding the entries, `None` for the top trie.
+	pub child: Option<Vec<u8>>,
+	/// Key-value pairs, in key order.
+	pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+	/// Read proof of the entries against the state root of the snapshot block.
+	pub proof: StorageProof,
+}
+
+/// Export a snapshot of the state at `block`. If `block` is `None`, the last
+/// finalized block will be used.
+///
+/// Only finalized blocks can be exported, so that the ancestry of the snapshot is canonical.
+/// The state is written in chunks of at most `chunk_size` entries.
+pub fn export_state_snapshot<B, BA, C>(
+	client: Arc<C>,
+	block: Option<BlockId<B>>,
+	mut output: impl Write,
+	chunk_size: usize,
+) -> Result<(), Error>
+where
+	C: HeaderBackend<B> + StorageProvider<B, BA> + ProofProvider<B>,
+	B: BlockT,
+	BA: tc_client_api::backend::Backend<B>,
+{
+	let info = client.info();
+	let block = block.unwrap_or_else(|| BlockId::Hash(info.finalized_hash));
+	let header = client.header(block.clone())?
+		.ok_or_else(|| Error::Other(format!("Unknown block {:?}", block)))?;
+	let hash = header.hash();
+	let number = *header.number();
+	if number > info.finalized_number || client.hash(number)? != Some(hash) {
+		return Err(Error::Other(format!("Block #{} ({}) is not finalized", number, hash)));
+	}
+
+	info!("Exporting state snapshot at #{} ({})", number, hash);
+	output.write_all(SNAPSHOT_MAGIC)?;
+
+	// Headers are written one at a time, using the same encoding as a `Vec` of headers.
+	output.write_all(&Compact(number.saturated_into::<u32>() + 1).encode())?;
+	let mut ancestor = Zero::zero();
+	loop {
+		let header = client.header(BlockId::Number(ancestor))?
+			.ok_or_else(|| Error::Other(format!("Missing header of block #{}", ancestor)))?;
+		output.write_all(&header.encode())?;
+		if ancestor == number {
+			break;
+		}
+		ancestor = ancestor + One::one();
+	}
+
+	let id = BlockId::Hash(hash);
+	let chunk_size = chunk_size.max(1);
+	let mut entries = 0;
+	let mut chunks = 0;
+
+	// Child tries come first, so that their roots are known when the top trie is imported.
+	let child_prefix = StorageKey(well_known_keys::CHILD_STORAGE_KEY_PREFIX.to_vec());
+	for key in client.storage_keys_iter(&id, Some(&child_prefix), None)? {
+		if !key.0.starts_with(well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX) {
+			return Err(Error::Other(
+				format!("Unsupported child trie {}", HexDisplay::from(&key.0)),
+			));
+		}
+		let child_key = &key.0[well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX.len()..];
+		let child_info = ChildInfo::new_default(child_key);
+		let keys = client.child_storage_keys(&id, &child_info, &StorageKey(Vec::new()))?;
+		for keys in keys.chunks(chunk_size) {
+			entries += write_chunk(&*client, &id, Some(&child_info), keys, &mut output)?;
+			chunks += 1;
+		}
+	}
+
+	let mut keys = Vec::with_capacity(chunk_size);
+	for key in client.storage_keys_iter(&id, None, None)? {
+		if well_known_keys::is_child_storage_key(&key.0) {
+			continue;
+		}
+
+		keys.push(key);
+		if keys.len() == chunk_size {
+			entries += write_chunk(&*client, &id, None, &keys, &mut output)?;
+			chunks += 1;
+			keys.clear();
+		}
+	}
+	if !keys.is_empty() {
+		entries += write_chunk(&*client, &id, None, &keys, &mut output)?;
+		chunks += 1;
+	}
+	output.write_all(&None::<SnapshotChunk>.encode())?;
+
+	info!("Exported {} entries in {} chunks", entries, chunks);
+	Ok(())
+}
+
+fn write_chunk<B, BA, C>(
+	client: &C,
+	id: &BlockId<B>,
+	child_info: Option<&ChildInfo>,
+	keys: &[StorageKey],
+	output: &mut impl Write,
+) -> Result<usize, Error>
+where
+	C: StorageProvider<B, BA> + ProofProvider<B>,
+	B: BlockT,
+	BA: tc_client_api::backend::Backend<B>,
+{
+	let mut entries = Vec::with_capacity(keys.len());
+	for key in keys {
+		let value = match child_info {
+			Some(child_info) => client.child_storage(id, child_info, key)?,
+			None => client.storage(id, key)?,
+		};
+		if let Some(value) = value {
+			entries.push((key.0.clone(), value.0));
+		}
+	}
+
+	let mut proof_keys = entries.iter().map(|(key, _)| &key[..]);
+	let proof = match child_info {
+		Some(child_info) => client.read_child_proof(id, child_info, &mut proof_keys)?,
+		None => client.read_proof(id, &mut proof_keys)?,
+	};
+
+	let count = entries.len();
+	let chunk = SnapshotChunk {
+		child: child_info.map(|child_info| child_info.storage_key().to_vec()),
+		entries,
+		proof,
+	};
+	output.write_all(&Some(chunk).encode())?;
+	Ok(count)
+}
+
+/// Initialize an empty database from a snapshot written by [`export_state_snapshot`].
+///
+/// The snapshot must start at the genesis block `genesis_hash` of the chain spec. Every chunk
+/// is checked against the state root of the snapshot block while reading, and its entries are
+/// written to the database as they are read. If the import fails, the database is left with
+/// part of the state and should be removed.
+pub fn import_state_snapshot<B: BlockT>(
+	backend: Arc<TFullBackend<B>>,
+	genesis_hash: B::Hash,
+	input: impl Read,
+) -> Result<(), Error> {
+	let mut reader = CodecIoReader(input);
+	let magic = <[u8; 8]>::decode(&mut reader)
+		.map_err(|e| format!("Failed to read the snapshot: {:?}", e))?;
+	if &magic != SNAPSHOT_MAGIC {
+		return Err("Input is not a state snapshot".into());
+	}
+
+	let headers = Vec::<B::Header>::decode(&mut reader)
+		.map_err(|e| format!("Failed to decode the snapshot headers: {:?}", e))?;
+	let base = headers.last().ok_or("Snapshot contains no headers")?;
+	let (number, hash, state_root) = (*base.number(), base.hash(), *base.state_root());
+	info!("Importing state snapshot at #{} ({})", number, hash);
+
+	let mut entries = SnapshotEntries::<B, _> {
+		reader,
+		state_root,
+		child: None,
+		chunk: Vec::new().into_iter(),
+		done: false,
+		count: 0,
+	};
+	backend.import_state_snapshot(genesis_hash, headers, &mut entries)?;
+	info!("Imported {} entries, best: #{} ({})", entries.count, number, hash);
+	Ok(())
+}
+
+/// The entries of a snapshot, read one chunk at a time.
+struct SnapshotEntries<B: BlockT, R> {
+	reader: CodecIoReader<R>,
+	state_root: B::Hash,
+	/// Prefixed storage key of the child trie of the current chunk.
+	child: Option<Vec<u8>>,
+	/// Remaining entries of the current chunk.
+	chunk: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+	done: bool,
+	count: usize,
+}
+
+impl<B: BlockT, R: Read> SnapshotEntries<B, R> {
+	/// Read and check the next chunk, returns `false` at the end of the snapshot.
+	fn read_chunk(&mut self) -> Result<bool, String> {
+		let chunk = match Option::<SnapshotChunk>::decode(&mut self.reader)
+			.map_err(|e| format!("Failed to decode a snapshot chunk: {:?}", e))?
+		{
+			Some(chunk) => chunk,
+			None => return Ok(false),
+		};
+
+		let child_info = chunk.child.as_ref().map(|key| ChildInfo::new_default(key));
+		let keys = chunk.entries.iter().map(|(key, _)| key);
+		let proved = match &child_info {
+			Some(child_info) => tp_state_machine::read_child_proof_check::<HashFor<B>, _>(
+				self.state_root,
+				chunk.proof,
+				child_info,
+				keys,
+			),
+			None => tp_state_machine::read_proof_check::<HashFor<B>, _>(self.state_root, chunk.proof, keys),
+		}.map_err(|e| format!("Invalid snapshot chunk proof: {}", e))?;
+
+		for (key, value) in &chunk.entries {
+			match proved.get(key) {
+				Some(Some(proved)) if proved == value => {},
+				_ => return Err(
+					format!("Snapshot entry {} doesn't match its proof", HexDisplay::from(key)),
+				),
+			}
+		}
+		self.child = child_info.map(|child_info| child_info.into_prefixed_storage_key().into_inner());
+		self.chunk = chunk.entries.into_iter();
+		Ok(true)
+	}
+}
+
+impl<B: BlockT, R: Read> Iterator for SnapshotEntries<B, R> {
+	type Item = tp_blockchain::Result<tc_client_db::SnapshotEntry>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some((key, value)) = self.chunk.next() {
+				self.count += 1;
+				return Some(Ok((self.child.clone(), key, value)));
+			}
+			if self.done {
+				return None;
+			}
+			match self.read_chunk() {
+				Ok(true) => {},
+				Ok(false) => self.done = true,
+				Err(e) => {
+					self.done = true;
+					return Some(Err(ClientError::Backend(e)));
+				},
+			}
+		}
+	}
+}
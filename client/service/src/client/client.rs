@@ -46,13 +46,14 @@ use tp_runtime::{
 };
 use tp_state_machine::{
 	DBValue, Backend as StateBackend, ChangesTrieAnchorBlockId,
-	prove_read, prove_child_read, ChangesTrieRootsStorage, ChangesTrieStorage,
-	ChangesTrieConfigurationRange, key_changes, key_changes_proof,
+	prove_read, prove_child_read, prove_range_read_with_size, prove_keys_paged,
+	ChangesTrieRootsStorage, ChangesTrieStorage, ChangesTrieConfigurationRange,
+	key_changes, key_changes_proof,
 };
 use tc_executor::RuntimeVersion;
 use tp_consensus::{
 	Error as ConsensusError, BlockStatus, BlockImportParams, BlockCheckParams,
-	ImportResult, BlockOrigin, ForkChoiceStrategy, RecordProof,
+	ImportResult, BlockOrigin, ForkChoiceStrategy, RecordProof, ImportedState,
 };
 use tp_blockchain::{
 	self as blockchain,
@@ -619,6 +620,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			fork_choice,
 			intermediates,
 			import_existing,
+			imported_state,
 			..
 		} = import_block;
 
@@ -658,6 +660,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			auxiliary,
 			fork_choice,
 			import_existing,
+			imported_state,
 		);
 
 		if let Ok(ImportResult::Imported(ref aux)) = result {
@@ -694,6 +697,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 		fork_choice: ForkChoiceStrategy,
 		import_existing: bool,
+		imported_state: Option<ImportedState<Block>>,
 	) -> tp_blockchain::Result<ImportResult> where
 		Self: ProvideRuntimeApi<Block>,
 		<Self as ProvideRuntimeApi<Block>>::Api: CoreApi<Block, Error = Error> +
@@ -712,8 +716,12 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		let info = self.backend.blockchain().info();
 
 		// the block is lower than our last finalized block so it must revert
-		// finality, refusing import.
-		if *import_headers.post().number() <= info.finalized_number {
+		// finality, refusing import. Blocks of the finalized chain that were
+		// imported without state can still be imported again.
+		let number = *import_headers.post().number();
+		if number <= info.finalized_number &&
+			!(import_existing && self.backend.blockchain().hash(number)? == Some(hash))
+		{
 			return Err(tp_blockchain::Error::NotInFinalizedChain);
 		}
 
@@ -725,6 +733,19 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			BlockOrigin::Genesis | BlockOrigin::NetworkInitialSync | BlockOrigin::File => false,
 		};
 
+		if let Some(imported_state) = imported_state {
+			if imported_state.block != hash {
+				return Err(Error::UnknownBlock(format!("{:?}", imported_state.block)));
+			}
+			// The downloaded state replaces the state of the ancestors, which may be missing.
+			self.backend.begin_state_operation(&mut operation.op, BlockId::Hash(Default::default()))?;
+			let state_root = operation.op.reset_storage(imported_state.state)?;
+			if state_root != *import_headers.post().state_root() {
+				warn!("Error importing state of block {}: state root mismatch", hash);
+				return Err(Error::InvalidStateRoot);
+			}
+		}
+
 		let storage_changes = match storage_changes {
 			Some(storage_changes) => {
 				self.backend.begin_state_operation(&mut operation.op, BlockId::Hash(parent_hash))?;
@@ -841,6 +862,11 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			<Self as ProvideRuntimeApi<Block>>::Api: CoreApi<Block, Error = Error> +
 				ApiExt<Block, StateBackend = B::State>,
 	{
+		// The complete state of the block is imported, there is nothing to execute.
+		if import_block.imported_state.is_some() {
+			return Ok(None)
+		}
+
 		let parent_hash = import_block.header.parent_hash();
 		let at = BlockId::Hash(*parent_hash);
 		let enact_state = match self.block_status(&at)? {
@@ -1211,6 +1237,30 @@ impl<B, E, Block, RA> ProofProvider<Block> for Client<B, E, Block, RA> where
 				.map_err(Into::into))
 	}
 
+	fn read_range_proof(
+		&self,
+		id: &BlockId<Block>,
+		child_info: Option<&ChildInfo>,
+		start_key: Option<&[u8]>,
+		size_limit: usize,
+	) -> tp_blockchain::Result<(Vec<(Vec<u8>, Vec<u8>)>, bool, StorageProof)> {
+		self.state_at(id)
+			.and_then(|state| prove_range_read_with_size(state, child_info, start_key, size_limit)
+				.map_err(Into::into))
+	}
+
+	fn read_keys_proof(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: u32,
+	) -> tp_blockchain::Result<(Vec<Vec<u8>>, StorageProof)> {
+		self.state_at(id)
+			.and_then(|state| prove_keys_paged(state, prefix, start_key, count)
+				.map_err(Into::into))
+	}
+
 	fn execution_proof(
 		&self,
 		id: &BlockId<Block>,
@@ -1423,6 +1473,87 @@ impl<B, E, Block, RA> StorageProvider<Block, B> for Client<B, E, Block, RA> wher
 		)
 	}
 
+	fn child_storage_keys_iter<'a>(
+		&self,
+		id: &BlockId<Block>,
+		child_info: ChildInfo,
+		prefix: Option<&'a StorageKey>,
+		start_key: Option<&StorageKey>
+	) -> tp_blockchain::Result<KeyIterator<'a, B::State, Block>> {
+		let state = self.state_at(id)?;
+		let start_key = start_key
+			.or(prefix)
+			.map(|key| key.0.clone())
+			.unwrap_or_else(Vec::new);
+		Ok(KeyIterator::new_child(state, child_info, prefix, start_key))
+	}
+
+	fn storage_pairs_while(
+		&self,
+		id: &BlockId<Block>,
+		child_info: Option<&ChildInfo>,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		f: &mut dyn FnMut(StorageKey, StorageData) -> bool,
+	) -> tp_blockchain::Result<()> {
+		let state = self.state_at(id)?;
+		let state_err = |e| tp_blockchain::Error::from_state(Box::new(e));
+		let next_key = |key: &[u8]| match child_info {
+			Some(child_info) => state.next_child_storage_key(child_info, key),
+			None => state.next_storage_key(key),
+		}.map_err(state_err);
+
+		// Seek to the first key of the range, the prefix itself may be a key.
+		let mut key = match start_key {
+			Some(start_key) if start_key.0 >= key_prefix.0 => next_key(&start_key.0)?,
+			_ => Some(key_prefix.0.clone()),
+		};
+		while let Some(current) = key.take() {
+			if !current.starts_with(&key_prefix.0) {
+				break;
+			}
+			let value = match child_info {
+				Some(child_info) => state.child_storage(child_info, &current),
+				None => state.storage(&current),
+			}.map_err(state_err)?;
+			if let Some(value) = value {
+				if !f(StorageKey(current.clone()), StorageData(value)) {
+					break;
+				}
+			}
+			key = next_key(&current)?;
+		}
+		Ok(())
+	}
+
+	fn storage_diff(
+		&self,
+		first: &BlockId<Block>,
+		last: &BlockId<Block>,
+		key_prefix: &StorageKey,
+		f: &mut dyn FnMut(StorageKey, Option<StorageData>, Option<StorageData>) -> bool,
+	) -> tp_blockchain::Result<()> {
+		let not_trie_backed = || tp_blockchain::Error::Backend(
+			"Storage diff is only supported for trie backed state".into()
+		);
+		let mut first_state = self.state_at(first)?;
+		let mut last_state = self.state_at(last)?;
+		let first_backend = first_state.as_trie_backend().ok_or_else(not_trie_backed)?;
+		let last_backend = last_state.as_trie_backend().ok_or_else(not_trie_backed)?;
+		tp_trie::trie_diff::<tp_trie::Layout<HashFor<Block>>, _, _, _>(
+			first_backend.essence(),
+			first_backend.root(),
+			last_backend.essence(),
+			last_backend.root(),
+			&key_prefix.0,
+			|key, first, last| f(
+				StorageKey(key.to_vec()),
+				first.map(|value| StorageData(value.to_vec())),
+				last.map(|value| StorageData(value.to_vec())),
+			),
+		).map_err(|e| tp_blockchain::Error::Backend(format!("Error computing storage diff: {:?}", e)))
+	}
+
 	fn max_key_changes_range(
 		&self,
 		first: NumberFor<Block>,
@@ -1751,7 +1882,8 @@ impl<B, E, Block, RA> tp_consensus::BlockImport<Block> for &Client<B, E, Block,
 		{
 			BlockStatus::InChainWithState | BlockStatus::Queued if !import_existing  => return Ok(ImportResult::AlreadyInChain),
 			BlockStatus::InChainWithState | BlockStatus::Queued => {},
-			BlockStatus::InChainPruned => return Ok(ImportResult::AlreadyInChain),
+			BlockStatus::InChainPruned if !import_existing => return Ok(ImportResult::AlreadyInChain),
+			BlockStatus::InChainPruned => {},
 			BlockStatus::Unknown => {},
 			BlockStatus::KnownBad => return Ok(ImportResult::KnownBad),
 		}
@@ -20,11 +20,12 @@
 
 pub use tc_client_db::{
 	Database, PruningMode, DatabaseSettingsSrc as DatabaseConfig,
-	KeepBlocks, TransactionStorageMode
+	KeepBlocks, TransactionStorageMode, StateStorageMode,
 };
 pub use tc_network::Multiaddr;
 pub use tc_network::config::{ExtTransport, MultiaddrWithPeerId, NetworkConfiguration, Role, NodeKeyConfig};
 pub use tc_executor::WasmExecutionMethod;
+pub use tc_rpc_server::{MethodFilter, RateLimit, RpcLimits, RpcMethodFilters};
 use tc_client_api::execution_extensions::ExecutionStrategies;
 
 use std::{io, future::Future, path::{PathBuf, Path}, pin::Pin, net::SocketAddr, sync::Arc};
@@ -67,6 +68,8 @@ pub struct Configuration {
 	pub keep_blocks: KeepBlocks,
 	/// Transaction storage scheme.
 	pub transaction_storage: TransactionStorageMode,
+	/// State trie node storage scheme.
+	pub state_storage: StateStorageMode,
 	/// Chain configuration.
 	pub chain_spec: Box<dyn ChainSpec>,
 	/// Wasm execution method.
@@ -85,10 +88,14 @@ pub struct Configuration {
 	pub rpc_ipc: Option<String>,
 	/// Maximum number of connections for WebSockets RPC server. `None` if default.
 	pub rpc_ws_max_connections: Option<usize>,
+	/// Limits applied to the calls of each HTTP & WS RPC connection.
+	pub rpc_limits: RpcLimits,
 	/// CORS settings for HTTP & WS servers. `None` if all origins are allowed.
 	pub rpc_cors: Option<Vec<String>>,
 	/// RPC methods to expose (by default only a safe subset or all of them).
 	pub rpc_methods: RpcMethods,
+	/// Methods allowed and denied by the HTTP & WS RPC servers, on top of `rpc_methods`.
+	pub rpc_method_filters: RpcMethodFilters,
 	/// Prometheus endpoint configuration. `None` if disabled.
 	pub prometheus_config: Option<PrometheusConfig>,
 	/// Telemetry service URL. `None` if disabled.
@@ -39,6 +39,7 @@ use std::net::SocketAddr;
 use std::collections::HashMap;
 use std::time::Duration;
 use std::task::Poll;
+use wasm_timer::UNIX_EPOCH;
 
 use futures::{Future, FutureExt, Stream, StreamExt, stream, compat::*};
 use tc_network::{NetworkStatus, network_state::NetworkState, PeerId};
@@ -51,15 +52,15 @@ use tetcore_utils::{status_sinks, mpsc::{tracing_unbounded, TracingUnboundedRece
 
 pub use self::error::Error;
 pub use self::builder::{
-	new_full_client, new_client, new_full_parts, new_light_parts,
-	spawn_tasks, build_network, build_offchain_workers,
+	new_full_client, new_client, new_db_backend, chain_spec_genesis_hash, new_full_parts,
+	new_light_parts, spawn_tasks, build_network, build_offchain_workers,
 	BuildNetworkParams, KeystoreContainer, NetworkStarter, SpawnTasksParams, TFullClient, TLightClient,
 	TFullBackend, TLightBackend, TLightBackendWithHash, TLightClientWithBackend,
 	TFullCallExecutor, TLightCallExecutor, RpcExtensionBuilder, NoopRpcExtensionBuilder,
 };
 pub use config::{
 	BasePath, Configuration, DatabaseConfig, PruningMode, Role, RpcMethods, TaskExecutor, TaskType,
-	KeepBlocks, TransactionStorageMode,
+	KeepBlocks, TransactionStorageMode, StateStorageMode,
 };
 pub use tc_chain_spec::{
 	ChainSpec, GenericChainSpec, Properties, RuntimeGenesis, Extension as ChainSpecExtension,
@@ -197,6 +198,7 @@ async fn build_network_future<
 	mut rpc_rx: TracingUnboundedReceiver<tc_rpc::system::Request<B>>,
 	should_have_peers: bool,
 	announce_imported_blocks: bool,
+	block_announce_data_builder: Option<Box<dyn Fn(&B::Header) -> Option<Vec<u8>> + Send>>,
 ) {
 	let mut imported_blocks_stream = client.import_notification_stream().fuse();
 
@@ -234,7 +236,11 @@ async fn build_network_future<
 				};
 
 				if announce_imported_blocks {
-					network.service().announce_block(notification.hash, None);
+					let data = match (&notification.origin, &block_announce_data_builder) {
+						(tp_consensus::BlockOrigin::Own, Some(builder)) => builder(&notification.header),
+						_ => None,
+					};
+					network.service().announce_block(notification.hash, data);
 				}
 
 				if notification.is_new_best {
@@ -278,6 +284,13 @@ async fn build_network_future<
 								roles: format!("{:?}", p.roles),
 								best_hash: p.best_hash,
 								best_number: p.best_number,
+								protocols: network.service().peer_traffic(&peer_id).into_iter()
+									.map(|(protocol, traffic)| tc_rpc::system::ProtocolTraffic {
+										protocol: protocol.into_owned(),
+										bytes_in: traffic.bytes_in,
+										bytes_out: traffic.bytes_out,
+									})
+									.collect(),
 							}
 						).collect());
 					}
@@ -302,6 +315,38 @@ async fn build_network_future<
 							))),
 						};
 					}
+					tc_rpc::system::Request::NetworkBanPeer(peer_id, duration, sender) => {
+						let _ = match peer_id.parse::<PeerId>() {
+							Ok(peer_id) => {
+								network.service().ban_peer(peer_id, duration.map(Duration::from_secs));
+								sender.send(Ok(()))
+							}
+							Err(e) => sender.send(Err(tc_rpc::system::error::Error::MalformattedPeerArg(
+								e.to_string(),
+							))),
+						};
+					}
+					tc_rpc::system::Request::NetworkUnbanPeer(peer_id, sender) => {
+						let _ = match peer_id.parse::<PeerId>() {
+							Ok(peer_id) => {
+								network.service().unban_peer(peer_id);
+								sender.send(Ok(()))
+							}
+							Err(e) => sender.send(Err(tc_rpc::system::error::Error::MalformattedPeerArg(
+								e.to_string(),
+							))),
+						};
+					}
+					tc_rpc::system::Request::NetworkBannedPeers(sender) => {
+						let _ = sender.send(network.banned_peers().into_iter().map(|banned|
+							tc_rpc::system::PeerBan {
+								peer_id: banned.peer_id.to_base58(),
+								expires: banned.until.and_then(|until|
+									until.duration_since(UNIX_EPOCH).ok().map(|until| until.as_secs())
+								),
+							}
+						).collect());
+					}
 					tc_rpc::system::Request::NodeRoles(sender) => {
 						use tc_rpc::system::NodeRole;
 
@@ -432,6 +477,8 @@ fn start_rpc_servers<
 				gen_handler(
 					deny_unsafe(&address, &config.rpc_methods),
 					tc_rpc_server::RpcMiddleware::new(rpc_metrics.clone(), "http")
+						.with_limits(config.rpc_limits.clone())
+						.with_method_filter(config.rpc_method_filters.http.clone())
 				),
 			),
 		)?.map(|s| waiting::HttpServer(Some(s))),
@@ -444,6 +491,8 @@ fn start_rpc_servers<
 				gen_handler(
 					deny_unsafe(&address, &config.rpc_methods),
 					tc_rpc_server::RpcMiddleware::new(rpc_metrics.clone(), "ws")
+						.with_limits(config.rpc_limits.clone())
+						.with_method_filter(config.rpc_method_filters.ws.clone())
 				),
 			),
 		)?.map(|s| waiting::WsServer(Some(s))),
@@ -549,7 +598,7 @@ where
 
 		let best_block_id = BlockId::hash(self.client.info().best_hash);
 
-		let import_future = self.pool.submit_one(&best_block_id, tp_transaction_pool::TransactionSource::External, uxt);
+		let import_future = self.pool.import_from_network(&best_block_id, uxt);
 		Box::pin(async move {
 			match import_future.await {
 				Ok(_) => TransactionImport::NewGood,
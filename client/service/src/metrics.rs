@@ -281,6 +281,11 @@ impl MetricsService {
 				metrics.state_db.with_label_values(&["non_canonical"]).set(
 					info.memory.state_db.non_canonical.as_bytes() as u64,
 				);
+				if let Some(spilled) = info.memory.state_db.non_canonical_spilled {
+					metrics.state_db.with_label_values(&["non_canonical_spilled"]).set(
+						spilled.as_bytes() as u64,
+					);
+				}
 				if let Some(pruning) = info.memory.state_db.pruning {
 					metrics.state_db.with_label_values(&["pruning"]).set(pruning.as_bytes() as u64);
 				}
@@ -32,7 +32,8 @@ use tc_client_api::{
 	StorageProvider, BlockBackend, in_mem, BlockchainEvents,
 };
 use tc_client_db::{
-	Backend, DatabaseSettings, DatabaseSettingsSrc, PruningMode, KeepBlocks, TransactionStorageMode
+	Backend, DatabaseSettings, DatabaseSettingsSrc, PruningMode, KeepBlocks, TransactionStorageMode,
+	StateStorageMode,
 };
 use tc_block_builder::BlockBuilderProvider;
 use tc_service::client::{self, Client, LocalCallExecutor, new_in_mem};
@@ -1270,6 +1271,7 @@ fn doesnt_import_blocks_that_revert_finality() {
 			state_pruning: PruningMode::ArchiveAll,
 			keep_blocks: KeepBlocks::All,
 			transaction_storage: TransactionStorageMode::BlockBody,
+			state_storage: StateStorageMode::Journaled,
 			source: DatabaseSettingsSrc::RocksDb {
 				path: tmp.path().into(),
 				cache_size: 1024,
@@ -1473,6 +1475,7 @@ fn returns_status_for_pruned_blocks() {
 			state_pruning: PruningMode::keep_blocks(1),
 			keep_blocks: KeepBlocks::All,
 			transaction_storage: TransactionStorageMode::BlockBody,
+			state_storage: StateStorageMode::Journaled,
 			source: DatabaseSettingsSrc::RocksDb {
 				path: tmp.path().into(),
 				cache_size: 1024,
@@ -35,7 +35,7 @@ use tc_service::{
 	GenericChainSpec,
 	ChainSpecExtension,
 	Configuration,
-	KeepBlocks, TransactionStorageMode,
+	KeepBlocks, TransactionStorageMode, StateStorageMode,
 	config::{BasePath, DatabaseConfig, KeystoreConfig},
 	RuntimeGenesis,
 	Role,
@@ -254,6 +254,7 @@ fn node_config<G: RuntimeGenesis + 'static, E: ChainSpecExtension + Clone + 'sta
 		state_pruning: Default::default(),
 		keep_blocks: KeepBlocks::All,
 		transaction_storage: TransactionStorageMode::BlockBody,
+		state_storage: StateStorageMode::Journaled,
 		chain_spec: Box::new((*spec).clone()),
 		wasm_method: tc_service::config::WasmExecutionMethod::Interpreted,
 		wasm_runtime_overrides: Default::default(),
@@ -262,8 +263,10 @@ fn node_config<G: RuntimeGenesis + 'static, E: ChainSpecExtension + Clone + 'sta
 		rpc_ipc: None,
 		rpc_ws: None,
 		rpc_ws_max_connections: None,
+		rpc_limits: Default::default(),
 		rpc_cors: None,
 		rpc_methods: Default::default(),
+		rpc_method_filters: Default::default(),
 		prometheus_config: None,
 		telemetry_endpoints: None,
 		telemetry_external_transport: None,
@@ -27,9 +27,16 @@
 //! Canonicalization function selects one root from the top of the tree and discards all other roots and
 //! their subtrees.
 //!
+//! When `Constraints::max_non_canonical_mem` is set, the values of the oldest non-canonical blocks
+//! that do not fit into the memory budget are dropped from the overlay and read back from the journal
+//! when needed.
+//!
 //! # Pruning.
 //! See `RefWindow` for pruning algorithm details. `StateDb` prunes on each canonicalization until pruning
 //! constraints are satisfied.
+//!
+//! Backends that count node references can leave the deleted nodes out of the pruning journal. The
+//! hashes of the blocks they have to prune themselves are then returned by `canonicalize_and_prune`.
 
 mod noncanonical;
 mod pruning;
@@ -89,6 +96,8 @@ pub enum Error<E: fmt::Debug> {
 	InvalidParent,
 	/// Invalid pruning mode specified. Contains expected mode.
 	InvalidPruningMode(String),
+	/// Journal record of a spilled non-canonical block is missing from the database.
+	MissingJournal,
 }
 
 /// Pinning error type.
@@ -112,6 +121,7 @@ impl<E: fmt::Debug> fmt::Debug for Error<E> {
 			Error::InvalidBlockNumber => write!(f, "Trying to insert block with invalid number"),
 			Error::InvalidParent => write!(f, "Trying to insert block with unknown parent"),
 			Error::InvalidPruningMode(e) => write!(f, "Expected pruning mode: {}", e),
+			Error::MissingJournal => write!(f, "Missing non-canonical journal record"),
 		}
 	}
 }
@@ -141,6 +151,9 @@ pub struct Constraints {
 	pub max_blocks: Option<u32>,
 	/// Maximum memory in the pruning overlay.
 	pub max_mem: Option<usize>,
+	/// Maximum memory of the values kept by the non-canonical overlay. Values of the older
+	/// non-canonical blocks beyond this limit are only kept in the database journal.
+	pub max_non_canonical_mem: Option<usize>,
 }
 
 /// Pruning mode.
@@ -160,6 +173,17 @@ impl PruningMode {
 		PruningMode::Constrained(Constraints {
 			max_blocks: Some(n),
 			max_mem: None,
+			max_non_canonical_mem: None,
+		})
+	}
+
+	/// Create a mode that keeps given number of blocks and limits memory used by the
+	/// non-canonical overlay to `max_non_canonical_mem` bytes.
+	pub fn keep_blocks_with_overlay_limit(n: u32, max_non_canonical_mem: usize) -> PruningMode {
+		PruningMode::Constrained(Constraints {
+			max_blocks: Some(n),
+			max_mem: None,
+			max_non_canonical_mem: Some(max_non_canonical_mem),
 		})
 	}
 
@@ -204,20 +228,32 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 	fn new<D: MetaDb>(
 		mode: PruningMode,
 		ref_counting: bool,
+		journal_deleted: bool,
 		db: &D,
 	) -> Result<StateDbSync<BlockHash, Key>, Error<D::Error>> {
-		trace!(target: "state-db", "StateDb settings: {:?}. Ref-counting: {}", mode, ref_counting);
+		trace!(
+			target: "state-db",
+			"StateDb settings: {:?}. Ref-counting: {}. Journal deleted: {}",
+			mode,
+			ref_counting,
+			journal_deleted,
+		);
 
 		// Check that settings match
 		Self::check_meta(&mode, db)?;
 
-		let non_canonical: NonCanonicalOverlay<BlockHash, Key> = NonCanonicalOverlay::new(db)?;
+		let max_non_canonical_mem = match mode {
+			PruningMode::Constrained(Constraints { max_non_canonical_mem, .. }) => max_non_canonical_mem,
+			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => None,
+		};
+		let non_canonical: NonCanonicalOverlay<BlockHash, Key> =
+			NonCanonicalOverlay::new(db, max_non_canonical_mem)?;
 		let pruning: Option<RefWindow<BlockHash, Key>> = match mode {
 			PruningMode::Constrained(Constraints {
 				max_mem: Some(_),
 				..
 			}) => unimplemented!(),
-			PruningMode::Constrained(_) => Some(RefWindow::new(db, ref_counting)?),
+			PruningMode::Constrained(_) => Some(RefWindow::new(db, ref_counting, journal_deleted)?),
 			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => None,
 		};
 
@@ -274,15 +310,37 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		}
 	}
 
-	fn canonicalize_block<E: fmt::Debug>(
+	fn insert_synced_block<E: fmt::Debug>(
 		&mut self,
 		hash: &BlockHash,
+		number: u64,
+		parent_hash: &BlockHash,
+		mut changeset: ChangeSet<Key>,
 	) -> Result<CommitSet<Key>, Error<E>> {
+		match self.mode {
+			PruningMode::ArchiveAll => {
+				changeset.deleted.clear();
+				Ok(CommitSet {
+					data: changeset,
+					meta: ChangeSet::default(),
+				})
+			},
+			PruningMode::Constrained(_) | PruningMode::ArchiveCanonical => {
+				self.non_canonical.insert_synced(hash, number, parent_hash, changeset)
+			}
+		}
+	}
+
+	fn canonicalize_block<D: MetaDb>(
+		&mut self,
+		hash: &BlockHash,
+		db: &D,
+	) -> Result<(CommitSet<Key>, Vec<BlockHash>), Error<D::Error>> {
 		let mut commit = CommitSet::default();
 		if self.mode == PruningMode::ArchiveAll {
-			return Ok(commit)
+			return Ok((commit, Vec::new()))
 		}
-		match self.non_canonical.canonicalize(&hash, &mut commit) {
+		match self.non_canonical.canonicalize(&hash, &mut commit, db) {
 			Ok(()) => {
 				if self.mode == PruningMode::ArchiveCanonical {
 					commit.data.deleted.clear();
@@ -293,8 +351,8 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		if let Some(ref mut pruning) = self.pruning {
 			pruning.note_canonical(&hash, &mut commit);
 		}
-		self.prune(&mut commit);
-		Ok(commit)
+		let pruned = self.prune(&mut commit);
+		Ok((commit, pruned))
 	}
 
 	fn best_canonical(&self) -> Option<u64> {
@@ -319,7 +377,8 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		}
 	}
 
-	fn prune(&mut self, commit: &mut CommitSet<Key>) {
+	fn prune(&mut self, commit: &mut CommitSet<Key>) -> Vec<BlockHash> {
+		let mut unjournaled = Vec::new();
 		if let (&mut Some(ref mut pruning), &PruningMode::Constrained(ref constraints)) = (&mut self.pruning, &self.mode) {
 			loop {
 				if pruning.window_size() <= constraints.max_blocks.unwrap_or(0) as u64 {
@@ -334,9 +393,10 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 				if pruning.next_hash().map_or(false, |h| pinned.contains_key(&h)) {
 					break;
 				}
-				pruning.prune_one(commit);
+				unjournaled.extend(pruning.prune_one(commit));
 			}
 		}
+		unjournaled
 	}
 
 	/// Revert all non-canonical blocks with the best block number.
@@ -390,16 +450,20 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		}
 	}
 
-	pub fn get<D: NodeDb, Q: ?Sized>(&self, key: &Q, db: &D) -> Result<Option<DBValue>, Error<D::Error>>
+	pub fn get<D, Q: ?Sized>(&self, key: &Q, db: &D) -> Result<Option<DBValue>, Error<<D as NodeDb>::Error>>
 	where
-		Q: AsRef<D::Key>,
+		D: NodeDb + MetaDb<Error = <D as NodeDb>::Error>,
+		Q: AsRef<<D as NodeDb>::Key>,
 		Key: std::borrow::Borrow<Q>,
 		Q: std::hash::Hash + Eq,
 	{
 		if let Some(value) = self.non_canonical.get(key) {
 			return Ok(Some(value));
 		}
-		db.get(key.as_ref()).map_err(|e| Error::Db(e))
+		if let Some(value) = self.non_canonical.get_spilled(key, db)? {
+			return Ok(Some(value));
+		}
+		NodeDb::get(db, key.as_ref()).map_err(|e| Error::Db(e))
 	}
 
 	fn apply_pending(&mut self) {
@@ -428,12 +492,39 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 	fn memory_info(&self) -> StateDbMemoryInfo {
 		StateDbMemoryInfo {
 			non_canonical: MemorySize::from_bytes(malloc_size(&self.non_canonical)),
+			non_canonical_spilled: match self.mode {
+				PruningMode::Constrained(Constraints { max_non_canonical_mem: Some(_), .. }) =>
+					Some(MemorySize::from_bytes(self.non_canonical.spilled_size())),
+				_ => None,
+			},
 			pruning: self.pruning.as_ref().map(|p| MemorySize::from_bytes(malloc_size(p))),
 			pinned: MemorySize::from_bytes(malloc_size(&self.pinned)),
 		}
 	}
 }
 
+/// Rewrite the journals of the state db in `db` for a backend that stores the nodes at other keys,
+/// e.g. when the database is converted to another storage scheme. `map_key` returns the new key
+/// of a node, and `ref_counting` and `journal_deleted` are the settings the converted database is
+/// opened with, see [`StateDb::new`].
+///
+/// Returns the rewritten metadata.
+pub fn map_journal_keys<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	ref_counting: bool,
+	journal_deleted: bool,
+	mut map_key: impl FnMut(Key) -> Key,
+) -> Result<ChangeSet<Vec<u8>>, Error<D::Error>> {
+	let mut inserted = noncanonical::map_journal_keys::<BlockHash, _, _>(db, &mut map_key)?;
+	inserted.extend(pruning::map_journal_keys::<BlockHash, _, _>(
+		db,
+		ref_counting,
+		journal_deleted,
+		&mut map_key,
+	)?);
+	Ok(ChangeSet { inserted, deleted: Vec::new() })
+}
+
 /// State DB maintenance. See module description.
 /// Can be shared across threads.
 pub struct StateDb<BlockHash: Hash, Key: Hash> {
@@ -442,13 +533,17 @@ pub struct StateDb<BlockHash: Hash, Key: Hash> {
 
 impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash, Key> {
 	/// Creates a new instance. Does not expect any metadata in the database.
+	///
+	/// Without `journal_deleted` the nodes deleted by each block are not journaled, and the
+	/// backend has to delete them itself when the block is pruned, see `canonicalize_and_prune`.
 	pub fn new<D: MetaDb>(
 		mode: PruningMode,
 		ref_counting: bool,
+		journal_deleted: bool,
 		db: &D,
 	) -> Result<StateDb<BlockHash, Key>, Error<D::Error>> {
 		Ok(StateDb {
-			db: RwLock::new(StateDbSync::new(mode, ref_counting, db)?)
+			db: RwLock::new(StateDbSync::new(mode, ref_counting, journal_deleted, db)?)
 		})
 	}
 
@@ -463,12 +558,38 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash
 		self.db.write().insert_block(hash, number, parent_hash, changeset)
 	}
 
-	/// Finalize a previously inserted block.
-	pub fn canonicalize_block<E: fmt::Debug>(
+	/// Add a block with a complete state obtained out of band, e.g. by state sync. The block does
+	/// not have to extend the canonical chain, but there must be no other non-canonical blocks.
+	pub fn insert_synced_block<E: fmt::Debug>(
 		&self,
 		hash: &BlockHash,
+		number: u64,
+		parent_hash: &BlockHash,
+		changeset: ChangeSet<Key>,
 	) -> Result<CommitSet<Key>, Error<E>> {
-		self.db.write().canonicalize_block(hash)
+		self.db.write().insert_synced_block(hash, number, parent_hash, changeset)
+	}
+
+	/// Finalize a previously inserted block. Values of the spilled non-canonical blocks
+	/// are read back from `db`.
+	pub fn canonicalize_block<D: MetaDb>(
+		&self,
+		hash: &BlockHash,
+		db: &D,
+	) -> Result<CommitSet<Key>, Error<D::Error>> {
+		self.canonicalize_and_prune(hash, db).map(|(commit, _)| commit)
+	}
+
+	/// Finalize a previously inserted block like `canonicalize_block`. Also returns the
+	/// hashes of the blocks pruned as a result whose deleted nodes were not journaled. The
+	/// backend has to delete the nodes of the state of their parents that their own state
+	/// doesn't reference.
+	pub fn canonicalize_and_prune<D: MetaDb>(
+		&self,
+		hash: &BlockHash,
+		db: &D,
+	) -> Result<(CommitSet<Key>, Vec<BlockHash>), Error<D::Error>> {
+		self.db.write().canonicalize_block(hash, db)
 	}
 
 	/// Prevents pruning of specified block and its descendants.
@@ -482,9 +603,10 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash
 	}
 
 	/// Get a value from non-canonical/pruning overlay or the backing DB.
-	pub fn get<D: NodeDb, Q: ?Sized>(&self, key: &Q, db: &D) -> Result<Option<DBValue>, Error<D::Error>>
+	pub fn get<D, Q: ?Sized>(&self, key: &Q, db: &D) -> Result<Option<DBValue>, Error<<D as NodeDb>::Error>>
 		where
-			Q: AsRef<D::Key>,
+			D: NodeDb + MetaDb<Error = <D as NodeDb>::Error>,
+			Q: AsRef<<D as NodeDb>::Key>,
 			Key: std::borrow::Borrow<Q>,
 			Q: std::hash::Hash + Eq,
 	{
@@ -533,7 +655,7 @@ mod tests {
 
 	fn make_test_db(settings: PruningMode) -> (TestDb, StateDb<H256, H256>) {
 		let mut db = make_db(&[91, 921, 922, 93, 94]);
-		let state_db = StateDb::new(settings, false, &db).unwrap();
+		let state_db = StateDb::new(settings, false, true, &db).unwrap();
 
 		db.commit(
 			&state_db
@@ -576,7 +698,7 @@ mod tests {
 				.unwrap(),
 		);
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(1)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(1), &db).unwrap());
 		state_db.apply_pending();
 		db.commit(
 			&state_db
@@ -589,9 +711,9 @@ mod tests {
 				.unwrap(),
 		);
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(21)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(21), &db).unwrap());
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(3)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(3), &db).unwrap());
 		state_db.apply_pending();
 
 		(db, state_db)
@@ -615,8 +737,20 @@ mod tests {
 		let (db, _) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(0),
 			max_mem: None,
+			max_non_canonical_mem: None,
+		}));
+		assert!(db.data_eq(&make_db(&[21, 3, 922, 94])));
+	}
+
+	#[test]
+	fn prune_window_0_spilled() {
+		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
+			max_blocks: Some(0),
+			max_mem: None,
+			max_non_canonical_mem: Some(0),
 		}));
 		assert!(db.data_eq(&make_db(&[21, 3, 922, 94])));
+		assert!(sdb.memory_info().non_canonical_spilled.is_some());
 	}
 
 	#[test]
@@ -624,6 +758,7 @@ mod tests {
 		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(1),
 			max_mem: None,
+			max_non_canonical_mem: None,
 		}));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
@@ -637,6 +772,7 @@ mod tests {
 		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(2),
 			max_mem: None,
+			max_non_canonical_mem: None,
 		}));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
@@ -648,7 +784,7 @@ mod tests {
 	#[test]
 	fn detects_incompatible_mode() {
 		let mut db = make_db(&[]);
-		let state_db = StateDb::new(PruningMode::ArchiveAll, false, &db).unwrap();
+		let state_db = StateDb::new(PruningMode::ArchiveAll, false, true, &db).unwrap();
 		db.commit(
 			&state_db
 			.insert_block::<io::Error>(
@@ -659,8 +795,12 @@ mod tests {
 			)
 			.unwrap(),
 		);
-		let new_mode = PruningMode::Constrained(Constraints { max_blocks: Some(2), max_mem: None });
-		let state_db: Result<StateDb<H256, H256>, _> = StateDb::new(new_mode, false, &db);
+		let new_mode = PruningMode::Constrained(Constraints {
+			max_blocks: Some(2),
+			max_mem: None,
+			max_non_canonical_mem: None,
+		});
+		let state_db: Result<StateDb<H256, H256>, _> = StateDb::new(new_mode, false, true, &db);
 		assert!(state_db.is_err());
 	}
 }
@@ -23,10 +23,10 @@
 //! `revert_pending`
 
 use std::fmt;
-use std::collections::{HashMap, VecDeque, hash_map::Entry};
+use std::collections::{HashMap, HashSet, VecDeque, hash_map::Entry};
 use super::{Error, DBValue, ChangeSet, CommitSet, MetaDb, Hash, to_meta_key};
 use codec::{Encode, Decode};
-use log::trace;
+use log::{trace, warn};
 
 const NON_CANONICAL_JOURNAL: &[u8] = b"noncanonical_journal";
 const LAST_CANONICAL: &[u8] = b"last_canonical";
@@ -39,10 +39,12 @@ pub struct NonCanonicalOverlay<BlockHash: Hash, Key: Hash> {
 	parents: HashMap<BlockHash, BlockHash>,
 	pending_canonicalizations: Vec<BlockHash>,
 	pending_insertions: Vec<BlockHash>,
-	values: HashMap<Key, (u32, DBValue)>, //ref counted
+	values: OverlayValues<Key>,
 	//would be deleted but kept around because block is pinned, ref counted.
 	pinned: HashMap<BlockHash, u32>,
 	pinned_insertions: HashMap<BlockHash, (Vec<Key>, u32)>,
+	//maximum size of the values kept in memory, overlays beyond it are spilled to the journal.
+	max_mem: Option<usize>,
 }
 
 #[derive(Encode, Decode)]
@@ -57,6 +59,48 @@ fn to_journal_key(block: u64, index: u64) -> Vec<u8> {
 	to_meta_key(NON_CANONICAL_JOURNAL, &(block, index))
 }
 
+fn read_journal<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	journal_key: &[u8],
+) -> Result<JournalRecord<BlockHash, Key>, Error<D::Error>> {
+	match db.get_meta(journal_key).map_err(|e| Error::Db(e))? {
+		Some(record) => Ok(Decode::decode(&mut record.as_slice())?),
+		None => Err(Error::MissingJournal),
+	}
+}
+
+/// Map the node keys of the journal records of the blocks that are not canonicalized with
+/// `map_key`. Returns the rewritten records by journal key.
+pub(crate) fn map_journal_keys<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	map_key: &mut impl FnMut(Key) -> Key,
+) -> Result<Vec<(Vec<u8>, DBValue)>, Error<D::Error>> {
+	let mut block = match db.get_meta(&to_meta_key(LAST_CANONICAL, &())).map_err(|e| Error::Db(e))? {
+		Some(buffer) => <(BlockHash, u64)>::decode(&mut buffer.as_slice())?.1 + 1,
+		None => return Ok(Vec::new()),
+	};
+	let mut records = Vec::new();
+	loop {
+		let mut index: u64 = 0;
+		while let Some(record) = db.get_meta(&to_journal_key(block, index)).map_err(|e| Error::Db(e))? {
+			let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+			let record = JournalRecord {
+				hash: record.hash,
+				parent_hash: record.parent_hash,
+				inserted: record.inserted.into_iter().map(|(k, v)| (map_key(k), v)).collect(),
+				deleted: record.deleted.into_iter().map(|k| map_key(k)).collect(),
+			};
+			records.push((to_journal_key(block, index), record.encode()));
+			index += 1;
+		}
+		if index == 0 {
+			break;
+		}
+		block += 1;
+	}
+	Ok(records)
+}
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(tetsy_util_mem_derive::MallocSizeOf)]
 struct BlockOverlay<BlockHash: Hash, Key: Hash> {
@@ -64,36 +108,123 @@ struct BlockOverlay<BlockHash: Hash, Key: Hash> {
 	journal_key: Vec<u8>,
 	inserted: Vec<Key>,
 	deleted: Vec<Key>,
+	// values of this overlay are only available in the journal record.
+	spilled: bool,
 }
 
-fn insert_values<Key: Hash>(values: &mut HashMap<Key, (u32, DBValue)>, inserted: Vec<(Key, DBValue)>) {
-	for (k, v) in inserted {
-		debug_assert!(values.get(&k).map_or(true, |(_, value)| *value == v));
-		let (ref mut counter, _) = values.entry(k).or_insert_with(|| (0, v));
-		*counter += 1;
-	}
+/// Values inserted by the overlays. Values of the spilled overlays are not kept in memory,
+/// only the journal records that contain them are tracked.
+#[derive(Default, tetsy_util_mem_derive::MallocSizeOf)]
+struct OverlayValues<Key: Hash> {
+	values: HashMap<Key, (u32, DBValue)>, //ref counted
+	spilled: HashMap<Key, (usize, Vec<Vec<u8>>)>, //value size and journal records containing the value
+	size: usize,
+	spilled_size: usize,
 }
 
-fn discard_values<Key: Hash>(values: &mut HashMap<Key, (u32, DBValue)>, inserted: Vec<Key>) {
-	for k in inserted {
-		match values.entry(k) {
-			Entry::Occupied(mut e) => {
-				let (ref mut counter, _) = e.get_mut();
-				*counter -= 1;
-				if *counter == 0 {
-					e.remove_entry();
+impl<Key: Hash> OverlayValues<Key> {
+	fn insert(&mut self, inserted: Vec<(Key, DBValue)>) {
+		for (k, v) in inserted {
+			debug_assert!(self.values.get(&k).map_or(true, |(_, value)| *value == v));
+			let size = &mut self.size;
+			let (ref mut counter, _) = self.values.entry(k).or_insert_with(|| {
+				*size += v.len();
+				(0, v)
+			});
+			*counter += 1;
+		}
+	}
+
+	fn discard(&mut self, inserted: Vec<Key>) {
+		for k in inserted {
+			match self.values.entry(k) {
+				Entry::Occupied(mut e) => {
+					let (ref mut counter, _) = e.get_mut();
+					*counter -= 1;
+					if *counter == 0 {
+						let (_, (_, value)) = e.remove_entry();
+						self.size -= value.len();
+					}
+				},
+				Entry::Vacant(_) => {
+					debug_assert!(false, "Trying to discard missing value");
+				}
+			}
+		}
+	}
+
+	fn insert_spilled<'a>(&mut self, inserted: impl Iterator<Item = (&'a Key, usize)>, journal_key: &[u8])
+		where Key: 'a
+	{
+		for (k, len) in inserted {
+			let spilled_size = &mut self.spilled_size;
+			let (_, ref mut journals) = self.spilled.entry(k.clone()).or_insert_with(|| {
+				*spilled_size += len;
+				(len, Vec::new())
+			});
+			journals.push(journal_key.to_vec());
+		}
+	}
+
+	fn discard_spilled(&mut self, inserted: Vec<Key>, journal_key: &[u8]) {
+		for k in inserted {
+			match self.spilled.entry(k) {
+				Entry::Occupied(mut e) => {
+					let (_, ref mut journals) = e.get_mut();
+					if let Some(index) = journals.iter().position(|j| j.as_slice() == journal_key) {
+						journals.swap_remove(index);
+					}
+					if journals.is_empty() {
+						let (_, (len, _)) = e.remove_entry();
+						self.spilled_size -= len;
+					}
+				},
+				Entry::Vacant(_) => {
+					debug_assert!(false, "Trying to discard missing spilled value");
 				}
-			},
-			Entry::Vacant(_) => {
-				debug_assert!(false, "Trying to discard missing value");
 			}
 		}
 	}
+
+	/// Move values of an overlay out of memory. Values shared with other overlays are
+	/// kept in memory as long as they are referenced.
+	fn spill(&mut self, inserted: &[Key], journal_key: &[u8]) {
+		let sizes: Vec<_> = inserted.iter()
+			.map(|k| self.values.get(k).map_or(0, |(_, v)| v.len()))
+			.collect();
+		self.insert_spilled(inserted.iter().zip(sizes), journal_key);
+		self.discard(inserted.to_vec());
+	}
+
+	/// Bring values of a spilled overlay back in memory.
+	fn restore(&mut self, inserted: Vec<(Key, DBValue)>, journal_key: &[u8]) {
+		self.discard_spilled(inserted.iter().map(|(k, _)| k.clone()).collect(), journal_key);
+		self.insert(inserted);
+	}
+
+	fn get<Q: ?Sized>(&self, key: &Q) -> Option<&DBValue>
+	where
+		Key: std::borrow::Borrow<Q>,
+		Q: std::hash::Hash + Eq,
+	{
+		self.values.get(key).map(|(_, value)| value)
+	}
+}
+
+fn discard_overlay_values<BlockHash: Hash, Key: Hash>(
+	values: &mut OverlayValues<Key>,
+	overlay: BlockOverlay<BlockHash, Key>,
+) {
+	if overlay.spilled {
+		values.discard_spilled(overlay.inserted, &overlay.journal_key);
+	} else {
+		values.discard(overlay.inserted);
+	}
 }
 
 fn discard_descendants<BlockHash: Hash, Key: Hash>(
 	levels: &mut (&mut [Vec<BlockOverlay<BlockHash, Key>>], &mut [Vec<BlockOverlay<BlockHash, Key>>]),
-	mut values: &mut HashMap<Key, (u32, DBValue)>,
+	values: &mut OverlayValues<Key>,
 	parents: &mut HashMap<BlockHash, BlockHash>,
 	pinned: &HashMap<BlockHash, u32>,
 	pinned_insertions: &mut HashMap<BlockHash, (Vec<Key>, u32)>,
@@ -128,12 +259,15 @@ fn discard_descendants<BlockHash: Hash, Key: Hash>(
 				}
 				if num_pinned != 0 {
 					// save to be discarded later.
-					pinned_insertions.insert(overlay.hash.clone(), (overlay.inserted, num_pinned));
+					pinned_insertions.insert(
+						overlay.hash.clone(),
+						(pinned_values(values, overlay), num_pinned),
+					);
 					pinned_children += num_pinned;
 				} else {
 					// discard immediately.
 					parents.remove(&overlay.hash);
-					discard_values(&mut values, overlay.inserted);
+					discard_overlay_values(values, overlay);
 				}
 				None
 			} else {
@@ -144,9 +278,28 @@ fn discard_descendants<BlockHash: Hash, Key: Hash>(
 	pinned_children
 }
 
+// Values of a discarded overlay that are kept around until the block is unpinned.
+fn pinned_values<BlockHash: Hash, Key: Hash>(
+	values: &mut OverlayValues<Key>,
+	overlay: BlockOverlay<BlockHash, Key>,
+) -> Vec<Key> {
+	if overlay.spilled {
+		// Journal record is deleted along with the discarded block, so the values can't be kept.
+		warn!(target: "state-db", "Discarding spilled values of pinned block {:?}", overlay.hash);
+		values.discard_spilled(overlay.inserted, &overlay.journal_key);
+		Vec::new()
+	} else {
+		overlay.inserted
+	}
+}
+
 impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 	/// Creates a new instance. Does not expect any metadata to be present in the DB.
-	pub fn new<D: MetaDb>(db: &D) -> Result<NonCanonicalOverlay<BlockHash, Key>, Error<D::Error>> {
+	/// When `max_mem` is set, values of the oldest journal records that do not fit are left in the DB.
+	pub fn new<D: MetaDb>(
+		db: &D,
+		max_mem: Option<usize>,
+	) -> Result<NonCanonicalOverlay<BlockHash, Key>, Error<D::Error>> {
 		let last_canonicalized = db.get_meta(&to_meta_key(LAST_CANONICAL, &()))
 			.map_err(|e| Error::Db(e))?;
 		let last_canonicalized = match last_canonicalized {
@@ -155,7 +308,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		};
 		let mut levels = VecDeque::new();
 		let mut parents = HashMap::new();
-		let mut values = HashMap::new();
+		let mut values = OverlayValues::default();
 		if let Some((ref hash, mut block)) = last_canonicalized {
 			// read the journal
 			trace!(target: "state-db", "Reading uncanonicalized journal. Last canonicalized #{} ({:?})", block, hash);
@@ -170,13 +323,14 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 						Some(record) => {
 							let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
 							let inserted = record.inserted.iter().map(|(k, _)| k.clone()).collect();
+							values.insert(record.inserted);
 							let overlay = BlockOverlay {
 								hash: record.hash.clone(),
 								journal_key,
 								inserted: inserted,
 								deleted: record.deleted,
+								spilled: false,
 							};
-							insert_values(&mut values, record.inserted);
 							trace!(target: "state-db", "Uncanonicalized journal entry {}.{} ({} inserted, {} deleted)", block, index, overlay.inserted.len(), overlay.deleted.len());
 							level.push(overlay);
 							parents.insert(record.hash, record.parent_hash);
@@ -194,7 +348,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			}
 			trace!(target: "state-db", "Finished reading uncanonicalized journal, {} entries", total);
 		}
-		Ok(NonCanonicalOverlay {
+		let mut overlay = NonCanonicalOverlay {
 			last_canonicalized,
 			levels,
 			parents,
@@ -203,7 +357,10 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			pinned: Default::default(),
 			pinned_insertions: Default::default(),
 			values: values,
-		})
+			max_mem,
+		};
+		overlay.spill();
+		Ok(overlay)
 	}
 
 	/// Insert a new block into the overlay. If inserted on the second level or lover expects parent to be present in the window.
@@ -250,6 +407,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			journal_key: journal_key.clone(),
 			inserted: inserted,
 			deleted: changeset.deleted.clone(),
+			spilled: false,
 		};
 		level.push(overlay);
 		self.parents.insert(hash.clone(), parent_hash.clone());
@@ -261,11 +419,22 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		};
 		commit.meta.inserted.push((journal_key, journal_record.encode()));
 		trace!(target: "state-db", "Inserted uncanonicalized changeset {}.{} ({} inserted, {} deleted)", number, index, journal_record.inserted.len(), journal_record.deleted.len());
-		insert_values(&mut self.values, journal_record.inserted);
+		self.values.insert(journal_record.inserted);
 		self.pending_insertions.push(hash.clone());
 		Ok(commit)
 	}
 
+	/// Insert a block whose state was obtained out of band, e.g. by state sync. The block does
+	/// not have to follow the last canonicalized block, but the overlay must not contain any other
+	/// blocks. Its parent becomes the last canonicalized block.
+	pub fn insert_synced<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		if number == 0 || !self.levels.is_empty() || !self.pending_canonicalizations.is_empty() {
+			return Err(Error::InvalidBlockNumber);
+		}
+		self.last_canonicalized = None;
+		self.insert(hash, number, parent_hash, changeset)
+	}
+
 	fn discard_journals(
 		&self,
 		level_index: usize,
@@ -311,11 +480,12 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 
 	/// Select a top-level root and canonicalized it. Discards all sibling subtrees and the root.
 	/// Returns a set of changes that need to be added to the DB.
-	pub fn canonicalize<E: fmt::Debug>(
+	pub fn canonicalize<D: MetaDb>(
 		&mut self,
 		hash: &BlockHash,
 		commit: &mut CommitSet<Key>,
-	) -> Result<(), Error<E>> {
+		db: &D,
+	) -> Result<(), Error<D::Error>> {
 		trace!(target: "state-db", "Canonicalizing {:?}", hash);
 		let level = self.levels.get(self.pending_canonicalizations.len()).ok_or_else(|| Error::InvalidBlock)?;
 		let index = level
@@ -340,10 +510,16 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 
 		// get the one we need to canonicalize
 		let overlay = &level[index];
-		commit.data.inserted.extend(overlay.inserted.iter()
-			.map(|k| (k.clone(), self.values.get(k).expect("For each key in overlays there's a value in values").1.clone())));
+		if overlay.spilled {
+			commit.data.inserted.extend(read_journal::<BlockHash, Key, D>(db, &overlay.journal_key)?.inserted);
+		} else {
+			commit.data.inserted.extend(overlay.inserted.iter()
+				.map(|k| (k.clone(), self.values.get(k).expect("For each key in overlays there's a value in values").clone())));
+		}
 		commit.data.deleted.extend(overlay.deleted.clone());
 
+		self.restore_pinned(hash, &discarded_blocks, db)?;
+
 		commit.meta.deleted.append(&mut discarded_journals);
 		let canonicalized = (hash.clone(), self.front_block_number() + self.pending_canonicalizations.len() as u64);
 		commit.meta.inserted.push((to_meta_key(LAST_CANONICAL, &()), canonicalized.encode()));
@@ -352,6 +528,42 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		Ok(())
 	}
 
+	/// Journal records of the discarded blocks are deleted on canonicalization. Pinned blocks
+	/// still need their values (and the values of their discarded ancestors), so these are
+	/// brought back in memory.
+	fn restore_pinned<D: MetaDb>(
+		&mut self,
+		canonicalized: &BlockHash,
+		discarded_blocks: &[BlockHash],
+		db: &D,
+	) -> Result<(), Error<D::Error>> {
+		let discarded: HashSet<&BlockHash> = discarded_blocks.iter()
+			.filter(|h| *h != canonicalized)
+			.collect();
+		let mut restore = HashSet::new();
+		for pinned in self.pinned.keys().filter(|h| discarded.contains(h)) {
+			let mut hash = pinned;
+			while discarded.contains(hash) && restore.insert(hash.clone()) {
+				match self.parents.get(hash) {
+					Some(parent) => hash = parent,
+					None => break,
+				}
+			}
+		}
+		if restore.is_empty() {
+			return Ok(());
+		}
+		for level in self.levels.iter_mut().skip(self.pending_canonicalizations.len()) {
+			for overlay in level.iter_mut().filter(|o| o.spilled && restore.contains(&o.hash)) {
+				let record = read_journal::<BlockHash, Key, D>(db, &overlay.journal_key)?;
+				trace!(target: "state-db", "Restoring spilled values of pinned block {:?}", overlay.hash);
+				self.values.restore(record.inserted, &overlay.journal_key);
+				overlay.spilled = false;
+			}
+		}
+		Ok(())
+	}
+
 	fn apply_canonicalizations(&mut self) {
 		let last = self.pending_canonicalizations.last().cloned();
 		let count = self.pending_canonicalizations.len() as u64;
@@ -381,10 +593,12 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 					pinned_children += 1;
 				}
 				if pinned_children != 0 {
-					self.pinned_insertions.insert(overlay.hash.clone(), (overlay.inserted, pinned_children));
+					let hash = overlay.hash.clone();
+					let inserted = pinned_values(&mut self.values, overlay);
+					self.pinned_insertions.insert(hash, (inserted, pinned_children));
 				} else {
 					self.parents.remove(&overlay.hash);
-					discard_values(&mut self.values, overlay.inserted);
+					discard_overlay_values(&mut self.values, overlay);
 				}
 			}
 		}
@@ -394,18 +608,68 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		}
 	}
 
-	/// Get a value from the node overlay. This searches in every existing changeset.
+	/// Get a value from the node overlay. This searches in every existing changeset
+	/// that is kept in memory.
 	pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<DBValue>
 	where
 		Key: std::borrow::Borrow<Q>,
 		Q: std::hash::Hash + Eq,
 	{
-		if let Some((_, value)) = self.values.get(&key) {
+		if let Some(value) = self.values.get(key) {
 			return Some(value.clone());
 		}
 		None
 	}
 
+	/// Get a value from the journal records of the spilled changesets.
+	pub fn get_spilled<D: MetaDb, Q: ?Sized>(&self, key: &Q, db: &D) -> Result<Option<DBValue>, Error<D::Error>>
+	where
+		Key: std::borrow::Borrow<Q>,
+		Q: std::hash::Hash + Eq,
+	{
+		if let Some((_, journals)) = self.values.spilled.get(key) {
+			for journal_key in journals {
+				let record = read_journal::<BlockHash, Key, D>(db, journal_key)?;
+				if let Some((_, value)) = record.inserted.into_iter().find(|(k, _)| std::borrow::Borrow::<Q>::borrow(k) == key) {
+					return Ok(Some(value));
+				}
+			}
+		}
+		Ok(None)
+	}
+
+	/// Total size of the values kept in memory.
+	pub fn values_size(&self) -> usize {
+		self.values.size
+	}
+
+	/// Total size of the values that were spilled to the journal.
+	pub fn spilled_size(&self) -> usize {
+		self.values.spilled_size
+	}
+
+	/// Spill values of the oldest overlays until the in-memory values fit in `max_mem`.
+	/// Pinned blocks are always kept in memory. Expects no pending changes.
+	fn spill(&mut self) {
+		let max_mem = match self.max_mem {
+			Some(max_mem) => max_mem,
+			None => return,
+		};
+		for level in self.levels.iter_mut() {
+			for overlay in level.iter_mut() {
+				if self.values.size <= max_mem {
+					return;
+				}
+				if overlay.spilled || self.pinned.contains_key(&overlay.hash) {
+					continue;
+				}
+				self.values.spill(&overlay.inserted, &overlay.journal_key);
+				overlay.spilled = true;
+				trace!(target: "state-db", "Spilled block {:?}, {} bytes in memory", overlay.hash, self.values.size);
+			}
+		}
+	}
+
 	/// Check if the block is in the canonicalization queue.
 	pub fn have_block(&self, hash: &BlockHash) -> bool {
 		(self.parents.contains_key(hash) || self.pending_insertions.contains(hash))
@@ -417,9 +681,9 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		self.levels.pop_back().map(|level| {
 			let mut commit = CommitSet::default();
 			for overlay in level.into_iter() {
-				commit.meta.deleted.push(overlay.journal_key);
+				commit.meta.deleted.push(overlay.journal_key.clone());
 				self.parents.remove(&overlay.hash);
-				discard_values(&mut self.values, overlay.inserted);
+				discard_overlay_values(&mut self.values, overlay);
 			}
 			commit
 		})
@@ -436,7 +700,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 				.expect("Hash is added in insert");
 
 			let	overlay = self.levels[level_index].pop().expect("Empty levels are not allowed in self.levels");
-			discard_values(&mut self.values, overlay.inserted);
+			discard_overlay_values(&mut self.values, overlay);
 			if self.levels[level_index].is_empty() {
 				debug_assert_eq!(level_index, self.levels.len() - 1);
 				self.levels.pop_back();
@@ -448,6 +712,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 	pub fn apply_pending(&mut self) {
 		self.apply_canonicalizations();
 		self.pending_insertions.clear();
+		self.spill();
 	}
 
 	/// Revert all pending changes
@@ -494,7 +759,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 						if entry.get().1 == 0 {
 							let (inserted, _) = entry.remove();
 							trace!(target: "state-db-pin", "Discarding unpinned non-canon block: {:?}", hash);
-							discard_values(&mut self.values, inserted);
+							self.values.discard(inserted);
 							self.parents.remove(&hash);
 							true
 						} else {
@@ -512,7 +777,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 mod tests {
 	use std::io;
 	use tet_core::H256;
-	use super::{NonCanonicalOverlay, to_journal_key};
+	use super::{NonCanonicalOverlay, to_journal_key, map_journal_keys};
 	use crate::{ChangeSet, CommitSet};
 	use crate::test::{make_db, make_changeset};
 
@@ -523,7 +788,7 @@ mod tests {
 	#[test]
 	fn created_from_empty_db() {
 		let db = make_db(&[]);
-		let overlay: NonCanonicalOverlay<H256, H256> = NonCanonicalOverlay::new(&db).unwrap();
+		let overlay: NonCanonicalOverlay<H256, H256> = NonCanonicalOverlay::new(&db, None).unwrap();
 		assert_eq!(overlay.last_canonicalized, None);
 		assert!(overlay.levels.is_empty());
 		assert!(overlay.parents.is_empty());
@@ -533,9 +798,9 @@ mod tests {
 	#[should_panic]
 	fn canonicalize_empty_panics() {
 		let db = make_db(&[]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&H256::default(), &mut commit).unwrap();
+		overlay.canonicalize(&H256::default(), &mut commit, &db).unwrap();
 	}
 
 	#[test]
@@ -544,7 +809,7 @@ mod tests {
 		let db = make_db(&[]);
 		let h1 = H256::random();
 		let h2 = H256::random();
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		overlay.insert::<io::Error>(&h1, 2, &H256::default(), ChangeSet::default()).unwrap();
 		overlay.insert::<io::Error>(&h2, 1, &h1, ChangeSet::default()).unwrap();
 	}
@@ -555,7 +820,7 @@ mod tests {
 		let h1 = H256::random();
 		let h2 = H256::random();
 		let db = make_db(&[]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		overlay.insert::<io::Error>(&h1, 1, &H256::default(), ChangeSet::default()).unwrap();
 		overlay.insert::<io::Error>(&h2, 3, &h1, ChangeSet::default()).unwrap();
 	}
@@ -566,7 +831,7 @@ mod tests {
 		let db = make_db(&[]);
 		let h1 = H256::random();
 		let h2 = H256::random();
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		overlay.insert::<io::Error>(&h1, 1, &H256::default(), ChangeSet::default()).unwrap();
 		overlay.insert::<io::Error>(&h2, 2, &H256::default(), ChangeSet::default()).unwrap();
 	}
@@ -577,17 +842,47 @@ mod tests {
 		let h1 = H256::random();
 		let h2 = H256::random();
 		let db = make_db(&[]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		overlay.insert::<io::Error>(&h1, 1, &H256::default(), ChangeSet::default()).unwrap();
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h2, &mut commit).unwrap();
+		overlay.canonicalize(&h2, &mut commit, &db).unwrap();
+	}
+
+	#[test]
+	fn insert_synced_after_gap() {
+		let h1 = H256::random();
+		let h2 = H256::random();
+		let h3 = H256::random();
+		let mut db = make_db(&[1, 2]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), make_changeset(&[3], &[])).unwrap());
+		let mut commit = CommitSet::default();
+		overlay.canonicalize(&h1, &mut commit, &db).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending();
+
+		assert!(overlay.insert::<io::Error>(&h2, 10, &H256::default(), make_changeset(&[5], &[])).is_err());
+		db.commit(&overlay.insert_synced::<io::Error>(&h2, 10, &H256::default(), make_changeset(&[5], &[])).unwrap());
+		assert_eq!(overlay.last_canonicalized_block_number(), Some(9));
+		db.commit(&overlay.insert::<io::Error>(&h3, 11, &h2, make_changeset(&[6], &[])).unwrap());
+		assert!(overlay.insert_synced::<io::Error>(&h1, 20, &h3, ChangeSet::default()).is_err());
+		overlay.apply_pending();
+
+		let mut commit = CommitSet::default();
+		overlay.canonicalize(&h2, &mut commit, &db).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending();
+		assert!(db.data_eq(&make_db(&[1, 2, 3, 5])));
+		assert_eq!(overlay.last_canonicalized_block_number(), Some(10));
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
+		assert_eq!(overlay, overlay2);
 	}
 
 	#[test]
 	fn insert_canonicalize_one() {
 		let h1 = H256::random();
 		let mut db = make_db(&[1, 2]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		let changeset = make_changeset(&[3, 4], &[2]);
 		let insertion = overlay.insert::<io::Error>(&h1, 1, &H256::default(), changeset.clone()).unwrap();
 		assert_eq!(insertion.data.inserted.len(), 0);
@@ -596,7 +891,7 @@ mod tests {
 		assert_eq!(insertion.meta.deleted.len(), 0);
 		db.commit(&insertion);
 		let mut finalization = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h1, &mut finalization).unwrap();
+		overlay.canonicalize(&h1, &mut finalization, &db).unwrap();
 		assert_eq!(finalization.data.inserted.len(), changeset.inserted.len());
 		assert_eq!(finalization.data.deleted.len(), changeset.deleted.len());
 		assert_eq!(finalization.meta.inserted.len(), 1);
@@ -610,32 +905,53 @@ mod tests {
 		let h1 = H256::random();
 		let h2 = H256::random();
 		let mut db = make_db(&[1, 2]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h1, 10, &H256::default(), make_changeset(&[3, 4], &[2])).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h2, 11, &h1, make_changeset(&[5], &[3])).unwrap());
 		assert_eq!(db.meta.len(), 3);
 
-		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		assert_eq!(overlay.levels, overlay2.levels);
 		assert_eq!(overlay.parents, overlay2.parents);
 		assert_eq!(overlay.last_canonicalized, overlay2.last_canonicalized);
 	}
 
+	#[test]
+	fn restore_from_journal_with_mapped_keys() {
+		let h1 = H256::random();
+		let h2 = H256::random();
+		let mut db = make_db(&[1, 2]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h1, 10, &H256::default(), make_changeset(&[3, 4], &[2])).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h2, 11, &h1, make_changeset(&[5], &[3])).unwrap());
+
+		let mut map_key = |key: H256| H256::from_low_u64_be(key.to_low_u64_be() + 10);
+		let records = map_journal_keys::<H256, H256, _>(&db, &mut map_key).unwrap();
+		assert_eq!(records.len(), 2);
+		db.meta.extend(records);
+
+		let overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
+		assert_eq!(overlay.get(&H256::from_low_u64_be(13)), Some(H256::from_low_u64_be(3).as_bytes().to_vec()));
+		assert_eq!(overlay.get(&H256::from_low_u64_be(15)), Some(H256::from_low_u64_be(5).as_bytes().to_vec()));
+		assert!(!contains(&overlay, 3));
+		assert_eq!(overlay.levels[1][0].deleted, vec![H256::from_low_u64_be(13)]);
+	}
+
 	#[test]
 	fn restore_from_journal_after_canonicalize() {
 		let h1 = H256::random();
 		let h2 = H256::random();
 		let mut db = make_db(&[1, 2]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h1, 10, &H256::default(), make_changeset(&[3, 4], &[2])).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h2, 11, &h1, make_changeset(&[5], &[3])).unwrap());
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h1, &mut commit).unwrap();
+		overlay.canonicalize(&h1, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending();
 		assert_eq!(overlay.levels.len(), 1);
 
-		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		assert_eq!(overlay.levels, overlay2.levels);
 		assert_eq!(overlay.parents, overlay2.parents);
 		assert_eq!(overlay.last_canonicalized, overlay2.last_canonicalized);
@@ -646,7 +962,7 @@ mod tests {
 		let h1 = H256::random();
 		let h2 = H256::random();
 		let mut db = make_db(&[1, 2, 3, 4]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		let changeset1 = make_changeset(&[5, 6], &[2]);
 		let changeset2 = make_changeset(&[7, 8], &[5, 3]);
 		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), changeset1).unwrap());
@@ -657,7 +973,7 @@ mod tests {
 		assert_eq!(overlay.levels.len(), 2);
 		assert_eq!(overlay.parents.len(), 2);
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h1, &mut commit).unwrap();
+		overlay.canonicalize(&h1, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(contains(&overlay, 5));
 		assert_eq!(overlay.levels.len(), 2);
@@ -668,7 +984,7 @@ mod tests {
 		assert!(!contains(&overlay, 5));
 		assert!(contains(&overlay, 7));
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h2, &mut commit).unwrap();
+		overlay.canonicalize(&h2, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending();
 		assert_eq!(overlay.levels.len(), 0);
@@ -682,12 +998,12 @@ mod tests {
 		let (h_1, c_1) = (H256::random(), make_changeset(&[1], &[]));
 		let (h_2, c_2) = (H256::random(), make_changeset(&[1], &[]));
 
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h_1, 1, &H256::default(), c_1).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_2, 1, &H256::default(), c_2).unwrap());
 		assert!(contains(&overlay, 1));
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_1, &mut commit).unwrap();
+		overlay.canonicalize(&h_1, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(contains(&overlay, 1));
 		overlay.apply_pending();
@@ -700,14 +1016,14 @@ mod tests {
 		let h2 = H256::random();
 		let h3 = H256::random();
 		let mut db = make_db(&[]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		let changeset = make_changeset(&[], &[]);
 		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), changeset.clone()).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h2, 2, &h1, changeset.clone()).unwrap());
 		overlay.apply_pending();
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h1, &mut commit).unwrap();
-		overlay.canonicalize::<io::Error>(&h2, &mut commit).unwrap();
+		overlay.canonicalize(&h1, &mut commit, &db).unwrap();
+		overlay.canonicalize(&h2, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		db.commit(&overlay.insert::<io::Error>(&h3, 3, &h2, changeset.clone()).unwrap());
 		overlay.apply_pending();
@@ -743,7 +1059,7 @@ mod tests {
 		let (h_1_2_3, c_1_2_3) = (H256::random(), make_changeset(&[123], &[]));
 		let (h_2_1_1, c_2_1_1) = (H256::random(), make_changeset(&[211], &[]));
 
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h_1, 1, &H256::default(), c_1).unwrap());
 
 		db.commit(&overlay.insert::<io::Error>(&h_1_1, 2, &h_1, c_1_1).unwrap());
@@ -771,14 +1087,14 @@ mod tests {
 		assert_eq!(overlay.last_canonicalized, Some((H256::default(), 0)));
 
 		// check if restoration from journal results in the same tree
-		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		assert_eq!(overlay.levels, overlay2.levels);
 		assert_eq!(overlay.parents, overlay2.parents);
 		assert_eq!(overlay.last_canonicalized, overlay2.last_canonicalized);
 
 		// canonicalize 1. 2 and all its children should be discarded
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_1, &mut commit).unwrap();
+		overlay.canonicalize(&h_1, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending();
 		assert_eq!(overlay.levels.len(), 2);
@@ -799,7 +1115,7 @@ mod tests {
 
 		// canonicalize 1_2. 1_1 and all its children should be discarded
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_1_2, &mut commit).unwrap();
+		overlay.canonicalize(&h_1_2, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending();
 		assert_eq!(overlay.levels.len(), 1);
@@ -816,7 +1132,7 @@ mod tests {
 
 		// canonicalize 1_2_2
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_1_2_2, &mut commit).unwrap();
+		overlay.canonicalize(&h_1_2_2, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending();
 		assert_eq!(overlay.levels.len(), 0);
@@ -830,7 +1146,7 @@ mod tests {
 		let h1 = H256::random();
 		let h2 = H256::random();
 		let mut db = make_db(&[1, 2, 3, 4]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		assert!(overlay.revert_one().is_none());
 		let changeset1 = make_changeset(&[5, 6], &[2]);
 		let changeset2 = make_changeset(&[7, 8], &[5, 3]);
@@ -853,7 +1169,7 @@ mod tests {
 		let h2_1 = H256::random();
 		let h2_2 = H256::random();
 		let db = make_db(&[]);
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		let changeset1 = make_changeset(&[5, 6], &[2]);
 		let changeset2 = make_changeset(&[7, 8], &[5, 3]);
 		let changeset3 = make_changeset(&[9], &[]);
@@ -882,7 +1198,7 @@ mod tests {
 		let (h_1, c_1) = (H256::random(), make_changeset(&[1], &[]));
 		let (h_2, c_2) = (H256::random(), make_changeset(&[2], &[]));
 
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h_1, 1, &H256::default(), c_1).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_2, 1, &H256::default(), c_2).unwrap());
 		overlay.apply_pending();
@@ -890,7 +1206,7 @@ mod tests {
 		overlay.pin(&h_1);
 
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_2, &mut commit).unwrap();
+		overlay.canonicalize(&h_2, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending();
 		assert!(contains(&overlay, 1));
@@ -911,7 +1227,7 @@ mod tests {
 		let (h_2, c_2) = (H256::random(), make_changeset(&[1], &[]));
 		let (h_3, c_3) = (H256::random(), make_changeset(&[], &[]));
 
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h_1, 1, &H256::default(), c_1).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_2, 1, &H256::default(), c_2).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_3, 1, &H256::default(), c_3).unwrap());
@@ -920,7 +1236,7 @@ mod tests {
 		overlay.pin(&h_1);
 
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_3, &mut commit).unwrap();
+		overlay.canonicalize(&h_3, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending(); // 1_2 should be discarded, 1_1 is pinned
 
@@ -940,7 +1256,7 @@ mod tests {
 		let (h_12, c_12) = (H256::random(), make_changeset(&[], &[]));
 		let (h_21, c_21) = (H256::random(), make_changeset(&[], &[]));
 
-		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
 		db.commit(&overlay.insert::<io::Error>(&h_11, 1, &H256::default(), c_11).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_12, 1, &H256::default(), c_12).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_21, 2, &h_11, c_21).unwrap());
@@ -949,7 +1265,7 @@ mod tests {
 		overlay.pin(&h_21);
 
 		let mut commit = CommitSet::default();
-		overlay.canonicalize::<io::Error>(&h_12, &mut commit).unwrap();
+		overlay.canonicalize(&h_12, &mut commit, &db).unwrap();
 		db.commit(&commit);
 		overlay.apply_pending(); // 1_1 and 2_1 should be both pinned
 
@@ -958,4 +1274,85 @@ mod tests {
 		assert!(!contains(&overlay, 1));
 		assert!(overlay.pinned.is_empty());
 	}
+
+	#[test]
+	fn spills_oldest_beyond_max_mem() {
+		let mut db = make_db(&[]);
+		let h1 = H256::random();
+		let h2 = H256::random();
+		// each value takes 32 bytes
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, Some(32)).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), make_changeset(&[1], &[])).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h2, 2, &h1, make_changeset(&[2], &[])).unwrap());
+		overlay.apply_pending();
+
+		assert!(!contains(&overlay, 1));
+		assert!(contains(&overlay, 2));
+		assert_eq!(overlay.values_size(), 32);
+		assert_eq!(overlay.spilled_size(), 32);
+		assert_eq!(
+			overlay.get_spilled(&H256::from_low_u64_be(1), &db).unwrap(),
+			Some(H256::from_low_u64_be(1).as_bytes().to_vec()),
+		);
+
+		let mut commit = CommitSet::default();
+		overlay.canonicalize(&h1, &mut commit, &db).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending();
+		assert_eq!(overlay.spilled_size(), 0);
+		assert!(db.data_eq(&make_db(&[1])));
+	}
+
+	#[test]
+	fn restores_spilled_pinned() {
+		let mut db = make_db(&[]);
+
+		// - 0 - 1_1 - 2_1
+		//     \ 1_2
+
+		let (h_11, c_11) = (H256::random(), make_changeset(&[1], &[]));
+		let (h_12, c_12) = (H256::random(), make_changeset(&[], &[]));
+		let (h_21, c_21) = (H256::random(), make_changeset(&[2], &[]));
+
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, Some(32)).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h_11, 1, &H256::default(), c_11).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h_12, 1, &H256::default(), c_12).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h_21, 2, &h_11, c_21).unwrap());
+		overlay.apply_pending();
+		assert!(!contains(&overlay, 1));
+
+		overlay.pin(&h_21);
+
+		let mut commit = CommitSet::default();
+		overlay.canonicalize(&h_12, &mut commit, &db).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending(); // 1_1 is restored since 2_1 is pinned
+
+		assert!(contains(&overlay, 1));
+		overlay.unpin(&h_21);
+		assert!(!contains(&overlay, 1));
+		assert_eq!(overlay.spilled_size(), 0);
+	}
+
+	#[test]
+	fn restore_from_journal_spilled() {
+		let h1 = H256::random();
+		let h2 = H256::random();
+		let mut db = make_db(&[]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db, None).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), make_changeset(&[1], &[])).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h2, 2, &h1, make_changeset(&[2], &[])).unwrap());
+		overlay.apply_pending();
+
+		// each value takes 32 bytes
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db, Some(32)).unwrap();
+		assert_eq!(overlay.parents, overlay2.parents);
+		assert!(!contains(&overlay2, 1));
+		assert!(contains(&overlay2, 2));
+		assert_eq!(overlay2.values_size(), 32);
+		assert_eq!(
+			overlay2.get_spilled(&H256::from_low_u64_be(1), &db).unwrap(),
+			Some(H256::from_low_u64_be(1).as_bytes().to_vec()),
+		);
+	}
 }
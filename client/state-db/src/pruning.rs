@@ -23,10 +23,15 @@
 //! If a node is re-inserted into the window it gets removed from
 //! the death list.
 //! The changes are journaled in the DB.
+//!
+//! Backends that count node references may opt out of journaling the deleted nodes. Only
+//! the block hashes are journaled then, and the backend finds the nodes to delete when
+//! the block is pruned, since both the state of the block and of its parent are still
+//! complete at that point.
 
 use std::collections::{HashMap, HashSet, VecDeque};
 use codec::{Encode, Decode};
-use crate::{CommitSet, Error, MetaDb, to_meta_key, Hash};
+use crate::{CommitSet, DBValue, Error, MetaDb, to_meta_key, Hash};
 use log::{trace, warn};
 
 const LAST_PRUNED: &[u8] = b"last_pruned";
@@ -51,6 +56,9 @@ pub struct RefWindow<BlockHash: Hash, Key: Hash> {
 	/// Setting this to false requires backend that supports reference
 	/// counting.
 	count_insertions: bool,
+	/// Journal the deleted nodes of each block. Setting this to false requires a backend
+	/// that counts references and deletes the nodes of pruned blocks itself.
+	journal_deleted: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, tetsy_util_mem_derive::MallocSizeOf)]
@@ -71,8 +79,46 @@ fn to_journal_key(block: u64) -> Vec<u8> {
 	to_meta_key(PRUNING_JOURNAL, &block)
 }
 
+/// Map the node keys of the pruning journal with `map_key`, for a window that counts insertions
+/// and journals the deleted nodes as given. Returns the rewritten records by journal key.
+pub(crate) fn map_journal_keys<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	count_insertions: bool,
+	journal_deleted: bool,
+	map_key: &mut impl FnMut(Key) -> Key,
+) -> Result<Vec<(Vec<u8>, DBValue)>, Error<D::Error>> {
+	let mut block = match db.get_meta(&to_meta_key(LAST_PRUNED, &())).map_err(|e| Error::Db(e))? {
+		Some(buffer) => u64::decode(&mut buffer.as_slice())? + 1,
+		None => 0,
+	};
+	let mut records = Vec::new();
+	while let Some(record) = db.get_meta(&to_journal_key(block)).map_err(|e| Error::Db(e))? {
+		let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+		let record = JournalRecord {
+			hash: record.hash,
+			inserted: if count_insertions {
+				record.inserted.into_iter().map(|k| map_key(k)).collect()
+			} else {
+				Vec::new()
+			},
+			deleted: if journal_deleted {
+				record.deleted.into_iter().map(|k| map_key(k)).collect()
+			} else {
+				Vec::new()
+			},
+		};
+		records.push((to_journal_key(block), record.encode()));
+		block += 1;
+	}
+	Ok(records)
+}
+
 impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
-	pub fn new<D: MetaDb>(db: &D, count_insertions: bool) -> Result<RefWindow<BlockHash, Key>, Error<D::Error>> {
+	pub fn new<D: MetaDb>(
+		db: &D,
+		count_insertions: bool,
+		journal_deleted: bool,
+	) -> Result<RefWindow<BlockHash, Key>, Error<D::Error>> {
 		let last_pruned = db.get_meta(&to_meta_key(LAST_PRUNED, &()))
 			.map_err(|e| Error::Db(e))?;
 		let pending_number: u64 = match last_pruned {
@@ -87,6 +133,7 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			pending_canonicalizations: 0,
 			pending_prunings: 0,
 			count_insertions,
+			journal_deleted,
 		};
 		// read the journal
 		trace!(target: "state-db", "Reading pruning journal. Pending #{}", pending_number);
@@ -150,7 +197,11 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 	}
 
 	/// Prune next block. Expects at least one block in the window. Adds changes to `commit`.
-	pub fn prune_one(&mut self, commit: &mut CommitSet<Key>) {
+	///
+	/// Returns the hash of the pruned block if its deleted nodes were not journaled and have
+	/// to be deleted by the backend. Records written before `journal_deleted` was disabled
+	/// still have their nodes deleted here.
+	pub fn prune_one(&mut self, commit: &mut CommitSet<Key>) -> Option<BlockHash> {
 		if let Some(pruned) = self.death_rows.get(self.pending_prunings) {
 			trace!(target: "state-db", "Pruning {:?} ({} deleted)", pruned.hash, pruned.deleted.len());
 			let index = self.pending_number + self.pending_prunings as u64;
@@ -158,9 +209,13 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), index.encode()));
 			commit.meta.deleted.push(pruned.journal_key.clone());
 			self.pending_prunings += 1;
+			if !self.journal_deleted && pruned.deleted.is_empty() {
+				return Some(pruned.hash.clone());
+			}
 		} else {
 			warn!(target: "state-db", "Trying to prune when there's nothing to prune");
 		}
+		None
 	}
 
 	/// Add a change set to the window. Creates a journal record and pushes it to `commit`
@@ -171,7 +226,10 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		} else {
 			Default::default()
 		};
-		let deleted = ::std::mem::take(&mut commit.data.deleted);
+		let mut deleted = ::std::mem::take(&mut commit.data.deleted);
+		if !self.journal_deleted {
+			deleted.clear();
+		}
 		let journal_record = JournalRecord {
 			hash: hash.clone(),
 			inserted,
@@ -224,7 +282,7 @@ mod tests {
 	use crate::test::{make_db, make_commit, TestDb};
 
 	fn check_journal(pruning: &RefWindow<H256, H256>, db: &TestDb) {
-		let restored: RefWindow<H256, H256> = RefWindow::new(db, pruning.count_insertions).unwrap();
+		let restored: RefWindow<H256, H256> = RefWindow::new(db, pruning.count_insertions, pruning.journal_deleted).unwrap();
 		assert_eq!(pruning.pending_number, restored.pending_number);
 		assert_eq!(pruning.death_rows, restored.death_rows);
 		assert_eq!(pruning.death_index, restored.death_index);
@@ -233,7 +291,7 @@ mod tests {
 	#[test]
 	fn created_from_empty_db() {
 		let db = make_db(&[]);
-		let pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		assert_eq!(pruning.pending_number, 0);
 		assert!(pruning.death_rows.is_empty());
 		assert!(pruning.death_index.is_empty());
@@ -242,7 +300,7 @@ mod tests {
 	#[test]
 	fn prune_empty() {
 		let db = make_db(&[]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		let mut commit = CommitSet::default();
 		pruning.prune_one(&mut commit);
 		assert_eq!(pruning.pending_number, 0);
@@ -255,7 +313,7 @@ mod tests {
 	#[test]
 	fn prune_one() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		let mut commit = make_commit(&[4, 5], &[1, 3]);
 		let h = H256::random();
 		pruning.note_canonical(&h, &mut commit);
@@ -284,7 +342,7 @@ mod tests {
 	#[test]
 	fn prune_two() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		let mut commit = make_commit(&[4], &[1]);
 		pruning.note_canonical(&H256::random(), &mut commit);
 		db.commit(&commit);
@@ -312,7 +370,7 @@ mod tests {
 	#[test]
 	fn prune_two_pending() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		let mut commit = make_commit(&[4], &[1]);
 		pruning.note_canonical(&H256::random(), &mut commit);
 		db.commit(&commit);
@@ -335,7 +393,7 @@ mod tests {
 	#[test]
 	fn reinserted_survives() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		let mut commit = make_commit(&[], &[2]);
 		pruning.note_canonical(&H256::random(), &mut commit);
 		db.commit(&commit);
@@ -368,7 +426,7 @@ mod tests {
 	#[test]
 	fn reinserted_survive_pending() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, true).unwrap();
 		let mut commit = make_commit(&[], &[2]);
 		pruning.note_canonical(&H256::random(), &mut commit);
 		db.commit(&commit);
@@ -398,7 +456,7 @@ mod tests {
 	#[test]
 	fn reinserted_ignores() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false).unwrap();
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, true).unwrap();
 		let mut commit = make_commit(&[], &[2]);
 		pruning.note_canonical(&H256::random(), &mut commit);
 		db.commit(&commit);
@@ -420,4 +478,41 @@ mod tests {
 		assert!(pruning.death_index.is_empty());
 	}
 
+	#[test]
+	fn unjournaled_deletions_are_left_to_the_backend() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, false).unwrap();
+		let mut commit = make_commit(&[4], &[1]);
+		let h = H256::random();
+		pruning.note_canonical(&h, &mut commit);
+		db.commit(&commit);
+		assert!(commit.data.deleted.is_empty());
+		pruning.apply_pending();
+		assert!(pruning.death_rows[0].deleted.is_empty());
+		check_journal(&pruning, &db);
+
+		let mut commit = CommitSet::default();
+		assert_eq!(pruning.prune_one(&mut commit), Some(h));
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[1, 2, 3, 4])));
+		assert!(!pruning.have_block(&h));
+	}
+
+	#[test]
+	fn journaled_deletions_are_applied_without_journaling() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, true).unwrap();
+		let mut commit = make_commit(&[4], &[1]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, false).unwrap();
+		let mut commit = CommitSet::default();
+		assert_eq!(pruning.prune_one(&mut commit), None);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[2, 3, 4])));
+	}
 }
@@ -0,0 +1,116 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Recording of the spans and events emitted while re-executing a block.
+//!
+//! Each step of the execution (initialization, every extrinsic, finalization) is run through
+//! [`collect`], which installs a `ProfilingLayer` as the default subscriber of the current
+//! thread for the duration of the step. Runtime spans only show up when the runtime was built
+//! with the `with-tracing` feature, storage accesses are always reported by the externalities.
+
+use std::sync::Arc;
+use parking_lot::Mutex;
+use tracing::{Level, dispatcher::{self, Dispatch}};
+use tracing_subscriber::{Registry, layer::SubscriberExt};
+use crate::{ProfilingLayer, SpanDatum, TraceEvent, TraceHandler, check_target, parse_target};
+
+/// Target of the storage access events emitted by the externalities.
+pub const STORAGE_TARGET: &str = "state";
+
+/// Default targets of a block trace.
+pub const DEFAULT_TARGETS: &str = "noble,fabric,state";
+
+/// Spans and events recorded by [`collect`], in the order they were completed.
+#[derive(Debug, Default)]
+pub struct Traces {
+	/// Spans, reported when they are exited.
+	pub spans: Vec<SpanDatum>,
+	/// Events.
+	pub events: Vec<TraceEvent>,
+}
+
+struct CollectingHandler {
+	targets: Vec<(String, Level)>,
+	traces: Arc<Mutex<Traces>>,
+}
+
+impl TraceHandler for CollectingHandler {
+	fn handle_span(&self, span: SpanDatum) {
+		if check_target(&self.targets, &span.target, &span.level) {
+			self.traces.lock().spans.push(span);
+		}
+	}
+
+	fn handle_event(&self, event: TraceEvent) {
+		if check_target(&self.targets, &event.target, &event.level) {
+			self.traces.lock().events.push(event);
+		}
+	}
+}
+
+/// Run `f` and record the spans and events it emits on the current thread.
+///
+/// `targets` is a comma separated list of targets with an optional level, as accepted by
+/// `ProfilingLayer::new`. Spans and events of other targets are dropped.
+pub fn collect<R>(targets: &str, f: impl FnOnce() -> R) -> (R, Traces) {
+	let traces = Arc::new(Mutex::new(Traces::default()));
+	let handler = CollectingHandler {
+		targets: targets.split(',').map(parse_target).collect(),
+		traces: traces.clone(),
+	};
+	// Wasm spans are filtered by the handler, let the layer hand all of them over.
+	let layer = ProfilingLayer::new_with_handler(Box::new(handler), "");
+	let dispatch = Dispatch::new(Registry::default().with(layer));
+	let result = dispatcher::with_default(&dispatch, f);
+	let traces = std::mem::take(&mut *traces.lock());
+	(result, traces)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn collects_matching_targets_only() {
+		let ((), traces) = collect("test_target,state=debug", || {
+			let span = tracing::info_span!(target: "test_target", "test_span");
+			let _guard = span.enter();
+			tracing::trace!(target: "state", method = "Get", "ignored");
+			tracing::debug!(target: "state", method = "Put", "recorded");
+			tracing::info!(target: "other_target", "ignored");
+		});
+
+		assert_eq!(traces.spans.len(), 1);
+		assert_eq!(traces.spans[0].name, "test_span");
+		assert_eq!(traces.events.len(), 1);
+		assert_eq!(traces.events[0].values.string_values.get("method").unwrap(), "Put");
+		assert_eq!(traces.events[0].parent_id, Some(traces.spans[0].id.clone()));
+	}
+
+	#[test]
+	fn does_not_leak_into_other_calls() {
+		let (_, first) = collect("test_target", || {
+			tracing::info!(target: "test_target", "first");
+		});
+		tracing::info!(target: "test_target", "outside");
+		let (_, second) = collect("test_target", || ());
+
+		assert_eq!(first.events.len(), 1);
+		assert!(second.events.is_empty());
+	}
+}
@@ -28,6 +28,7 @@
 
 #![warn(missing_docs)]
 
+pub mod block;
 pub mod logging;
 
 use rustc_hash::FxHashMap;
@@ -237,13 +238,17 @@ impl ProfilingLayer {
 	}
 
 	fn check_target(&self, target: &str, level: &Level) -> bool {
-		for t in &self.targets {
-			if target.starts_with(t.0.as_str()) && level <= &t.1 {
-				return true;
-			}
+		check_target(&self.targets, target, level)
+	}
+}
+
+fn check_target(targets: &[(String, Level)], target: &str, level: &Level) -> bool {
+	for t in targets {
+		if target.starts_with(t.0.as_str()) && level <= &t.1 {
+			return true;
 		}
-		false
 	}
+	false
 }
 
 // Default to TRACE if no level given or unable to parse Level
@@ -248,6 +248,19 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 		}
 	}
 
+	/// Sets the minimal priority increase (in percent) required to replace ready transactions.
+	pub fn set_min_replace_bump(&mut self, percent: u32) {
+		self.ready.set_min_replace_bump(percent);
+	}
+
+	/// Sets the length of the `provides` tag prefix identifying the sender of a transaction.
+	///
+	/// The transactions already in the pool are indexed again.
+	pub fn set_sender_tag_prefix(&mut self, tag_prefix: usize) {
+		self.ready.set_sender_tag_prefix(tag_prefix);
+		self.future.set_sender_tag_prefix(tag_prefix);
+	}
+
 	/// Temporary enables future transactions, runs closure and then restores
 	/// `reject_future_transactions` flag back to previous value.
 	///
@@ -383,6 +396,11 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 		self.future.all()
 	}
 
+	/// Returns future transactions in the pool along with the tags they are missing.
+	pub fn futures_with_missing_tags(&self) -> impl Iterator<Item=(Arc<Transaction<Hash, Ex>>, Vec<Tag>)> + '_ {
+		self.future.waiting().map(|(tx, missing)| (tx.clone(), missing.iter().cloned().collect()))
+	}
+
 	/// Returns pool transactions given list of hashes.
 	///
 	/// Includes both ready and future pool. For every hash in the `hashes`
@@ -405,50 +423,48 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 
 	/// Makes sure that the transactions in the queues stay within provided limits.
 	///
-	/// Removes and returns worst transactions from the queues and all transactions that depend on them.
+	/// Removes and returns worst transactions from the queues and all transactions that depend on them,
+	/// along with the reason of their removal.
+	///
+	/// Senders over their quota are trimmed first. Then, as long as a queue exceeds its global limit,
+	/// transactions of the sender with the most transactions in that queue are removed.
+	/// The senders are identified with the tag prefix set by `set_sender_tag_prefix`.
 	/// Technically the worst transaction should be evaluated by computing the entire pending set.
-	/// We use a simplified approach to remove the transaction that occupies the pool for the longest time.
-	pub fn enforce_limits(&mut self, ready: &Limit, future: &Limit) -> Vec<Arc<Transaction<Hash, Ex>>> {
+	/// We use a simplified approach: the lowest priority transaction of that sender that was
+	/// imported last goes first, so that senders keep the beginning of their nonce chains. When
+	/// several senders have the same number of transactions, the one that occupies the pool for the
+	/// longest time is picked.
+	pub fn enforce_limits(
+		&mut self,
+		ready: &Limit,
+		future: &Limit,
+		sender: &SenderLimit,
+	) -> Vec<(Arc<Transaction<Hash, Ex>>, DropReason)> {
 		let mut removed = vec![];
 
-		while ready.is_exceeded(self.ready.len(), self.ready.bytes()) {
-			// find the worst transaction
-			let minimal = self.ready
-				.fold(|minimal, current| {
-					let transaction = &current.transaction;
-					match minimal {
-						None => Some(transaction.clone()),
-						Some(ref tx) if tx.insertion_id > transaction.insertion_id => {
-							Some(transaction.clone())
-						},
-						other => other,
-					}
-				});
+		if let Some(quota) = sender.ready {
+			while let Some(hash) = self.ready.senders().worst(quota) {
+				removed.extend(self.remove_subtree(&[hash]).into_iter().map(|tx| (tx, DropReason::SenderQuota)));
+			}
+		}
 
-			if let Some(minimal) = minimal {
-				removed.append(&mut self.remove_subtree(&[minimal.transaction.hash.clone()]))
-			} else {
-				break;
+		if let Some(quota) = sender.future {
+			while let Some(hash) = self.future.senders().worst(quota) {
+				removed.extend(self.remove_subtree(&[hash]).into_iter().map(|tx| (tx, DropReason::SenderQuota)));
 			}
 		}
 
-		while future.is_exceeded(self.future.len(), self.future.bytes()) {
-			// find the worst transaction
-			let minimal = self.future
-				.fold(|minimal, current| {
-					match minimal {
-						None => Some(current.clone()),
-						Some(ref tx) if tx.imported_at > current.imported_at => {
-							Some(current.clone())
-						},
-						other => other,
-					}
-				});
+		while ready.is_exceeded(self.ready.len(), self.ready.bytes()) {
+			match self.ready.senders().worst(0) {
+				Some(hash) => removed.extend(self.remove_subtree(&[hash]).into_iter().map(|tx| (tx, DropReason::Limit))),
+				None => break,
+			}
+		}
 
-			if let Some(minimal) = minimal {
-				removed.append(&mut self.remove_subtree(&[minimal.transaction.hash.clone()]))
-			} else {
-				break;
+		while future.is_exceeded(self.future.len(), self.future.bytes()) {
+			match self.future.senders().worst(0) {
+				Some(hash) => removed.extend(self.remove_subtree(&[hash]).into_iter().map(|tx| (tx, DropReason::Limit))),
+				None => break,
 			}
 		}
 
@@ -543,6 +559,34 @@ impl Limit {
 	}
 }
 
+/// Per-sender queue limits.
+///
+/// Transactions are attributed to a sender by the prefix of the first tag they provide,
+/// which for the usual `(account, nonce)` tags identifies the account.
+#[derive(Debug, Clone, Default)]
+pub struct SenderLimit {
+	/// Maximal number of ready transactions of a single sender.
+	pub ready: Option<usize>,
+	/// Maximal number of future transactions of a single sender.
+	pub future: Option<usize>,
+}
+
+impl SenderLimit {
+	/// Returns true if any per-sender quota is set.
+	pub fn is_enabled(&self) -> bool {
+		self.ready.is_some() || self.future.is_some()
+	}
+}
+
+/// Reason of a transaction being removed by `BasePool::enforce_limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+	/// The queue exceeded its global limits.
+	Limit,
+	/// The sender exceeded its quota.
+	SenderQuota,
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -1201,4 +1245,85 @@ source: TransactionSource::External, requires: [03,02], provides: [04], data: [4
 		assert_eq!(pool.reject_future_transactions, true);
 		assert_eq!(pool.future.len(), 1);
 	}
+
+	fn sender_tx(sender: u8, nonce: u8, hash: Hash) -> Transaction<Hash, Vec<u8>> {
+		Transaction {
+			data: vec![sender, nonce],
+			bytes: 1,
+			hash,
+			priority: 5u64,
+			valid_till: 64u64,
+			requires: if nonce == 0 { vec![] } else { vec![vec![sender, nonce - 1]] },
+			provides: vec![vec![sender, nonce]],
+			propagate: true,
+			source: Source::External,
+		}
+	}
+
+	#[test]
+	fn should_enforce_sender_quota() {
+		// given
+		let mut pool = pool();
+		pool.set_sender_tag_prefix(1);
+		pool.import(sender_tx(1, 0, 1)).unwrap();
+		pool.import(sender_tx(1, 1, 2)).unwrap();
+		pool.import(sender_tx(1, 2, 3)).unwrap();
+		pool.import(sender_tx(2, 0, 4)).unwrap();
+		let limit = Limit { count: 100, total_bytes: 100 };
+		let sender = SenderLimit { ready: Some(2), future: None };
+
+		// when
+		let removed = pool.enforce_limits(&limit, &limit, &sender);
+
+		// then
+		assert_eq!(removed.len(), 1);
+		assert_eq!(removed[0].0.hash, 3);
+		assert_eq!(removed[0].1, DropReason::SenderQuota);
+		assert_eq!(pool.ready.len(), 3);
+		assert!(!pool.ready.contains(&3));
+	}
+
+	#[test]
+	fn should_evict_largest_sender_first() {
+		// given
+		let mut pool = pool();
+		pool.set_sender_tag_prefix(1);
+		pool.import(sender_tx(2, 0, 4)).unwrap();
+		pool.import(sender_tx(1, 0, 1)).unwrap();
+		pool.import(sender_tx(1, 1, 2)).unwrap();
+		pool.import(sender_tx(1, 2, 3)).unwrap();
+		let ready = Limit { count: 2, total_bytes: 100 };
+		let future = Limit { count: 100, total_bytes: 100 };
+		let sender = SenderLimit::default();
+
+		// when
+		let removed = pool.enforce_limits(&ready, &future, &sender);
+
+		// then
+		assert_eq!(
+			removed.iter().map(|(tx, reason)| (tx.hash, *reason)).collect::<Vec<_>>(),
+			vec![(3, DropReason::Limit), (2, DropReason::Limit)],
+		);
+		assert!(pool.ready.contains(&4));
+		assert!(pool.ready.contains(&1));
+	}
+
+	#[test]
+	fn should_keep_senders_across_promotion_and_pruning() {
+		// given
+		let mut pool = pool();
+		pool.set_sender_tag_prefix(1);
+		pool.import(sender_tx(1, 1, 2)).unwrap();
+		pool.import(sender_tx(1, 2, 3)).unwrap();
+		assert_eq!(pool.future.senders().worst(1), Some(3));
+
+		// when
+		pool.import(sender_tx(1, 0, 1)).unwrap();
+		pool.prune_tags(vec![vec![1, 0]]);
+
+		// then
+		assert_eq!(pool.future.senders().worst(0), None);
+		assert_eq!(pool.ready.senders().worst(1), Some(3));
+		assert_eq!(pool.ready.senders().worst(2), None);
+	}
 }
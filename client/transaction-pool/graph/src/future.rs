@@ -29,7 +29,7 @@ use tp_runtime::transaction_validity::{
 };
 use wasm_timer::Instant;
 
-use crate::base_pool::Transaction;
+use crate::{base_pool::Transaction, senders::SenderIndex};
 
 #[cfg_attr(not(target_os = "unknown"), derive(tetsy_util_mem::MallocSizeOf))]
 /// Transaction with partially satisfied dependencies.
@@ -119,6 +119,8 @@ pub struct FutureTransactions<Hash: hash::Hash + Eq, Ex> {
 	wanted_tags: HashMap<Tag, HashSet<Hash>>,
 	/// Transactions waiting for a particular other transaction
 	waiting: HashMap<Hash, WaitingTransaction<Hash, Ex>>,
+	/// Future transactions by sender.
+	senders: SenderIndex<Hash>,
 }
 
 impl<Hash: hash::Hash + Eq, Ex> Default for FutureTransactions<Hash, Ex> {
@@ -126,6 +128,7 @@ impl<Hash: hash::Hash + Eq, Ex> Default for FutureTransactions<Hash, Ex> {
 		FutureTransactions {
 			wanted_tags: Default::default(),
 			waiting: Default::default(),
+			senders: Default::default(),
 		}
 	}
 }
@@ -138,6 +141,21 @@ qed
 #";
 
 impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
+	/// Sets the length of the tag prefix identifying the sender of a transaction.
+	pub fn set_sender_tag_prefix(&mut self, tag_prefix: usize) {
+		self.senders.reset(tag_prefix);
+		let mut transactions = self.waiting.values().collect::<Vec<_>>();
+		transactions.sort_by_key(|tx| tx.imported_at);
+		for tx in transactions {
+			self.senders.insert(&tx.transaction);
+		}
+	}
+
+	/// Future transactions by sender.
+	pub fn senders(&self) -> &SenderIndex<Hash> {
+		&self.senders
+	}
+
 	/// Import transaction to Future queue.
 	///
 	/// Only transactions that don't have all their tags satisfied should occupy
@@ -155,6 +173,7 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 		}
 
 		// Add the transaction to a by-hash waiting map
+		self.senders.insert(&tx.transaction);
 		self.waiting.insert(tx.transaction.hash.clone(), tx);
 	}
 
@@ -186,6 +205,7 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 
 					if is_ready {
 						let tx = self.waiting.remove(&hash).expect(WAITING_PROOF);
+						self.senders.remove(&hash);
 						became_ready.push(tx);
 					}
 				}
@@ -202,6 +222,7 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 		let mut removed = vec![];
 		for hash in hashes {
 			if let Some(waiting_tx) = self.waiting.remove(hash) {
+				self.senders.remove(hash);
 				// remove from wanted_tags as well
 				for tag in waiting_tx.missing_tags {
 					let remove = if let Some(wanted) = self.wanted_tags.get_mut(&tag) {
@@ -219,21 +240,20 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 		removed
 	}
 
-	/// Fold a list of future transactions to compute a single value.
-	pub fn fold<R, F: FnMut(Option<R>, &WaitingTransaction<Hash, Ex>) -> Option<R>>(&mut self, f: F) -> Option<R> {
-		self.waiting
-			.values()
-			.fold(None, f)
-	}
-
 	/// Returns iterator over all future transactions
 	pub fn all(&self) -> impl Iterator<Item=&Transaction<Hash, Ex>> {
 		self.waiting.values().map(|waiting| &*waiting.transaction)
 	}
 
+	/// Returns iterator over all future transactions along with the tags they are missing.
+	pub fn waiting(&self) -> impl Iterator<Item=(&Arc<Transaction<Hash, Ex>>, &HashSet<Tag>)> {
+		self.waiting.values().map(|waiting| (&waiting.transaction, &waiting.missing_tags))
+	}
+
 	/// Removes and returns all future transactions.
 	pub fn clear(&mut self) -> Vec<Arc<Transaction<Hash, Ex>>> {
 		self.wanted_tags.clear();
+		self.senders.clear();
 		self.waiting.drain().map(|(_, tx)| tx.transaction).collect()
 	}
 
@@ -31,6 +31,7 @@ mod listener;
 mod pool;
 mod ready;
 mod rotator;
+mod senders;
 mod validated_pool;
 mod tracked_map;
 
@@ -20,31 +20,50 @@
 use std::{
 	collections::HashMap, hash, fmt::Debug,
 };
+use futures::channel::mpsc::{channel, Receiver, Sender};
 use linked_hash_map::LinkedHashMap;
 use serde::Serialize;
 use crate::{watcher, ChainApi, ExtrinsicHash, BlockHash};
 use log::{debug, trace, warn};
+use retain_mut::RetainMut;
 use tp_runtime::traits;
+use tp_transaction_pool::PoolEvent;
 
 /// Extrinsic pool default listener.
 pub struct Listener<H: hash::Hash + Eq, C: ChainApi> {
 	watchers: HashMap<H, watcher::Sender<H, ExtrinsicHash<C>>>,
 	finality_watchers: LinkedHashMap<ExtrinsicHash<C>, Vec<H>>,
+	event_sinks: Vec<Sender<PoolEvent<H, BlockHash<C>>>>,
 }
 
 /// Maximum number of blocks awaiting finality at any time.
 const MAX_FINALITY_WATCHERS: usize = 512;
 
+/// Number of pool events buffered for each event stream.
+const EVENT_BUFFER_SIZE: usize = 4096;
+
 impl<H: hash::Hash + Eq + Debug, C: ChainApi> Default for Listener<H, C> {
 	fn default() -> Self {
 		Listener {
 			watchers: Default::default(),
 			finality_watchers: Default::default(),
+			event_sinks: Default::default(),
 		}
 	}
 }
 
 impl<H: hash::Hash + traits::Member + Serialize, C: ChainApi> Listener<H, C> {
+	fn notify(&mut self, event: PoolEvent<H, BlockHash<C>>) {
+		self.event_sinks.retain_mut(|sink| match sink.try_send(event.clone()) {
+			Ok(()) => true,
+			Err(e) if e.is_full() => {
+				warn!(target: "txpool", "Trying to send a pool event but the channel is full");
+				true
+			},
+			Err(_) => false,
+		});
+	}
+
 	fn fire<F>(&mut self, hash: &H, fun: F) where F: FnOnce(&mut watcher::Sender<H, ExtrinsicHash<C>>) {
 		let clean = if let Some(h) = self.watchers.get_mut(hash) {
 			fun(h);
@@ -66,6 +85,13 @@ impl<H: hash::Hash + traits::Member + Serialize, C: ChainApi> Listener<H, C> {
 		sender.new_watcher(hash)
 	}
 
+	/// Creates a new stream of events about all transactions in the pool.
+	pub fn create_event_stream(&mut self) -> Receiver<PoolEvent<H, BlockHash<C>>> {
+		let (sink, stream) = channel(EVENT_BUFFER_SIZE);
+		self.event_sinks.push(sink);
+		stream
+	}
+
 	/// Notify the listeners about extrinsic broadcast.
 	pub fn broadcasted(&mut self, hash: &H, peers: Vec<String>) {
 		trace!(target: "txpool", "[{:?}] Broadcasted", hash);
@@ -76,15 +102,25 @@ impl<H: hash::Hash + traits::Member + Serialize, C: ChainApi> Listener<H, C> {
 	pub fn ready(&mut self, tx: &H, old: Option<&H>) {
 		trace!(target: "txpool", "[{:?}] Ready (replaced with {:?})", tx, old);
 		self.fire(tx, |watcher| watcher.ready());
+		self.notify(PoolEvent::Ready(tx.clone()));
 		if let Some(old) = old {
 			self.fire(old, |watcher| watcher.usurped(tx.clone()));
+			self.notify(PoolEvent::Usurped { hash: old.clone(), by: tx.clone() });
 		}
 	}
 
+	/// Transaction was promoted from the future pool to the ready pool.
+	pub fn promoted(&mut self, tx: &H) {
+		trace!(target: "txpool", "[{:?}] Promoted", tx);
+		self.fire(tx, |watcher| watcher.ready());
+		self.notify(PoolEvent::Promoted(tx.clone()));
+	}
+
 	/// New transaction was added to the future pool.
 	pub fn future(&mut self, tx: &H) {
 		trace!(target: "txpool", "[{:?}] Future", tx);
 		self.fire(tx, |watcher| watcher.future());
+		self.notify(PoolEvent::Future(tx.clone()));
 	}
 
 	/// Transaction was dropped from the pool because of the limit.
@@ -93,7 +129,24 @@ impl<H: hash::Hash + traits::Member + Serialize, C: ChainApi> Listener<H, C> {
 		self.fire(tx, |watcher| match by {
 			Some(t) => watcher.usurped(t.clone()),
 			None => watcher.dropped(),
-		})
+		});
+		self.notify(match by {
+			Some(t) => PoolEvent::Usurped { hash: tx.clone(), by: t.clone() },
+			None => PoolEvent::Dropped(tx.clone()),
+		});
+	}
+
+	/// Transaction was dropped because its sender exceeded the quota.
+	pub fn dropped_for_quota(&mut self, tx: &H) {
+		trace!(target: "txpool", "[{:?}] Dropped (sender quota exceeded)", tx);
+		self.fire(tx, |watcher| watcher.dropped_for_quota());
+		self.notify(PoolEvent::DroppedForQuota(tx.clone()));
+	}
+
+	/// Transaction was temporarily banned from the pool.
+	pub fn banned(&mut self, tx: &H) {
+		trace!(target: "txpool", "[{:?}] Banned", tx);
+		self.notify(PoolEvent::Banned(tx.clone()));
 	}
 
 	/// Transaction was removed as invalid.
@@ -104,12 +157,14 @@ impl<H: hash::Hash + traits::Member + Serialize, C: ChainApi> Listener<H, C> {
 			debug!(target: "txpool", "[{:?}] Extrinsic invalid", tx);
 		}
 		self.fire(tx, |watcher| watcher.invalid());
+		self.notify(PoolEvent::Invalid(tx.clone()));
 	}
 
 	/// Transaction was pruned from the pool.
 	pub fn pruned(&mut self, block_hash: BlockHash<C>, tx: &H) {
 		debug!(target: "txpool", "[{:?}] Pruned at {:?}", tx, block_hash);
 		self.fire(tx, |s| s.in_block(block_hash));
+		self.notify(PoolEvent::Pruned { hash: tx.clone(), block: block_hash });
 		self.finality_watchers.entry(block_hash).or_insert(vec![]).push(tx.clone());
 
 		while self.finality_watchers.len() > MAX_FINALITY_WATCHERS {
@@ -105,8 +105,22 @@ pub struct Options {
 	pub ready: base::Limit,
 	/// Future queue limits.
 	pub future: base::Limit,
+	/// Per-sender queue limits.
+	pub sender: base::SenderLimit,
+	/// Length of the `provides` tag prefix identifying the sender of a transaction, for the
+	/// per-sender limits and the eviction of transactions when the pool is full.
+	pub sender_tag_prefix: usize,
+	/// Minimal priority increase (in percent) for a transaction to replace the ones
+	/// providing the same tags.
+	///
+	/// Any strictly higher priority replaces when `0`, the default.
+	pub min_replace_bump: u32,
 	/// Reject future transactions.
 	pub reject_future_transactions: bool,
+	/// Path of the journal of the transactions submitted to this node, replayed on startup.
+	///
+	/// The journal is disabled when `None`.
+	pub journal: Option<std::path::PathBuf>,
 }
 
 impl Default for Options {
@@ -120,7 +134,11 @@ impl Default for Options {
 				count: 512,
 				total_bytes: 1 * 1024 * 1024,
 			},
+			sender: Default::default(),
+			sender_tag_prefix: crate::senders::DEFAULT_TAG_PREFIX,
+			min_replace_bump: 0,
 			reject_future_transactions: false,
+			journal: None,
 		}
 	}
 }
@@ -157,6 +175,8 @@ impl<B: ChainApi> Pool<B> {
 	}
 
 	/// Imports a bunch of unverified extrinsics to the pool
+	///
+	/// The results are returned in the order the extrinsics were passed.
 	pub async fn submit_at(
 		&self,
 		at: &BlockId<B::Block>,
@@ -193,6 +213,33 @@ impl<B: ChainApi> Pool<B> {
 		res.expect("One extrinsic passed; one result returned; qed")
 	}
 
+	/// Imports one unverified extrinsic replacing the given ready transaction.
+	///
+	/// The extrinsic is only imported if the replaced transaction is still ready, and if it
+	/// provides some of its tags and does not require any other tags, so that it ends up in
+	/// the ready queue in its place. The priority bump required for replacement still applies.
+	pub async fn replace_one(
+		&self,
+		at: &BlockId<B::Block>,
+		source: TransactionSource,
+		xt: ExtrinsicFor<B>,
+		replaces: ExtrinsicHash<B>,
+	) -> Result<ExtrinsicHash<B>, B::Error> {
+		// Fail early, the replaced transaction is checked again when importing.
+		if self.validated_pool.ready_by_hash(&replaces).is_none() {
+			return Err(error::Error::NoReplacement.into());
+		}
+		let block_number = self.resolve_block_number(at)?;
+		let (_, tx) = self.verify_one(
+			at,
+			block_number,
+			source,
+			xt,
+			CheckBannedBeforeVerify::Yes,
+		).await;
+		self.validated_pool.submit_replacement(tx, &replaces)
+	}
+
 	/// Import a single extrinsic and starts to watch their progress in the pool.
 	pub async fn submit_and_watch(
 		&self,
@@ -211,6 +258,23 @@ impl<B: ChainApi> Pool<B> {
 		self.validated_pool.submit_and_watch(tx)
 	}
 
+	/// Import a bunch of extrinsics and start to watch their progress in the pool.
+	///
+	/// The extrinsics are validated in one batch and imported in the order they were passed,
+	/// so that transactions depending on the previous ones go straight to the ready queue.
+	pub async fn submit_and_watch_at(
+		&self,
+		at: &BlockId<B::Block>,
+		source: TransactionSource,
+		xts: impl IntoIterator<Item=ExtrinsicFor<B>>,
+	) -> Result<Vec<Result<Watcher<ExtrinsicHash<B>, ExtrinsicHash<B>>, B::Error>>, B::Error> {
+		let xts = xts.into_iter().map(|xt| (source, xt));
+		let validated_transactions = self.verify(at, xts, CheckBannedBeforeVerify::Yes).await?;
+		Ok(validated_transactions.into_iter()
+			.map(|(_, tx)| self.validated_pool.submit_and_watch(tx))
+			.collect())
+	}
+
 	/// Resubmit some transaction that were validated elsewhere.
 	pub fn resubmit(
 		&self,
@@ -372,19 +436,21 @@ impl<B: ChainApi> Pool<B> {
 	}
 
 	/// Returns future that validates a bunch of transactions at given block.
+	///
+	/// The validated transactions are returned in the order they were passed.
 	async fn verify(
 		&self,
 		at: &BlockId<B::Block>,
 		xts: impl IntoIterator<Item=(TransactionSource, ExtrinsicFor<B>)>,
 		check: CheckBannedBeforeVerify,
-	) -> Result<HashMap<ExtrinsicHash<B>, ValidatedTransactionFor<B>>, B::Error> {
+	) -> Result<Vec<(ExtrinsicHash<B>, ValidatedTransactionFor<B>)>, B::Error> {
 		// we need a block number to compute tx validity
 		let block_number = self.resolve_block_number(at)?;
 
 		let res = futures::future::join_all(
 			xts.into_iter()
 				.map(|(source, xt)| self.verify_one(at, block_number, source, xt, check))
-		).await.into_iter().collect::<HashMap<_, _>>();
+		).await;
 
 		Ok(res)
 	}
@@ -715,6 +781,59 @@ mod tests {
 		assert!(pool.validated_pool.rotator().is_banned(&hash1));
 	}
 
+	#[test]
+	fn should_return_results_in_submission_order() {
+		// given
+		let pool = pool();
+		let xts = (0..5).rev().map(|nonce| uxt(Transfer {
+			from: AccountId::from_h256(H256::from_low_u64_be(1)),
+			to: AccountId::from_h256(H256::from_low_u64_be(2)),
+			amount: 5,
+			nonce,
+		})).collect::<Vec<_>>();
+		let hashes = xts.iter().map(|xt| pool.hash_of(xt)).collect::<Vec<_>>();
+
+		// when
+		let results = block_on(pool.submit_at(
+			&BlockId::Number(0),
+			SOURCE,
+			xts.iter().cloned().chain(std::iter::once(xts[0].clone())),
+		)).unwrap();
+
+		// then
+		assert_eq!(results.len(), 6);
+		for (result, hash) in results.iter().zip(&hashes) {
+			assert_eq!(result.as_ref().unwrap(), hash);
+		}
+		assert_matches!(results[5], Err(error::Error::AlreadyImported(_)));
+		assert_eq!(pool.validated_pool().status().ready, 5);
+	}
+
+	#[test]
+	fn should_not_submit_transaction_that_does_not_replace() {
+		// given
+		let pool = pool();
+		let hash = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
+			from: AccountId::from_h256(H256::from_low_u64_be(1)),
+			to: AccountId::from_h256(H256::from_low_u64_be(2)),
+			amount: 5,
+			nonce: 0,
+		}))).unwrap();
+
+		// when
+		let xt = uxt(Transfer {
+			from: AccountId::from_h256(H256::from_low_u64_be(1)),
+			to: AccountId::from_h256(H256::from_low_u64_be(2)),
+			amount: 5,
+			nonce: 1,
+		});
+		let err = block_on(pool.replace_one(&BlockId::Number(0), SOURCE, xt, hash)).unwrap_err();
+
+		// then
+		assert_matches!(err, error::Error::NoReplacement);
+		assert_eq!(pool.validated_pool().status().ready, 1);
+	}
+
 	#[test]
 	fn should_limit_futures() {
 		// given
@@ -797,6 +916,40 @@ mod tests {
 
 	mod listener {
 		use super::*;
+		use tp_transaction_pool::PoolEvent;
+
+		#[test]
+		fn should_stream_events_of_all_transactions() {
+			// given
+			let pool = pool();
+			let events = pool.validated_pool().pool_event_stream();
+			let xt = |nonce| uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce,
+			});
+
+			// when
+			let hash1 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, xt(1))).unwrap();
+			let hash0 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, xt(0))).unwrap();
+			pool.validated_pool().remove_invalid(&[hash1]);
+			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]], vec![])).unwrap();
+
+			// then
+			let mut stream = futures::executor::block_on_stream(events);
+			assert_eq!(stream.next(), Some(PoolEvent::Future(hash1)));
+			assert_eq!(stream.next(), Some(PoolEvent::Ready(hash0)));
+			assert_eq!(stream.next(), Some(PoolEvent::Promoted(hash1)));
+			assert_eq!(stream.next(), Some(PoolEvent::Banned(hash1)));
+			assert_eq!(stream.next(), Some(PoolEvent::Invalid(hash1)));
+			// the pruned transaction is stale when revalidated
+			assert_eq!(stream.next(), Some(PoolEvent::Banned(hash0)));
+			assert_eq!(
+				stream.next(),
+				Some(PoolEvent::Pruned { hash: hash0, block: H256::from_low_u64_be(2).into() }),
+			);
+		}
 
 		#[test]
 		fn should_trigger_ready_and_finalized() {
@@ -970,6 +1123,41 @@ mod tests {
 			assert_eq!(stream.next(), Some(TransactionStatus::Dropped));
 		}
 
+		#[test]
+		fn should_trigger_dropped_for_quota() {
+			// given
+			let pool = Pool::new(Options {
+				sender: base::SenderLimit { ready: Some(1), future: None },
+				sender_tag_prefix: 0,
+				..Default::default()
+			}, TestApi::default().into());
+
+			let xt = uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce: 1,
+			});
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, xt)).unwrap();
+			assert_eq!(pool.validated_pool().status().future, 1);
+
+			// when
+			let xt = uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce: 0,
+			});
+			block_on(pool.submit_one(&BlockId::Number(0), SOURCE, xt)).unwrap();
+			assert_eq!(pool.validated_pool().status().ready, 1);
+
+			// then
+			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
+			assert_eq!(stream.next(), Some(TransactionStatus::Future));
+			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
+			assert_eq!(stream.next(), Some(TransactionStatus::DroppedForQuota));
+		}
+
 		#[test]
 		fn should_handle_pruning_in_the_middle_of_import() {
 			// given
@@ -34,6 +34,7 @@ use tp_transaction_pool::error;
 use crate::{
 	base_pool::Transaction,
 	future::WaitingTransaction,
+	senders::SenderIndex,
 	tracked_map::{self, ReadOnlyTrackedMap, TrackedMap},
 };
 
@@ -118,6 +119,10 @@ pub struct ReadyTransactions<Hash: hash::Hash + Eq, Ex> {
 	ready: TrackedMap<Hash, ReadyTx<Hash, Ex>>,
 	/// Best transactions that are ready to be included to the block without any other previous transaction.
 	best: BTreeSet<TransactionRef<Hash, Ex>>,
+	/// Minimal priority increase (in percent) required to replace transactions.
+	min_replace_bump: u32,
+	/// Ready transactions by sender.
+	senders: SenderIndex<Hash>,
 }
 
 impl<Hash, Ex> tracked_map::Size for ReadyTx<Hash, Ex> {
@@ -133,11 +138,37 @@ impl<Hash: hash::Hash + Eq, Ex> Default for ReadyTransactions<Hash, Ex> {
 			provided_tags: Default::default(),
 			ready: Default::default(),
 			best: Default::default(),
+			min_replace_bump: 0,
+			senders: Default::default(),
 		}
 	}
 }
 
 impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
+	/// Sets the minimal priority increase (in percent) a transaction needs to replace
+	/// the transactions providing the same tags.
+	///
+	/// The replacement always needs a strictly higher priority, even with no bump required.
+	pub fn set_min_replace_bump(&mut self, percent: u32) {
+		self.min_replace_bump = percent;
+	}
+
+	/// Sets the length of the tag prefix identifying the sender of a transaction.
+	pub fn set_sender_tag_prefix(&mut self, tag_prefix: usize) {
+		self.senders.reset(tag_prefix);
+		let ready = self.ready.read();
+		let mut transactions = ready.values().map(|tx| &tx.transaction).collect::<Vec<_>>();
+		transactions.sort_by_key(|tx| tx.insertion_id);
+		for tx in transactions {
+			self.senders.insert(&tx.transaction);
+		}
+	}
+
+	/// Ready transactions by sender.
+	pub fn senders(&self) -> &SenderIndex<Hash> {
+		&self.senders
+	}
+
 	/// Borrows a map of tags that are provided by transactions in this queue.
 	pub fn provided_tags(&self) -> &HashMap<Tag, Hash> {
 		&self.provided_tags
@@ -218,6 +249,7 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 		}
 
 		// insert to Ready
+		self.senders.insert(&transaction.transaction);
 		ready.insert(hash, ReadyTx {
 			transaction,
 			unlocks,
@@ -227,14 +259,6 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 		Ok(replaced)
 	}
 
-	/// Fold a list of ready transactions to compute a single value.
-	pub fn fold<R, F: FnMut(Option<R>, &ReadyTx<Hash, Ex>) -> Option<R>>(&mut self, f: F) -> Option<R> {
-		self.ready
-			.read()
-			.values()
-			.fold(None, f)
-	}
-
 	/// Returns true if given hash is part of the queue.
 	pub fn contains(&self, hash: &Hash) -> bool {
 		self.ready.read().contains_key(hash)
@@ -277,6 +301,7 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 		let mut ready = self.ready.write();
 		while let Some(hash) = to_remove.pop() {
 			if let Some(mut tx) = ready.remove(&hash) {
+				self.senders.remove(&hash);
 				let invalidated = tx.transaction.transaction.provides
 					.iter()
 					.filter(|tag| provides_tag_filter
@@ -332,6 +357,7 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 				.and_then(|hash| self.ready.write().remove(&hash));
 
 			if let Some(tx) = res {
+				self.senders.remove(&tx.transaction.transaction.hash);
 				let unlocks = tx.unlocks;
 
 				// Make sure we remove it from best txs
@@ -407,7 +433,8 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 	///
 	/// In case that's true it determines if the priority of transactions that
 	/// we are about to replace is lower than the priority of the replacement transaction.
-	/// We remove/replace old transactions in case they have lower priority.
+	/// We remove/replace old transactions in case they have lower priority
+	/// by at least `min_replace_bump` percent.
 	///
 	/// In case replacement is successful returns a list of removed transactions
 	/// and a list of hashes that are still in pool and gets unlocked by the new transaction.
@@ -441,7 +468,9 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 			};
 
 			// bail - the transaction has too low priority to replace the old ones
-			if old_priority >= tx.priority {
+			let bump = (u128::from(old_priority) * u128::from(self.min_replace_bump) / 100)
+				.min(u64::max_value().into()) as u64;
+			if old_priority >= tx.priority || old_priority.saturating_add(bump) > tx.priority {
 				return Err(error::Error::TooLowPriority { old: old_priority, new: tx.priority })
 			}
 
@@ -605,6 +634,59 @@ mod tests {
 		assert_eq!(ready.get().count(), 1);
 	}
 
+	#[test]
+	fn should_require_minimal_priority_bump_to_replace() {
+		// given
+		let mut ready = ReadyTransactions::default();
+		ready.set_min_replace_bump(10);
+		let mut tx1 = tx(1);
+		tx1.requires.clear();
+		tx1.provides = vec![vec![3]];
+		tx1.priority = 100;
+		let mut tx2 = tx(2);
+		tx2.requires.clear();
+		tx2.provides = vec![vec![3]];
+		tx2.priority = 109;
+		import(&mut ready, tx1).unwrap();
+
+		// when
+		let err = import(&mut ready, tx2.clone()).unwrap_err();
+		tx2.priority = 110;
+		let replaced = import(&mut ready, tx2).unwrap();
+
+		// then
+		assert!(matches!(err, error::Error::TooLowPriority { old: 100, new: 109 }));
+		assert_eq!(replaced.len(), 1);
+		assert_eq!(replaced[0].hash, 1);
+		assert_eq!(ready.get().map(|tx| tx.hash).collect::<Vec<_>>(), vec![2]);
+	}
+
+	#[test]
+	fn should_compute_priority_bump_without_overflow() {
+		// given
+		let mut ready = ReadyTransactions::default();
+		ready.set_min_replace_bump(10);
+		let old_priority = u64::max_value() / 2;
+		let mut tx1 = tx(1);
+		tx1.requires.clear();
+		tx1.provides = vec![vec![3]];
+		tx1.priority = old_priority;
+		let mut tx2 = tx(2);
+		tx2.requires.clear();
+		tx2.provides = vec![vec![3]];
+		tx2.priority = old_priority + old_priority / 20;
+		import(&mut ready, tx1).unwrap();
+
+		// when
+		let err = import(&mut ready, tx2.clone()).unwrap_err();
+		tx2.priority = old_priority + old_priority / 10;
+		let replaced = import(&mut ready, tx2).unwrap();
+
+		// then
+		assert!(matches!(err, error::Error::TooLowPriority { .. }));
+		assert_eq!(replaced.len(), 1);
+	}
+
 	#[test]
 	fn should_replace_multiple_transactions_correctly() {
 		// given
@@ -0,0 +1,212 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Index of the transactions of a queue by sender.
+//!
+//! Transactions are attributed to a sender by the prefix of the first tag they provide, which
+//! for the usual `(account, nonce)` tags identifies the account. The index is kept up to date
+//! as transactions enter and leave the queue, so that the transaction to evict when enforcing
+//! the limits is found without scanning the queue.
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	hash,
+};
+
+use tp_runtime::transaction_validity::{
+	TransactionTag as Tag,
+	TransactionPriority as Priority,
+};
+
+use crate::base_pool::Transaction;
+
+/// Default length of the tag prefix identifying the sender.
+pub const DEFAULT_TAG_PREFIX: usize = 32;
+
+/// Transactions of a single sender.
+#[derive(Debug, tetsy_util_mem::MallocSizeOf)]
+struct SenderTransactions<Hash> {
+	/// Transactions by priority, then from the most recently indexed one, as
+	/// `u64::max_value() - seq`.
+	by_priority: BTreeMap<(Priority, u64), Hash>,
+	/// Sequence numbers of the transactions.
+	seqs: BTreeSet<u64>,
+}
+
+impl<Hash> Default for SenderTransactions<Hash> {
+	fn default() -> Self {
+		SenderTransactions {
+			by_priority: Default::default(),
+			seqs: Default::default(),
+		}
+	}
+}
+
+impl<Hash> SenderTransactions<Hash> {
+	/// Key of the sender in `SenderIndex::by_count`.
+	fn count_key(&self, sender: Tag) -> Option<(usize, u64, Tag)> {
+		let oldest = *self.seqs.iter().next()?;
+		Some((self.by_priority.len(), u64::max_value() - oldest, sender))
+	}
+}
+
+/// Index of the transactions of a queue by sender.
+#[derive(Debug, tetsy_util_mem::MallocSizeOf)]
+pub struct SenderIndex<Hash: hash::Hash + Eq> {
+	/// Length of the `provides` tag prefix identifying the sender.
+	tag_prefix: usize,
+	/// Sequence number of the next indexed transaction.
+	next_seq: u64,
+	/// Sender, priority and sequence number of the indexed transactions.
+	by_hash: HashMap<Hash, (Tag, Priority, u64)>,
+	/// Transactions of each sender.
+	senders: HashMap<Tag, SenderTransactions<Hash>>,
+	/// Senders by number of transactions, then from the one with the oldest transaction, as
+	/// `u64::max_value() - seq`.
+	by_count: BTreeSet<(usize, u64, Tag)>,
+}
+
+impl<Hash: hash::Hash + Eq> Default for SenderIndex<Hash> {
+	fn default() -> Self {
+		SenderIndex {
+			tag_prefix: DEFAULT_TAG_PREFIX,
+			next_seq: 0,
+			by_hash: Default::default(),
+			senders: Default::default(),
+			by_count: Default::default(),
+		}
+	}
+}
+
+impl<Hash: hash::Hash + Eq + Clone> SenderIndex<Hash> {
+	/// Sets the length of the tag prefix identifying the sender and clears the index.
+	///
+	/// The transactions of the queue have to be indexed again.
+	pub fn reset(&mut self, tag_prefix: usize) {
+		*self = SenderIndex { tag_prefix, ..Default::default() };
+	}
+
+	/// Indexes a transaction entering the queue.
+	pub fn insert<Ex>(&mut self, tx: &Transaction<Hash, Ex>) {
+		let sender = tx.provides.first()
+			.map(|tag| tag[..tag.len().min(self.tag_prefix)].to_vec())
+			.unwrap_or_default();
+		let seq = self.next_seq;
+		self.next_seq += 1;
+
+		let txs = self.senders.entry(sender.clone()).or_default();
+		if let Some(key) = txs.count_key(sender.clone()) {
+			self.by_count.remove(&key);
+		}
+		txs.by_priority.insert((tx.priority, u64::max_value() - seq), tx.hash.clone());
+		txs.seqs.insert(seq);
+		self.by_count.extend(txs.count_key(sender.clone()));
+		self.by_hash.insert(tx.hash.clone(), (sender, tx.priority, seq));
+	}
+
+	/// Removes a transaction leaving the queue from the index.
+	pub fn remove(&mut self, hash: &Hash) {
+		let (sender, priority, seq) = match self.by_hash.remove(hash) {
+			Some(entry) => entry,
+			None => return,
+		};
+		let txs = match self.senders.get_mut(&sender) {
+			Some(txs) => txs,
+			None => return,
+		};
+		if let Some(key) = txs.count_key(sender.clone()) {
+			self.by_count.remove(&key);
+		}
+		txs.by_priority.remove(&(priority, u64::max_value() - seq));
+		txs.seqs.remove(&seq);
+		match txs.count_key(sender.clone()) {
+			Some(key) => { self.by_count.insert(key); },
+			None => { self.senders.remove(&sender); },
+		}
+	}
+
+	/// Clears the index.
+	pub fn clear(&mut self) {
+		self.reset(self.tag_prefix);
+	}
+
+	/// Returns the transaction to remove from a sender with more than `quota` transactions.
+	///
+	/// The sender with the most transactions is picked, the one with the oldest transaction
+	/// on ties, and its lowest priority, most recently indexed transaction is returned.
+	pub fn worst(&self, quota: usize) -> Option<Hash> {
+		let (count, _, sender) = self.by_count.iter().next_back()?;
+		if *count <= quota {
+			return None;
+		}
+		self.senders.get(sender)?.by_priority.values().next().cloned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tp_runtime::transaction_validity::TransactionSource;
+
+	fn tx(sender: u8, nonce: u8, priority: Priority) -> Transaction<u64, ()> {
+		Transaction {
+			data: (),
+			bytes: 1,
+			hash: (sender as u64) << 8 | nonce as u64,
+			priority,
+			valid_till: 64,
+			requires: vec![],
+			provides: vec![vec![sender, nonce]],
+			propagate: true,
+			source: TransactionSource::External,
+		}
+	}
+
+	#[test]
+	fn picks_lowest_priority_latest_transaction_of_largest_sender() {
+		let mut index = SenderIndex::default();
+		index.reset(1);
+		index.insert(&tx(1, 0, 5));
+		index.insert(&tx(2, 0, 5));
+		index.insert(&tx(2, 1, 1));
+		index.insert(&tx(2, 2, 1));
+		index.insert(&tx(2, 3, 5));
+
+		assert_eq!(index.worst(4), None);
+		assert_eq!(index.worst(3), Some(tx(2, 2, 1).hash));
+		index.remove(&tx(2, 2, 1).hash);
+		assert_eq!(index.worst(3), None);
+		assert_eq!(index.worst(0), Some(tx(2, 1, 1).hash));
+	}
+
+	#[test]
+	fn breaks_ties_by_oldest_transaction() {
+		let mut index = SenderIndex::default();
+		index.reset(1);
+		index.insert(&tx(1, 0, 5));
+		index.insert(&tx(2, 0, 5));
+		assert_eq!(index.worst(0), Some(tx(1, 0, 5).hash));
+
+		index.remove(&tx(1, 0, 5).hash);
+		index.insert(&tx(1, 1, 5));
+		assert_eq!(index.worst(0), Some(tx(2, 0, 5).hash));
+
+		index.clear();
+		assert_eq!(index.worst(0), None);
+	}
+}
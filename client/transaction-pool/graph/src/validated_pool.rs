@@ -34,9 +34,9 @@ use tp_runtime::{
 	traits::{self, SaturatedConversion},
 	transaction_validity::{TransactionTag as Tag, ValidTransaction, TransactionSource},
 };
-use tp_transaction_pool::{error, PoolStatus};
+use tp_transaction_pool::{error, PoolEvent, PoolStatus};
 use wasm_timer::Instant;
-use futures::channel::mpsc::{channel, Sender};
+use futures::channel::mpsc::{channel, Receiver, Sender};
 use retain_mut::RetainMut;
 
 use crate::base_pool::PruneStatus;
@@ -117,7 +117,9 @@ where
 impl<B: ChainApi> ValidatedPool<B> {
 	/// Create a new transaction pool.
 	pub fn new(options: Options, api: Arc<B>) -> Self {
-		let base_pool = base::BasePool::new(options.reject_future_transactions);
+		let mut base_pool = base::BasePool::new(options.reject_future_transactions);
+		base_pool.set_min_replace_bump(options.min_replace_bump);
+		base_pool.set_sender_tag_prefix(options.sender_tag_prefix);
 		ValidatedPool {
 			options,
 			listener: Default::default(),
@@ -130,7 +132,13 @@ impl<B: ChainApi> ValidatedPool<B> {
 
 	/// Bans given set of hashes.
 	pub fn ban(&self, now: &Instant, hashes: impl IntoIterator<Item=ExtrinsicHash<B>>) {
-		self.rotator.ban(now, hashes)
+		let hashes = hashes.into_iter().collect::<Vec<_>>();
+		self.rotator.ban(now, hashes.iter().cloned());
+
+		let mut listener = self.listener.write();
+		for hash in &hashes {
+			listener.banned(hash);
+		}
 	}
 
 	/// Returns true if transaction with given hash is currently banned from the pool.
@@ -163,9 +171,31 @@ impl<B: ChainApi> ValidatedPool<B> {
 		txs: impl IntoIterator<Item=ValidatedTransactionFor<B>>,
 	) -> Vec<Result<ExtrinsicHash<B>, B::Error>> {
 		let results = txs.into_iter()
-			.map(|validated_tx| self.submit_one(validated_tx))
+			.map(|validated_tx| self.submit_one(validated_tx, None))
 			.collect::<Vec<_>>();
+		self.enforce_limits_after(results)
+	}
+
+	/// Imports a pre-validated transaction replacing the given ready transaction.
+	///
+	/// The transaction is only imported if the replaced one is still ready, and if it provides
+	/// some of its tags without requiring any other tag. This is checked under the same lock
+	/// as the import.
+	pub fn submit_replacement(
+		&self,
+		tx: ValidatedTransactionFor<B>,
+		replaces: &ExtrinsicHash<B>,
+	) -> Result<ExtrinsicHash<B>, B::Error> {
+		self.enforce_limits_after(vec![self.submit_one(tx, Some(replaces))])
+			.pop()
+			.expect("One extrinsic passed; one result returned; qed")
+	}
 
+	/// Enforces the pool limits after an import, failing the imports of the removed transactions.
+	fn enforce_limits_after(
+		&self,
+		results: Vec<Result<ExtrinsicHash<B>, B::Error>>,
+	) -> Vec<Result<ExtrinsicHash<B>, B::Error>> {
 		// only enforce limits if there is at least one imported transaction
 		let removed = if results.iter().any(|res| res.is_ok()) {
 			self.enforce_limits()
@@ -174,16 +204,36 @@ impl<B: ChainApi> ValidatedPool<B> {
 		};
 
 		results.into_iter().map(|res| match res {
-			Ok(ref hash) if removed.contains(hash) => Err(error::Error::ImmediatelyDropped.into()),
+			Ok(ref hash) => match removed.get(hash) {
+				Some(base::DropReason::Limit) => Err(error::Error::ImmediatelyDropped.into()),
+				Some(base::DropReason::SenderQuota) => Err(error::Error::SenderQuotaExceeded.into()),
+				None => res,
+			},
 			other => other,
 		}).collect()
 	}
 
-	/// Submit single pre-validated transaction to the pool.
-	fn submit_one(&self, tx: ValidatedTransactionFor<B>) -> Result<ExtrinsicHash<B>, B::Error> {
+	/// Submit single pre-validated transaction to the pool, replacing the given ready transaction
+	/// if any.
+	fn submit_one(
+		&self,
+		tx: ValidatedTransactionFor<B>,
+		replaces: Option<&ExtrinsicHash<B>>,
+	) -> Result<ExtrinsicHash<B>, B::Error> {
 		match tx {
 			ValidatedTransaction::Valid(tx) => {
-				let imported = self.pool.write().import(tx)?;
+				let imported = {
+					let mut pool = self.pool.write();
+					if let Some(replaces) = replaces {
+						let replaced = pool.ready_by_hash(replaces).ok_or(error::Error::NoReplacement)?;
+						let replaces_tags = tx.provides.iter().any(|tag| replaced.provides.contains(tag));
+						let same_requirements = tx.requires.iter().all(|tag| replaced.requires.contains(tag));
+						if !replaces_tags || !same_requirements {
+							return Err(error::Error::NoReplacement.into());
+						}
+					}
+					pool.import(tx)?
+				};
 
 				if let base::Imported::Ready { ref hash, .. } = imported {
 					self.import_notification_sinks.lock()
@@ -207,7 +257,7 @@ impl<B: ChainApi> ValidatedPool<B> {
 				Ok(imported.hash().clone())
 			},
 			ValidatedTransaction::Invalid(hash, err) => {
-				self.rotator.ban(&Instant::now(), std::iter::once(hash));
+				self.ban(&Instant::now(), std::iter::once(hash));
 				Err(err.into())
 			},
 			ValidatedTransaction::Unknown(hash, err) => {
@@ -217,13 +267,15 @@ impl<B: ChainApi> ValidatedPool<B> {
 		}
 	}
 
-	fn enforce_limits(&self) -> HashSet<ExtrinsicHash<B>> {
+	fn enforce_limits(&self) -> HashMap<ExtrinsicHash<B>, base::DropReason> {
 		let status = self.pool.read().status();
 		let ready_limit = &self.options.ready;
 		let future_limit = &self.options.future;
+		let sender_limit = &self.options.sender;
 
 		log::debug!(target: "txpool", "Pool Status: {:?}", status);
-		if ready_limit.is_exceeded(status.ready, status.ready_bytes)
+		if sender_limit.is_enabled()
+			|| ready_limit.is_exceeded(status.ready, status.ready_bytes)
 			|| future_limit.is_exceeded(status.future, status.future_bytes)
 		{
 			log::debug!(
@@ -234,22 +286,21 @@ impl<B: ChainApi> ValidatedPool<B> {
 			);
 
 			// clean up the pool
-			let removed = {
-				let mut pool = self.pool.write();
-				let removed = pool.enforce_limits(ready_limit, future_limit)
-					.into_iter().map(|x| x.hash.clone()).collect::<HashSet<_>>();
-				// ban all removed transactions
-				self.rotator.ban(&Instant::now(), removed.iter().map(|x| x.clone()));
-				removed
-			};
+			let removed = self.pool.write().enforce_limits(ready_limit, future_limit, sender_limit)
+				.into_iter().map(|(x, reason)| (x.hash.clone(), reason)).collect::<HashMap<_, _>>();
+			// ban all removed transactions
+			self.ban(&Instant::now(), removed.keys().cloned());
 			if !removed.is_empty() {
 				log::debug!(target: "txpool", "Enforcing limits: {} dropped", removed.len());
 			}
 
 			// run notifications
 			let mut listener = self.listener.write();
-			for h in &removed {
-				listener.dropped(h, None);
+			for (h, reason) in &removed {
+				match reason {
+					base::DropReason::Limit => listener.dropped(h, None),
+					base::DropReason::SenderQuota => listener.dropped_for_quota(h),
+				}
 			}
 
 			removed
@@ -273,7 +324,7 @@ impl<B: ChainApi> ValidatedPool<B> {
 					.map(|_| watcher)
 			},
 			ValidatedTransaction::Invalid(hash, err) => {
-				self.rotator.ban(&Instant::now(), std::iter::once(hash));
+				self.ban(&Instant::now(), std::iter::once(hash));
 				Err(err.into())
 			},
 			ValidatedTransaction::Unknown(_, err) => Err(err.into()),
@@ -549,6 +600,11 @@ impl<B: ChainApi> ValidatedPool<B> {
 		stream
 	}
 
+	/// Get an event stream of all transactions entering, moving within and leaving the pool.
+	pub fn pool_event_stream(&self) -> Receiver<PoolEvent<ExtrinsicHash<B>, BlockHash<B>>> {
+		self.listener.write().create_event_stream()
+	}
+
 	/// Invoked when extrinsics are broadcasted.
 	pub fn on_broadcasted(&self, propagated: HashMap<ExtrinsicHash<B>, Vec<String>>) {
 		let mut listener = self.listener.write();
@@ -572,7 +628,7 @@ impl<B: ChainApi> ValidatedPool<B> {
 		log::debug!(target: "txpool", "Removing invalid transactions: {:?}", hashes);
 
 		// temporarily ban invalid transactions
-		self.rotator.ban(&Instant::now(), hashes.iter().cloned());
+		self.ban(&Instant::now(), hashes.iter().cloned());
 
 		let invalid = self.pool.write().remove_subtree(hashes);
 
@@ -591,6 +647,11 @@ impl<B: ChainApi> ValidatedPool<B> {
 		self.pool.read().ready()
 	}
 
+	/// Get future transactions along with the tags they are missing.
+	pub fn futures(&self) -> Vec<(TransactionFor<B>, Vec<Tag>)> {
+		self.pool.read().futures_with_missing_tags().collect()
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> PoolStatus {
 		self.pool.read().status()
@@ -626,7 +687,7 @@ fn fire_events<H, B, Ex>(
 				listener.dropped(&r.hash, Some(hash));
 			}
 			for p in promoted {
-				listener.ready(p, None);
+				listener.promoted(p);
 			}
 		},
 		base::Imported::Future { ref hash } => {
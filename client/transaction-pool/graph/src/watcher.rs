@@ -123,6 +123,12 @@ impl<H: Clone, BH: Clone> Sender<H, BH> {
 		self.is_finalized = true;
 	}
 
+	/// Transaction has been dropped from the pool because its sender exceeded the quota.
+	pub fn dropped_for_quota(&mut self) {
+		self.send(TransactionStatus::DroppedForQuota);
+		self.is_finalized = true;
+	}
+
 	/// The extrinsic has been broadcast to the given peers.
 	pub fn broadcast(&mut self, peers: Vec<String>) {
 		self.send(TransactionStatus::Broadcast(peers))
@@ -0,0 +1,319 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! On-disk journal of the transactions submitted to this node.
+//!
+//! The journal is an append-only log of SCALE encoded records, each one either adding an
+//! encoded extrinsic or removing it by hash. It is rewritten with only the live entries when
+//! it is opened and whenever removed records outweigh the live ones. A record that was only
+//! partially written when the node went down is ignored.
+
+use std::{
+	fs::{self, File, OpenOptions},
+	hash,
+	io::{self, Write},
+	path::{Path, PathBuf},
+};
+use codec::{Encode, Decode};
+use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
+
+/// Number of records the log is allowed to grow to before it is compacted, on top of twice
+/// the number of live entries.
+const COMPACTION_SLACK: usize = 1024;
+
+#[derive(Encode, Decode)]
+enum Record<Hash> {
+	Add(Hash, Vec<u8>),
+	Remove(Hash),
+}
+
+struct Entry<BlockHash, Number> {
+	encoded: Vec<u8>,
+	/// Hash and number of the block of the best chain the transaction was included in, if any.
+	included_in: Option<(BlockHash, Number)>,
+}
+
+struct Inner<Hash: hash::Hash + Eq, BlockHash, Number> {
+	file: File,
+	entries: LinkedHashMap<Hash, Entry<BlockHash, Number>>,
+	records: usize,
+}
+
+/// Journal of the encoded extrinsics submitted to this node, keyed by hash.
+///
+/// Write errors are logged and otherwise ignored: the journal only makes the pool survive
+/// restarts, it never prevents a transaction from being imported.
+pub struct Journal<Hash: hash::Hash + Eq, BlockHash, Number> {
+	path: PathBuf,
+	inner: Mutex<Inner<Hash, BlockHash, Number>>,
+}
+
+impl<Hash, BlockHash, Number> Journal<Hash, BlockHash, Number> where
+	Hash: hash::Hash + Eq + Clone + Encode + Decode,
+	BlockHash: Clone + PartialEq,
+	Number: Copy + PartialOrd,
+{
+	/// Open the journal at `path`, creating it if it does not exist.
+	pub fn open(path: &Path) -> io::Result<Self> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		let data = match fs::read(path) {
+			Ok(data) => data,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+			Err(e) => return Err(e),
+		};
+
+		let mut entries = LinkedHashMap::new();
+		let mut input = &data[..];
+		while !input.is_empty() {
+			match Record::<Hash>::decode(&mut input) {
+				Ok(Record::Add(hash, encoded)) => {
+					entries.insert(hash, Entry { encoded, included_in: None });
+				},
+				Ok(Record::Remove(hash)) => {
+					entries.remove(&hash);
+				},
+				Err(e) => {
+					log::warn!(
+						target: "txpool",
+						"Ignoring {} trailing bytes of the transaction journal: {:?}",
+						input.len(),
+						e,
+					);
+					break;
+				},
+			}
+		}
+
+		let file = write_compacted(path, &entries)?;
+		Ok(Journal {
+			path: path.to_owned(),
+			inner: Mutex::new(Inner { file, records: entries.len(), entries }),
+		})
+	}
+
+	/// Hashes and encoded extrinsics of the journal, in the order they were added.
+	pub fn transactions(&self) -> Vec<(Hash, Vec<u8>)> {
+		self.inner.lock().entries.iter()
+			.map(|(hash, entry)| (hash.clone(), entry.encoded.clone()))
+			.collect()
+	}
+
+	/// Record the encoded extrinsic with the given hash.
+	pub fn add(&self, hash: Hash, encoded: Vec<u8>) {
+		let mut inner = self.inner.lock();
+		if inner.entries.contains_key(&hash) {
+			return;
+		}
+		self.append(&mut inner, &Record::Add(hash.clone(), encoded.clone()));
+		inner.entries.insert(hash, Entry { encoded, included_in: None });
+	}
+
+	/// Remove the transactions with the given hashes.
+	pub fn remove<'a>(&self, hashes: impl IntoIterator<Item = &'a Hash>) where Hash: 'a {
+		let mut inner = self.inner.lock();
+		for hash in hashes {
+			if inner.entries.remove(hash).is_some() {
+				self.append(&mut inner, &Record::Remove(hash.clone()));
+			}
+		}
+		self.maybe_compact(&mut inner);
+	}
+
+	/// Remember that the transactions with the given hashes were included in block `number`.
+	pub fn mark_included<'a>(
+		&self,
+		block: &BlockHash,
+		number: Number,
+		hashes: impl IntoIterator<Item = &'a Hash>,
+	) where Hash: 'a {
+		let mut inner = self.inner.lock();
+		for hash in hashes {
+			if let Some(entry) = inner.entries.get_mut(hash) {
+				entry.included_in = Some((block.clone(), number));
+			}
+		}
+	}
+
+	/// Forget that transactions were included in `block`, which was retracted.
+	pub fn mark_retracted(&self, block: &BlockHash) {
+		for entry in self.inner.lock().entries.values_mut() {
+			if entry.included_in.as_ref().map_or(false, |(hash, _)| hash == block) {
+				entry.included_in = None;
+			}
+		}
+	}
+
+	/// Forget that transactions were included in blocks up to the finalized block `number`.
+	///
+	/// The transactions of the finalized chain are expected to be removed, the remaining ones
+	/// were included in blocks of abandoned forks.
+	pub fn mark_finalized(&self, number: Number) {
+		for entry in self.inner.lock().entries.values_mut() {
+			if entry.included_in.as_ref().map_or(false, |(_, included)| *included <= number) {
+				entry.included_in = None;
+			}
+		}
+	}
+
+	/// Remove the transactions that `is_live` rejects, unless they are included in a block.
+	///
+	/// Included transactions are only removed once the block is finalized, or once it is
+	/// retracted or abandoned and they are not resubmitted to the pool.
+	pub fn retain_live(&self, mut is_live: impl FnMut(&Hash) -> bool) {
+		let dead = self.inner.lock().entries.iter()
+			.filter(|(hash, entry)| entry.included_in.is_none() && !is_live(hash))
+			.map(|(hash, _)| hash.clone())
+			.collect::<Vec<_>>();
+		self.remove(&dead);
+	}
+
+	fn append(&self, inner: &mut Inner<Hash, BlockHash, Number>, record: &Record<Hash>) {
+		inner.records += 1;
+		if let Err(e) = inner.file.write_all(&record.encode()).and_then(|_| inner.file.flush()) {
+			log::warn!(
+				target: "txpool",
+				"Failed to write to the transaction journal {}: {:?}",
+				self.path.display(),
+				e,
+			);
+		}
+	}
+
+	fn maybe_compact(&self, inner: &mut Inner<Hash, BlockHash, Number>) {
+		if inner.records <= inner.entries.len() * 2 + COMPACTION_SLACK {
+			return;
+		}
+		match write_compacted(&self.path, &inner.entries) {
+			Ok(file) => {
+				inner.file = file;
+				inner.records = inner.entries.len();
+			},
+			Err(e) => log::warn!(
+				target: "txpool",
+				"Failed to compact the transaction journal {}: {:?}",
+				self.path.display(),
+				e,
+			),
+		}
+	}
+}
+
+/// Atomically replace the log at `path` with one adding `entries`, and open it for appending.
+fn write_compacted<Hash: hash::Hash + Eq + Clone + Encode, BlockHash, Number>(
+	path: &Path,
+	entries: &LinkedHashMap<Hash, Entry<BlockHash, Number>>,
+) -> io::Result<File> {
+	let tmp_path = path.with_extension("tmp");
+	{
+		let mut tmp = File::create(&tmp_path)?;
+		for (hash, entry) in entries {
+			tmp.write_all(&Record::Add(hash.clone(), entry.encoded.clone()).encode())?;
+		}
+		tmp.sync_all()?;
+	}
+	fs::rename(&tmp_path, path)?;
+	OpenOptions::new().append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn restores_entries_after_reopening() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("journal");
+		{
+			let journal = Journal::<u64, u64, u64>::open(&path).unwrap();
+			journal.add(1, vec![1]);
+			journal.add(2, vec![2, 2]);
+			journal.add(3, vec![3]);
+			journal.remove(&[2]);
+		}
+		{
+			let journal = Journal::<u64, u64, u64>::open(&path).unwrap();
+			assert_eq!(journal.transactions(), vec![(1, vec![1]), (3, vec![3])]);
+			journal.mark_included(&10, 1, &[1]);
+			journal.retain_live(|_| false);
+			assert_eq!(journal.transactions(), vec![(1, vec![1])]);
+		}
+	}
+
+	#[test]
+	fn removes_retracted_transactions_that_are_not_live() {
+		let dir = tempfile::tempdir().unwrap();
+		let journal = Journal::<u64, u64, u64>::open(&dir.path().join("journal")).unwrap();
+		journal.add(1, vec![1]);
+		journal.add(2, vec![2]);
+		journal.mark_included(&10, 1, &[1]);
+		journal.mark_included(&11, 1, &[2]);
+
+		journal.mark_retracted(&10);
+		journal.retain_live(|_| false);
+		assert_eq!(journal.transactions(), vec![(2, vec![2])]);
+	}
+
+	#[test]
+	fn removes_abandoned_fork_transactions_that_are_not_live() {
+		let dir = tempfile::tempdir().unwrap();
+		let journal = Journal::<u64, u64, u64>::open(&dir.path().join("journal")).unwrap();
+		journal.add(1, vec![1]);
+		journal.add(2, vec![2]);
+		journal.mark_included(&10, 1, &[1]);
+		journal.mark_included(&20, 2, &[2]);
+
+		journal.mark_finalized(1);
+		journal.retain_live(|_| false);
+		assert_eq!(journal.transactions(), vec![(2, vec![2])]);
+	}
+
+	#[test]
+	fn ignores_truncated_record() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("journal");
+		{
+			let journal = Journal::<u64, u64, u64>::open(&path).unwrap();
+			journal.add(1, vec![1]);
+			journal.add(2, vec![2; 10]);
+		}
+		let data = fs::read(&path).unwrap();
+		fs::write(&path, &data[..data.len() - 3]).unwrap();
+
+		let journal = Journal::<u64, u64, u64>::open(&path).unwrap();
+		assert_eq!(journal.transactions(), vec![(1, vec![1])]);
+	}
+
+	#[test]
+	fn compacts_removed_records() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("journal");
+		let journal = Journal::<u64, u64, u64>::open(&path).unwrap();
+		for i in 0..(COMPACTION_SLACK as u64 + 10) {
+			journal.add(i, vec![0; 32]);
+			journal.remove(&[i]);
+		}
+		journal.add(u64::max_value(), vec![1]);
+		assert!(fs::metadata(&path).unwrap().len() < 64 * COMPACTION_SLACK as u64);
+		drop(journal);
+
+		assert_eq!(Journal::<u64, u64, u64>::open(&path).unwrap().transactions(), vec![(u64::max_value(), vec![1])]);
+	}
+}
@@ -23,6 +23,7 @@
 #![warn(unused_extern_crates)]
 
 mod api;
+mod journal;
 mod revalidation;
 mod metrics;
 
@@ -37,16 +38,18 @@ pub use crate::api::{FullChainApi, LightChainApi};
 use std::{collections::{HashMap, HashSet}, sync::Arc, pin::Pin, convert::TryInto};
 use futures::{prelude::*, future::{self, ready}, channel::oneshot};
 use parking_lot::Mutex;
+use codec::{Decode, Encode};
 
 use tp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, NumberFor, AtLeast32Bit, Extrinsic, Zero},
+	transaction_validity::TransactionTag,
 };
 use tet_core::traits::SpawnNamed;
 use tp_transaction_pool::{
 	TransactionPool, PoolStatus, ImportNotificationStream, TxHash, TransactionFor,
 	TransactionStatusStreamFor, MaintainedTransactionPool, PoolFuture, ChainEvent,
-	TransactionSource,
+	TransactionSource, PoolEventStream, BlockHash, error::IntoPoolError,
 };
 use tc_transaction_graph::{ChainApi, ExtrinsicHash};
 use wasm_timer::Instant;
@@ -81,6 +84,7 @@ pub struct BasicPool<PoolApi, Block>
 	revalidation_queue: Arc<revalidation::RevalidationQueue<PoolApi>>,
 	ready_poll: Arc<Mutex<ReadyPoll<ReadyIteratorFor<PoolApi>, Block>>>,
 	metrics: PrometheusMetrics,
+	journal: Option<Arc<journal::Journal<ExtrinsicHash<PoolApi>, Block::Hash, NumberFor<Block>>>>,
 }
 
 struct ReadyPoll<T, Block: BlockT> {
@@ -174,6 +178,7 @@ impl<PoolApi, Block> BasicPool<PoolApi, Block>
 				revalidation_strategy: Arc::new(Mutex::new(RevalidationStrategy::Always)),
 				ready_poll: Default::default(),
 				metrics: Default::default(),
+				journal: None,
 			},
 			background_task,
 			notifier,
@@ -189,6 +194,18 @@ impl<PoolApi, Block> BasicPool<PoolApi, Block>
 		revalidation_type: RevalidationType,
 		spawner: impl SpawnNamed,
 	) -> Self {
+		let journal = options.journal.as_ref().and_then(|path| match journal::Journal::open(path) {
+			Ok(journal) => Some(Arc::new(journal)),
+			Err(e) => {
+				log::warn!(
+					target: "txpool",
+					"Failed to open the transaction journal {}, transactions will not be persisted: {:?}",
+					path.display(),
+					e,
+				);
+				None
+			},
+		});
 		let pool = Arc::new(tc_transaction_graph::Pool::new(options, pool_api.clone()));
 		let (revalidation_queue, background_task) = match revalidation_type {
 			RevalidationType::Light => (revalidation::RevalidationQueue::new(pool_api.clone(), pool.clone()), None),
@@ -214,6 +231,7 @@ impl<PoolApi, Block> BasicPool<PoolApi, Block>
 			)),
 			ready_poll: Default::default(),
 			metrics: PrometheusMetrics::new(prometheus),
+			journal,
 		}
 	}
 
@@ -221,6 +239,58 @@ impl<PoolApi, Block> BasicPool<PoolApi, Block>
 	pub fn pool(&self) -> &Arc<tc_transaction_graph::Pool<PoolApi>> {
 		&self.pool
 	}
+
+	/// Returns a future submitting the journaled transactions again at block `at`, if the
+	/// journal is enabled.
+	///
+	/// Transactions that are no longer valid are removed from the journal.
+	pub fn replay_journal(&self, at: BlockId<Block>) -> Option<Pin<Box<dyn Future<Output=()> + Send>>> {
+		let journal = self.journal.clone()?;
+		let pool = self.pool.clone();
+
+		Some(async move {
+			let mut hashes = Vec::new();
+			let mut xts: Vec<tc_transaction_graph::ExtrinsicFor<PoolApi>> = Vec::new();
+			for (hash, encoded) in journal.transactions() {
+				match Decode::decode(&mut &encoded[..]) {
+					Ok(xt) => {
+						hashes.push(hash);
+						xts.push(xt);
+					},
+					Err(e) => {
+						log::warn!(target: "txpool", "[{:?}] Dropping undecodable journaled transaction: {:?}", hash, e);
+						journal.remove(&[hash]);
+					},
+				}
+			}
+			if xts.is_empty() {
+				return;
+			}
+
+			let count = xts.len();
+			let results = match pool.submit_at(&at, TransactionSource::External, xts).await {
+				Ok(results) => results,
+				Err(e) => {
+					log::warn!(target: "txpool", "Failed to replay the transaction journal at {:?}: {:?}", at, e);
+					return;
+				},
+			};
+			let invalid = hashes.iter()
+				.zip(results)
+				.filter_map(|(hash, result)| match result.map_err(IntoPoolError::into_pool_error) {
+					Err(Ok(tp_transaction_pool::error::Error::InvalidTransaction(_))) => Some(hash),
+					_ => None,
+				})
+				.collect::<Vec<_>>();
+			journal.remove(invalid.iter().copied());
+			log::info!(
+				target: "txpool",
+				"Resubmitted {} journaled transactions, {} of them are no longer valid",
+				count,
+				invalid.len(),
+			);
+		}.boxed())
+	}
 }
 
 impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
@@ -242,11 +312,23 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 		xts: Vec<TransactionFor<Self>>,
 	) -> PoolFuture<Vec<Result<TxHash<Self>, Self::Error>>, Self::Error> {
 		let pool = self.pool.clone();
+		let journal = self.journal.clone();
 		let at = *at;
 
 		self.metrics.report(|metrics| metrics.submitted_transactions.inc_by(xts.len() as u64));
 
-		async move { pool.submit_at(&at, source, xts).await }.boxed()
+		async move {
+			let encoded = journal.as_ref().map(|_| xts.iter().map(Encode::encode).collect::<Vec<_>>());
+			let result = pool.submit_at(&at, source, xts).await;
+			if let (Ok(results), Some(journal), Some(encoded)) = (&result, journal, encoded) {
+				for (result, encoded) in results.iter().zip(encoded) {
+					if let Ok(hash) = result {
+						journal.add(hash.clone(), encoded);
+					}
+				}
+			}
+			result
+		}.boxed()
 	}
 
 	fn submit_one(
@@ -256,11 +338,71 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 		xt: TransactionFor<Self>,
 	) -> PoolFuture<TxHash<Self>, Self::Error> {
 		let pool = self.pool.clone();
+		let journal = self.journal.clone();
 		let at = *at;
 
 		self.metrics.report(|metrics| metrics.submitted_transactions.inc());
 
-		async move { pool.submit_one(&at, source, xt).await }.boxed()
+		async move {
+			let encoded = journal.as_ref().map(|_| xt.encode());
+			let result = pool.submit_one(&at, source, xt).await;
+			if let (Ok(hash), Some(journal), Some(encoded)) = (&result, journal, encoded) {
+				journal.add(hash.clone(), encoded);
+			}
+			result
+		}.boxed()
+	}
+
+	fn submit_and_watch_at(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xts: Vec<TransactionFor<Self>>,
+	) -> PoolFuture<Vec<Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>>, Self::Error> {
+		let pool = self.pool.clone();
+		let journal = self.journal.clone();
+		let at = *at;
+
+		self.metrics.report(|metrics| metrics.submitted_transactions.inc_by(xts.len() as u64));
+
+		async move {
+			let encoded = journal.as_ref().map(|_| xts.iter().map(Encode::encode).collect::<Vec<_>>());
+			let result = pool.submit_and_watch_at(&at, source, xts).await;
+			if let (Ok(results), Some(journal), Some(encoded)) = (&result, journal, encoded) {
+				for (result, encoded) in results.iter().zip(encoded) {
+					if let Ok(watcher) = result {
+						journal.add(watcher.hash().clone(), encoded);
+					}
+				}
+			}
+			result.map(|results| results.into_iter()
+				.map(|result| result.map(|watcher| Box::new(watcher.into_stream()) as _))
+				.collect()
+			)
+		}.boxed()
+	}
+
+	fn replace_at(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xt: TransactionFor<Self>,
+		replaces: TxHash<Self>,
+	) -> PoolFuture<TxHash<Self>, Self::Error> {
+		let pool = self.pool.clone();
+		let journal = self.journal.clone();
+		let at = *at;
+
+		self.metrics.report(|metrics| metrics.submitted_transactions.inc());
+
+		async move {
+			let encoded = journal.as_ref().map(|_| xt.encode());
+			let result = pool.replace_one(&at, source, xt, replaces).await;
+			if let (Ok(hash), Some(journal), Some(encoded)) = (&result, journal, encoded) {
+				journal.add(hash.clone(), encoded);
+			}
+			result
+		}.boxed()
 	}
 
 	fn submit_and_watch(
@@ -271,18 +413,38 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 	) -> PoolFuture<Box<TransactionStatusStreamFor<Self>>, Self::Error> {
 		let at = *at;
 		let pool = self.pool.clone();
+		let journal = self.journal.clone();
 
 		self.metrics.report(|metrics| metrics.submitted_transactions.inc());
 
 		async move {
-			pool.submit_and_watch(&at, source, xt)
-				.map(|result| result.map(|watcher| Box::new(watcher.into_stream()) as _))
-				.await
+			let encoded = journal.as_ref().map(|_| xt.encode());
+			let result = pool.submit_and_watch(&at, source, xt).await;
+			if let (Ok(watcher), Some(journal), Some(encoded)) = (&result, journal, encoded) {
+				journal.add(watcher.hash().clone(), encoded);
+			}
+			result.map(|watcher| Box::new(watcher.into_stream()) as _)
 		}.boxed()
 	}
 
+	fn import_from_network(
+		&self,
+		at: &BlockId<Self::Block>,
+		xt: TransactionFor<Self>,
+	) -> PoolFuture<TxHash<Self>, Self::Error> {
+		let pool = self.pool.clone();
+		let at = *at;
+
+		self.metrics.report(|metrics| metrics.submitted_transactions.inc());
+
+		async move { pool.submit_one(&at, TransactionSource::External, xt).await }.boxed()
+	}
+
 	fn remove_invalid(&self, hashes: &[TxHash<Self>]) -> Vec<Arc<Self::InPoolTransaction>> {
 		let removed = self.pool.validated_pool().remove_invalid(hashes);
+		if let Some(journal) = &self.journal {
+			journal.remove(hashes);
+		}
 		self.metrics.report(|metrics| metrics.validations_invalid.inc_by(removed.len() as u64));
 		removed
 	}
@@ -295,6 +457,10 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 		self.pool.validated_pool().import_notification_stream()
 	}
 
+	fn pool_event_stream(&self) -> PoolEventStream<TxHash<Self>, BlockHash<Self>> {
+		self.pool.validated_pool().pool_event_stream()
+	}
+
 	fn hash_of(&self, xt: &TransactionFor<Self>) -> TxHash<Self> {
 		self.pool.hash_of(xt)
 	}
@@ -307,6 +473,10 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 		self.pool.validated_pool().ready_by_hash(hash)
 	}
 
+	fn futures(&self) -> Vec<(Arc<Self::InPoolTransaction>, Vec<TransactionTag>)> {
+		self.pool.validated_pool().futures()
+	}
+
 	fn ready_at(&self, at: NumberFor<Self::Block>) -> PolledIterator<PoolApi> {
 		if self.ready_poll.lock().updated_at() >= at {
 			log::trace!(target: "txpool", "Transaction pool already processed block  #{}", at);
@@ -344,10 +514,17 @@ where
 		client: Arc<Client>,
 		fetcher: Arc<Fetcher>,
 	) -> Self {
+		let best_block = BlockId::Hash(client.info().best_hash);
 		let pool_api = Arc::new(LightChainApi::new(client, fetcher));
-		Self::with_revalidation_type(
-			options, pool_api, prometheus, RevalidationType::Light, spawner,
-		)
+		let pool = Self::with_revalidation_type(
+			options, pool_api, prometheus, RevalidationType::Light, spawner.clone(),
+		);
+
+		if let Some(replay) = pool.replay_journal(best_block) {
+			spawner.spawn("txpool-journal", replay);
+		}
+
+		pool
 	}
 }
 
@@ -356,7 +533,8 @@ where
 	Block: BlockT,
 	Client: tp_api::ProvideRuntimeApi<Block>
 		+ tc_client_api::BlockBackend<Block>
-		+ tp_runtime::traits::BlockIdTo<Block>,
+		+ tp_runtime::traits::BlockIdTo<Block>
+		+ tp_blockchain::HeaderBackend<Block>,
 	Client: tc_client_api::ExecutorProvider<Block> + Send + Sync + 'static,
 	Client::Api: tp_transaction_pool::runtime_api::TaggedTransactionQueue<Block>,
 	tp_api::ApiErrorFor<Client, Block>: Send + std::fmt::Display,
@@ -370,9 +548,13 @@ where
 	) -> Arc<Self> {
 		let pool_api = Arc::new(FullChainApi::new(client.clone(), prometheus));
 		let pool = Arc::new(Self::with_revalidation_type(
-			options, pool_api, prometheus, RevalidationType::Full, spawner
+			options, pool_api, prometheus, RevalidationType::Full, spawner.clone(),
 		));
 
+		if let Some(replay) = pool.replay_journal(BlockId::Hash(client.info().best_hash)) {
+			spawner.spawn("txpool-journal", replay);
+		}
+
 		// make transaction pool available for off-chain runtime calls.
 		client.execution_extensions().register_transaction_pool(&pool);
 
@@ -575,6 +757,7 @@ impl<PoolApi, Block> MaintainedTransactionPool for BasicPool<PoolApi, Block>
 				let revalidation_queue = self.revalidation_queue.clone();
 				let ready_poll = self.ready_poll.clone();
 				let metrics = self.metrics.clone();
+				let journal = self.journal.clone();
 
 				async move {
 					// We keep track of everything we prune so that later we won't add
@@ -590,6 +773,9 @@ impl<PoolApi, Block> MaintainedTransactionPool for BasicPool<PoolApi, Block>
 						for retracted in tree_route.retracted() {
 							// notify txs awaiting finality that it has been retracted
 							pool.validated_pool().on_block_retracted(retracted.hash.clone());
+							if let Some(ref journal) = journal {
+								journal.mark_retracted(&retracted.hash);
+							}
 						}
 
 						future::join_all(
@@ -603,12 +789,19 @@ impl<PoolApi, Block> MaintainedTransactionPool for BasicPool<PoolApi, Block>
 										&*pool,
 									),
 								),
-						).await.into_iter().for_each(|enacted_log|{
+						).await.into_iter().zip(tree_route.enacted()).for_each(|(enacted_log, enacted)| {
+							if let Some(ref journal) = journal {
+								journal.mark_included(&enacted.hash, enacted.number, &enacted_log);
+							}
 							pruned_log.extend(enacted_log);
 						})
 					}
 
-					pruned_log.extend(prune_known_txs_for_block(id.clone(), &*api, &*pool).await);
+					let best_log = prune_known_txs_for_block(id.clone(), &*api, &*pool).await;
+					if let Some(ref journal) = journal {
+						journal.mark_included(&hash, block_number, &best_log);
+					}
+					pruned_log.extend(best_log);
 
 					metrics.report(
 						|metrics| metrics.block_transactions_pruned.inc_by(pruned_log.len() as u64)
@@ -694,6 +887,8 @@ impl<PoolApi, Block> MaintainedTransactionPool for BasicPool<PoolApi, Block>
 			}
 			ChainEvent::Finalized { hash } => {
 				let pool = self.pool.clone();
+				let api = self.api.clone();
+				let journal = self.journal.clone();
 				async move {
 					if let Err(e) = pool.validated_pool().on_block_finalized(hash).await {
 						log::warn!(
@@ -702,6 +897,30 @@ impl<PoolApi, Block> MaintainedTransactionPool for BasicPool<PoolApi, Block>
 							e, hash
 						)
 					}
+
+					if let Some(journal) = journal {
+						let finalized = api.block_body(&BlockId::Hash(hash)).await
+							.unwrap_or_else(|e| {
+								log::warn!(target: "txpool", "Prune journaled transactions: error request {:?}!", e);
+								None
+							})
+							.unwrap_or_default()
+							.into_iter()
+							.map(|tx| pool.hash_of(&tx))
+							.collect::<Vec<_>>();
+						journal.remove(&finalized);
+						match api.block_id_to_number(&BlockId::Hash(hash)) {
+							Ok(Some(number)) => journal.mark_finalized(number),
+							_ => log::warn!(
+								target: "txpool",
+								"Prune journaled transactions: no number for finalized block {:?}",
+								hash,
+							),
+						}
+						// Transactions that left the pool without being included were either
+						// found invalid or dropped, they would not be accepted again.
+						journal.retain_live(|hash| pool.validated_pool().check_is_known(hash, true).is_err());
+					}
 				}.boxed()
 			}
 		}
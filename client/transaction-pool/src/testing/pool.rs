@@ -459,6 +459,50 @@ fn can_track_heap_size() {
 	assert!(tetsy_util_mem::malloc_size(&pool) > 3000);
 }
 
+#[test]
+fn should_replay_journaled_transactions() {
+	let dir = tempfile::tempdir().unwrap();
+	let journaled_pool = |api: TestApi| {
+		let options = txpool::Options {
+			journal: Some(dir.path().join("journal")),
+			..Default::default()
+		};
+		BasicPool::with_revalidation_type(
+			options,
+			Arc::new(api),
+			None,
+			RevalidationType::Light,
+			tet_core::testing::TaskExecutor::new(),
+		)
+	};
+	let xt = uxt(Alice, 209);
+
+	{
+		let pool = journaled_pool(TestApi::with_alice_nonce(209));
+		block_on(pool.submit_one(&BlockId::number(0), SOURCE, xt.clone())).unwrap();
+		block_on(pool.import_from_network(&BlockId::number(0), uxt(Alice, 210))).unwrap();
+	}
+	{
+		let pool = journaled_pool(TestApi::with_alice_nonce(209));
+		block_on(pool.replay_journal(BlockId::number(0)).unwrap());
+		let ready: Vec<_> = pool.ready().map(|tx| tx.data.transfer().nonce).collect();
+		assert_eq!(ready, vec![209]);
+	}
+	{
+		let api = TestApi::with_alice_nonce(209);
+		api.add_invalid(&xt);
+		let pool = journaled_pool(api);
+		block_on(pool.replay_journal(BlockId::number(0)).unwrap());
+		assert_eq!(pool.status().ready, 0);
+	}
+	{
+		// the invalid transaction was removed from the journal
+		let pool = journaled_pool(TestApi::with_alice_nonce(209));
+		block_on(pool.replay_journal(BlockId::number(0)).unwrap());
+		assert_eq!(pool.status().ready, 0);
+	}
+}
+
 #[test]
 fn finalization() {
 	let xt = uxt(Alice, 209);
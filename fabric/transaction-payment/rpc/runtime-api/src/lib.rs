@@ -20,15 +20,22 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use codec::Codec;
-use tp_runtime::traits::MaybeDisplay;
+use tetcore_std::vec::Vec;
+use tp_runtime::{Perbill, traits::MaybeDisplay};
 
 pub use noble_transaction_payment::{FeeDetails, InclusionFee, RuntimeDispatchInfo};
 
 tp_api::decl_runtime_apis! {
+	#[api_version(2)]
 	pub trait TransactionPaymentApi<Balance> where
 		Balance: Codec + MaybeDisplay,
 	{
 		fn query_info(uxt: Block::Extrinsic, len: u32) -> RuntimeDispatchInfo<Balance>;
 		fn query_fee_details(uxt: Block::Extrinsic, len: u32) -> FeeDetails<Balance>;
+		/// Returns the minimal tip paid by the signed extrinsics, `None` if none of them is signed.
+		fn query_min_tip(uxts: Vec<Block::Extrinsic>) -> Option<Balance>;
+		/// Returns how full the block is, relative to the maximum block weight.
+		#[skip_initialize_block]
+		fn query_block_fullness() -> Perbill;
 	}
 }
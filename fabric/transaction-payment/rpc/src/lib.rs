@@ -20,12 +20,22 @@
 use std::sync::Arc;
 use std::convert::TryInto;
 use codec::{Codec, Decode};
+use futures::StreamExt;
+use linked_hash_map::LinkedHashMap;
+use log::debug;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tc_client_api::{BlockBackend, BlockchainEvents};
 use tp_blockchain::HeaderBackend;
+use tp_consensus::BlockOrigin;
 use tetsy_jsonrpc_core::{Error as RpcError, ErrorCode, Result};
 use tetsy_jsonrpc_derive::rpc;
-use tp_runtime::{generic::BlockId, traits::{Block as BlockT, MaybeDisplay}};
-use tp_api::ProvideRuntimeApi;
-use tet_core::Bytes;
+use tp_runtime::{
+	Perbill, generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, MaybeDisplay, Zero},
+};
+use tp_api::{ApiExt, ProvideRuntimeApi};
+use tet_core::{Bytes, U256};
 use tp_rpc::number::NumberOrHex;
 use noble_transaction_payment_rpc_runtime_api::{FeeDetails, InclusionFee, RuntimeDispatchInfo};
 pub use noble_transaction_payment_rpc_runtime_api::TransactionPaymentApi as TransactionPaymentRuntimeApi;
@@ -45,18 +55,198 @@ pub trait TransactionPaymentApi<BlockHash, ResponseType> {
 		encoded_xt: Bytes,
 		at: Option<BlockHash>
 	) -> Result<FeeDetails<NumberOrHex>>;
+	/// Suggest tips for getting an extrinsic included within the given numbers of blocks.
+	///
+	/// The suggestions are based on how full the recent blocks up to `at` were and on the
+	/// minimal tip paid by the extrinsics they included. Only the blocks imported since the
+	/// node started, outside of the initial sync, are taken into account.
+	#[rpc(name = "payment_estimateInclusion")]
+	fn estimate_inclusion(
+		&self,
+		targets: Vec<u32>,
+		at: Option<BlockHash>
+	) -> Result<InclusionEstimate<BlockHash>>;
+}
+
+/// Number of recent blocks the inclusion estimate is based on.
+const ESTIMATE_WINDOW: usize = 20;
+/// Number of per-block statistics kept in the cache.
+const STATS_CACHE_SIZE: usize = 256;
+/// Blocks below this fullness are considered to have included any extrinsic, whatever its tip.
+const CONGESTION_THRESHOLD: Perbill = Perbill::from_percent(50);
+
+/// Inclusion statistics of a single block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct BlockInclusion<BlockHash> {
+	/// Hash of the block.
+	pub hash: BlockHash,
+	/// Consumed weight of the block, relative to the maximum block weight.
+	pub fullness: Perbill,
+	/// The minimal tip paid by a signed extrinsic of the block, if it has any.
+	pub min_tip: Option<NumberOrHex>,
+}
+
+/// A tip suggested for inclusion within `target` blocks.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct TipSuggestion {
+	/// The number of blocks the extrinsic should be included within.
+	pub target: u32,
+	/// The suggested tip.
+	pub tip: NumberOrHex,
+}
+
+/// The result of `payment_estimateInclusion`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct InclusionEstimate<BlockHash> {
+	/// Statistics of the blocks the estimate is based on, most recent first.
+	pub blocks: Vec<BlockInclusion<BlockHash>>,
+	/// Suggested tips, in the order of the requested targets.
+	pub suggestions: Vec<TipSuggestion>,
+}
+
+/// Statistics of a single block.
+#[derive(Clone, Copy)]
+struct BlockStats {
+	fullness: Perbill,
+	min_tip: Option<U256>,
+}
+
+impl BlockStats {
+	/// The tip an extrinsic needed to be included in this block.
+	fn required_tip(&self) -> U256 {
+		if self.fullness < CONGESTION_THRESHOLD {
+			U256::zero()
+		} else {
+			self.min_tip.unwrap_or_else(U256::zero)
+		}
+	}
+}
+
+/// Returns the tip that would have been sufficient in at least one of `target` blocks,
+/// given the tips required by the recent blocks.
+///
+/// `required` must be sorted in ascending order.
+fn suggest_tip(required: &[U256], target: u32) -> U256 {
+	if required.is_empty() {
+		return U256::zero()
+	}
+	let target = target.max(1) as usize;
+	let needed = (required.len() + target - 1) / target;
+	required[needed - 1]
+}
+
+/// Inclusion statistics of the recently imported blocks.
+///
+/// They are collected by [`collect_inclusion_stats`] as blocks are imported, so that
+/// `payment_estimateInclusion` never has to call into the runtime.
+pub struct InclusionStats<Block: BlockT> {
+	blocks: Mutex<LinkedHashMap<Block::Hash, BlockStats>>,
+}
+
+impl<Block: BlockT> InclusionStats<Block> {
+	/// Create empty statistics.
+	pub fn new() -> Self {
+		InclusionStats { blocks: Mutex::new(LinkedHashMap::new()) }
+	}
+
+	fn get(&self, hash: &Block::Hash) -> Option<BlockStats> {
+		self.blocks.lock().get(hash).copied()
+	}
+
+	fn insert(&self, hash: Block::Hash, stats: BlockStats) {
+		let mut blocks = self.blocks.lock();
+		blocks.insert(hash, stats);
+		while blocks.len() > STATS_CACHE_SIZE {
+			blocks.pop_front();
+		}
+	}
+}
+
+impl<Block: BlockT> Default for InclusionStats<Block> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Collect the inclusion statistics of the blocks imported by `client` into `stats`.
+///
+/// Blocks imported during the initial sync are skipped, as well as blocks whose runtime
+/// doesn't support querying tips. The returned future never ends and should be spawned.
+pub async fn collect_inclusion_stats<C, Block, Balance>(client: Arc<C>, stats: Arc<InclusionStats<Block>>) where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + BlockBackend<Block> + BlockchainEvents<Block>,
+	C::Api: TransactionPaymentRuntimeApi<Block, Balance>,
+	Balance: Codec + MaybeDisplay + Copy + TryInto<NumberOrHex>,
+{
+	let mut imports = client.import_notification_stream();
+	while let Some(notification) = imports.next().await {
+		if notification.origin == BlockOrigin::NetworkInitialSync {
+			continue
+		}
+		match block_stats::<_, _, Balance>(&*client, notification.hash) {
+			Ok(Some(block)) => stats.insert(notification.hash, block),
+			Ok(None) => {},
+			Err(e) => debug!(
+				target: "rpc",
+				"Unable to collect inclusion statistics of {}: {:?}",
+				notification.hash,
+				e,
+			),
+		}
+	}
+}
+
+/// Compute the inclusion statistics of the given block, `None` if its body is not available
+/// or if its runtime doesn't support querying tips.
+fn block_stats<C, Block, Balance>(
+	client: &C,
+	hash: Block::Hash,
+) -> std::result::Result<Option<BlockStats>, String> where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + BlockBackend<Block>,
+	C::Api: TransactionPaymentRuntimeApi<Block, Balance>,
+	Balance: Codec + MaybeDisplay + Copy + TryInto<NumberOrHex>,
+{
+	let at = BlockId::hash(hash);
+	let body = match client.block_body(&at).map_err(|e| format!("{:?}", e))? {
+		Some(body) => body,
+		None => return Ok(None),
+	};
+
+	let api = client.runtime_api();
+	let supported = api.has_api_with::<dyn TransactionPaymentRuntimeApi<Block, Balance>, _>(
+		&at,
+		|version| version >= 2,
+	).map_err(|e| format!("{:?}", e))?;
+	if !supported {
+		return Ok(None)
+	}
+
+	let fullness = api.query_block_fullness(&at).map_err(|e| format!("{:?}", e))?;
+	let min_tip = api.query_min_tip(&at, body).map_err(|e| format!("{:?}", e))?
+		.and_then(|tip| TryInto::<NumberOrHex>::try_into(tip).ok())
+		.map(NumberOrHex::into_u256);
+	Ok(Some(BlockStats { fullness, min_tip }))
 }
 
 /// A struct that implements the [`TransactionPaymentApi`].
-pub struct TransactionPayment<C, P> {
+pub struct TransactionPayment<C, P: BlockT> {
 	client: Arc<C>,
+	stats: Arc<InclusionStats<P>>,
 	_marker: std::marker::PhantomData<P>,
 }
 
-impl<C, P> TransactionPayment<C, P> {
-	/// Create new `TransactionPayment` with the given reference to the client.
-	pub fn new(client: Arc<C>) -> Self {
-		Self { client, _marker: Default::default() }
+impl<C, P: BlockT> TransactionPayment<C, P> {
+	/// Create new `TransactionPayment` with the given reference to the client and the inclusion
+	/// statistics collected by [`collect_inclusion_stats`].
+	pub fn new(client: Arc<C>, stats: Arc<InclusionStats<P>>) -> Self {
+		Self { client, stats, _marker: Default::default() }
 	}
 }
 
@@ -155,4 +345,100 @@ where
 			tip: Default::default(),
 		})
 	}
+
+	fn estimate_inclusion(
+		&self,
+		targets: Vec<u32>,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<InclusionEstimate<<Block as BlockT>::Hash>> {
+		if targets.iter().any(|target| *target == 0) {
+			return Err(RpcError {
+				code: ErrorCode::InvalidParams,
+				message: "Inclusion targets must be at least one block.".into(),
+				data: None,
+			})
+		}
+
+		let mut hash = at.unwrap_or_else(||
+			// If the block hash is not supplied assume the best block.
+			self.client.info().best_hash
+		);
+
+		let mut blocks = Vec::new();
+		let mut required = Vec::new();
+		while blocks.len() < ESTIMATE_WINDOW {
+			let header = match self.client.header(BlockId::hash(hash)).map_err(runtime_error)? {
+				Some(header) if !header.number().is_zero() => header,
+				_ => break,
+			};
+			// Older blocks haven't been imported since the node started, or their runtime
+			// doesn't support querying tips.
+			let stats = match self.stats.get(&hash) {
+				Some(stats) => stats,
+				None => break,
+			};
+
+			blocks.push(BlockInclusion {
+				hash,
+				fullness: stats.fullness,
+				min_tip: stats.min_tip.map(Into::into),
+			});
+			required.push(stats.required_tip());
+			hash = *header.parent_hash();
+		}
+
+		required.sort();
+		let suggestions = targets.into_iter()
+			.map(|target| TipSuggestion { target, tip: suggest_tip(&required, target).into() })
+			.collect();
+
+		Ok(InclusionEstimate { blocks, suggestions })
+	}
+}
+
+fn runtime_error<E: std::fmt::Debug>(e: E) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(Error::RuntimeError.into()),
+		message: "Unable to estimate inclusion.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_suggest_tips_for_targets() {
+		let required: Vec<U256> = vec![0u32, 0, 5, 10, 20].into_iter().map(Into::into).collect();
+
+		assert_eq!(suggest_tip(&required, 1), U256::from(20u32));
+		assert_eq!(suggest_tip(&required, 2), U256::from(5u32));
+		assert_eq!(suggest_tip(&required, 3), U256::from(0u32));
+		assert_eq!(suggest_tip(&[], 1), U256::zero());
+	}
+
+	#[test]
+	fn should_not_require_tip_in_blocks_with_room() {
+		let stats = BlockStats { fullness: Perbill::from_percent(10), min_tip: Some(U256::from(100u32)) };
+		assert_eq!(stats.required_tip(), U256::zero());
+
+		let stats = BlockStats { fullness: Perbill::from_percent(90), min_tip: Some(U256::from(100u32)) };
+		assert_eq!(stats.required_tip(), U256::from(100u32));
+
+		let stats = BlockStats { fullness: Perbill::from_percent(90), min_tip: None };
+		assert_eq!(stats.required_tip(), U256::zero());
+	}
+
+	#[test]
+	fn should_keep_stats_of_recent_blocks() {
+		type Block = tp_runtime::testing::Block<tp_runtime::testing::ExtrinsicWrapper<u64>>;
+		let stats = InclusionStats::<Block>::new();
+		let block = BlockStats { fullness: Perbill::from_percent(10), min_tip: None };
+		for n in 0..STATS_CACHE_SIZE as u64 + 1 {
+			stats.insert(tet_core::H256::from_low_u64_be(n), block);
+		}
+		assert!(stats.get(&tet_core::H256::from_low_u64_be(0)).is_none());
+		assert!(stats.get(&tet_core::H256::from_low_u64_be(STATS_CACHE_SIZE as u64)).is_some());
+	}
 }
@@ -59,7 +59,7 @@ use fabric_support::{
 	dispatch::DispatchResult,
 };
 use tp_runtime::{
-	FixedU128, FixedPointNumber, FixedPointOperand, Perquintill, RuntimeDebug,
+	FixedU128, FixedPointNumber, FixedPointOperand, Perbill, Perquintill, RuntimeDebug,
 	transaction_validity::{
 		TransactionPriority, ValidTransaction, TransactionValidityError, TransactionValidity,
 	},
@@ -357,6 +357,17 @@ impl<T: Config> Module<T> where
 		Self::compute_fee_details(len, &dispatch_info, 0u32.into())
 	}
 
+	/// Query the weight consumed by the current block, relative to the maximum block weight.
+	///
+	/// The consumed weight is only reset when the next block is initialized, so on the state of
+	/// an imported block this returns how full that block was.
+	pub fn query_block_fullness() -> Perbill {
+		Perbill::from_rational_approximation(
+			<fabric_system::Module<T>>::block_weight().total(),
+			T::BlockWeights::get().max_block,
+		)
+	}
+
 	/// Compute the final fee value for a particular transaction.
 	pub fn compute_fee(
 		len: u32,
@@ -485,6 +496,11 @@ impl<T: Config> ChargeTransactionPayment<T> where
 		Self(fee)
 	}
 
+	/// Returns the tip paid on top of the fee.
+	pub fn tip(&self) -> BalanceOf<T> {
+		self.0
+	}
+
 	fn withdraw_fee(
 		&self,
 		who: &T::AccountId,
@@ -995,6 +1011,16 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn query_block_fullness_works() {
+		ExtBuilder::default().build().execute_with(|| {
+			assert_eq!(TransactionPayment::query_block_fullness(), Perbill::zero());
+
+			System::register_extra_weight_unchecked(BlockWeights::get().max_block, DispatchClass::Normal);
+			assert_eq!(TransactionPayment::query_block_fullness(), Perbill::one());
+		});
+	}
+
 	#[test]
 	fn compute_fee_works_without_multiplier() {
 		ExtBuilder::default()
@@ -111,6 +111,15 @@ pub struct BlockCheckParams<Block: BlockT> {
 	pub import_existing: bool,
 }
 
+/// Complete state of a block, downloaded by state sync.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImportedState<B: BlockT> {
+	/// Target block hash.
+	pub block: B::Hash,
+	/// State keys and values.
+	pub state: tet_core::storage::Storage,
+}
+
 /// Data required to import a Block.
 #[non_exhaustive]
 pub struct BlockImportParams<Block: BlockT, Transaction> {
@@ -162,6 +171,9 @@ pub struct BlockImportParams<Block: BlockT, Transaction> {
 	pub allow_missing_state: bool,
 	/// Re-validate existing block.
 	pub import_existing: bool,
+	/// Complete state of the block. If this is `Some(_)`, the block is not executed and the state
+	/// replaces the state of the parent block.
+	pub imported_state: Option<ImportedState<Block>>,
 	/// Cached full header hash (with post-digests applied).
 	pub post_hash: Option<Block::Hash>,
 }
@@ -184,6 +196,7 @@ impl<Block: BlockT, Transaction> BlockImportParams<Block, Transaction> {
 			fork_choice: None,
 			allow_missing_state: false,
 			import_existing: false,
+			imported_state: None,
 			post_hash: None,
 		}
 	}
@@ -224,6 +237,7 @@ impl<Block: BlockT, Transaction> BlockImportParams<Block, Transaction> {
 			allow_missing_state: self.allow_missing_state,
 			fork_choice: self.fork_choice,
 			import_existing: self.import_existing,
+			imported_state: self.imported_state,
 			post_hash: self.post_hash,
 		}
 	}
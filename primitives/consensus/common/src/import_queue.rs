@@ -34,7 +34,7 @@ use crate::{
 	error::Error as ConsensusError,
 	block_import::{
 		BlockImport, BlockOrigin, BlockImportParams, ImportedAux, JustificationImport, ImportResult,
-		BlockCheckParams,
+		BlockCheckParams, ImportedState,
 	},
 	metrics::Metrics,
 };
@@ -76,6 +76,8 @@ pub struct IncomingBlock<B: BlockT> {
 	pub allow_missing_state: bool,
 	/// Re-validate existing block.
 	pub import_existing: bool,
+	/// Complete state of the block, downloaded by state sync.
+	pub state: Option<ImportedState<B>>,
 }
 
 /// Type of keys in the blockchain cache that consensus module could use for its needs.
@@ -260,6 +262,8 @@ pub(crate) fn import_single_block_metered<B: BlockT, V: Verifier<B>, Transaction
 		cache.extend(keys.into_iter());
 	}
 	import_block.allow_missing_state = block.allow_missing_state;
+	import_block.import_existing = block.import_existing;
+	import_block.imported_state = block.state;
 
 	let imported = import_handle.import_block(import_block.convert_transaction(), cache);
 	if let Some(metrics) = metrics.as_ref() {
@@ -529,6 +529,7 @@ mod tests {
 					origin: None,
 					allow_missing_state: false,
 					import_existing: false,
+					state: None,
 				}],
 			)))
 			.unwrap();
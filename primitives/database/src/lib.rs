@@ -140,6 +140,14 @@ pub trait Database<H: Clone>: Send + Sync {
 	/// currently stored.
 	fn lookup(&self, hash: &H) -> Option<Vec<u8>>;
 
+	/// Call `f` with every key and value stored in `col`, in no particular order, until it
+	/// returns `false`.
+	///
+	/// Databases that can't enumerate their keys return an error.
+	fn iter_column(&self, _col: ColumnId, _f: &mut dyn FnMut(&[u8], &[u8]) -> bool) -> error::Result<()> {
+		Err(error::DatabaseError("Iterating over a column is not supported by the database".into()))
+	}
+
 	/// Call `f` with the preimage stored for `hash` and return the result, or `None` if no preimage
 	/// is currently stored.
 	///
@@ -52,6 +52,18 @@ impl<H> Database<H> for MemDb<H>
 		let s = self.0.read();
 		s.1.get(hash).cloned()
 	}
+
+	fn iter_column(&self, col: ColumnId, f: &mut dyn FnMut(&[u8], &[u8]) -> bool) -> error::Result<()> {
+		let s = self.0.read();
+		if let Some(c) = s.0.get(&col) {
+			for (key, value) in c.iter() {
+				if !f(key, value) {
+					break;
+				}
+			}
+		}
+		Ok(())
+	}
 }
 
 impl<H> MemDb<H>
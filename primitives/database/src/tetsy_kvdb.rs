@@ -57,4 +57,13 @@ impl<D: KeyValueDB, H: Clone> Database<H> for DbAdapter<D> {
 	fn lookup(&self, _hash: &H) -> Option<Vec<u8>> {
 		unimplemented!();
 	}
+
+	fn iter_column(&self, col: ColumnId, f: &mut dyn FnMut(&[u8], &[u8]) -> bool) -> error::Result<()> {
+		for (key, value) in self.0.iter(col) {
+			if !f(&key, &value) {
+				break;
+			}
+		}
+		Ok(())
+	}
 }
@@ -57,6 +57,35 @@ fn guard() -> () {
 	()
 }
 
+/// Report a storage read or write as a structured `tracing` event, so that block tracing can
+/// pick it up along with the runtime spans.
+#[cfg(feature = "std")]
+fn trace_storage_access(
+	ext_id: u16,
+	method: &'static str,
+	child_info: Option<&ChildInfo>,
+	key: &[u8],
+	value: Option<&StorageValue>,
+) {
+	tracing::trace!(
+		target: "state",
+		method,
+		ext_id,
+		child = ?child_info.map(|child_info| HexDisplay::from(child_info.storage_key())),
+		key = %HexDisplay::from(key),
+		value = ?value.map(HexDisplay::from)
+	);
+}
+
+#[cfg(not(feature = "std"))]
+fn trace_storage_access(
+	_ext_id: u16,
+	_method: &'static str,
+	_child_info: Option<&ChildInfo>,
+	_key: &[u8],
+	_value: Option<&StorageValue>,
+) {}
+
 /// Errors that can occur when interacting with the externalities.
 #[cfg(feature = "std")]
 #[derive(Debug, Copy, Clone)]
@@ -201,11 +230,7 @@ where
 		let _guard = guard();
 		let result = self.overlay.storage(key).map(|x| x.map(|x| x.to_vec())).unwrap_or_else(||
 			self.backend.storage(key).expect(EXT_NOT_ALLOWED_TO_FAIL));
-		trace!(target: "state", "{:04x}: Get {}={:?}",
-			self.id,
-			HexDisplay::from(&key),
-			result.as_ref().map(HexDisplay::from)
-		);
+		trace_storage_access(self.id, "Get", None, key, result.as_ref());
 		result
 	}
 
@@ -238,12 +263,7 @@ where
 					.expect(EXT_NOT_ALLOWED_TO_FAIL)
 			);
 
-		trace!(target: "state", "{:04x}: GetChild({}) {}={:?}",
-			self.id,
-			HexDisplay::from(&child_info.storage_key()),
-			HexDisplay::from(&key),
-			result.as_ref().map(HexDisplay::from)
-		);
+		trace_storage_access(self.id, "GetChild", Some(child_info), key, result.as_ref());
 
 		result
 	}
@@ -354,11 +374,7 @@ where
 	}
 
 	fn place_storage(&mut self, key: StorageKey, value: Option<StorageValue>) {
-		trace!(target: "state", "{:04x}: Put {}={:?}",
-			self.id,
-			HexDisplay::from(&key),
-			value.as_ref().map(HexDisplay::from)
-		);
+		trace_storage_access(self.id, "Put", None, &key, value.as_ref());
 		let _guard = guard();
 		if is_child_storage_key(&key) {
 			warn!(target: "trie", "Refuse to directly set child storage key");
@@ -375,12 +391,7 @@ where
 		key: StorageKey,
 		value: Option<StorageValue>,
 	) {
-		trace!(target: "state", "{:04x}: PutChild({}) {}={:?}",
-			self.id,
-			HexDisplay::from(&child_info.storage_key()),
-			HexDisplay::from(&key),
-			value.as_ref().map(HexDisplay::from)
-		);
+		trace_storage_access(self.id, "PutChild", Some(child_info), &key, value.as_ref());
 		let _guard = guard();
 
 		self.mark_dirty();
@@ -392,10 +403,7 @@ where
 		child_info: &ChildInfo,
 		limit: Option<u32>,
 	) -> bool {
-		trace!(target: "state", "{:04x}: KillChild({})",
-			self.id,
-			HexDisplay::from(&child_info.storage_key()),
-		);
+		trace_storage_access(self.id, "KillChild", Some(child_info), &[], None);
 		let _guard = guard();
 		self.mark_dirty();
 		self.overlay.clear_child_storage(child_info);
@@ -428,10 +436,7 @@ where
 	}
 
 	fn clear_prefix(&mut self, prefix: &[u8]) {
-		trace!(target: "state", "{:04x}: ClearPrefix {}",
-			self.id,
-			HexDisplay::from(&prefix),
-		);
+		trace_storage_access(self.id, "ClearPrefix", None, prefix, None);
 		let _guard = guard();
 
 		if tet_core::storage::well_known_keys::starts_with_child_storage_key(prefix) {
@@ -451,11 +456,7 @@ where
 		child_info: &ChildInfo,
 		prefix: &[u8],
 	) {
-		trace!(target: "state", "{:04x}: ClearChildPrefix({}) {}",
-			self.id,
-			HexDisplay::from(&child_info.storage_key()),
-			HexDisplay::from(&prefix),
-		);
+		trace_storage_access(self.id, "ClearChildPrefix", Some(child_info), prefix, None);
 		let _guard = guard();
 
 		self.mark_dirty();
@@ -470,11 +471,7 @@ where
 		key: Vec<u8>,
 		value: Vec<u8>,
 	) {
-		trace!(target: "state", "{:04x}: Append {}={}",
-			self.id,
-			HexDisplay::from(&key),
-			HexDisplay::from(&value),
-		);
+		trace_storage_access(self.id, "Append", None, &key, Some(&value));
 
 		let _guard = guard();
 		self.mark_dirty();
@@ -784,6 +784,51 @@ mod execution {
 		Ok(proving_backend.extract_proof())
 	}
 
+	/// Generate a range proof of the storage entries following `start_key`, reading at least
+	/// one entry and stopping once `size_limit` bytes of keys and values are read.
+	///
+	/// `None` starts at the first entry of the trie. Returns the entries, whether they include
+	/// the last entry of the trie, and the proof.
+	pub fn prove_range_read_with_size<B, H>(
+		mut backend: B,
+		child_info: Option<&ChildInfo>,
+		start_key: Option<&[u8]>,
+		size_limit: usize,
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool, StorageProof), Box<dyn Error>>
+	where
+		B: Backend<H>,
+		H: Hasher,
+		H::Out: Ord + Codec,
+	{
+		let trie_backend = backend.as_trie_backend()
+			.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+		let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+		let (entries, complete) = read_range(&proving_backend, child_info, start_key, size_limit)?;
+		Ok((entries, complete, proving_backend.extract_proof()))
+	}
+
+	/// Generate a proof of the storage keys starting with `prefix` that follow `start_key`,
+	/// reading at most `count` keys.
+	///
+	/// `None` starts at `prefix`. Returns the keys and the proof.
+	pub fn prove_keys_paged<B, H>(
+		mut backend: B,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: u32,
+	) -> Result<(Vec<Vec<u8>>, StorageProof), Box<dyn Error>>
+	where
+		B: Backend<H>,
+		H: Hasher,
+		H::Out: Ord + Codec,
+	{
+		let trie_backend = backend.as_trie_backend()
+			.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+		let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+		let keys = read_keys_paged(&proving_backend, prefix, start_key, count)?;
+		Ok((keys, proving_backend.extract_proof()))
+	}
+
 	/// Check storage read proof, generated by `prove_read` call.
 	pub fn read_proof_check<H, I>(
 		root: H::Out,
@@ -831,6 +876,119 @@ mod execution {
 		Ok(result)
 	}
 
+	/// Check a range proof, generated by `prove_range_read_with_size` call.
+	///
+	/// Fails unless `entries` are exactly the storage entries following `start_key` and, if
+	/// `complete` is set, the last entries of the trie.
+	pub fn read_range_proof_check<H>(
+		root: H::Out,
+		proof: StorageProof,
+		child_info: Option<&ChildInfo>,
+		start_key: Option<&[u8]>,
+		entries: &[(Vec<u8>, Vec<u8>)],
+		complete: bool,
+	) -> Result<(), Box<dyn Error>>
+	where
+		H: Hasher,
+		H::Out: Ord + Codec,
+	{
+		let proving_backend = create_proof_check_backend::<H>(root, proof)?;
+		let size = entries.iter().map(|(key, value)| key.len() + value.len()).sum();
+		let (proved, proved_complete) = read_range(&proving_backend, child_info, start_key, size)?;
+		if proved.as_slice() != entries || proved_complete != complete {
+			return Err(Box::new(ExecutionError::InvalidProof));
+		}
+		Ok(())
+	}
+
+	/// Check a keys proof, generated by `prove_keys_paged` call, returning the proved keys.
+	pub fn read_keys_proof_check<H>(
+		root: H::Out,
+		proof: StorageProof,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: u32,
+	) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+	where
+		H: Hasher,
+		H::Out: Ord + Codec,
+	{
+		let proving_backend = create_proof_check_backend::<H>(root, proof)?;
+		read_keys_paged(&proving_backend, prefix, start_key, count)
+	}
+
+	/// Read the storage keys following `start_key`, see `prove_keys_paged`.
+	fn read_keys_paged<B, H>(
+		backend: &B,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: u32,
+	) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+	where
+		B: Backend<H>,
+		H: Hasher,
+	{
+		let mut current = start_key.unwrap_or(prefix).to_vec();
+		let mut keys = Vec::new();
+		while keys.len() < count as usize {
+			match backend.next_storage_key(&current).map_err(|e| Box::new(e) as Box<dyn Error>)? {
+				Some(next) if next.starts_with(prefix) => {
+					keys.push(next.clone());
+					current = next;
+				},
+				_ => break,
+			}
+		}
+		Ok(keys)
+	}
+
+	/// Read the storage entries following `start_key`, see `prove_range_read_with_size`.
+	fn read_range<B, H>(
+		backend: &B,
+		child_info: Option<&ChildInfo>,
+		start_key: Option<&[u8]>,
+		size_limit: usize,
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<dyn Error>>
+	where
+		B: Backend<H>,
+		H: Hasher,
+	{
+		let next_key = |key: &[u8]| {
+			let next = match child_info {
+				Some(child_info) => backend.next_child_storage_key(child_info, key),
+				None => backend.next_storage_key(key),
+			};
+			next.map_err(|e| Box::new(e) as Box<dyn Error>)
+		};
+		let value = |key: &[u8]| {
+			let value = match child_info {
+				Some(child_info) => backend.child_storage(child_info, key),
+				None => backend.storage(key),
+			};
+			value.map_err(|e| Box::new(e) as Box<dyn Error>)
+		};
+
+		let mut key = match start_key {
+			Some(start_key) => next_key(start_key)?,
+			// The empty key is the only one that can't be found with `next_key`.
+			None if value(&[])?.is_some() => Some(Vec::new()),
+			None => next_key(&[])?,
+		};
+		let mut entries = Vec::new();
+		let mut size = 0;
+		while let Some(current) = key {
+			if !entries.is_empty() && size >= size_limit {
+				return Ok((entries, false));
+			}
+			let current_value = value(&current)?
+				.ok_or_else(|| Box::new(ExecutionError::InvalidProof) as Box<dyn Error>)?;
+			size += current.len() + current_value.len();
+			key = next_key(&current)?;
+			entries.push((current, current_value));
+		}
+		Ok((entries, true))
+	}
+
 	/// Check storage read proof on pre-created proving backend.
 	pub fn read_proof_check_on_proving_backend<H>(
 		proving_backend: &TrieBackend<MemoryDB<H>, H>,
@@ -1456,6 +1614,112 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn prove_keys_paged_and_proof_check_works() {
+		let remote_root = trie_backend::tests::test_trie().storage_root(::std::iter::empty()).0;
+		let (keys, proof) = prove_keys_paged(
+			trie_backend::tests::test_trie(),
+			b"value",
+			None,
+			1,
+		).unwrap();
+		assert_eq!(keys, vec![b"value1".to_vec()]);
+		assert_eq!(
+			read_keys_proof_check::<BlakeTwo256>(remote_root, proof, b"value", None, 1).unwrap(),
+			keys,
+		);
+
+		let (keys, proof) = prove_keys_paged(
+			trie_backend::tests::test_trie(),
+			b"value",
+			Some(&b"value1"[..]),
+			10,
+		).unwrap();
+		assert_eq!(keys, vec![b"value2".to_vec()]);
+		assert_eq!(
+			read_keys_proof_check::<BlakeTwo256>(
+				remote_root,
+				proof,
+				b"value",
+				Some(&b"value1"[..]),
+				10,
+			).unwrap(),
+			keys,
+		);
+
+		// keys can't be proved without a proof
+		assert!(read_keys_proof_check::<BlakeTwo256>(
+			remote_root,
+			StorageProof::empty(),
+			b"value",
+			None,
+			1,
+		).is_err());
+	}
+
+	#[test]
+	fn prove_range_read_and_proof_check_works() {
+		let remote_root = trie_backend::tests::test_trie().storage_root(::std::iter::empty()).0;
+		let mut start_key = None;
+		let mut entries = Vec::new();
+		loop {
+			let (chunk, complete, proof) = prove_range_read_with_size(
+				trie_backend::tests::test_trie(),
+				None,
+				start_key.as_ref().map(|k: &Vec<u8>| &k[..]),
+				1,
+			).unwrap();
+			assert_eq!(chunk.len(), 1);
+			read_range_proof_check::<BlakeTwo256>(
+				remote_root,
+				proof.clone(),
+				None,
+				start_key.as_ref().map(|k: &Vec<u8>| &k[..]),
+				&chunk,
+				complete,
+			).unwrap();
+			// a proof can't claim the range is complete when it isn't
+			assert_eq!(
+				read_range_proof_check::<BlakeTwo256>(
+					remote_root,
+					proof,
+					None,
+					start_key.as_ref().map(|k: &Vec<u8>| &k[..]),
+					&chunk,
+					!complete,
+				).is_ok(),
+				false,
+			);
+			start_key = chunk.last().map(|(k, _)| k.clone());
+			entries.extend(chunk);
+			if complete {
+				break;
+			}
+		}
+		assert_eq!(entries, trie_backend::tests::test_trie().pairs());
+
+		// on child trie
+		let child_info = ChildInfo::new_default(b"sub1");
+		let (chunk, complete, proof) = prove_range_read_with_size(
+			trie_backend::tests::test_trie(),
+			Some(&child_info),
+			None,
+			usize::max_value(),
+		).unwrap();
+		assert!(complete);
+		assert_eq!(chunk, vec![(b"value3".to_vec(), vec![142]), (b"value4".to_vec(), vec![124])]);
+		read_range_proof_check::<BlakeTwo256>(remote_root, proof.clone(), Some(&child_info), None, &chunk, true)
+			.unwrap();
+		assert!(read_range_proof_check::<BlakeTwo256>(
+			remote_root,
+			proof,
+			Some(&child_info),
+			None,
+			&chunk[1..],
+			true,
+		).is_err());
+	}
+
 	#[test]
 	fn child_storage_uuid() {
 
@@ -54,12 +54,18 @@ pub enum Error {
 		/// Transaction entering the pool.
 		new: Priority
 	},
+	#[error("Transaction would not replace the given one")]
+	NoReplacement,
+
 	#[error("Transaction with cyclic dependency")]
 	CycleDetected,
 
 	#[error("Transaction couldn't enter the pool because of the limit")]
 	ImmediatelyDropped,
 
+	#[error("Transaction couldn't enter the pool because of the sender quota")]
+	SenderQuotaExceeded,
+
 	#[from(ignore)]
 	#[error("{0}")]
 	InvalidBlockId(String),
@@ -69,6 +69,7 @@ impl PoolStatus {
 ///		- `Invalid`
 ///		- `Usurped`
 ///		- `Dropped`
+///		- `DroppedForQuota`
 ///	4. Re-entering the pool:
 ///		- `Retracted`
 ///	5. Block finalized:
@@ -122,16 +123,55 @@ pub enum TransactionStatus<Hash, BlockHash> {
 	Usurped(Hash),
 	/// Transaction has been dropped from the pool because of the limit.
 	Dropped,
+	/// Transaction has been dropped from the pool because its sender exceeded the quota.
+	DroppedForQuota,
 	/// Transaction is no longer valid in the current state.
 	Invalid,
 }
 
+/// Event about any transaction in the pool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PoolEvent<Hash, BlockHash> {
+	/// Transaction was imported to the future queue.
+	Future(Hash),
+	/// Transaction was imported to the ready queue.
+	Ready(Hash),
+	/// Transaction was promoted from the future to the ready queue.
+	Promoted(Hash),
+	/// Transaction was replaced by another one providing the same tags.
+	Usurped {
+		/// The replaced transaction.
+		hash: Hash,
+		/// The replacement transaction.
+		by: Hash,
+	},
+	/// Transaction was dropped because of the pool limits.
+	Dropped(Hash),
+	/// Transaction was dropped because its sender exceeded the quota.
+	DroppedForQuota(Hash),
+	/// Transaction is no longer valid.
+	Invalid(Hash),
+	/// Transaction was pruned from the pool after being included in a block.
+	Pruned {
+		/// The pruned transaction.
+		hash: Hash,
+		/// The block the transaction was included in.
+		block: BlockHash,
+	},
+	/// Transaction was temporarily banned from the pool.
+	Banned(Hash),
+}
+
 /// The stream of transaction events.
 pub type TransactionStatusStream<Hash, BlockHash> = dyn Stream<Item=TransactionStatus<Hash, BlockHash>> + Send + Unpin;
 
 /// The import notification event stream.
 pub type ImportNotificationStream<H> = futures::channel::mpsc::Receiver<H>;
 
+/// The stream of events about all transactions in the pool.
+pub type PoolEventStream<H, BH> = futures::channel::mpsc::Receiver<PoolEvent<H, BH>>;
+
 /// Transaction hash type for a pool.
 pub type TxHash<P> = <P as TransactionPool>::Hash;
 /// Block hash type for a pool.
@@ -212,6 +252,43 @@ pub trait TransactionPool: Send + Sync {
 		xt: TransactionFor<Self>,
 	) -> PoolFuture<Box<TransactionStatusStreamFor<Self>>, Self::Error>;
 
+	/// Returns a future that imports a bunch of unverified transactions and starts to watch
+	/// their progress in the pool.
+	///
+	/// The transactions are imported in the order they were passed and the results
+	/// are returned in the same order.
+	fn submit_and_watch_at(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xts: Vec<TransactionFor<Self>>,
+	) -> PoolFuture<Vec<Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>>, Self::Error>;
+
+	/// Returns a future that imports one unverified transaction replacing the given one.
+	///
+	/// The transaction is refused without being imported unless it would replace
+	/// the ready transaction with the `replaces` hash.
+	fn replace_at(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xt: TransactionFor<Self>,
+		replaces: TxHash<Self>,
+	) -> PoolFuture<TxHash<Self>, Self::Error>;
+
+	// *** Networking
+	/// Returns a future that imports one transaction received from a peer.
+	///
+	/// Unlike with `submit_one`, the transaction is not considered as submitted to this node,
+	/// e.g. pools keeping a journal of their transactions do not record it.
+	fn import_from_network(
+		&self,
+		at: &BlockId<Self::Block>,
+		xt: TransactionFor<Self>,
+	) -> PoolFuture<TxHash<Self>, Self::Error> {
+		self.submit_one(at, TransactionSource::External, xt)
+	}
+
 	// *** Block production / Networking
 	/// Get an iterator for ready transactions ordered by priority.
 	///
@@ -223,6 +300,10 @@ pub trait TransactionPool: Send + Sync {
 	/// Get an iterator for ready transactions ordered by priority.
 	fn ready(&self) -> Box<dyn Iterator<Item=Arc<Self::InPoolTransaction>> + Send>;
 
+	// *** RPC
+	/// Get future transactions along with the tags each of them is still missing.
+	fn futures(&self) -> Vec<(Arc<Self::InPoolTransaction>, Vec<TransactionTag>)>;
+
 	// *** Block production
 	/// Remove transactions identified by given hashes (and dependent transactions) from the pool.
 	fn remove_invalid(&self, hashes: &[TxHash<Self>]) -> Vec<Arc<Self::InPoolTransaction>>;
@@ -235,6 +316,10 @@ pub trait TransactionPool: Send + Sync {
 	/// Return an event stream of transactions imported to the pool.
 	fn import_notification_stream(&self) -> ImportNotificationStream<TxHash<Self>>;
 
+	// *** RPC
+	/// Return an event stream of all transactions entering, moving within and leaving the pool.
+	fn pool_event_stream(&self) -> PoolEventStream<TxHash<Self>, BlockHash<Self>>;
+
 	// *** networking
 	/// Notify the pool about transactions broadcast.
 	fn on_broadcasted(&self, propagations: HashMap<TxHash<Self>, Vec<String>>);
@@ -24,6 +24,8 @@ mod node_header;
 mod node_codec;
 mod storage_proof;
 mod trie_stream;
+mod trie_diff;
+mod trie_codec;
 
 use tetcore_std::{boxed::Box, marker::PhantomData, vec::Vec, borrow::Borrow};
 use tetsy_hash_db::{Hasher, Prefix};
@@ -33,9 +35,13 @@ pub use tetsy_trie_db::proof::VerifyError;
 pub use error::Error;
 /// The Tetcore format implementation of `TrieStream`.
 pub use trie_stream::TrieStream;
+/// Comparison of two tries that skips their common subtrees.
+pub use trie_diff::{trie_diff, trie_removed_nodes};
 /// The Tetcore format implementation of `NodeCodec`.
 pub use node_codec::NodeCodec;
-pub use storage_proof::StorageProof;
+pub use storage_proof::{StorageProof, CompactProof};
+/// Compact encoding of storage proofs.
+pub use trie_codec::{encode_compact, decode_compact, Error as CompactProofError};
 /// Various re-exports from the `tetsy-trie-db` crate.
 pub use tetsy_trie_db::{
 	Trie, TrieMut, DBValue, Recorder, CError, Query, TrieLayout, TrieConfiguration, nibble_ops, TrieDBIterator,
@@ -890,4 +896,159 @@ mod tests {
 
 		assert_eq!(first_storage_root, second_storage_root);
 	}
+
+	#[test]
+	fn trie_diff_reports_changed_keys() {
+		let pairs: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64)
+			.map(|i| (vec![i % 4, i, i / 3], vec![i; (i % 40) as usize + 1]))
+			.collect();
+		let mut db = PrefixedMemoryDB::<Blake2Hasher>::default();
+		let mut root_a = Default::default();
+		populate_trie::<Layout>(&mut db, &mut root_a, &pairs);
+
+		let mut root_b = root_a;
+		{
+			let mut t = TrieDBMut::<Layout>::from_existing(&mut db, &mut root_b).unwrap();
+			t.insert(&[1, 5, 1], b"changed").unwrap();
+			t.insert(&[2, 200], b"added").unwrap();
+			t.remove(&[3, 7, 2]).unwrap();
+			t.remove(&[0, 0, 0]).unwrap();
+		}
+
+		let diff = |prefix: &[u8]| {
+			let mut changes = Vec::new();
+			trie_diff::<Layout, _, _, _>(&db, &root_a, &db, &root_b, prefix, |key, a, b| {
+				changes.push((key.to_vec(), a.map(|v| v.to_vec()), b.map(|v| v.to_vec())));
+				true
+			}).unwrap();
+			changes
+		};
+
+		assert_eq!(diff(&[]), vec![
+			(vec![0, 0, 0], Some(vec![0]), None),
+			(vec![1, 5, 1], Some(vec![5; 6]), Some(b"changed".to_vec())),
+			(vec![2, 200], None, Some(b"added".to_vec())),
+			(vec![3, 7, 2], Some(vec![7; 8]), None),
+		]);
+		assert_eq!(diff(&[2]), vec![(vec![2, 200], None, Some(b"added".to_vec()))]);
+		assert!(diff(&[1, 6]).is_empty());
+
+		let mut count = 0;
+		trie_diff::<Layout, _, _, _>(&db, &root_a, &db, &root_b, &[], |_, _, _| {
+			count += 1;
+			false
+		}).unwrap();
+		assert_eq!(count, 1);
+	}
+
+	#[test]
+	fn trie_removed_nodes_reports_replaced_nodes() {
+		let pairs: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64)
+			.map(|i| (vec![i % 4, i, i / 3], vec![i; (i % 40) as usize + 1]))
+			.collect();
+		let mut db_a = PrefixedMemoryDB::<Blake2Hasher>::default();
+		let mut root_a = Default::default();
+		populate_trie::<Layout>(&mut db_a, &mut root_a, &pairs);
+
+		let mut db_b = db_a.clone();
+		let mut root_b = root_a;
+		{
+			let mut t = TrieDBMut::<Layout>::from_existing(&mut db_b, &mut root_b).unwrap();
+			t.insert(&[1, 5, 1], b"changed").unwrap();
+			t.insert(&[2, 200], b"added").unwrap();
+			t.remove(&[3, 7, 2]).unwrap();
+		}
+		db_b.purge();
+
+		let removed = |root_b: &_| {
+			let mut removed = Vec::new();
+			trie_removed_nodes::<Layout, _, _, _>(&db_a, &root_a, &db_b, root_b, |hash, prefix| {
+				removed.push(prefixed_key::<Blake2Hasher>(hash, prefix));
+			}).unwrap();
+			removed.sort();
+			removed
+		};
+		let mut expected: Vec<_> = db_a.keys().into_iter()
+			.filter(|(key, _)| !db_b.keys().contains_key(key))
+			.map(|(key, _)| key)
+			.collect();
+		expected.sort();
+		assert!(!expected.is_empty());
+		assert_eq!(removed(&root_b), expected);
+		assert!(removed(&root_a).is_empty());
+	}
+
+	#[test]
+	fn compact_proof_round_trips() {
+		let pairs: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64)
+			.map(|i| (vec![i % 4, i, i / 3], vec![i; (i % 40) as usize + 1]))
+			.collect();
+		let mut db = MemoryDB::<Blake2Hasher>::default();
+		let mut root = Default::default();
+		populate_trie::<Layout>(&mut db, &mut root, &pairs);
+
+		let mut recorder = Recorder::new();
+		{
+			let trie = TrieDB::<Layout>::new(&db, &root).unwrap();
+			for key in &[vec![1, 5, 1], vec![3, 7, 2], vec![2, 200]] {
+				trie.get_with(key, &mut recorder).unwrap();
+			}
+		}
+		let proof = StorageProof::new(recorder.drain().into_iter().map(|record| record.data).collect());
+
+		let compact = proof.clone().into_compact_proof::<Blake2Hasher>(root).unwrap();
+		assert!(compact.encoded_size() < proof.encoded_size());
+
+		let (decoded, decoded_root) = compact.to_storage_proof::<Blake2Hasher>(Some(&root)).unwrap();
+		assert_eq!(decoded_root, root);
+		let nodes = |proof: StorageProof| proof.iter_nodes().collect::<std::collections::BTreeSet<_>>();
+		assert_eq!(nodes(decoded.clone()), nodes(proof));
+		let decoded_db = decoded.into_memory_db::<Blake2Hasher>();
+		assert_eq!(read_trie_value::<Layout, _>(&decoded_db, &root, &[1, 5, 1]).unwrap(), Some(vec![5; 6]));
+		assert_eq!(read_trie_value::<Layout, _>(&decoded_db, &root, &[2, 200]).unwrap(), None);
+
+		assert!(matches!(
+			compact.to_storage_proof::<Blake2Hasher>(Some(&Default::default())),
+			Err(CompactProofError::RootMismatch(..)),
+		));
+	}
+
+	#[test]
+	fn compact_proof_rejects_child_tries_out_of_key_order() {
+		use tet_core::storage::well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
+
+		let mut db = MemoryDB::<Blake2Hasher>::default();
+		let mut child_roots = Vec::new();
+		for child in 0u8..2 {
+			let pairs: Vec<(Vec<u8>, Vec<u8>)> = (0u8..16).map(|i| (vec![child, i], vec![i; 40])).collect();
+			let mut child_root = Default::default();
+			populate_trie::<Layout>(&mut db, &mut child_root, &pairs);
+			child_roots.push(child_root);
+		}
+		let top: Vec<(Vec<u8>, Vec<u8>)> = child_roots.iter().enumerate()
+			.map(|(i, child_root)| {
+				let mut key = DEFAULT_CHILD_STORAGE_KEY_PREFIX.to_vec();
+				key.push(i as u8);
+				(key, child_root.as_ref().to_vec())
+			})
+			.collect();
+		let mut root = Default::default();
+		populate_trie::<Layout>(&mut db, &mut root, &top);
+
+		let encode = |root| tetsy_trie_db::encode_compact::<Layout>(&TrieDB::<Layout>::new(&db, root).unwrap()).unwrap();
+		let mut in_order = encode(&root);
+		in_order.extend(encode(&child_roots[0]));
+		in_order.extend(encode(&child_roots[1]));
+		let mut out_of_order = encode(&root);
+		out_of_order.extend(encode(&child_roots[1]));
+		out_of_order.extend(encode(&child_roots[0]));
+
+		let mut decoded_db = MemoryDB::<Blake2Hasher>::default();
+		assert_eq!(decode_compact::<Layout, _>(&mut decoded_db, &in_order, Some(&root)).unwrap(), root);
+		let mut decoded_db = MemoryDB::<Blake2Hasher>::default();
+		assert!(matches!(
+			decode_compact::<Layout, _>(&mut decoded_db, &out_of_order, Some(&root)),
+			Err(CompactProofError::ExtraneousChildProof(child_root)) if child_root == child_roots[0],
+		));
+	}
 }
@@ -63,6 +63,14 @@ impl StorageProof {
 		self.into()
 	}
 
+	/// Encode as a `CompactProof` of the trie at `root`.
+	pub fn into_compact_proof<H: Hasher>(
+		self,
+		root: H::Out,
+	) -> Result<CompactProof, crate::CompactProofError<crate::Layout<H>>> {
+		crate::encode_compact::<crate::Layout<H>>(self, root)
+	}
+
 	/// Merges multiple storage proofs covering potentially different sets of keys into one proof
 	/// covering all keys. The merged proof output may be smaller than the aggregate size of the input
 	/// proofs due to deduplication of trie nodes.
@@ -77,6 +85,39 @@ impl StorageProof {
 	}
 }
 
+/// A storage proof in compact form.
+///
+/// The nodes are ordered as in a traversal of the tries and the hashes of the children that
+/// are part of the proof are omitted, they are recomputed on decoding. This makes the proof
+/// smaller and lets it be checked in a single pass, but decoding requires the whole proof.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct CompactProof {
+	/// The compact encoded nodes, in traversal order.
+	pub encoded_nodes: Vec<Vec<u8>>,
+}
+
+impl CompactProof {
+	/// Decode to a full storage proof, returned along with the root of the top trie.
+	///
+	/// Fails if `expected_root` is given and the proof is about another trie.
+	pub fn to_storage_proof<H: Hasher>(
+		&self,
+		expected_root: Option<&H::Out>,
+	) -> Result<(StorageProof, H::Out), crate::CompactProofError<crate::Layout<H>>> {
+		let mut db = crate::MemoryDB::<H>::default();
+		let root = crate::decode_compact::<crate::Layout<H>, _>(
+			&mut db,
+			&self.encoded_nodes,
+			expected_root,
+		)?;
+		let trie_nodes = db.drain()
+			.into_iter()
+			.filter_map(|(_, (node, references))| if references > 0 { Some(node) } else { None })
+			.collect();
+		Ok((StorageProof::new(trie_nodes), root))
+	}
+}
+
 /// An iterator over trie nodes constructed from a storage proof. The nodes are not guaranteed to
 /// be traversed in any particular order.
 pub struct StorageProofNodeIterator {
@@ -0,0 +1,165 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compact encoding of storage proofs.
+//!
+//! The nodes of the top trie are encoded in traversal order with the hashes of the children
+//! that are part of the proof omitted, they are recomputed when decoding. The nodes of every
+//! default child trie whose root is part of the proof follow, in the order of their child
+//! storage keys.
+
+use tetcore_std::{boxed::Box, vec::Vec};
+use tetsy_hash_db::{HashDB as HashDBT, HashDBRef};
+use tetsy_trie_db::{DBValue, TrieIterator};
+use tet_core::storage::well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
+use crate::{
+	CompactProof, StorageProof, Trie, TrieConfiguration, TrieDB, TrieError, TrieHash,
+	EMPTY_PREFIX,
+};
+
+/// Error of the compact proof encoding and decoding.
+pub enum Error<L: TrieConfiguration> {
+	/// The decoded top trie root does not match the expected one.
+	RootMismatch(TrieHash<L>, TrieHash<L>),
+	/// The proof does not contain the root of the top trie.
+	IncompleteProof,
+	/// A child trie was decoded whose root is not part of the top trie.
+	ExtraneousChildProof(TrieHash<L>),
+	/// A child trie root stored in the top trie is not a hash.
+	InvalidChildRoot(Vec<u8>, Vec<u8>),
+	/// Error of the underlying trie.
+	Trie(Box<TrieError<L>>),
+}
+
+impl<L: TrieConfiguration> From<Box<TrieError<L>>> for Error<L> {
+	fn from(error: Box<TrieError<L>>) -> Self {
+		Error::Trie(error)
+	}
+}
+
+#[cfg(feature = "std")]
+impl<L: TrieConfiguration> std::fmt::Debug for Error<L> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Error::RootMismatch(root, expected) =>
+				write!(f, "Proof root {:?} does not match the expected root {:?}", root, expected),
+			Error::IncompleteProof => write!(f, "Proof does not contain the root node"),
+			Error::ExtraneousChildProof(root) =>
+				write!(f, "Proof contains the unreferenced child trie {:?}", root),
+			Error::InvalidChildRoot(key, root) =>
+				write!(f, "Invalid child trie root {:?} at {:?}", root, key),
+			Error::Trie(error) => write!(f, "Trie error: {:?}", error),
+		}
+	}
+}
+
+/// Collect the roots of the default child tries referenced by the top trie at `root`.
+///
+/// Missing nodes are skipped, only the child roots that are part of the proof are returned.
+fn child_roots<L: TrieConfiguration, DB: HashDBRef<L::Hash, DBValue>>(
+	db: &DB,
+	root: &TrieHash<L>,
+) -> Result<Vec<TrieHash<L>>, Error<L>> {
+	let trie = TrieDB::<L>::new(db, root)?;
+	let mut iter = trie.iter()?;
+	let mut roots = Vec::new();
+	if iter.seek(DEFAULT_CHILD_STORAGE_KEY_PREFIX).is_err() {
+		return Ok(roots);
+	}
+	for item in iter {
+		match item {
+			Ok((key, value)) => {
+				if !key.starts_with(DEFAULT_CHILD_STORAGE_KEY_PREFIX) {
+					break;
+				}
+				let mut child_root = TrieHash::<L>::default();
+				if child_root.as_ref().len() != value.len() {
+					return Err(Error::InvalidChildRoot(key, value));
+				}
+				child_root.as_mut().copy_from_slice(&value);
+				roots.push(child_root);
+			},
+			Err(error) => match *error {
+				// Nodes that are not part of the proof are expected.
+				tetsy_trie_db::TrieError::IncompleteDatabase(_) => (),
+				error => return Err(Box::new(error).into()),
+			},
+		}
+	}
+	Ok(roots)
+}
+
+/// Encode `proof` of the trie at `root` in the compact form.
+pub fn encode_compact<L: TrieConfiguration>(
+	proof: StorageProof,
+	root: TrieHash<L>,
+) -> Result<CompactProof, Error<L>> {
+	let db = proof.into_memory_db::<L::Hash>();
+	let mut encoded_nodes = {
+		let trie = TrieDB::<L>::new(&db, &root)?;
+		tetsy_trie_db::encode_compact::<L>(&trie)?
+	};
+
+	for child_root in child_roots::<L, _>(&db, &root)? {
+		// A child root can be part of the proof without any of the child trie nodes.
+		if !HashDBRef::contains(&db, &child_root, EMPTY_PREFIX) {
+			continue;
+		}
+		let trie = TrieDB::<L>::new(&db, &child_root)?;
+		encoded_nodes.extend(tetsy_trie_db::encode_compact::<L>(&trie)?);
+	}
+
+	Ok(CompactProof { encoded_nodes })
+}
+
+/// Decode the compact `encoded` nodes into `db` and return the root of the top trie.
+///
+/// When `expected_root` is given, a proof of another trie is rejected before decoding the
+/// child tries.
+pub fn decode_compact<L, DB>(
+	db: &mut DB,
+	encoded: &[Vec<u8>],
+	expected_root: Option<&TrieHash<L>>,
+) -> Result<TrieHash<L>, Error<L>> where
+	L: TrieConfiguration,
+	DB: HashDBT<L::Hash, DBValue> + HashDBRef<L::Hash, DBValue>,
+{
+	let (root, mut used) = tetsy_trie_db::decode_compact::<L, _, _>(db, encoded)?;
+	if let Some(expected_root) = expected_root {
+		if expected_root != &root {
+			return Err(Error::RootMismatch(root, *expected_root));
+		}
+	}
+	if !HashDBRef::contains(&*db, &root, EMPTY_PREFIX) {
+		return Err(Error::IncompleteProof);
+	}
+
+	// Child tries are encoded in the order of their child storage keys, as returned by
+	// `child_roots`, some of them may be missing.
+	let mut child_roots = child_roots::<L, DB>(&*db, &root)?.into_iter();
+	while used < encoded.len() {
+		let (child_root, child_used) = tetsy_trie_db::decode_compact::<L, _, _>(
+			db,
+			&encoded[used..],
+		)?;
+		used += child_used;
+		if !child_roots.any(|root| root == child_root) {
+			return Err(Error::ExtraneousChildProof(child_root));
+		}
+	}
+	Ok(root)
+}
@@ -0,0 +1,352 @@
+// This file is part of Tetcore.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Comparison of two tries by walking both of them at once.
+//!
+//! Both tries are seen as plain radix-16 trees: a node with a partial key behaves as a chain
+//! of single child branches. Two sides that point to the same node at the same offset into
+//! its partial key have identical contents, so such subtrees are never loaded.
+
+use tetcore_std::{boxed::Box, vec::Vec};
+use tetsy_hash_db::{HashDBRef, Prefix};
+use tetsy_trie_db::{
+	DBValue, NibbleSlice, NodeCodec as NodeCodecT, TrieLayout,
+	node::{Node, NodeHandle},
+};
+use crate::{TrieError, TrieHash};
+
+/// Number of children of a branch.
+const NIBBLE_LENGTH: usize = 16;
+
+#[derive(Clone, PartialEq, Eq)]
+enum NodeRef {
+	Hash(Vec<u8>),
+	Inline(Vec<u8>),
+}
+
+impl<'a> From<&NodeHandle<'a>> for NodeRef {
+	fn from(handle: &NodeHandle<'a>) -> Self {
+		match *handle {
+			NodeHandle::Hash(hash) => NodeRef::Hash(hash.to_vec()),
+			NodeHandle::Inline(data) => NodeRef::Inline(data.to_vec()),
+		}
+	}
+}
+
+/// A node, positioned after the first `skip` nibbles of its partial key.
+#[derive(Clone, PartialEq, Eq)]
+struct Cursor {
+	node: NodeRef,
+	skip: usize,
+}
+
+/// The content of a cursor at the current depth.
+#[derive(Default)]
+struct Expanded {
+	value: Option<Vec<u8>>,
+	children: [Option<Cursor>; NIBBLE_LENGTH],
+}
+
+struct TrieDiff<'a, L: TrieLayout, A, B, F> {
+	db_a: &'a A,
+	db_b: &'a B,
+	prefix: Vec<u8>,
+	path: Vec<u8>,
+	f: F,
+	_layout: tetcore_std::marker::PhantomData<L>,
+}
+
+impl<'a, L, A, B, F> TrieDiff<'a, L, A, B, F> where
+	L: TrieLayout,
+	A: HashDBRef<L::Hash, DBValue>,
+	B: HashDBRef<L::Hash, DBValue>,
+	F: FnMut(&[u8], Option<&[u8]>, Option<&[u8]>) -> bool,
+{
+	/// Compare the subtrees under the current path, returns `false` if `f` aborted the walk.
+	fn walk(&mut self, a: Option<Cursor>, b: Option<Cursor>) -> Result<bool, Box<TrieError<L>>> {
+		if a == b {
+			return Ok(true);
+		}
+		let a = expand::<L, _>(self.db_a, &self.path, a)?;
+		let b = expand::<L, _>(self.db_b, &self.path, b)?;
+
+		let depth = self.path.len();
+		if depth >= self.prefix.len() && depth % 2 == 0 && a.value != b.value {
+			let key = pack(&self.path);
+			if !(self.f)(&key, a.value.as_deref(), b.value.as_deref()) {
+				return Ok(false);
+			}
+		}
+
+		let children = a.children.iter().cloned().zip(b.children.iter().cloned());
+		for (nibble, (child_a, child_b)) in children.enumerate() {
+			if self.prefix.get(depth).map_or(false, |n| *n as usize != nibble) {
+				continue;
+			}
+			self.path.push(nibble as u8);
+			let proceed = self.walk(child_a, child_b)?;
+			self.path.pop();
+			if !proceed {
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+}
+
+/// Walks an old and a new trie at once to find the nodes of the old one that the new one
+/// doesn't reference from the same position.
+struct RemovedNodes<'a, L: TrieLayout, A, B, F> {
+	db_a: &'a A,
+	db_b: &'a B,
+	path: Vec<u8>,
+	f: F,
+	_layout: tetcore_std::marker::PhantomData<L>,
+}
+
+impl<'a, L, A, B, F> RemovedNodes<'a, L, A, B, F> where
+	L: TrieLayout,
+	A: HashDBRef<L::Hash, DBValue>,
+	B: HashDBRef<L::Hash, DBValue>,
+	F: FnMut(&TrieHash<L>, Prefix),
+{
+	fn walk(&mut self, a: Option<Cursor>, b: Option<Cursor>) -> Result<(), Box<TrieError<L>>> {
+		if a == b {
+			return Ok(());
+		}
+		// The child of an extension sits at the same position as the end of the extension,
+		// where the other trie may reference it directly.
+		let (a, b) = if L::USE_EXTENSION {
+			(follow_extension::<L, _>(self.db_a, &self.path, a)?, follow_extension::<L, _>(self.db_b, &self.path, b)?)
+		} else {
+			(a, b)
+		};
+		if a == b {
+			return Ok(());
+		}
+		if let Some(Cursor { node: NodeRef::Hash(bytes), skip: 0 }) = &a {
+			let hash = decode_hash::<L>(bytes)?;
+			if hash != L::Codec::hashed_null_node() {
+				let (key, last) = node_prefix(&self.path);
+				(self.f)(&hash, (&key, last));
+			}
+		}
+		if a.is_none() {
+			return Ok(());
+		}
+
+		let a = expand::<L, _>(self.db_a, &self.path, a)?;
+		let b = expand::<L, _>(self.db_b, &self.path, b)?;
+		let children = a.children.iter().cloned().zip(b.children.iter().cloned());
+		for (nibble, (child_a, child_b)) in children.enumerate() {
+			self.path.push(nibble as u8);
+			self.walk(child_a, child_b)?;
+			self.path.pop();
+		}
+		Ok(())
+	}
+}
+
+/// Replace a cursor at the end of the partial key of an extension by the child of the extension.
+fn follow_extension<L, DB>(
+	db: &DB,
+	path: &[u8],
+	mut cursor: Option<Cursor>,
+) -> Result<Option<Cursor>, Box<TrieError<L>>> where
+	L: TrieLayout,
+	DB: HashDBRef<L::Hash, DBValue>,
+{
+	while let Some(current) = cursor.as_ref().filter(|c| c.skip > 0) {
+		let (hash, data) = load::<L, _>(db, &path[..path.len() - current.skip], &current.node)?;
+		match L::Codec::decode(&data).map_err(|e| Box::new(TrieError::DecoderError(hash, e)))? {
+			Node::Extension(partial, child) if current.skip >= partial.len() => {
+				cursor = Some(Cursor { node: (&child).into(), skip: 0 });
+			},
+			_ => break,
+		}
+	}
+	Ok(cursor)
+}
+
+/// Resolve `cursor` into the value and children found at `path`.
+fn expand<L, DB>(
+	db: &DB,
+	path: &[u8],
+	cursor: Option<Cursor>,
+) -> Result<Expanded, Box<TrieError<L>>> where
+	L: TrieLayout,
+	DB: HashDBRef<L::Hash, DBValue>,
+{
+	let mut cursor = match cursor {
+		Some(cursor) => cursor,
+		None => return Ok(Expanded::default()),
+	};
+	loop {
+		let (hash, data) = load::<L, _>(db, &path[..path.len() - cursor.skip], &cursor.node)?;
+		let node = L::Codec::decode(&data)
+			.map_err(|e| Box::new(TrieError::DecoderError(hash, e)))?;
+		let (partial, children, value) = match node {
+			Node::Empty => return Ok(Expanded::default()),
+			Node::Leaf(partial, value) => (partial, None, Some(value)),
+			Node::Extension(partial, child) => {
+				if cursor.skip < partial.len() {
+					return Ok(single_child(partial, &cursor));
+				}
+				cursor = Cursor { node: (&child).into(), skip: 0 };
+				continue;
+			},
+			Node::Branch(children, value) => (NibbleSlice::new(&[]), Some(children), value),
+			Node::NibbledBranch(partial, children, value) => (partial, Some(children), value),
+		};
+		if cursor.skip < partial.len() {
+			return Ok(single_child(partial, &cursor));
+		}
+
+		let mut expanded = Expanded {
+			value: value.map(|v| v.to_vec()),
+			children: Default::default(),
+		};
+		if let Some(children) = children {
+			for (nibble, child) in children.iter().enumerate() {
+				expanded.children[nibble] = child.as_ref()
+					.map(|child| Cursor { node: child.into(), skip: 0 });
+			}
+		}
+		return Ok(expanded);
+	}
+}
+
+/// The only child of a node that is still in the middle of its partial key.
+fn single_child(partial: NibbleSlice, cursor: &Cursor) -> Expanded {
+	let mut expanded = Expanded::default();
+	expanded.children[partial.at(cursor.skip) as usize] = Some(Cursor {
+		node: cursor.node.clone(),
+		skip: cursor.skip + 1,
+	});
+	expanded
+}
+
+/// Fetch the encoded node, `node_path` being the nibbles before its partial key.
+fn load<L, DB>(
+	db: &DB,
+	node_path: &[u8],
+	node: &NodeRef,
+) -> Result<(TrieHash<L>, Vec<u8>), Box<TrieError<L>>> where
+	L: TrieLayout,
+	DB: HashDBRef<L::Hash, DBValue>,
+{
+	match node {
+		// Inline nodes have no hash, errors are reported against the default one.
+		NodeRef::Inline(data) => Ok((Default::default(), data.clone())),
+		NodeRef::Hash(bytes) => {
+			let hash = decode_hash::<L>(bytes)?;
+			if hash == L::Codec::hashed_null_node() {
+				return Ok((hash, L::Codec::empty_node().to_vec()));
+			}
+			let (key, last) = node_prefix(node_path);
+			db.get(&hash, (&key, last))
+				.map(|data| (hash, data))
+				.ok_or_else(|| Box::new(TrieError::IncompleteDatabase(hash)))
+		},
+	}
+}
+
+fn decode_hash<L: TrieLayout>(bytes: &[u8]) -> Result<TrieHash<L>, Box<TrieError<L>>> {
+	let mut hash = TrieHash::<L>::default();
+	if bytes.len() != hash.as_ref().len() {
+		return Err(Box::new(TrieError::InvalidHash(hash, bytes.to_vec())));
+	}
+	hash.as_mut().copy_from_slice(bytes);
+	Ok(hash)
+}
+
+/// The database prefix of a node, `node_path` being the nibbles before its partial key.
+fn node_prefix(node_path: &[u8]) -> (Vec<u8>, Option<u8>) {
+	let key = pack(&node_path[..node_path.len() - node_path.len() % 2]);
+	let last = if node_path.len() % 2 == 1 {
+		Some(node_path[node_path.len() - 1] << 4)
+	} else {
+		None
+	};
+	(key, last)
+}
+
+/// Pack an even number of nibbles into bytes.
+fn pack(nibbles: &[u8]) -> Vec<u8> {
+	nibbles.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect()
+}
+
+/// Call `f` with the key and both values for every key starting with `prefix` whose value
+/// differs between the trie at `root_a` and the trie at `root_b`. A missing value means the
+/// key is absent from that trie. Keys are visited in lexicographic order and the walk
+/// aborts as soon as `f` returns false.
+///
+/// Subtrees that both tries reference by the same node hash are skipped without being read,
+/// so the cost is proportional to the difference rather than to the size of the tries.
+pub fn trie_diff<L, A, B, F>(
+	db_a: &A,
+	root_a: &TrieHash<L>,
+	db_b: &B,
+	root_b: &TrieHash<L>,
+	prefix: &[u8],
+	f: F,
+) -> Result<(), Box<TrieError<L>>> where
+	L: TrieLayout,
+	A: HashDBRef<L::Hash, DBValue>,
+	B: HashDBRef<L::Hash, DBValue>,
+	F: FnMut(&[u8], Option<&[u8]>, Option<&[u8]>) -> bool,
+{
+	let mut diff = TrieDiff::<L, _, _, _> {
+		db_a,
+		db_b,
+		prefix: prefix.iter().flat_map(|b| tetcore_std::vec![b >> 4, b & 0x0f]).collect(),
+		path: Vec::new(),
+		f,
+		_layout: Default::default(),
+	};
+	let cursor = |root: &TrieHash<L>| Some(Cursor { node: NodeRef::Hash(root.as_ref().to_vec()), skip: 0 });
+	diff.walk(cursor(root_a), cursor(root_b)).map(|_| ())
+}
+
+/// Call `f` with the hash and prefix of every node of the trie at `root_a` that the trie at
+/// `root_b` doesn't reference from the same position. When the second trie was obtained by
+/// changing the first one, these are the nodes that the change removed.
+///
+/// As with [`trie_diff`], subtrees shared by both tries are skipped without being read.
+/// Inline nodes are not reported since they are not stored on their own.
+pub fn trie_removed_nodes<L, A, B, F>(
+	db_a: &A,
+	root_a: &TrieHash<L>,
+	db_b: &B,
+	root_b: &TrieHash<L>,
+	f: F,
+) -> Result<(), Box<TrieError<L>>> where
+	L: TrieLayout,
+	A: HashDBRef<L::Hash, DBValue>,
+	B: HashDBRef<L::Hash, DBValue>,
+	F: FnMut(&TrieHash<L>, Prefix),
+{
+	let mut removed = RemovedNodes::<L, _, _, _> {
+		db_a,
+		db_b,
+		path: Vec::new(),
+		f,
+		_layout: Default::default(),
+	};
+	let cursor = |root: &TrieHash<L>| Some(Cursor { node: NodeRef::Hash(root.as_ref().to_vec()), skip: 0 });
+	removed.walk(cursor(root_a), cursor(root_b))
+}
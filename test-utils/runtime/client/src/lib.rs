@@ -35,9 +35,13 @@ use tet_core::{sr25519, ChangesTrieConfiguration};
 use tet_core::storage::{ChildInfo, Storage, StorageChild};
 use tetcore_test_runtime::genesismap::{GenesisConfig, additional_storage_with_genesis};
 use tp_runtime::traits::{Block as BlockT, Header as HeaderT, Hash as HashT, NumberFor, HashFor};
-use tc_client_api::light::{
-	RemoteCallRequest, RemoteChangesRequest, RemoteBodyRequest,
-	Fetcher, RemoteHeaderRequest, RemoteReadRequest, RemoteReadChildRequest,
+use tc_client_api::{
+	StorageProof,
+	light::{
+		RemoteCallRequest, RemoteChangesRequest, RemoteBodyRequest,
+		Fetcher, RemoteHeaderRequest, RemoteReadRequest, RemoteReadChildRequest,
+		RemoteReadKeysRequest,
+	},
 };
 
 /// A prelude to import in tests.
@@ -300,6 +304,8 @@ impl Fetcher<tetcore_test_runtime::Block> for LightFetcher {
 	type RemoteCallResult = FetcherFutureResult<Vec<u8>>;
 	type RemoteChangesResult = FetcherFutureResult<Vec<(NumberFor<tetcore_test_runtime::Block>, u32)>>;
 	type RemoteBodyResult = FetcherFutureResult<Vec<tetcore_test_runtime::Extrinsic>>;
+	type RemoteProofResult = FetcherFutureResult<StorageProof>;
+	type RemoteKeysResult = FetcherFutureResult<Vec<Vec<u8>>>;
 
 	fn remote_header(&self, _: RemoteHeaderRequest<tetcore_test_runtime::Header>) -> Self::RemoteHeaderResult {
 		unimplemented!()
@@ -316,6 +322,20 @@ impl Fetcher<tetcore_test_runtime::Block> for LightFetcher {
 		unimplemented!()
 	}
 
+	fn remote_read_child_proof(
+		&self,
+		_: RemoteReadChildRequest<tetcore_test_runtime::Header>,
+	) -> Self::RemoteProofResult {
+		unimplemented!()
+	}
+
+	fn remote_read_keys(
+		&self,
+		_: RemoteReadKeysRequest<tetcore_test_runtime::Header>,
+	) -> Self::RemoteKeysResult {
+		unimplemented!()
+	}
+
 	fn remote_call(&self, req: RemoteCallRequest<tetcore_test_runtime::Header>) -> Self::RemoteCallResult {
 		match self.call {
 			Some(ref call) => futures::future::ready(call(req)),
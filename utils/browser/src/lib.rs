@@ -22,7 +22,7 @@ use tc_service::{
 	RpcSession, Role, Configuration, TaskManager, RpcHandlers,
 	config::{DatabaseConfig, KeystoreConfig, NetworkConfiguration},
 	GenericChainSpec, RuntimeGenesis,
-	KeepBlocks, TransactionStorageMode,
+	KeepBlocks, TransactionStorageMode, StateStorageMode,
 };
 use tc_telemetry::{TelemetryHandle, TelemetrySpan};
 use tc_tracing::logging::LoggerBuilder;
@@ -106,12 +106,15 @@ where
 		state_pruning: Default::default(),
 		keep_blocks: KeepBlocks::All,
 		transaction_storage: TransactionStorageMode::BlockBody,
+		state_storage: StateStorageMode::Journaled,
 		rpc_cors: Default::default(),
 		rpc_http: Default::default(),
 		rpc_ipc: Default::default(),
 		rpc_ws: Default::default(),
 		rpc_ws_max_connections: Default::default(),
+		rpc_limits: Default::default(),
 		rpc_methods: Default::default(),
+		rpc_method_filters: Default::default(),
 		state_cache_child_ratio: Default::default(),
 		state_cache_size: Default::default(),
 		tracing_receiver: Default::default(),